@@ -0,0 +1,4 @@
+// Tauri command handlers backed by Nor Core
+
+pub mod session;
+pub mod vault;
@@ -0,0 +1,73 @@
+// Wallet session commands: password re-authentication, locking and the
+// idle-timeout watchdog
+use std::time::Duration;
+
+use nor_core::{SessionPolicy, SessionStatus, WalletManager};
+use tauri::{AppHandle, Emitter};
+
+/// How often the watchdog checks the idle timeout
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Event emitted to the frontend when the vault locks itself
+pub const SESSION_LOCKED_EVENT: &str = "session-locked";
+
+fn manager() -> Result<WalletManager, String> {
+    WalletManager::new().map_err(|e| e.to_string())
+}
+
+/// Confirm the vault password before exporting a mnemonic or private key.
+/// The vault must be unlocked first, see `vault_unlock`.
+#[tauri::command]
+pub fn session_authorize(password: String) -> Result<(), String> {
+    manager()?
+        .authorize_sensitive(password)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn session_lock_all() -> Result<(), String> {
+    manager()?.lock_all().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn session_status() -> Result<SessionStatus, String> {
+    manager()?.session_status().map_err(|e| e.to_string())
+}
+
+/// Loosening either limit fails until `session_authorize`
+#[tauri::command]
+pub fn session_set_policy(policy: SessionPolicy) -> Result<(), String> {
+    manager()?
+        .set_session_policy(policy)
+        .map_err(|e| e.to_string())
+}
+
+/// Fails with "Password confirmation required" until `session_authorize`
+#[tauri::command]
+pub fn wallet_export_mnemonic(wallet_id: String) -> Result<String, String> {
+    manager()?
+        .export_mnemonic(wallet_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn wallet_export_private_key(wallet_id: String, account_index: u32) -> Result<String, String> {
+    manager()?
+        .export_private_key(wallet_id, account_index)
+        .map_err(|e| e.to_string())
+}
+
+/// Lock the vault once it has been idle too long, even if the UI never
+/// touches a wallet again, and tell the frontend
+pub fn spawn_idle_watchdog(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+            let locked =
+                manager().and_then(|m| m.enforce_idle_timeout().map_err(|e| e.to_string()));
+            if let Ok(true) = locked {
+                let _ = app.emit(SESSION_LOCKED_EVENT, ());
+            }
+        }
+    });
+}
@@ -0,0 +1,48 @@
+// Vault commands: the encrypted wallet file in the app data directory, which
+// has to be created or unlocked before a session can be authorized
+use std::path::PathBuf;
+
+use nor_core::WalletManager;
+use tauri::{AppHandle, Manager};
+
+const VAULT_FILE: &str = "wallets.vault";
+
+fn manager() -> Result<WalletManager, String> {
+    WalletManager::new().map_err(|e| e.to_string())
+}
+
+fn vault_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join(VAULT_FILE))
+}
+
+/// Whether to show the create screen rather than the unlock screen
+#[tauri::command]
+pub fn vault_exists(app: AppHandle) -> Result<bool, String> {
+    Ok(vault_path(&app)?.exists())
+}
+
+/// Encrypt the wallets held so far under `password` and keep the file in sync
+#[tauri::command]
+pub fn vault_create(app: AppHandle, password: String) -> Result<(), String> {
+    let path = vault_path(&app)?.to_string_lossy().into_owned();
+    manager()?
+        .create_vault(path, password)
+        .map_err(|e| e.to_string())
+}
+
+/// Load the wallets, also after the idle watchdog has locked them. Returns
+/// the wallet ids.
+#[tauri::command]
+pub fn vault_unlock(app: AppHandle, password: String) -> Result<Vec<String>, String> {
+    let path = vault_path(&app)?.to_string_lossy().into_owned();
+    manager()?
+        .unlock_vault(path, password)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn vault_is_locked() -> Result<bool, String> {
+    Ok(manager()?.is_locked())
+}
@@ -1,3 +1,4 @@
+pub mod commands;
 mod services;
 
 pub use services::supabase_service::SupabaseService;
@@ -1,14 +1,29 @@
 // Prevents additional console window on Windows in release builds
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod commands;
 mod services;
 
+use commands::{session, vault};
 use services::supabase_service::SupabaseService;
 
 fn main() {
     tauri::Builder::default()
+        .setup(|app| {
+            session::spawn_idle_watchdog(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
-            // Add command handlers here
+            vault::vault_exists,
+            vault::vault_create,
+            vault::vault_unlock,
+            vault::vault_is_locked,
+            session::session_authorize,
+            session::session_lock_all,
+            session::session_status,
+            session::session_set_policy,
+            session::wallet_export_mnemonic,
+            session::wallet_export_private_key,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,7 +1,9 @@
 use crate::{error::*, types::*};
-use crate::storage;
+use crate::audit::SigningAction;
+use crate::policy::SpendRequest;
+use crate::signer::{LocalSigner, Signer};
+use crate::storage::Keyring;
 use ethers_core::utils::keccak256;
-use secp256k1::{Message, Secp256k1, ecdsa::RecoverableSignature, ecdsa::RecoveryId};
 
 pub fn create_account(owner_address: String, chain_id: u64, entry_point: String) -> Result<String> {
     // ERC-4337 smart account address computation
@@ -21,30 +23,30 @@ pub fn build_user_operation(params: UserOpParams) -> Result<UserOperation> {
 }
 
 pub fn sign_user_operation(
+    keyring: &Keyring,
     wallet_id: String,
     account_index: u32,
     params: UserOpParams,
 ) -> Result<String> {
-    // Get wallet and secret key
-    let wallet = storage::get_wallet(&wallet_id)?;
-    let secret_key = wallet.get_secret_key(account_index)?;
-    
-    // Compute UserOp hash
+    let signer = LocalSigner::new(keyring, &wallet_id, Chain::Evm, account_index);
+    sign_user_operation_audited(keyring, &signer, params)
+}
+
+/// [`sign_user_operation_with`], subject to the keyring's spending policy and
+/// recorded in its audit log
+pub(crate) fn sign_user_operation_audited(
+    keyring: &Keyring,
+    signer: &dyn Signer,
+    params: UserOpParams,
+) -> Result<String> {
+    let action = SigningAction::user_operation(SpendRequest::user_op(&signer.address()?, &params)?);
+    keyring.sign_audited(signer, action, |signer| sign_user_operation_with(signer, params))
+}
+
+/// [`sign_user_operation`] with any signer
+pub fn sign_user_operation_with(signer: &dyn Signer, params: UserOpParams) -> Result<String> {
     let hash = compute_userop_hash(&params)?;
-    
-    // Sign the hash (recoverable signature)
-    let message = Message::from_digest_slice(&hash)?;
-    let secp = Secp256k1::new();
-    let recoverable_sig = secp.sign_ecdsa_recoverable(&message, &secret_key);
-    let (recovery_id, compact_sig) = recoverable_sig.serialize_compact();
-    
-    // Serialize signature - compact_sig is 64 bytes (32 r + 32 s)
-    let mut sig_bytes = [0u8; 65];
-    sig_bytes[0..32].copy_from_slice(&compact_sig[0..32]); // r
-    sig_bytes[32..64].copy_from_slice(&compact_sig[32..64]); // s
-    sig_bytes[64] = recovery_id.to_i32() as u8 + 27;
-    
-    Ok(format!("0x{}", hex::encode(sig_bytes)))
+    Ok(signer.sign_digest(&hash)?.to_hex())
 }
 
 pub fn estimate_user_op_gas(params: UserOpParams, bundler_url: String) -> Result<GasEstimate> {
@@ -102,7 +104,7 @@ pub fn send_user_operation(params: UserOpParams, bundler_url: String) -> Result<
     response.result.ok_or(CoreError::RpcError)
 }
 
-fn compute_userop_hash(params: &UserOpParams) -> Result<[u8; 32]> {
+pub(crate) fn compute_userop_hash(params: &UserOpParams) -> Result<[u8; 32]> {
     // Simplified UserOp hash computation
     let packed = format!(
         "{}{}{}{}{}{}{}{}{}{}{}",
@@ -118,6 +120,10 @@ fn compute_userop_hash(params: &UserOpParams) -> Result<[u8; 32]> {
         params.paymaster_and_data,
         params.signature
     );
+    let packed = match params.chain_id {
+        Some(chain_id) => format!("{}{}", packed, chain_id),
+        None => packed,
+    };
 
     Ok(keccak256(packed.as_bytes()))
 }
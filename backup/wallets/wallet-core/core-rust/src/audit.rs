@@ -0,0 +1,647 @@
+// Signing audit log
+// Append-only record of every signature produced through the keyring, and of
+// every one the spending policy refused. Each entry commits to the hash of
+// the one before it, so editing or deleting entries breaks the chain. Log
+// files are hashed with a key kept in the vault, which also remembers where
+// the chain ended, so neither a recomputed chain nor a cut-off tail passes.
+
+use crate::policy::SpendRequest;
+use crate::signer::{EcdsaSignature, Signer};
+use crate::{eip712, evm};
+use crate::{error::*, types::*};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+/// `prev_hash` of the first entry
+const GENESIS_HASH: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Longest message excerpt quoted in a summary
+const MESSAGE_PREVIEW_CHARS: usize = 64;
+
+/// A signing request, described for the log
+pub(crate) struct SigningAction {
+    pub chain: Chain,
+    pub operation: SigningOperation,
+    pub summary: String,
+    /// What the spending policy checks; None for messages, which authorize nothing
+    pub spend: Option<SpendRequest>,
+}
+
+impl SigningAction {
+    pub fn transaction(request: SpendRequest) -> Self {
+        Self {
+            chain: request.chain,
+            operation: SigningOperation::Transaction,
+            summary: describe(&request),
+            spend: Some(request),
+        }
+    }
+
+    pub fn user_operation(request: SpendRequest) -> Self {
+        Self {
+            chain: request.chain,
+            operation: SigningOperation::UserOperation,
+            summary: format!("user operation: {}", describe(&request)),
+            spend: Some(request),
+        }
+    }
+
+    /// Text messages are previewed as text, anything else as hex
+    pub fn message(chain: Chain, message: &[u8]) -> Self {
+        let summary = match std::str::from_utf8(message) {
+            Ok(text) => {
+                let mut preview: String = text.chars().take(MESSAGE_PREVIEW_CHARS).collect();
+                if preview.len() < text.len() {
+                    preview.push('…');
+                }
+                format!("message {:?}", preview)
+            }
+            Err(_) => {
+                let shown = message.len().min(MESSAGE_PREVIEW_CHARS / 2);
+                let mut preview = hex::encode(&message[..shown]);
+                if shown < message.len() {
+                    preview.push('…');
+                }
+                format!("message 0x{}", preview)
+            }
+        };
+        Self {
+            chain,
+            operation: SigningOperation::Message,
+            summary,
+            spend: None,
+        }
+    }
+
+    pub fn typed_data(from: &str, typed_data_json: &str) -> Self {
+        let value: serde_json::Value = serde_json::from_str(typed_data_json).unwrap_or_default();
+        let mut summary = match value["primaryType"].as_str() {
+            Some(primary_type) => format!("typed data {}", primary_type),
+            None => "typed data".to_string(),
+        };
+        if let Some(name) = value["domain"]["name"].as_str() {
+            summary.push_str(&format!(" for {:?}", name));
+        }
+        Self {
+            chain: Chain::Evm,
+            operation: SigningOperation::TypedData,
+            summary,
+            spend: Some(SpendRequest::typed_data(from, typed_data_json)),
+        }
+    }
+}
+
+/// One line summary of what a transaction moves and calls
+fn describe(request: &SpendRequest) -> String {
+    let mut summary = request
+        .calls
+        .iter()
+        .map(|call| {
+            let mut summary = match &call.to {
+                Some(to) => format!("send {} to {}", call.value, to),
+                None => format!("deploy a contract with {}", call.value),
+            };
+            if let Some(selector) = call.data.get(..4) {
+                summary.push_str(&format!(", calling 0x{}", hex::encode(selector)));
+            }
+            summary
+        })
+        .collect::<Vec<_>>()
+        .join("; ");
+    for recipient in &request.recipients {
+        if !request
+            .calls
+            .iter()
+            .any(|call| call.to.as_ref() == Some(recipient))
+        {
+            summary.push_str(&format!(", recipient {}", recipient));
+        }
+    }
+    if let Some(chain_id) = request.chain_id {
+        summary.push_str(&format!(" on chain {}", chain_id));
+    }
+    summary
+}
+
+/// What the vault keeps about the log file: the key entry hashes are made
+/// with and the last entry it saw
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct AuditState {
+    key: String,
+    entries: u64,
+    head: String,
+}
+
+impl AuditState {
+    /// A fresh key, for a vault that has not kept a log yet
+    pub fn generate() -> Self {
+        let mut key = Zeroizing::new([0u8; 32]);
+        rand::thread_rng().fill_bytes(key.as_mut());
+        Self {
+            key: hex::encode(key.as_ref()),
+            entries: 0,
+            head: GENESIS_HASH.to_string(),
+        }
+    }
+
+    fn key(&self) -> Result<Zeroizing<[u8; 32]>> {
+        let mut key = Zeroizing::new([0u8; 32]);
+        hex::decode_to_slice(&self.key, key.as_mut()).map_err(|_| CoreError::StorageError)?;
+        Ok(key)
+    }
+}
+
+/// Entries in memory, mirrored to a JSON lines file once one is opened.
+/// Only file entries are keyed: the in-memory log of a keyring without a
+/// vault has nothing to key them with.
+#[derive(Default)]
+pub(crate) struct AuditLog {
+    entries: Vec<AuditEntry>,
+    path: Option<PathBuf>,
+    /// The vault's state for the log, while the vault is unlocked
+    vault: Option<AuditState>,
+}
+
+impl AuditLog {
+    /// Continue the log at `path`, creating it if missing. A file that fails
+    /// verification, or ends before the last entry the vault saw, is refused
+    /// rather than extended.
+    pub fn open(path: &Path, state: AuditState) -> Result<Self> {
+        let entries = match fs::read_to_string(path) {
+            Ok(contents) => parse_lines(&contents).map_err(|_| CoreError::AuditLogTampered)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        if !verify_entries(&entries, Some(&*state.key()?)).valid {
+            return Err(CoreError::AuditLogTampered);
+        }
+        // Entries past the vault's head are fine: saving the vault after an
+        // append can fail, and only the key holder could have written them
+        let head = match state.entries {
+            0 => Some(GENESIS_HASH),
+            n => entries.get(n as usize - 1).map(|entry| entry.hash.as_str()),
+        };
+        if head != Some(state.head.as_str()) {
+            return Err(CoreError::AuditLogTampered);
+        }
+        Ok(Self {
+            entries,
+            path: Some(path.to_path_buf()),
+            vault: Some(state),
+        })
+    }
+
+    /// Use the unlocked vault's state, or a fresh one if it has none. A log
+    /// file keyed by another vault is closed.
+    pub fn attach_vault(&mut self, state: Option<AuditState>) {
+        let state = state.unwrap_or_else(AuditState::generate);
+        let same_key = self
+            .vault
+            .as_ref()
+            .is_some_and(|current| current.key == state.key);
+        if self.path.is_some() && !same_key {
+            *self = Self::default();
+        }
+        self.vault = Some(state);
+    }
+
+    /// Forget the key when the vault locks; a log file then takes no entries
+    pub fn detach_vault(&mut self) {
+        self.vault = None;
+    }
+
+    /// What to save in the vault, with the head moved to the last entry
+    /// when a file is open
+    pub fn state(&self) -> Option<AuditState> {
+        let mut state = self.vault.clone()?;
+        if self.path.is_some() {
+            state.entries = self.entries.len() as u64;
+            state.head = self
+                .entries
+                .last()
+                .map_or(GENESIS_HASH.to_string(), |last| last.hash.clone());
+        }
+        Some(state)
+    }
+
+    pub fn is_file_backed(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Whether entries exist that were never written to a file
+    pub fn has_unsaved_entries(&self) -> bool {
+        self.path.is_none() && !self.entries.is_empty()
+    }
+
+    /// The key entry hashes are made with; none for a log kept in memory
+    fn key(&self) -> Result<Option<Zeroizing<[u8; 32]>>> {
+        if self.path.is_none() {
+            return Ok(None);
+        }
+        let state = self.vault.as_ref().ok_or(CoreError::VaultLocked)?;
+        state.key().map(Some)
+    }
+
+    /// Add an entry for `action`, writing it out before it counts
+    pub fn append(
+        &mut self,
+        action: &SigningAction,
+        account: String,
+        digest: Option<[u8; 32]>,
+        violation: Option<PolicyViolation>,
+    ) -> Result<()> {
+        let decision = match (&violation, &action.spend) {
+            (Some(_), _) => PolicyDecision::Blocked,
+            (None, Some(_)) => PolicyDecision::Allowed,
+            (None, None) => PolicyDecision::NotApplicable,
+        };
+        let mut entry = AuditEntry {
+            sequence: self.entries.len() as u64,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_err(|_| CoreError::InternalError)?
+                .as_secs(),
+            chain: action.chain,
+            account,
+            operation: action.operation,
+            digest: digest.map(|d| format!("0x{}", hex::encode(d))),
+            summary: action.summary.clone(),
+            decision,
+            violation,
+            prev_hash: self
+                .entries
+                .last()
+                .map_or(GENESIS_HASH.to_string(), |last| last.hash.clone()),
+            hash: String::new(),
+        };
+        entry.hash = entry_hash(&entry, self.key()?.as_deref())?;
+
+        if let Some(path) = &self.path {
+            let mut line = serde_json::to_vec(&entry)?;
+            line.push(b'\n');
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            file.write_all(&line)?;
+            file.sync_data()?;
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Check the chain and, for a file-backed log, that the file still holds
+    /// exactly the entries this process wrote or loaded
+    pub fn verify(&self) -> Result<AuditVerification> {
+        let key = self.key()?;
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(verify_entries(&self.entries, None)),
+        };
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let stored = match parse_lines(&contents) {
+            Ok(stored) => stored,
+            Err(verification) => return Ok(verification),
+        };
+        let verification = verify_entries(&stored, key.as_deref());
+        if !verification.valid {
+            return Ok(verification);
+        }
+
+        let count = stored.len() as u64;
+        let mismatch = stored
+            .iter()
+            .zip(&self.entries)
+            .position(|(stored, known)| stored != known);
+        Ok(match mismatch {
+            Some(index) => invalid(count, index as u64, "entry was replaced"),
+            None if stored.len() < self.entries.len() => {
+                invalid(count, count, "entries were removed from the end")
+            }
+            None if stored.len() > self.entries.len() => invalid(
+                count,
+                self.entries.len() as u64,
+                "entries were added by someone else",
+            ),
+            None => verification,
+        })
+    }
+
+    /// One JSON entry per line, oldest first
+    pub fn export(&self) -> Result<String> {
+        let mut jsonl = String::new();
+        for entry in &self.entries {
+            jsonl.push_str(&serde_json::to_string(entry)?);
+            jsonl.push('\n');
+        }
+        Ok(jsonl)
+    }
+
+    /// Check an export of this log on its own, with the log's key. Entries
+    /// cut from the end cannot be detected this way; compare the last hash
+    /// with [`AuditLog::verify`]'s.
+    pub fn verify_export(&self, jsonl: &str) -> Result<AuditVerification> {
+        let key = self.key()?;
+        Ok(match parse_lines(jsonl) {
+            Ok(entries) => verify_entries(&entries, key.as_deref()),
+            Err(verification) => verification,
+        })
+    }
+}
+
+fn parse_lines(jsonl: &str) -> std::result::Result<Vec<AuditEntry>, AuditVerification> {
+    let mut entries = Vec::new();
+    for line in jsonl.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => {
+                let count = entries.len() as u64;
+                return Err(invalid(count, count, "entry is not valid JSON"));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn verify_entries(entries: &[AuditEntry], key: Option<&[u8; 32]>) -> AuditVerification {
+    let count = entries.len() as u64;
+    let mut prev_hash = GENESIS_HASH;
+    for (index, entry) in entries.iter().enumerate() {
+        let index = index as u64;
+        if entry.sequence != index {
+            return invalid(count, index, "entries are missing or out of order");
+        }
+        if entry.prev_hash != prev_hash {
+            return invalid(count, index, "entry does not follow the one before it");
+        }
+        if entry_hash(entry, key).ok().as_ref() != Some(&entry.hash) {
+            return invalid(count, index, "entry was edited");
+        }
+        prev_hash = &entry.hash;
+    }
+    AuditVerification {
+        valid: true,
+        entries: count,
+        first_invalid: None,
+        reason: None,
+    }
+}
+
+fn invalid(entries: u64, first_invalid: u64, reason: &str) -> AuditVerification {
+    AuditVerification {
+        valid: false,
+        entries,
+        first_invalid: Some(first_invalid),
+        reason: Some(reason.to_string()),
+    }
+}
+
+/// HMAC-SHA256 under `key`, or plain SHA-256 without one, of the entry's
+/// JSON with `hash` left empty; `prev_hash` is part of it
+fn entry_hash(entry: &AuditEntry, key: Option<&[u8; 32]>) -> Result<String> {
+    let mut unhashed = entry.clone();
+    unhashed.hash = String::new();
+    let json = serde_json::to_vec(&unhashed)?;
+    let digest: [u8; 32] = match key {
+        Some(key) => {
+            let mut mac =
+                Hmac::<Sha256>::new_from_slice(key).map_err(|_| CoreError::InternalError)?;
+            mac.update(&json);
+            mac.finalize().into_bytes().into()
+        }
+        None => Sha256::digest(&json).into(),
+    };
+    Ok(format!("0x{}", hex::encode(digest)))
+}
+
+/// Passes everything to `inner`, noting the digest of what it signs
+pub(crate) struct DigestRecorder<'a> {
+    inner: &'a dyn Signer,
+    digest: Mutex<Option<[u8; 32]>>,
+}
+
+impl<'a> DigestRecorder<'a> {
+    pub fn new(inner: &'a dyn Signer) -> Self {
+        Self {
+            inner,
+            digest: Mutex::new(None),
+        }
+    }
+
+    /// The last digest signed
+    pub fn digest(&self) -> Option<[u8; 32]> {
+        self.digest.lock().ok().and_then(|digest| *digest)
+    }
+
+    fn note(&self, digest: [u8; 32]) {
+        if let Ok(mut noted) = self.digest.lock() {
+            *noted = Some(digest);
+        }
+    }
+}
+
+impl Signer for DigestRecorder<'_> {
+    fn sign_digest(&self, digest: &[u8; 32]) -> Result<EcdsaSignature> {
+        self.note(*digest);
+        self.inner.sign_digest(digest)
+    }
+
+    fn public_key(&self) -> Result<PublicKey> {
+        self.inner.public_key()
+    }
+
+    fn address(&self) -> Result<String> {
+        self.inner.address()
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<EcdsaSignature> {
+        self.note(evm::personal_message_hash(message));
+        self.inner.sign_message(message)
+    }
+
+    fn sign_typed_data(&self, typed_data_json: &str) -> Result<EcdsaSignature> {
+        self.note(eip712::hash_typed_data(typed_data_json)?.digest);
+        self.inner.sign_typed_data(typed_data_json)
+    }
+
+    fn sign_transaction(&self, params: &EvmTxParams) -> Result<EcdsaSignature> {
+        self.note(evm::transaction_sighash(params)?);
+        self.inner.sign_transaction(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(value: &str) -> SigningAction {
+        let params = EvmTxParams {
+            to: "0x3535353535353535353535353535353535353535".to_string(),
+            value: value.to_string(),
+            chain_id: 1,
+            ..Default::default()
+        };
+        SigningAction::transaction(SpendRequest::evm("0xme", &params).unwrap())
+    }
+
+    fn fixed_state() -> AuditState {
+        AuditState {
+            key: hex::encode([7u8; 32]),
+            entries: 0,
+            head: GENESIS_HASH.to_string(),
+        }
+    }
+
+    fn log_with(path: &Path, state: AuditState, count: usize) -> AuditLog {
+        let mut log = AuditLog::open(path, state).unwrap();
+        for i in 0..count {
+            log.append(
+                &action(&i.to_string()),
+                "0xme".to_string(),
+                Some([i as u8; 32]),
+                None,
+            )
+            .unwrap();
+        }
+        log
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_entries_chain_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = log_with(&path, fixed_state(), 3);
+
+        assert_eq!(log.entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(log.entries[2].prev_hash, log.entries[1].hash);
+        assert_eq!(
+            log.entries[1].summary,
+            "send 1 to 0x3535353535353535353535353535353535353535 on chain 1"
+        );
+        assert_eq!(log.entries[1].decision, PolicyDecision::Allowed);
+        assert!(log.verify().unwrap().valid);
+        assert_eq!(log.export().unwrap(), fs::read_to_string(&path).unwrap());
+
+        // Reopening continues the same chain
+        let reopened = log_with(&path, log.state().unwrap(), 1);
+        assert_eq!(reopened.entries.len(), 4);
+        assert_eq!(reopened.entries[3].prev_hash, log.entries[2].hash);
+        assert!(
+            reopened
+                .verify_export(&reopened.export().unwrap())
+                .unwrap()
+                .valid
+        );
+
+        // Entries are keyed: another key, or none, sees an edited chain
+        assert!(matches!(
+            AuditLog::open(&path, AuditState::generate()),
+            Err(CoreError::AuditLogTampered)
+        ));
+        let unkeyed = AuditLog::default();
+        assert!(!unkeyed.verify_export(&log.export().unwrap()).unwrap().valid);
+
+        // A locked vault takes the key away, and with it appending
+        let mut locked = reopened;
+        locked.detach_vault();
+        assert!(matches!(
+            locked.append(&action("1"), "0xme".to_string(), None, None),
+            Err(CoreError::VaultLocked)
+        ));
+    }
+
+    #[test]
+    fn test_edits_and_deletions_are_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = log_with(&path, fixed_state(), 4);
+        let original = lines(&path);
+
+        // Edited summary
+        let mut edited = original.clone();
+        edited[1] = edited[1].replace("send 1 to", "send 9 to");
+        fs::write(&path, edited.join("\n")).unwrap();
+        let verification = log.verify().unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.first_invalid, Some(1));
+        assert!(matches!(
+            AuditLog::open(&path, fixed_state()),
+            Err(CoreError::AuditLogTampered)
+        ));
+
+        // Entry removed from the middle
+        let mut removed = original.clone();
+        removed.remove(2);
+        let verification = log.verify_export(&removed.join("\n")).unwrap();
+        assert_eq!(verification.first_invalid, Some(2));
+
+        // Cut from the end: a valid chain, but not the one the vault saw end
+        fs::write(&path, original[..2].join("\n")).unwrap();
+        assert!(log.verify_export(&original[..2].join("\n")).unwrap().valid);
+        let verification = log.verify().unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.first_invalid, Some(2));
+        assert!(matches!(
+            AuditLog::open(&path, log.state().unwrap()),
+            Err(CoreError::AuditLogTampered)
+        ));
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            AuditLog::open(&path, log.state().unwrap()),
+            Err(CoreError::AuditLogTampered)
+        ));
+
+        fs::write(&path, "not json\n").unwrap();
+        assert_eq!(log.verify().unwrap().first_invalid, Some(0));
+    }
+
+    #[test]
+    fn test_blocked_and_valueless_decisions() {
+        let mut log = AuditLog::default();
+        let violation = PolicyViolation {
+            rule_id: "daily".to_string(),
+            rule_type: "daily_limit".to_string(),
+            reason: "over".to_string(),
+        };
+        log.append(&action("5"), "0xme".to_string(), None, Some(violation))
+            .unwrap();
+        log.append(
+            &SigningAction::message(Chain::Tron, "x".repeat(100).as_bytes()),
+            "T".to_string(),
+            Some([1; 32]),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(log.entries[0].decision, PolicyDecision::Blocked);
+        assert_eq!(log.entries[0].digest, None);
+        assert_eq!(log.entries[1].decision, PolicyDecision::NotApplicable);
+        assert!(log.entries[1].summary.ends_with("…\""));
+        assert_eq!(
+            SigningAction::message(Chain::Evm, &[0xff, 0x00]).summary,
+            "message 0xff00"
+        );
+        assert!(log.has_unsaved_entries());
+        assert!(log.verify().unwrap().valid);
+    }
+}
@@ -0,0 +1,392 @@
+// Portable encrypted backup bundles
+// Versioned JSON envelope around the wallets and app settings, readable on any
+// device; older bundle versions are migrated step by step on restore
+
+use crate::crypto::{Wallet, WalletRecord};
+use crate::error::*;
+use crate::types::BackupSettings;
+use crate::vault::{self, KdfParams, CIPHER};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Version written by this build.
+///
+/// 1. A copy of the vault file: wallets only
+/// 2. `nor-backup` envelope adding app settings and per-wallet fingerprints
+/// 3. Account labels move from the settings into each wallet's metadata
+pub const BACKUP_VERSION: u32 = 3;
+
+const FORMAT: &str = "nor-backup";
+
+/// On-disk layout of a bundle. Unknown fields are ignored so a newer minor
+/// addition does not break older readers of the same version.
+#[derive(Serialize, Deserialize)]
+struct BackupFile {
+    format: String,
+    version: u32,
+    created_at: u64,
+    kdf: KdfParams,
+    cipher: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Authenticated header: tampering with any of it fails decryption
+#[derive(Serialize)]
+struct BackupHeader<'a> {
+    format: &'a str,
+    version: u32,
+    created_at: u64,
+    kdf: &'a KdfParams,
+    cipher: &'a str,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct BackupWallet {
+    pub record: WalletRecord,
+    /// Checked after rebuilding, so a wallet that would restore to different
+    /// keys is caught. Absent in bundles migrated from version 1.
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct BackupPayload {
+    pub wallets: Vec<BackupWallet>,
+    #[serde(default)]
+    pub settings: BackupSettings,
+}
+
+/// Encrypt wallets and settings into a bundle
+pub(crate) fn seal(payload: &BackupPayload, password: &str, kdf: KdfParams) -> Result<String> {
+    seal_version(BACKUP_VERSION, payload, password, kdf)
+}
+
+fn seal_version(
+    version: u32,
+    payload: &impl Serialize,
+    password: &str,
+    kdf: KdfParams,
+) -> Result<String> {
+    let created_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|_| CoreError::InternalError)?
+        .as_secs();
+
+    let key = kdf.derive_key(password)?;
+    let plaintext = Zeroizing::new(serde_json::to_vec(payload)?);
+    let aad = header_bytes(version, created_at, &kdf)?;
+    let (nonce, ciphertext) = vault::seal(&key, &aad, &plaintext)?;
+
+    Ok(serde_json::to_string_pretty(&BackupFile {
+        format: FORMAT.to_string(),
+        version,
+        created_at,
+        kdf,
+        cipher: CIPHER.to_string(),
+        nonce,
+        ciphertext,
+    })?)
+}
+
+/// Decrypt a bundle of any supported version, returning that version and
+/// the payload migrated to the current layout
+pub(crate) fn open(bundle: &str, password: &str) -> Result<(u32, BackupPayload)> {
+    let envelope: Value = serde_json::from_str(bundle).map_err(|_| CoreError::InvalidBackup)?;
+    let version = envelope
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or(CoreError::InvalidBackup)? as u32;
+
+    // Version 1 bundles are plain vault files, which carry no format tag and
+    // the vault's own version
+    if envelope.get("format").is_none() {
+        if version == 0 || version > vault::VAULT_VERSION {
+            return Err(CoreError::InvalidBackup);
+        }
+        let (_, _, contents) =
+            vault::decrypt_contents(bundle.as_bytes(), password).map_err(|e| match e {
+                CoreError::StorageError => CoreError::InvalidBackup,
+                e => e,
+            })?;
+        return Ok((1, from_v1(contents.wallets)));
+    }
+
+    if envelope["format"] != FORMAT {
+        return Err(CoreError::InvalidBackup);
+    }
+    if version > BACKUP_VERSION {
+        return Err(CoreError::UnsupportedBackupVersion);
+    }
+
+    let file: BackupFile =
+        serde_json::from_value(envelope).map_err(|_| CoreError::InvalidBackup)?;
+    if file.cipher != CIPHER {
+        return Err(CoreError::UnsupportedBackupVersion);
+    }
+
+    let key = file
+        .kdf
+        .derive_key(password)
+        .map_err(|_| CoreError::InvalidBackup)?;
+    let aad = header_bytes(file.version, file.created_at, &file.kdf)?;
+    let plaintext = vault::open(&key, &aad, &file.nonce, &file.ciphertext)?;
+
+    let mut payload: Value =
+        serde_json::from_slice(&plaintext).map_err(|_| CoreError::InvalidBackup)?;
+    let migrated = migrate(file.version, &mut payload);
+    wipe_strings(&mut payload);
+
+    Ok((file.version, migrated?))
+}
+
+/// Rebuild a bundled wallet, checking it derives to the keys it was saved with
+pub(crate) fn restore_wallet(wallet: &BackupWallet) -> Result<Wallet> {
+    let restored = Wallet::from_record(&wallet.record)?;
+    match &wallet.fingerprint {
+        Some(fingerprint) if *fingerprint != restored.fingerprint() => {
+            Err(CoreError::InvalidBackup)
+        }
+        _ => Ok(restored),
+    }
+}
+
+/// Upgrade a decrypted payload one version at a time
+fn migrate(version: u32, payload: &mut Value) -> Result<BackupPayload> {
+    // Each step rewrites the previous version's layout in place; version 1
+    // never gets here as its envelope differs
+    for step in version..BACKUP_VERSION {
+        match step {
+            2 => v2_to_v3(payload)?,
+            _ => return Err(CoreError::UnsupportedBackupVersion),
+        }
+    }
+
+    BackupPayload::deserialize(&*payload).map_err(|_| CoreError::InvalidBackup)
+}
+
+/// Version 2 kept account labels in the settings, keyed by wallet id
+fn v2_to_v3(payload: &mut Value) -> Result<()> {
+    let labels = payload
+        .get_mut("settings")
+        .and_then(Value::as_object_mut)
+        .and_then(|settings| settings.remove("account_labels"));
+    let Some(Value::Array(labels)) = labels else {
+        return Ok(());
+    };
+    let wallets = payload
+        .get_mut("wallets")
+        .and_then(Value::as_array_mut)
+        .ok_or(CoreError::InvalidBackup)?;
+
+    for label in labels {
+        let record = wallets
+            .iter_mut()
+            .filter_map(|wallet| wallet.get_mut("record"))
+            .find(|record| record["id"] == label["wallet_id"]);
+        let Some(record) = record.and_then(Value::as_object_mut) else {
+            continue;
+        };
+        let entries = record
+            .entry("account_metadata")
+            .or_insert_with(|| json!([]));
+        if let Value::Array(entries) = entries {
+            entries.push(json!({
+                "chain": label["chain"],
+                "index": label["index"],
+                "metadata": { "label": label["label"] },
+            }));
+        }
+    }
+    Ok(())
+}
+
+fn from_v1(wallets: Vec<WalletRecord>) -> BackupPayload {
+    BackupPayload {
+        wallets: wallets
+            .into_iter()
+            .map(|record| BackupWallet {
+                record,
+                fingerprint: None,
+            })
+            .collect(),
+        settings: BackupSettings::default(),
+    }
+}
+
+fn header_bytes(version: u32, created_at: u64, kdf: &KdfParams) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(&BackupHeader {
+        format: FORMAT,
+        version,
+        created_at,
+        kdf,
+        cipher: CIPHER,
+    })?)
+}
+
+/// Zero every string in a JSON tree; the generic form of a decrypted payload
+/// holds mnemonics that the typed records would otherwise wipe
+fn wipe_strings(value: &mut Value) {
+    match value {
+        Value::String(s) => s.zeroize(),
+        Value::Array(items) => items.iter_mut().for_each(wipe_strings),
+        Value::Object(map) => map.values_mut().for_each(wipe_strings),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AccountMetadata, Chain, CustomToken};
+
+    fn payload() -> BackupPayload {
+        let mut wallet = Wallet::from_entropy(&[11u8; 16], Some("pass")).unwrap();
+        wallet.derive_account(2).unwrap();
+        let metadata = AccountMetadata {
+            label: Some("Savings".to_string()),
+            ..Default::default()
+        };
+        wallet
+            .set_account_metadata(Chain::Evm, 2, metadata)
+            .unwrap();
+        BackupPayload {
+            wallets: vec![BackupWallet {
+                fingerprint: Some(wallet.fingerprint()),
+                record: wallet.to_record().unwrap(),
+            }],
+            settings: BackupSettings {
+                networks: Vec::new(),
+                tokens: vec![CustomToken {
+                    chain_id: 65001,
+                    address: "0x3535353535353535353535353535353535353535".to_string(),
+                    symbol: "TKN".to_string(),
+                    decimals: 6,
+                    name: None,
+                }],
+            },
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let original = payload();
+        let bundle = seal(&original, "pw", KdfParams::pbkdf2(1000)).unwrap();
+        assert!(!bundle.contains("Savings"));
+
+        let (version, restored) = open(&bundle, "pw").unwrap();
+        assert_eq!(version, BACKUP_VERSION);
+        assert_eq!(restored.settings, original.settings);
+        let wallet = restore_wallet(&restored.wallets[0]).unwrap();
+        let accounts = wallet.to_wallet_data().accounts;
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[1].metadata.label.as_deref(), Some("Savings"));
+
+        assert!(matches!(
+            open(&bundle, "wrong"),
+            Err(CoreError::InvalidPassword)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_tampering_and_newer_versions() {
+        let bundle = seal(&payload(), "pw", KdfParams::pbkdf2(1000)).unwrap();
+        let mut file: Value = serde_json::from_str(&bundle).unwrap();
+
+        let mut backdated = file.clone();
+        backdated["created_at"] = serde_json::json!(0);
+        assert!(open(&backdated.to_string(), "pw").is_err());
+
+        file["version"] = serde_json::json!(BACKUP_VERSION + 1);
+        assert!(matches!(
+            open(&file.to_string(), "pw"),
+            Err(CoreError::UnsupportedBackupVersion)
+        ));
+        assert!(matches!(
+            open("{\"hello\":1}", "pw"),
+            Err(CoreError::InvalidBackup)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_unbounded_kdf_params() {
+        let bundle = seal(&payload(), "pw", KdfParams::pbkdf2(1000)).unwrap();
+        let file: Value = serde_json::from_str(&bundle).unwrap();
+
+        let mut pbkdf2 = file.clone();
+        pbkdf2["kdf"]["iterations"] = u32::MAX.into();
+        let mut argon2 = file;
+        argon2["kdf"] = serde_json::to_value(KdfParams::argon2id_with_cost(u32::MAX, 1)).unwrap();
+        for bundle in [pbkdf2, argon2] {
+            assert!(matches!(
+                open(&bundle.to_string(), "pw"),
+                Err(CoreError::InvalidBackup)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_migrates_version_2_account_labels() {
+        let mut wallet = Wallet::from_entropy(&[13u8; 16], None).unwrap();
+        wallet.derive_account(1).unwrap();
+        let mut record = serde_json::to_value(wallet.to_record().unwrap()).unwrap();
+        record.as_object_mut().unwrap().remove("account_metadata");
+        let v2 = json!({
+            "wallets": [{ "record": record, "fingerprint": wallet.fingerprint() }],
+            "settings": {
+                "networks": [],
+                "tokens": [],
+                "account_labels": [
+                    { "wallet_id": wallet.id, "chain": "Evm", "index": 1, "label": "Trading" },
+                    { "wallet_id": "gone", "chain": "Evm", "index": 0, "label": "Orphan" },
+                ],
+            },
+        });
+        let bundle = seal_version(2, &v2, "pw", KdfParams::pbkdf2(1000)).unwrap();
+
+        let (version, payload) = open(&bundle, "pw").unwrap();
+        assert_eq!(version, 2);
+        let restored = restore_wallet(&payload.wallets[0]).unwrap();
+        let accounts = restored.to_wallet_data().accounts;
+        assert_eq!(accounts[0].metadata, AccountMetadata::default());
+        assert_eq!(accounts[1].metadata.label.as_deref(), Some("Trading"));
+    }
+
+    #[test]
+    fn test_fingerprint_mismatch_is_rejected() {
+        let mut bundled = payload();
+        bundled.wallets[0].fingerprint = Some("00".repeat(16));
+        assert!(matches!(
+            restore_wallet(&bundled.wallets[0]),
+            Err(CoreError::InvalidBackup)
+        ));
+    }
+
+    #[test]
+    fn test_restores_version_1_vault_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallets.vault");
+        let wallet = Wallet::from_entropy(&[12u8; 16], None).unwrap();
+        let contents = vault::VaultPayload {
+            wallets: vec![wallet.to_record().unwrap()],
+            ..Default::default()
+        };
+        vault::Vault::create(&path, "pw", KdfParams::pbkdf2(1000), &contents).unwrap();
+
+        let (version, payload) = open(&std::fs::read_to_string(&path).unwrap(), "pw").unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(payload.settings, BackupSettings::default());
+        let restored = restore_wallet(&payload.wallets[0]).unwrap();
+        assert_eq!(restored.id, wallet.id);
+        assert_eq!(restored.fingerprint(), wallet.fingerprint());
+
+        let mut file: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        file["kdf"]["iterations"] = u32::MAX.into();
+        assert!(matches!(
+            open(&file.to_string(), "pw"),
+            Err(CoreError::InvalidBackup)
+        ));
+    }
+}
@@ -35,8 +35,10 @@ impl NetworkConfig {
             explorer_url: NOR_CHAIN_EXPLORER.to_string(),
         }
     }
+}
 
-    pub fn default() -> Self {
+impl Default for NetworkConfig {
+    fn default() -> Self {
         Self::nor_chain()
     }
 }
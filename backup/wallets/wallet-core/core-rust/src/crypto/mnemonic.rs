@@ -0,0 +1,224 @@
+// BIP-39 mnemonic parsing across wordlists
+// Language detection, word-level validation and prefix completion for seed entry
+
+use bip39::{Language, Mnemonic};
+use std::borrow::Cow;
+
+use crate::{error::*, types::*};
+
+impl From<MnemonicLanguage> for Language {
+    fn from(language: MnemonicLanguage) -> Self {
+        match language {
+            MnemonicLanguage::English => Language::English,
+            MnemonicLanguage::ChineseSimplified => Language::SimplifiedChinese,
+            MnemonicLanguage::ChineseTraditional => Language::TraditionalChinese,
+            MnemonicLanguage::Czech => Language::Czech,
+            MnemonicLanguage::French => Language::French,
+            MnemonicLanguage::Italian => Language::Italian,
+            MnemonicLanguage::Japanese => Language::Japanese,
+            MnemonicLanguage::Korean => Language::Korean,
+            MnemonicLanguage::Portuguese => Language::Portuguese,
+            MnemonicLanguage::Spanish => Language::Spanish,
+        }
+    }
+}
+
+impl From<Language> for MnemonicLanguage {
+    fn from(language: Language) -> Self {
+        match language {
+            Language::English => MnemonicLanguage::English,
+            Language::SimplifiedChinese => MnemonicLanguage::ChineseSimplified,
+            Language::TraditionalChinese => MnemonicLanguage::ChineseTraditional,
+            Language::Czech => MnemonicLanguage::Czech,
+            Language::French => MnemonicLanguage::French,
+            Language::Italian => MnemonicLanguage::Italian,
+            Language::Japanese => MnemonicLanguage::Japanese,
+            Language::Korean => MnemonicLanguage::Korean,
+            Language::Portuguese => MnemonicLanguage::Portuguese,
+            Language::Spanish => MnemonicLanguage::Spanish,
+        }
+    }
+}
+
+/// Parse a mnemonic in `language`, or in whichever wordlist it belongs to
+pub(crate) fn parse(phrase: &str, language: Option<MnemonicLanguage>) -> Result<Mnemonic> {
+    if let Some(language) = language {
+        return Ok(Mnemonic::parse_in(language.into(), phrase)?);
+    }
+
+    match Mnemonic::parse(phrase) {
+        // French and English share some words; take the list whose checksum holds
+        Err(bip39::Error::AmbiguousLanguages(candidates)) => {
+            let mut first_error = None;
+            for language in candidates.iter() {
+                match Mnemonic::parse_in(language, phrase) {
+                    Ok(mnemonic) => return Ok(mnemonic),
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+            Err(first_error.map_or(CoreError::InvalidMnemonic, Into::into))
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Check a mnemonic word by word, so a UI can point at what is wrong
+pub fn validate(phrase: &str, language: Option<MnemonicLanguage>) -> MnemonicValidation {
+    let mut phrase = Cow::Borrowed(phrase);
+    Mnemonic::normalize_utf8_cow(&mut phrase);
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    if words.is_empty() {
+        return MnemonicValidation::BadWordCount { count: 0 };
+    }
+
+    let unknown = |index: usize| MnemonicValidation::UnknownWord {
+        index: index as u32,
+        word: words[index].to_string(),
+    };
+
+    let language: Language = match language {
+        Some(language) => language.into(),
+        None => match Mnemonic::language_of(phrase.as_ref()) {
+            Ok(language) => language,
+            Err(bip39::Error::UnknownWord(index)) => return unknown(index),
+            Err(bip39::Error::AmbiguousLanguages(candidates)) => {
+                let parsed = candidates
+                    .iter()
+                    .find_map(|language| Mnemonic::parse_in_normalized(language, &phrase).ok());
+                return match parsed {
+                    Some(mnemonic) => MnemonicValidation::Valid {
+                        language: mnemonic.language().into(),
+                    },
+                    None => MnemonicValidation::AmbiguousLanguage {
+                        candidates: candidates.iter().map(Into::into).collect(),
+                    },
+                };
+            }
+            Err(_) => return MnemonicValidation::BadWordCount { count: 0 },
+        },
+    };
+
+    // Unknown words are reported before the count, which is wrong while typing
+    if let Some(index) = words
+        .iter()
+        .position(|word| language.find_word(word).is_none())
+    {
+        return unknown(index);
+    }
+
+    match Mnemonic::parse_in_normalized(language, &phrase) {
+        Ok(_) => MnemonicValidation::Valid {
+            language: language.into(),
+        },
+        Err(bip39::Error::BadWordCount(count)) => MnemonicValidation::BadWordCount {
+            count: count as u32,
+        },
+        Err(bip39::Error::UnknownWord(index)) => unknown(index),
+        Err(_) => MnemonicValidation::InvalidChecksum {
+            language: language.into(),
+        },
+    }
+}
+
+/// Up to `limit` words of `language` starting with `prefix`
+pub fn suggest_words(prefix: &str, language: MnemonicLanguage, limit: u32) -> Vec<String> {
+    let mut prefix = Cow::Borrowed(prefix.trim());
+    Mnemonic::normalize_utf8_cow(&mut prefix);
+    if prefix.is_empty() {
+        return Vec::new();
+    }
+
+    Language::from(language)
+        .words_by_prefix(&prefix)
+        .iter()
+        .take(limit as usize)
+        .map(|word| word.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABANDON: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_detects_language() {
+        let spanish = Mnemonic::from_entropy_in(Language::Spanish, &[0u8; 16])
+            .unwrap()
+            .to_string();
+        let parsed = parse(&spanish, None).unwrap();
+        assert_eq!(parsed.language(), Language::Spanish);
+        assert_eq!(
+            validate(&spanish, None),
+            MnemonicValidation::Valid {
+                language: MnemonicLanguage::Spanish
+            }
+        );
+
+        // "abandon" is in both the English and French lists
+        assert_eq!(parse(ABANDON, None).unwrap().language(), Language::English);
+        assert_eq!(
+            validate(ABANDON, None),
+            MnemonicValidation::Valid {
+                language: MnemonicLanguage::English
+            }
+        );
+    }
+
+    #[test]
+    fn test_reports_unknown_word() {
+        let mut words: Vec<&str> = ABANDON.split(' ').collect();
+        words[2] = "abandonn";
+        let phrase = words.join(" ");
+        assert_eq!(
+            validate(&phrase, Some(MnemonicLanguage::English)),
+            MnemonicValidation::UnknownWord {
+                index: 2,
+                word: "abandonn".to_string()
+            }
+        );
+        assert!(matches!(
+            parse(&phrase, Some(MnemonicLanguage::English)),
+            Err(CoreError::UnknownMnemonicWord)
+        ));
+    }
+
+    #[test]
+    fn test_distinguishes_length_and_checksum() {
+        let short = "abandon abandon abandon";
+        assert_eq!(
+            validate(short, Some(MnemonicLanguage::English)),
+            MnemonicValidation::BadWordCount { count: 3 }
+        );
+        assert!(matches!(
+            parse(short, None),
+            Err(CoreError::InvalidMnemonicLength)
+        ));
+
+        let bad_checksum = ABANDON.replace("about", "abandon");
+        assert_eq!(
+            validate(&bad_checksum, Some(MnemonicLanguage::English)),
+            MnemonicValidation::InvalidChecksum {
+                language: MnemonicLanguage::English
+            }
+        );
+        assert!(matches!(
+            parse(&bad_checksum, Some(MnemonicLanguage::English)),
+            Err(CoreError::InvalidMnemonicChecksum)
+        ));
+    }
+
+    #[test]
+    fn test_suggest_words() {
+        assert_eq!(
+            suggest_words("zo", MnemonicLanguage::English, 10),
+            vec!["zone", "zoo"]
+        );
+        assert_eq!(suggest_words("ab", MnemonicLanguage::English, 3).len(), 3);
+        assert!(suggest_words("", MnemonicLanguage::English, 10).is_empty());
+        assert!(suggest_words("qx", MnemonicLanguage::English, 10).is_empty());
+    }
+}
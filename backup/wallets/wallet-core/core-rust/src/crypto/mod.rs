@@ -1,215 +1,1047 @@
-use bip32::{DerivationPath, ExtendedPrivateKey, XPrv};
-use bip39::{Language, Mnemonic};
-use k256::ecdsa::SigningKey;
+use bip32::{ChildNumber, DerivationPath, Prefix, PublicKey as _, XPrv, XPub};
+use bip39::Mnemonic;
 use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tiny_keccak::{Hasher, Keccak};
-use zeroize::Zeroize;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::{error::*, types::*};
 
-const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0";
+pub(crate) mod mnemonic;
+pub(crate) mod secret;
+pub(crate) mod slip39;
 
-#[derive(Clone)]
+pub use secret::{SecretBytes, SecretString};
+
+/// TRON accounts always use coin type 195, whatever the wallet's EVM scheme
+const TRON_DERIVATION_TEMPLATE: &str = "m/44'/195'/0'/0/{index}";
+
+/// Version byte of TRON mainnet addresses
+const TRON_ADDRESS_PREFIX: u8 = 0x41;
+
+/// A wallet and its secrets. Not `Clone`: secrets live in exactly one place and
+/// are wiped when the wallet is dropped. Signing keys are derived on demand and
+/// only lent out through [`Wallet::with_secret_key`].
 pub struct Wallet {
     pub id: String,
-    mnemonic: Option<String>,
-    master_key: ExtendedPrivateKey<SigningKey>,
+    mnemonic: Option<SecretString>,
+    passphrase: Option<SecretString>,
+    // BIP-32 seed; for SLIP-39 recoveries the master secret, with no mnemonic
+    seed: Option<SecretBytes>,
+    // Raw key of wallets imported from a single private key
+    imported_key: Option<SecretBytes>,
+    scheme: DerivationScheme,
+    watch: Option<WatchSource>,
     accounts: Vec<DerivedAccount>,
+    created_at: SystemTime,
+    metadata: WalletMetadata,
+}
+
+/// Public material a watch-only wallet derives its addresses from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum WatchSource {
+    /// Account-level xpub; addresses at `child_template` below it
+    Xpub {
+        xpub: String,
+        chain: Chain,
+        child_template: String,
+        /// BIP-32 fingerprint of the master key the xpub was derived from
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        master_fingerprint: Option<u32>,
+    },
+    /// A fixed list of addresses, one account per entry
+    Addresses {
+        chain: Chain,
+        addresses: Vec<String>,
+    },
+}
+
+/// Secret material needed to rebuild a wallet, as persisted by the vault
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum WalletSecret {
+    Mnemonic {
+        phrase: String,
+        passphrase: Option<String>,
+    },
+    PrivateKey {
+        key: String,
+    },
+    /// Hex BIP-32 seed recovered from SLIP-39 shares
+    MasterSecret {
+        secret: String,
+    },
+    /// Watch-only wallets hold no secret, only what they were created from
+    WatchOnly {
+        #[zeroize(skip)]
+        source: WatchSource,
+    },
+}
+
+/// Serializable form of a wallet: its id, secret and derived account indices
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub(crate) struct WalletRecord {
+    pub id: String,
+    pub secret: WalletSecret,
+    pub account_indices: Vec<u32>,
+    // Records written before schemes existed were all BIP44
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub scheme: DerivationScheme,
+    #[serde(default)]
+    pub tron_account_indices: Vec<u32>,
+    /// Seconds since the Unix epoch; absent in records written before it was kept
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub created_at: Option<u64>,
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub metadata: WalletMetadata,
+    /// Only accounts whose metadata differs from the default
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub account_metadata: Vec<AccountMetadataRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AccountMetadataRecord {
+    pub chain: Chain,
+    pub index: u32,
+    pub metadata: AccountMetadata,
 }
 
-#[derive(Clone)]
+/// Public half of a derived account; the private key is never kept
 struct DerivedAccount {
+    chain: Chain,
     index: u32,
-    secret_key: SecretKey,
-    public_key: PublicKey,
+    public_key: Option<PublicKey>,
     address: String,
     derivation_path: String,
+    metadata: AccountMetadata,
 }
 
 impl Wallet {
     /// Create a new wallet from entropy
     pub fn from_entropy(entropy: &[u8], passphrase: Option<&str>) -> Result<Self> {
-        let mnemonic = Mnemonic::from_entropy(entropy)?;
-        Self::from_mnemonic_internal(mnemonic, passphrase)
+        Self::from_entropy_with_scheme(entropy, passphrase, DerivationScheme::Bip44)
+    }
+
+    pub fn from_entropy_with_scheme(
+        entropy: &[u8],
+        passphrase: Option<&str>,
+        scheme: DerivationScheme,
+    ) -> Result<Self> {
+        Self::from_entropy_in(MnemonicLanguage::English, entropy, passphrase, scheme)
+    }
+
+    /// Create a new wallet whose mnemonic uses `language`'s wordlist
+    pub fn from_entropy_in(
+        language: MnemonicLanguage,
+        entropy: &[u8],
+        passphrase: Option<&str>,
+        scheme: DerivationScheme,
+    ) -> Result<Self> {
+        let mnemonic = Mnemonic::from_entropy_in(language.into(), entropy)?;
+        Self::from_mnemonic_internal(mnemonic, passphrase, scheme)
     }
 
-    /// Create wallet from mnemonic phrase
+    /// Create wallet from mnemonic phrase in any BIP-39 language
     pub fn from_mnemonic(mnemonic_str: &str, passphrase: Option<&str>) -> Result<Self> {
-        let mnemonic = Mnemonic::parse_in(Language::English, mnemonic_str)?;
-        Self::from_mnemonic_internal(mnemonic, passphrase)
+        Self::from_mnemonic_with_scheme(mnemonic_str, passphrase, DerivationScheme::Bip44)
     }
 
-    fn from_mnemonic_internal(mnemonic: Mnemonic, passphrase: Option<&str>) -> Result<Self> {
-        let seed = mnemonic.to_seed(passphrase.unwrap_or(""));
-        let master_key = XPrv::new(&seed).map_err(|_| CoreError::InternalError)?;
+    pub fn from_mnemonic_with_scheme(
+        mnemonic_str: &str,
+        passphrase: Option<&str>,
+        scheme: DerivationScheme,
+    ) -> Result<Self> {
+        let mnemonic = mnemonic::parse(mnemonic_str, None)?;
+        Self::from_mnemonic_internal(mnemonic, passphrase, scheme)
+    }
+
+    fn from_mnemonic_internal(
+        mnemonic: Mnemonic,
+        passphrase: Option<&str>,
+        scheme: DerivationScheme,
+    ) -> Result<Self> {
+        validate_scheme(&scheme)?;
+
+        let seed = Zeroizing::new(mnemonic.to_seed(passphrase.unwrap_or("")));
 
         let id = uuid::Uuid::new_v4().to_string();
 
         let mut wallet = Self {
             id,
-            mnemonic: Some(mnemonic.to_string()),
-            master_key,
+            mnemonic: Some(SecretString::new(mnemonic.to_string())),
+            passphrase: passphrase.map(|p| SecretString::new(p.to_string())),
+            seed: Some(SecretBytes::new(seed.to_vec())),
+            imported_key: None,
+            scheme,
+            watch: None,
             accounts: Vec::new(),
+            created_at: creation_time(),
+            metadata: WalletMetadata::default(),
         };
 
         // Derive first account by default
-        wallet.derive_account_internal(0)?;
+        wallet.derive_account_internal(Chain::Evm, 0)?;
 
         Ok(wallet)
     }
 
+    /// Wallet whose BIP-32 seed is a SLIP-39 master secret
+    pub fn from_master_secret(master_secret: &[u8]) -> Result<Self> {
+        // Reject seeds BIP-32 cannot use before storing anything
+        XPrv::new(master_secret).map_err(|_| CoreError::InvalidInput)?;
+
+        let mut wallet = Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            mnemonic: None,
+            passphrase: None,
+            seed: Some(SecretBytes::new(master_secret.to_vec())),
+            imported_key: None,
+            scheme: DerivationScheme::Bip44,
+            watch: None,
+            accounts: Vec::new(),
+            created_at: creation_time(),
+            metadata: WalletMetadata::default(),
+        };
+        wallet.derive_account_internal(Chain::Evm, 0)?;
+
+        Ok(wallet)
+    }
+
+    /// Recover a wallet from a quorum of SLIP-39 mnemonic shares
+    pub fn from_slip39(mnemonics: &[String], passphrase: &str) -> Result<Self> {
+        let master_secret = slip39::combine_mnemonics(mnemonics, passphrase)?;
+        Self::from_master_secret(&master_secret)
+    }
+
+    /// Split this wallet's BIP-32 seed into SLIP-39 shares, one list per group.
+    ///
+    /// Mnemonic wallets split their BIP-39 seed, so the shares recover the same
+    /// accounts but not the original phrase.
+    pub fn to_slip39(
+        &self,
+        group_threshold: u8,
+        groups: &[Slip39Group],
+        passphrase: &str,
+        iteration_exponent: u8,
+    ) -> Result<Vec<Vec<String>>> {
+        let seed = self.seed()?;
+        slip39::generate_mnemonics(
+            group_threshold,
+            groups,
+            seed.expose(),
+            passphrase,
+            iteration_exponent,
+        )
+    }
+
+    fn seed(&self) -> Result<&SecretBytes> {
+        if self.is_watch_only() {
+            return Err(CoreError::WatchOnly);
+        }
+        // Imported keys have no seed
+        self.seed.as_ref().ok_or(CoreError::InvalidInput)
+    }
+
+    /// BIP-32 root key, rebuilt from the seed for each use rather than kept
+    fn master_key(&self) -> Result<XPrv> {
+        XPrv::new(self.seed()?.expose()).map_err(|_| CoreError::InternalError)
+    }
+
+    /// Whether accounts are derived from a seed rather than imported
+    fn is_hd(&self) -> bool {
+        self.seed.is_some()
+    }
+
     /// Create wallet from a single private key (no mnemonic)
     pub fn from_private_key(private_key_hex: &str) -> Result<Self> {
         let private_key_hex = private_key_hex.trim_start_matches("0x");
-        let secret_bytes =
-            hex::decode(private_key_hex).map_err(|_| CoreError::InvalidPrivateKey)?;
-
-        let secret_key = SecretKey::from_slice(&secret_bytes)?;
-        let secp = Secp256k1::new();
-        let public_key = PublicKey::from_secret_key(&secp, &secret_key);
-        let address = public_key_to_address(&public_key);
-
-        let account = DerivedAccount {
-            index: 0,
-            secret_key,
-            public_key,
-            address,
-            derivation_path: "imported".to_string(),
-        };
+        let secret_bytes = SecretBytes::new(
+            hex::decode(private_key_hex).map_err(|_| CoreError::InvalidPrivateKey)?,
+        );
 
-        // Create a dummy master key (won't be used for imported keys)
-        let mut seed_bytes = [0u8; 64];
-        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut seed_bytes);
-        let master_key = XPrv::new(&seed_bytes).map_err(|_| CoreError::InternalError)?;
+        let mut secret_key = SecretKey::from_slice(secret_bytes.expose())?;
+        let account = account_for_key(Chain::Evm, 0, &secret_key, "imported".to_string());
+        secret_key.non_secure_erase();
 
         Ok(Self {
             id: uuid::Uuid::new_v4().to_string(),
             mnemonic: None,
-            master_key,
+            passphrase: None,
+            seed: None,
+            imported_key: Some(secret_bytes),
+            scheme: DerivationScheme::Bip44,
+            watch: None,
             accounts: vec![account],
+            created_at: creation_time(),
+            metadata: WalletMetadata::default(),
         })
     }
 
-    /// Export mnemonic (if available)
-    pub fn export_mnemonic(&self) -> Result<String> {
-        self.mnemonic.clone().ok_or(CoreError::InvalidInput)
+    /// Watch-only wallet deriving addresses from an account-level xpub.
+    /// `child_template` is relative to the xpub, e.g. `0/{index}`;
+    /// `master_fingerprint` is that of the seed the xpub came from, if known.
+    pub fn from_xpub(
+        xpub: &str,
+        chain: Chain,
+        child_template: &str,
+        master_fingerprint: Option<u32>,
+    ) -> Result<Self> {
+        let parsed = XPub::from_str(xpub).map_err(|_| CoreError::InvalidInput)?;
+        let source = WatchSource::Xpub {
+            xpub: xpub.to_string(),
+            chain,
+            child_template: child_template.to_string(),
+            master_fingerprint,
+        };
+
+        // Validate the template up front so a bad one fails on import
+        xpub_child(&parsed, child_template, 0)?;
+
+        let mut wallet = Self::watching(source)?;
+        wallet.derive_chain_account(chain, 0)?;
+        Ok(wallet)
     }
 
-    /// Export private key for specific account
-    pub fn export_private_key(&self, account_index: u32) -> Result<String> {
-        let account = self
-            .accounts
+    /// Watch-only wallet over a fixed list of addresses
+    pub fn from_addresses(chain: Chain, addresses: &[String]) -> Result<Self> {
+        if addresses.is_empty() {
+            return Err(CoreError::InvalidInput);
+        }
+
+        let addresses = addresses
             .iter()
-            .find(|a| a.index == account_index)
-            .ok_or(CoreError::InvalidInput)?;
+            .map(|address| normalize_address(chain, address))
+            .collect::<Result<Vec<_>>>()?;
 
-        Ok(format!(
-            "0x{}",
-            hex::encode(account.secret_key.secret_bytes())
-        ))
+        let mut wallet = Self::watching(WatchSource::Addresses {
+            chain,
+            addresses: addresses.clone(),
+        })?;
+        wallet.accounts = addresses
+            .into_iter()
+            .enumerate()
+            .map(|(index, address)| DerivedAccount {
+                chain,
+                index: index as u32,
+                public_key: None,
+                address,
+                derivation_path: "watch".to_string(),
+                metadata: AccountMetadata::default(),
+            })
+            .collect();
+        Ok(wallet)
+    }
+
+    fn watching(source: WatchSource) -> Result<Self> {
+        Ok(Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            mnemonic: None,
+            passphrase: None,
+            seed: None,
+            imported_key: None,
+            scheme: DerivationScheme::Bip44,
+            watch: Some(source),
+            accounts: Vec::new(),
+            created_at: creation_time(),
+            metadata: WalletMetadata::default(),
+        })
+    }
+
+    pub fn is_watch_only(&self) -> bool {
+        self.watch.is_some()
+    }
+
+    /// Rebuild a wallet from a persisted record, keeping its id
+    pub(crate) fn from_record(record: &WalletRecord) -> Result<Self> {
+        let mut wallet = match &record.secret {
+            WalletSecret::Mnemonic { phrase, passphrase } => Self::from_mnemonic_with_scheme(
+                phrase,
+                passphrase.as_deref(),
+                record.scheme.clone(),
+            )?,
+            WalletSecret::PrivateKey { key } => Self::from_private_key(key)?,
+            WalletSecret::MasterSecret { secret } => {
+                let secret =
+                    Zeroizing::new(hex::decode(secret).map_err(|_| CoreError::StorageError)?);
+                Self::from_master_secret(&secret)?
+            }
+            WalletSecret::WatchOnly { source } => match source {
+                WatchSource::Xpub {
+                    xpub,
+                    chain,
+                    child_template,
+                    master_fingerprint,
+                } => Self::from_xpub(xpub, *chain, child_template, *master_fingerprint)?,
+                WatchSource::Addresses { chain, addresses } => {
+                    Self::from_addresses(*chain, addresses)?
+                }
+            },
+        };
+
+        wallet.id = record.id.clone();
+        // Imported keys have a single account; everything else re-derives
+        if wallet.is_hd() || wallet.watch.is_some() {
+            for &index in &record.account_indices {
+                wallet.derive_account(index)?;
+            }
+        }
+        for &index in &record.tron_account_indices {
+            wallet.derive_chain_account(Chain::Tron, index)?;
+        }
+
+        // Older records get the time they are first loaded
+        if let Some(secs) = record.created_at {
+            wallet.created_at = UNIX_EPOCH + Duration::from_secs(secs);
+        }
+        wallet.metadata = record.metadata.clone();
+        for entry in &record.account_metadata {
+            if let Some(account) = wallet.find_account_mut(entry.chain, entry.index) {
+                account.metadata = entry.metadata.clone();
+            }
+        }
+
+        Ok(wallet)
+    }
+
+    /// Snapshot the secret material for encrypted persistence
+    pub(crate) fn to_record(&self) -> Result<WalletRecord> {
+        let secret = match (&self.mnemonic, &self.seed, &self.imported_key, &self.watch) {
+            (Some(phrase), _, _, _) => WalletSecret::Mnemonic {
+                phrase: phrase.expose().to_string(),
+                passphrase: self.passphrase.as_ref().map(|p| p.expose().to_string()),
+            },
+            (None, Some(seed), _, _) => WalletSecret::MasterSecret {
+                secret: hex::encode(seed.expose()),
+            },
+            (None, None, Some(key), _) => WalletSecret::PrivateKey {
+                key: hex::encode(key.expose()),
+            },
+            (None, None, None, Some(source)) => WalletSecret::WatchOnly {
+                source: source.clone(),
+            },
+            (None, None, None, None) => return Err(CoreError::InternalError),
+        };
+
+        Ok(WalletRecord {
+            id: self.id.clone(),
+            secret,
+            account_indices: self.indices(Chain::Evm),
+            scheme: self.scheme.clone(),
+            tron_account_indices: self.indices(Chain::Tron),
+            created_at: self
+                .created_at
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs()),
+            metadata: self.metadata.clone(),
+            account_metadata: self
+                .accounts
+                .iter()
+                .filter(|a| a.metadata != AccountMetadata::default())
+                .map(|a| AccountMetadataRecord {
+                    chain: a.chain,
+                    index: a.index,
+                    metadata: a.metadata.clone(),
+                })
+                .collect(),
+        })
+    }
+
+    /// Export mnemonic (if available)
+    pub fn export_mnemonic(&self) -> Result<SecretString> {
+        if self.is_watch_only() {
+            return Err(CoreError::WatchOnly);
+        }
+        self.mnemonic
+            .as_ref()
+            .map(|m| SecretString::new(m.expose().to_string()))
+            .ok_or(CoreError::InvalidInput)
+    }
+
+    /// Account-level extended public key for `chain`, from which a watch-only
+    /// wallet can derive the same addresses.
+    ///
+    /// Only schemes whose index sits below the last hardened level can be
+    /// watched this way, so Ledger Live layouts are rejected.
+    pub fn export_xpub(&self, chain: Chain) -> Result<AccountXpub> {
+        if let Some(WatchSource::Xpub {
+            xpub,
+            chain: watched,
+            child_template,
+            master_fingerprint,
+        }) = &self.watch
+        {
+            if *watched != chain {
+                return Err(CoreError::InvalidInput);
+            }
+            return Ok(AccountXpub {
+                xpub: xpub.clone(),
+                chain,
+                account_path: String::new(),
+                child_template: child_template.clone(),
+                master_fingerprint: *master_fingerprint,
+            });
+        }
+        if !self.is_hd() {
+            return Err(CoreError::InvalidInput);
+        }
+
+        let template = match chain {
+            Chain::Evm => self.scheme.template(),
+            Chain::Tron => TRON_DERIVATION_TEMPLATE,
+        };
+        let (account_path, child_template) = split_account_template(template)?;
+        let master_key = self.master_key()?;
+        let account_key = derive_xprv(&master_key, &account_path)?;
+
+        Ok(AccountXpub {
+            xpub: account_key.public_key().to_string(Prefix::XPUB),
+            chain,
+            account_path,
+            child_template,
+            master_fingerprint: Some(u32::from_be_bytes(master_key.public_key().fingerprint())),
+        })
+    }
+
+    /// BIP-32 fingerprint of the master key, as QR and hardware signers use
+    /// to tell seeds apart. Known for seed wallets and for xpub imports that
+    /// were given it.
+    pub fn master_fingerprint(&self) -> Option<u32> {
+        match &self.watch {
+            Some(WatchSource::Xpub {
+                master_fingerprint, ..
+            }) => *master_fingerprint,
+            Some(WatchSource::Addresses { .. }) => None,
+            None if self.is_hd() => self
+                .master_key()
+                .ok()
+                .map(|key| u32::from_be_bytes(key.public_key().fingerprint())),
+            None => None,
+        }
+    }
+
+    /// Export private key for specific account
+    pub fn export_private_key(&self, account_index: u32) -> Result<SecretString> {
+        self.with_secret_key(Chain::Evm, account_index, |secret_key| {
+            let bytes = Zeroizing::new(secret_key.secret_bytes());
+            let encoded = Zeroizing::new(hex::encode(*bytes));
+            Ok(SecretString::new(format!("0x{}", *encoded)))
+        })
     }
 
     /// Derive a new account
     pub fn derive_account(&mut self, index: u32) -> Result<Account> {
-        // Check if already derived
-        if let Some(existing) = self.accounts.iter().find(|a| a.index == index) {
-            return Ok(Account {
-                address: existing.address.clone(),
-                public_key: format!("0x{}", hex::encode(existing.public_key.serialize())),
-                index,
-                derivation_path: existing.derivation_path.clone(),
-            });
+        self.derive_chain_account(Chain::Evm, index)
+    }
+
+    /// Derive an account for `chain`, or return it if already derived
+    pub fn derive_chain_account(&mut self, chain: Chain, index: u32) -> Result<Account> {
+        if let Some(existing) = self.find_account(chain, index) {
+            return Ok(existing.to_account());
         }
 
-        self.derive_account_internal(index)
+        self.derive_account_internal(chain, index)
     }
 
-    fn derive_account_internal(&mut self, index: u32) -> Result<Account> {
-        let path = format!("{}/{}", DEFAULT_DERIVATION_PATH, index);
-        let derivation_path =
-            DerivationPath::from_str(&path).map_err(|_| CoreError::InternalError)?;
+    fn derive_account_internal(&mut self, chain: Chain, index: u32) -> Result<Account> {
+        if let Some(source) = &self.watch {
+            let account = match source {
+                WatchSource::Xpub {
+                    xpub,
+                    chain: watched,
+                    child_template,
+                    ..
+                } if *watched == chain => {
+                    let parsed = XPub::from_str(xpub).map_err(|_| CoreError::InvalidInput)?;
+                    let (public_key, path) = xpub_child(&parsed, child_template, index)?;
+                    account_for_public_key(chain, index, public_key, path)
+                }
+                // Address lists are fully populated at creation
+                _ => return Err(CoreError::InvalidInput),
+            };
 
-        let derived_key =
-            derivation_path
-                .iter()
-                .try_fold(self.master_key.clone(), |key, child_index| {
-                    key.derive_child(child_index.clone())
-                        .map_err(|_| CoreError::InternalError)
-                })?;
-
-        // Convert k256::SigningKey to secp256k1::SecretKey
-        let k256_private_key = derived_key.private_key();
-        let secret_bytes = k256_private_key.to_bytes();
-        let secret_key =
-            SecretKey::from_slice(&secret_bytes).map_err(|_| CoreError::InternalError)?;
-
-        let secp = Secp256k1::new();
-        let public_key = PublicKey::from_secret_key(&secp, &secret_key);
-        let address = public_key_to_address(&public_key);
-
-        let account = DerivedAccount {
-            index,
-            secret_key,
-            public_key,
-            address: address.clone(),
-            derivation_path: path.clone(),
+            let data = account.to_account();
+            self.accounts.push(account);
+            return Ok(data);
+        }
+
+        let account = match (chain, self.is_hd()) {
+            (Chain::Evm, true) => {
+                derive_at(&self.master_key()?, chain, &self.scheme.path(index), index)?
+            }
+            (Chain::Tron, true) => {
+                let path = TRON_DERIVATION_TEMPLATE
+                    .replace(DerivationScheme::INDEX_PLACEHOLDER, &index.to_string());
+                derive_at(&self.master_key()?, chain, &path, index)?
+            }
+            // An imported key has one account, addressable on every chain
+            (_, false) if index == 0 => self.imported_account(chain)?,
+            (_, false) => return Err(CoreError::InvalidInput),
         };
 
+        let data = account.to_account();
         self.accounts.push(account);
 
-        Ok(Account {
-            address,
-            public_key: format!("0x{}", hex::encode(public_key.serialize())),
-            index,
-            derivation_path: path,
-        })
+        Ok(data)
+    }
+
+    /// Derive `count` accounts from `start` without adding them to the wallet.
+    /// `scheme` applies to EVM accounts of mnemonic wallets only.
+    pub fn preview_accounts(
+        &self,
+        chain: Chain,
+        scheme: &DerivationScheme,
+        start: u32,
+        count: u32,
+    ) -> Result<Vec<Account>> {
+        let indices = start..start.saturating_add(count);
+
+        match (&self.watch, self.is_hd()) {
+            (
+                Some(WatchSource::Xpub {
+                    xpub,
+                    chain: watched,
+                    child_template,
+                    ..
+                }),
+                _,
+            ) if *watched == chain => {
+                let parsed = XPub::from_str(xpub).map_err(|_| CoreError::InvalidInput)?;
+                indices
+                    .map(|index| {
+                        let (public_key, path) = xpub_child(&parsed, child_template, index)?;
+                        Ok(account_for_public_key(chain, index, public_key, path).to_account())
+                    })
+                    .collect()
+            }
+            (Some(WatchSource::Addresses { chain: watched, .. }), _) if *watched == chain => {
+                Ok(self
+                    .accounts
+                    .iter()
+                    .filter(|a| indices.contains(&a.index))
+                    .map(DerivedAccount::to_account)
+                    .collect())
+            }
+            (Some(_), _) => Err(CoreError::InvalidInput),
+            (None, true) => {
+                validate_scheme(scheme)?;
+                let master_key = self.master_key()?;
+                indices
+                    .map(|index| {
+                        let path = match chain {
+                            Chain::Evm => scheme.path(index),
+                            Chain::Tron => TRON_DERIVATION_TEMPLATE
+                                .replace(DerivationScheme::INDEX_PLACEHOLDER, &index.to_string()),
+                        };
+                        derive_at(&master_key, chain, &path, index).map(|a| a.to_account())
+                    })
+                    .collect()
+            }
+            // An imported key only has account 0
+            (None, false) if indices.contains(&0) => {
+                Ok(vec![self.imported_account(chain)?.to_account()])
+            }
+            (None, false) => Ok(Vec::new()),
+        }
+    }
+
+    fn imported_account(&self, chain: Chain) -> Result<DerivedAccount> {
+        let key = self.imported_key.as_ref().ok_or(CoreError::InternalError)?;
+        let mut secret_key = SecretKey::from_slice(key.expose())?;
+        let account = account_for_key(chain, 0, &secret_key, "imported".to_string());
+        secret_key.non_secure_erase();
+        Ok(account)
+    }
+
+    fn find_account(&self, chain: Chain, index: u32) -> Option<&DerivedAccount> {
+        self.accounts
+            .iter()
+            .find(|a| a.chain == chain && a.index == index)
+    }
+
+    fn find_account_mut(&mut self, chain: Chain, index: u32) -> Option<&mut DerivedAccount> {
+        self.accounts
+            .iter_mut()
+            .find(|a| a.chain == chain && a.index == index)
+    }
+
+    pub fn metadata(&self) -> &WalletMetadata {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: WalletMetadata) {
+        self.metadata = metadata;
+    }
+
+    /// Set the metadata of an already derived account
+    pub fn set_account_metadata(
+        &mut self,
+        chain: Chain,
+        index: u32,
+        metadata: AccountMetadata,
+    ) -> Result<()> {
+        let account = self
+            .find_account_mut(chain, index)
+            .ok_or(CoreError::InvalidInput)?;
+        account.metadata = metadata;
+        Ok(())
+    }
+
+    fn indices(&self, chain: Chain) -> Vec<u32> {
+        self.accounts
+            .iter()
+            .filter(|a| a.chain == chain)
+            .map(|a| a.index)
+            .collect()
+    }
+
+    pub fn scheme(&self) -> &DerivationScheme {
+        &self.scheme
+    }
+
+    /// Identity of the wallet's key material, the same on every device.
+    ///
+    /// Seed wallets hash the master public key, so the passphrase and seed
+    /// matter but the derivation scheme does not; imported keys hash their
+    /// public key and watch-only wallets what they watch.
+    pub fn fingerprint(&self) -> String {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"nor-wallet-fingerprint");
+        match (&self.watch, self.is_hd()) {
+            (
+                Some(WatchSource::Xpub {
+                    xpub,
+                    chain,
+                    child_template,
+                    ..
+                }),
+                _,
+            ) => {
+                hasher.update(b"xpub");
+                hasher.update(chain_tag(*chain));
+                // Hash the key itself, so the version prefix does not matter
+                if let Ok(parsed) = XPub::from_str(xpub) {
+                    hasher.update(parsed.public_key().to_bytes());
+                    hasher.update(parsed.attrs().chain_code);
+                }
+                hasher.update(child_template.as_bytes());
+            }
+            (Some(WatchSource::Addresses { chain, addresses }), _) => {
+                hasher.update(b"addresses");
+                hasher.update(chain_tag(*chain));
+                for address in addresses {
+                    hasher.update(address.to_lowercase().as_bytes());
+                }
+            }
+            (None, true) => {
+                hasher.update(b"seed");
+                if let Ok(master_key) = self.master_key() {
+                    hasher.update(master_key.public_key().to_bytes());
+                }
+            }
+            (None, false) => {
+                hasher.update(b"key");
+                if let Some(public_key) =
+                    self.find_account(Chain::Evm, 0).and_then(|a| a.public_key)
+                {
+                    hasher.update(public_key.serialize());
+                }
+            }
+        }
+
+        hex::encode(&hasher.finalize()[..16])
     }
 
     /// Convert to public Wallet type
     pub fn to_wallet_data(&self) -> crate::types::Wallet {
-        let accounts = self
+        let mut accounts: Vec<Account> = self
             .accounts
             .iter()
-            .map(|a| Account {
-                address: a.address.clone(),
-                public_key: format!("0x{}", hex::encode(a.public_key.serialize())),
-                index: a.index,
-                derivation_path: a.derivation_path.clone(),
-            })
+            .map(DerivedAccount::to_account)
             .collect();
+        accounts.sort_by_key(|a| a.metadata.sort_order);
 
         crate::types::Wallet {
             id: self.id.clone(),
+            fingerprint: self.fingerprint(),
             accounts,
-            created_at: std::time::SystemTime::now(),
+            created_at: self.created_at,
+            metadata: self.metadata.clone(),
         }
     }
 
-    /// Get secret key for account (for signing)
-    pub fn get_secret_key(&self, account_index: u32) -> Result<SecretKey> {
-        self.accounts
-            .iter()
-            .find(|a| a.index == account_index)
-            .map(|a| a.secret_key)
+    /// Lend the signing key of an already derived account to `f`.
+    ///
+    /// The key is derived for the call and erased when `f` returns or
+    /// panics, so it never outlives the borrow.
+    pub fn with_secret_key<T>(
+        &self,
+        chain: Chain,
+        account_index: u32,
+        f: impl FnOnce(&SecretKey) -> Result<T>,
+    ) -> Result<T> {
+        if self.is_watch_only() {
+            return Err(CoreError::WatchOnly);
+        }
+        let account = self
+            .find_account(chain, account_index)
+            .ok_or(CoreError::InvalidInput)?;
+
+        let secret_key = ErasedOnDrop(match &self.imported_key {
+            Some(key) => SecretKey::from_slice(key.expose())?,
+            None => secret_key_at(&self.master_key()?, &account.derivation_path)?,
+        });
+        f(&secret_key.0)
+    }
+
+    /// Address of an already derived account
+    pub fn get_address(&self, chain: Chain, account_index: u32) -> Result<String> {
+        self.find_account(chain, account_index)
+            .map(|a| a.address.clone())
+            .ok_or(CoreError::InvalidInput)
+    }
+
+    /// Public key of an already derived account; address-only watch wallets have none
+    pub fn get_public_key(&self, chain: Chain, account_index: u32) -> Result<PublicKey> {
+        self.find_account(chain, account_index)
+            .and_then(|a| a.public_key)
             .ok_or(CoreError::InvalidInput)
     }
 }
 
+impl Wallet {
+    /// Zero every secret buffer in place
+    fn wipe(&mut self) {
+        let secrets = [self.mnemonic.as_mut(), self.passphrase.as_mut()];
+        for secret in secrets.into_iter().flatten() {
+            secret.wipe();
+        }
+        let bytes = [self.seed.as_mut(), self.imported_key.as_mut()];
+        for secret in bytes.into_iter().flatten() {
+            secret.wipe();
+        }
+    }
+}
+
 impl Drop for Wallet {
     fn drop(&mut self) {
-        // Zeroize sensitive data
-        if let Some(ref mut mnemonic) = self.mnemonic {
-            mnemonic.zeroize();
+        // The containers wipe themselves too; doing it here covers every
+        // secret at once, before any field is freed
+        self.wipe();
+    }
+}
+
+impl DerivedAccount {
+    fn to_account(&self) -> Account {
+        Account {
+            address: self.address.clone(),
+            public_key: self
+                .public_key
+                .map(|key| format!("0x{}", hex::encode(key.serialize())))
+                .unwrap_or_default(),
+            index: self.index,
+            derivation_path: self.derivation_path.clone(),
+            chain: self.chain,
+            metadata: self.metadata.clone(),
         }
-        for account in &mut self.accounts {
-            account.secret_key.non_secure_erase();
+    }
+}
+
+/// Derive the first `count` accounts of a mnemonic under every known scheme,
+/// so users can find which layout their previous wallet used
+pub fn scan_derivation_schemes(
+    mnemonic_str: &str,
+    passphrase: Option<&str>,
+    count: u32,
+) -> Result<Vec<SchemeAccounts>> {
+    let mnemonic = mnemonic::parse(mnemonic_str, None)?;
+    let seed = mnemonic.to_seed(passphrase.unwrap_or(""));
+    let master_key = XPrv::new(seed).map_err(|_| CoreError::InternalError)?;
+
+    DerivationScheme::known()
+        .into_iter()
+        .map(|scheme| {
+            let accounts = (0..count)
+                .map(|index| {
+                    derive_at(&master_key, Chain::Evm, &scheme.path(index), index)
+                        .map(|a| a.to_account())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(SchemeAccounts { scheme, accounts })
+        })
+        .collect()
+}
+
+/// Now, to the whole second records keep, so a reloaded wallet sorts the same
+fn creation_time() -> SystemTime {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn chain_tag(chain: Chain) -> &'static [u8] {
+    match chain {
+        Chain::Evm => b"evm",
+        Chain::Tron => b"tron",
+    }
+}
+
+/// A custom template must contain exactly one `{index}` and form a valid path
+fn validate_scheme(scheme: &DerivationScheme) -> Result<()> {
+    if scheme
+        .template()
+        .matches(DerivationScheme::INDEX_PLACEHOLDER)
+        .count()
+        != 1
+    {
+        return Err(CoreError::InvalidInput);
+    }
+    DerivationPath::from_str(&scheme.path(0)).map_err(|_| CoreError::InvalidInput)?;
+    Ok(())
+}
+
+fn derive_at(master_key: &XPrv, chain: Chain, path: &str, index: u32) -> Result<DerivedAccount> {
+    let mut secret_key = secret_key_at(master_key, path)?;
+    let account = account_for_key(chain, index, &secret_key, path.to_string());
+    secret_key.non_secure_erase();
+    Ok(account)
+}
+
+/// A lent signing key, erased when dropped so a panic in the borrower
+/// cannot leave it behind
+struct ErasedOnDrop(SecretKey);
+
+impl Drop for ErasedOnDrop {
+    fn drop(&mut self) {
+        self.0.non_secure_erase();
+    }
+}
+
+/// Signing key at `path`; callers erase it once done
+fn secret_key_at(master_key: &XPrv, path: &str) -> Result<SecretKey> {
+    let derived_key = derive_xprv(master_key, path)?;
+
+    // Convert k256::SigningKey to secp256k1::SecretKey
+    let secret_bytes = Zeroizing::new(<[u8; 32]>::from(derived_key.private_key().to_bytes()));
+    SecretKey::from_slice(secret_bytes.as_slice()).map_err(|_| CoreError::InternalError)
+}
+
+fn derive_xprv(master_key: &XPrv, path: &str) -> Result<XPrv> {
+    let derivation_path = DerivationPath::from_str(path).map_err(|_| CoreError::InvalidInput)?;
+
+    let derived_key = derivation_path
+        .iter()
+        .try_fold(master_key.clone(), |key, child_index| {
+            key.derive_child(child_index)
+                .map_err(|_| CoreError::InternalError)
+        })?;
+    Ok(derived_key)
+}
+
+fn account_for_key(
+    chain: Chain,
+    index: u32,
+    secret_key: &SecretKey,
+    derivation_path: String,
+) -> DerivedAccount {
+    let secp = Secp256k1::new();
+    let public_key = PublicKey::from_secret_key(&secp, secret_key);
+    account_for_public_key(chain, index, public_key, derivation_path)
+}
+
+fn account_for_public_key(
+    chain: Chain,
+    index: u32,
+    public_key: PublicKey,
+    derivation_path: String,
+) -> DerivedAccount {
+    let address = match chain {
+        Chain::Evm => public_key_to_address(&public_key),
+        Chain::Tron => public_key_to_tron_address(&public_key),
+    };
+
+    DerivedAccount {
+        chain,
+        index,
+        public_key: Some(public_key),
+        address,
+        derivation_path,
+        metadata: AccountMetadata::default(),
+    }
+}
+
+/// Split a path template into its hardened account prefix and the
+/// non-hardened remainder, e.g. `m/44'/60'/0'` and `0/{index}`
+fn split_account_template(template: &str) -> Result<(String, String)> {
+    let segments: Vec<&str> = template.split('/').collect();
+    let first_soft = segments
+        .iter()
+        .skip(1)
+        .position(|segment| !segment.ends_with('\'') && !segment.ends_with('h'))
+        .map(|position| position + 1)
+        .ok_or(CoreError::InvalidInput)?;
+
+    let account_path = segments[..first_soft].join("/");
+    let child_template = segments[first_soft..].join("/");
+    if account_path.contains(DerivationScheme::INDEX_PLACEHOLDER)
+        || child_template.contains('\'')
+        || !child_template.contains(DerivationScheme::INDEX_PLACEHOLDER)
+    {
+        return Err(CoreError::InvalidInput);
+    }
+
+    Ok((account_path, child_template))
+}
+
+/// Public key at `child_template` below an xpub, and its relative path
+fn xpub_child(xpub: &XPub, child_template: &str, index: u32) -> Result<(PublicKey, String)> {
+    let relative = child_template.replace(DerivationScheme::INDEX_PLACEHOLDER, &index.to_string());
+
+    let child = relative.split('/').try_fold(xpub.clone(), |key, segment| {
+        let number = segment
+            .parse::<u32>()
+            .map_err(|_| CoreError::InvalidInput)?;
+        let child_number = ChildNumber::new(number, false).map_err(|_| CoreError::InvalidInput)?;
+        key.derive_child(child_number)
+            .map_err(|_| CoreError::InternalError)
+    })?;
+
+    let public_key = PublicKey::from_slice(&child.public_key().to_bytes())?;
+    Ok((public_key, format!("xpub/{}", relative)))
+}
+
+/// Lowercase 0x addresses, checksum-verify TRON ones
+fn normalize_address(chain: Chain, address: &str) -> Result<String> {
+    let valid = match chain {
+        Chain::Evm => {
+            address.len() == 42
+                && address.starts_with("0x")
+                && address[2..].chars().all(|c| c.is_ascii_hexdigit())
         }
+        Chain::Tron => crate::tron::validate_address(address.to_string())?,
+    };
+    if !valid {
+        return Err(CoreError::InvalidAddress);
     }
+
+    Ok(match chain {
+        Chain::Evm => address.to_lowercase(),
+        Chain::Tron => address.to_string(),
+    })
 }
 
 /// Convert public key to Ethereum address
-fn public_key_to_address(public_key: &PublicKey) -> String {
+pub(crate) fn public_key_to_address(public_key: &PublicKey) -> String {
     let public_key_bytes = &public_key.serialize_uncompressed()[1..]; // Remove 0x04 prefix
 
     let mut hasher = Keccak::v256();
@@ -220,6 +1052,21 @@ fn public_key_to_address(public_key: &PublicKey) -> String {
     format!("0x{}", hex::encode(&hash[12..]))
 }
 
+/// Convert public key to a base58check TRON address (`T...`)
+pub(crate) fn public_key_to_tron_address(public_key: &PublicKey) -> String {
+    let public_key_bytes = &public_key.serialize_uncompressed()[1..];
+
+    let mut hasher = Keccak::v256();
+    let mut hash = [0u8; 32];
+    hasher.update(public_key_bytes);
+    hasher.finalize(&mut hash);
+
+    let mut payload = Vec::with_capacity(21);
+    payload.push(TRON_ADDRESS_PREFIX);
+    payload.extend_from_slice(&hash[12..]);
+    bs58::encode(payload).with_check().into_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -244,6 +1091,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_record_round_trip() {
+        let mut wallet = Wallet::from_entropy(&[5u8; 16], Some("extra words")).unwrap();
+        wallet.derive_account(4).unwrap();
+
+        let restored = Wallet::from_record(&wallet.to_record().unwrap()).unwrap();
+        assert_eq!(restored.id, wallet.id);
+        assert_eq!(restored.accounts.len(), 2);
+        assert_eq!(restored.accounts[1].address, wallet.accounts[1].address);
+
+        let imported = Wallet::from_private_key(
+            "0x4646464646464646464646464646464646464646464646464646464646464646",
+        )
+        .unwrap();
+        let restored = Wallet::from_record(&imported.to_record().unwrap()).unwrap();
+        assert_eq!(restored.accounts[0].address, imported.accounts[0].address);
+    }
+
+    #[test]
+    fn test_record_keeps_metadata_and_creation_time() {
+        let mut wallet = Wallet::from_entropy(&[6u8; 16], None).unwrap();
+        wallet.derive_account(1).unwrap();
+        wallet.created_at = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        wallet.set_metadata(WalletMetadata {
+            label: Some("Cold".to_string()),
+            sort_order: 3,
+            ..Default::default()
+        });
+        let account = AccountMetadata {
+            label: Some("Rent".to_string()),
+            sort_order: 0,
+            hidden: true,
+            ..Default::default()
+        };
+        wallet
+            .set_account_metadata(Chain::Evm, 1, account.clone())
+            .unwrap();
+        assert!(wallet
+            .set_account_metadata(Chain::Tron, 0, AccountMetadata::default())
+            .is_err());
+
+        let restored = Wallet::from_record(&wallet.to_record().unwrap()).unwrap();
+        assert_eq!(restored.created_at, wallet.created_at);
+        assert_eq!(restored.metadata(), wallet.metadata());
+        let data = restored.to_wallet_data();
+        assert_eq!(data.accounts[1].metadata, account);
+
+        // Records written before metadata existed still load
+        let mut legacy = serde_json::to_value(wallet.to_record().unwrap()).unwrap();
+        for field in ["created_at", "metadata", "account_metadata"] {
+            legacy.as_object_mut().unwrap().remove(field);
+        }
+        let legacy: WalletRecord = serde_json::from_value(legacy).unwrap();
+        let loaded = Wallet::from_record(&legacy).unwrap();
+        assert_eq!(loaded.metadata(), &WalletMetadata::default());
+    }
+
     #[test]
     fn test_derive_multiple_accounts() {
         let entropy = [1u8; 16];
@@ -255,4 +1159,392 @@ mod tests {
         assert_ne!(account1.address, account2.address);
         assert_eq!(wallet.accounts.len(), 3); // 0, 1, 2
     }
+
+    const ABANDON: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_scheme_paths() {
+        let mut ledger =
+            Wallet::from_mnemonic_with_scheme(ABANDON, None, DerivationScheme::LedgerLive).unwrap();
+        let mut mew =
+            Wallet::from_mnemonic_with_scheme(ABANDON, None, DerivationScheme::LegacyMew).unwrap();
+
+        // Ledger Live and BIP44 agree on the first account only
+        assert_eq!(
+            ledger.accounts[0].address,
+            "0x9858effd232b4033e47d90003d41ec34ecaeda94"
+        );
+        assert_eq!(
+            ledger.derive_account(2).unwrap().derivation_path,
+            "m/44'/60'/2'/0/0"
+        );
+        assert_eq!(
+            mew.derive_account(2).unwrap().derivation_path,
+            "m/44'/60'/0'/2"
+        );
+        assert_ne!(mew.accounts[0].address, ledger.accounts[0].address);
+
+        let custom = Wallet::from_mnemonic_with_scheme(
+            ABANDON,
+            None,
+            DerivationScheme::Custom {
+                template: "m/44'/60'/0'/{index}".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(custom.accounts[0].address, mew.accounts[0].address);
+    }
+
+    #[test]
+    fn test_rejects_invalid_custom_template() {
+        for template in [
+            "m/44'/60'/0'/0",
+            "m/44'/{index}'/{index}",
+            "44/60/{index}",
+            "m/x/{index}",
+        ] {
+            assert!(matches!(
+                Wallet::from_mnemonic_with_scheme(
+                    ABANDON,
+                    None,
+                    DerivationScheme::Custom {
+                        template: template.to_string()
+                    }
+                ),
+                Err(CoreError::InvalidInput)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_record_keeps_scheme() {
+        let mut wallet =
+            Wallet::from_entropy_with_scheme(&[2u8; 16], None, DerivationScheme::LedgerLive)
+                .unwrap();
+        wallet.derive_account(1).unwrap();
+
+        let restored = Wallet::from_record(&wallet.to_record().unwrap()).unwrap();
+        assert_eq!(restored.scheme(), &DerivationScheme::LedgerLive);
+        assert_eq!(restored.accounts[1].address, wallet.accounts[1].address);
+
+        // Records persisted before schemes existed load as BIP44
+        let legacy: WalletRecord = serde_json::from_str(&format!(
+            r#"{{"id":"x","secret":{{"kind":"mnemonic","phrase":"{ABANDON}","passphrase":null}},"account_indices":[0]}}"#
+        ))
+        .unwrap();
+        assert_eq!(legacy.scheme, DerivationScheme::Bip44);
+    }
+
+    #[test]
+    fn test_tron_address_encoding() {
+        let secret_key = SecretKey::from_slice(&[[0u8; 31].as_slice(), &[1]].concat()).unwrap();
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+
+        assert_eq!(
+            public_key_to_address(&public_key),
+            "0x7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+        assert_eq!(
+            public_key_to_tron_address(&public_key),
+            "TMVQGm1qAQYVdetCeGRRkTWYYrLXuHK2HC"
+        );
+    }
+
+    #[test]
+    fn test_tron_accounts() {
+        let mut wallet = Wallet::from_mnemonic(ABANDON, None).unwrap();
+        let tron = wallet.derive_chain_account(Chain::Tron, 0).unwrap();
+
+        assert_eq!(tron.chain, Chain::Tron);
+        assert_eq!(tron.derivation_path, "m/44'/195'/0'/0/0");
+        assert_eq!(tron.address, "TUEZSdKsoDHQMeZwihtdoBiN46zxhGWYdH");
+        assert!(crate::tron::validate_address(tron.address.clone()).unwrap());
+        let key_of = |chain| {
+            wallet
+                .with_secret_key(chain, 0, |key| Ok(key.secret_bytes()))
+                .unwrap()
+        };
+        assert_ne!(key_of(Chain::Tron), key_of(Chain::Evm));
+
+        // TRON indices survive persistence alongside the EVM ones
+        wallet.derive_chain_account(Chain::Tron, 5).unwrap();
+        let restored = Wallet::from_record(&wallet.to_record().unwrap()).unwrap();
+        assert_eq!(
+            restored.get_address(Chain::Tron, 5).unwrap(),
+            wallet.get_address(Chain::Tron, 5).unwrap()
+        );
+        assert_eq!(restored.to_wallet_data().accounts.len(), 3);
+    }
+
+    #[test]
+    fn test_imported_key_tron_account() {
+        let mut wallet = Wallet::from_private_key(
+            "0x0000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+
+        let tron = wallet.derive_chain_account(Chain::Tron, 0).unwrap();
+        assert_eq!(tron.address, "TMVQGm1qAQYVdetCeGRRkTWYYrLXuHK2HC");
+        assert!(wallet.derive_chain_account(Chain::Tron, 1).is_err());
+
+        let restored = Wallet::from_record(&wallet.to_record().unwrap()).unwrap();
+        assert_eq!(restored.get_address(Chain::Tron, 0).unwrap(), tron.address);
+    }
+
+    #[test]
+    fn test_xpub_watch_only_matches_wallet() {
+        let mut wallet = Wallet::from_mnemonic(ABANDON, None).unwrap();
+        wallet.derive_account(7).unwrap();
+        wallet.derive_chain_account(Chain::Tron, 2).unwrap();
+
+        let exported = wallet.export_xpub(Chain::Evm).unwrap();
+        assert_eq!(exported.account_path, "m/44'/60'/0'");
+        assert_eq!(exported.child_template, "0/{index}");
+        assert!(exported.xpub.starts_with("xpub"));
+        // The well-known fingerprint of the all-"abandon" seed
+        assert_eq!(exported.master_fingerprint, Some(0x73c5da0a));
+        assert_eq!(wallet.master_fingerprint(), Some(0x73c5da0a));
+
+        let mut watch = Wallet::from_xpub(
+            &exported.xpub,
+            Chain::Evm,
+            &exported.child_template,
+            exported.master_fingerprint,
+        )
+        .unwrap();
+        assert_eq!(watch.master_fingerprint(), Some(0x73c5da0a));
+        assert_eq!(watch.accounts[0].address, wallet.accounts[0].address);
+        assert_eq!(
+            watch.derive_account(7).unwrap().address,
+            wallet.get_address(Chain::Evm, 7).unwrap()
+        );
+
+        let tron = wallet.export_xpub(Chain::Tron).unwrap();
+        let mut watch_tron = Wallet::from_xpub(&tron.xpub, Chain::Tron, "0/{index}", None).unwrap();
+        assert_eq!(
+            watch_tron
+                .derive_chain_account(Chain::Tron, 2)
+                .unwrap()
+                .address,
+            wallet.get_address(Chain::Tron, 2).unwrap()
+        );
+
+        // Legacy MEW puts the index right below the account level
+        let mew =
+            Wallet::from_mnemonic_with_scheme(ABANDON, None, DerivationScheme::LegacyMew).unwrap();
+        let exported = mew.export_xpub(Chain::Evm).unwrap();
+        assert_eq!(exported.child_template, "{index}");
+        let watch = Wallet::from_xpub(&exported.xpub, Chain::Evm, "{index}", None).unwrap();
+        assert_eq!(watch.accounts[0].address, mew.accounts[0].address);
+
+        // Ledger Live hardens the index, so no single xpub covers it
+        let ledger =
+            Wallet::from_mnemonic_with_scheme(ABANDON, None, DerivationScheme::LedgerLive).unwrap();
+        assert!(ledger.export_xpub(Chain::Evm).is_err());
+    }
+
+    #[test]
+    fn test_watch_only_cannot_sign() {
+        let wallet = Wallet::from_mnemonic(ABANDON, None).unwrap();
+        let exported = wallet.export_xpub(Chain::Evm).unwrap();
+        let watch = Wallet::from_xpub(&exported.xpub, Chain::Evm, "0/{index}", None).unwrap();
+
+        assert!(watch.is_watch_only());
+        assert!(matches!(
+            watch.with_secret_key(Chain::Evm, 0, |_| Ok(())),
+            Err(CoreError::WatchOnly)
+        ));
+        assert!(matches!(
+            watch.export_private_key(0),
+            Err(CoreError::WatchOnly)
+        ));
+        assert!(matches!(watch.export_mnemonic(), Err(CoreError::WatchOnly)));
+        assert!(Wallet::from_xpub(&exported.xpub, Chain::Evm, "0'/{index}", None).is_err());
+        assert!(Wallet::from_xpub("xpub-nonsense", Chain::Evm, "0/{index}", None).is_err());
+    }
+
+    #[test]
+    fn test_address_watch_only() {
+        let addresses = vec![
+            "0x9858EfFD232B4033E47d90003D41EC34EcaEda94".to_string(),
+            "0x7e5f4552091a69125d5dfcb7b8c2659029395bdf".to_string(),
+        ];
+        let mut watch = Wallet::from_addresses(Chain::Evm, &addresses).unwrap();
+
+        let data = watch.to_wallet_data();
+        assert_eq!(data.accounts.len(), 2);
+        assert_eq!(
+            data.accounts[0].address,
+            "0x9858effd232b4033e47d90003d41ec34ecaeda94"
+        );
+        assert_eq!(data.accounts[1].index, 1);
+        assert_eq!(
+            watch.derive_account(1).unwrap().address,
+            data.accounts[1].address
+        );
+        assert!(watch.derive_account(2).is_err());
+        assert!(matches!(
+            watch.with_secret_key(Chain::Evm, 0, |_| Ok(())),
+            Err(CoreError::WatchOnly)
+        ));
+
+        let restored = Wallet::from_record(&watch.to_record().unwrap()).unwrap();
+        assert!(restored.is_watch_only());
+        assert_eq!(restored.to_wallet_data().accounts.len(), 2);
+
+        assert!(matches!(
+            Wallet::from_addresses(Chain::Evm, &["0x1234".to_string()]),
+            Err(CoreError::InvalidAddress)
+        ));
+        assert!(Wallet::from_addresses(
+            Chain::Tron,
+            &["TMVQGm1qAQYVdetCeGRRkTWYYrLXuHK2HC".to_string()]
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        let wallet = Wallet::from_mnemonic(ABANDON, None).unwrap();
+        let fingerprint = wallet.fingerprint();
+        assert_eq!(fingerprint.len(), 32);
+
+        // Independent of the random id and the derivation scheme
+        let again =
+            Wallet::from_mnemonic_with_scheme(ABANDON, None, DerivationScheme::LedgerLive).unwrap();
+        assert_ne!(again.id, wallet.id);
+        assert_eq!(again.fingerprint(), fingerprint);
+        assert_eq!(wallet.to_wallet_data().fingerprint, fingerprint);
+
+        // The same seed restored from SLIP-39 shares is the same wallet
+        let groups = [Slip39Group {
+            member_threshold: 1,
+            member_count: 1,
+        }];
+        let shares = wallet.to_slip39(1, &groups, "", 0).unwrap();
+        let restored = Wallet::from_slip39(&shares[0], "").unwrap();
+        assert_eq!(restored.fingerprint(), fingerprint);
+
+        // A passphrase, an imported key and a watch-only copy are all distinct
+        let with_passphrase = Wallet::from_mnemonic(ABANDON, Some("x")).unwrap();
+        assert_ne!(with_passphrase.fingerprint(), fingerprint);
+        let key = wallet.export_private_key(0).unwrap();
+        let imported = Wallet::from_private_key(key.expose()).unwrap();
+        assert_ne!(imported.fingerprint(), fingerprint);
+        assert_eq!(
+            Wallet::from_private_key(key.expose())
+                .unwrap()
+                .fingerprint(),
+            imported.fingerprint()
+        );
+        let xpub = wallet.export_xpub(Chain::Evm).unwrap();
+        let watch = Wallet::from_xpub(&xpub.xpub, Chain::Evm, &xpub.child_template, None).unwrap();
+        assert_ne!(watch.fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn test_non_english_mnemonic() {
+        let wallet = Wallet::from_entropy_in(
+            MnemonicLanguage::Japanese,
+            &[3u8; 16],
+            None,
+            DerivationScheme::Bip44,
+        )
+        .unwrap();
+        let phrase = wallet.export_mnemonic().unwrap();
+
+        // The language is detected on import and when reloading the record
+        let imported = Wallet::from_mnemonic(phrase.expose(), None).unwrap();
+        assert_eq!(imported.accounts[0].address, wallet.accounts[0].address);
+        let reloaded = Wallet::from_record(&wallet.to_record().unwrap()).unwrap();
+        assert_eq!(reloaded.accounts[0].address, wallet.accounts[0].address);
+
+        // Same entropy in another language is a different seed
+        let english = Wallet::from_entropy(&[3u8; 16], None).unwrap();
+        assert_ne!(english.accounts[0].address, wallet.accounts[0].address);
+    }
+
+    #[test]
+    fn test_slip39_backup_restores_accounts() {
+        let mut wallet = Wallet::from_mnemonic(ABANDON, Some("extra")).unwrap();
+        wallet.derive_account(2).unwrap();
+        let groups = [Slip39Group {
+            member_threshold: 2,
+            member_count: 3,
+        }];
+        let shares = wallet.to_slip39(1, &groups, "", 0).unwrap();
+        // The 64-byte BIP-39 seed makes 59-word shares
+        assert_eq!(shares[0][0].split(' ').count(), 59);
+
+        let mut restored = Wallet::from_slip39(&shares[0][1..], "").unwrap();
+        restored.derive_account(2).unwrap();
+        assert_eq!(restored.accounts[0].address, wallet.accounts[0].address);
+        assert_eq!(restored.accounts[1].address, wallet.accounts[1].address);
+        assert!(restored.export_mnemonic().is_err());
+
+        // Restored wallets persist their seed and can be split again
+        let reloaded = Wallet::from_record(&restored.to_record().unwrap()).unwrap();
+        assert_eq!(reloaded.accounts.len(), 2);
+        assert_eq!(reloaded.accounts[1].address, wallet.accounts[1].address);
+        assert!(reloaded.to_slip39(1, &groups, "", 0).is_ok());
+
+        let imported = Wallet::from_private_key(
+            "0x4646464646464646464646464646464646464646464646464646464646464646",
+        )
+        .unwrap();
+        assert!(imported.to_slip39(1, &groups, "", 0).is_err());
+    }
+
+    #[test]
+    fn test_signing_key_is_borrowed_not_stored() {
+        let wallet = Wallet::from_mnemonic(ABANDON, None).unwrap();
+        let public_key = wallet
+            .with_secret_key(Chain::Evm, 0, |key| {
+                Ok(PublicKey::from_secret_key(&Secp256k1::new(), key))
+            })
+            .unwrap();
+        assert_eq!(
+            public_key_to_address(&public_key),
+            "0x9858effd232b4033e47d90003d41ec34ecaeda94"
+        );
+        assert!(wallet.with_secret_key(Chain::Evm, 1, |_| Ok(())).is_err());
+
+        let key = wallet.export_private_key(0).unwrap();
+        assert_eq!(format!("{:?}", key), "SecretString(<redacted>)");
+        assert_eq!(key.expose().len(), 66);
+    }
+
+    #[test]
+    fn test_wipe_clears_wallet_secrets() {
+        let mut wallet = Wallet::from_mnemonic(ABANDON, Some("extra")).unwrap();
+        let seed = wallet.seed.as_ref().unwrap().buffer();
+        assert!(!secret::is_wiped(seed));
+
+        // What Drop runs, observed while the buffers are still allocated
+        wallet.wipe();
+        assert!(secret::is_wiped(seed));
+        assert_eq!(wallet.mnemonic.as_ref().unwrap().expose(), "");
+        assert_eq!(wallet.passphrase.as_ref().unwrap().expose(), "");
+
+        let mut imported = Wallet::from_private_key(
+            "0x4646464646464646464646464646464646464646464646464646464646464646",
+        )
+        .unwrap();
+        let key = imported.imported_key.as_ref().unwrap().buffer();
+        imported.wipe();
+        assert!(secret::is_wiped(key));
+    }
+
+    #[test]
+    fn test_scan_derivation_schemes() {
+        let scans = scan_derivation_schemes(ABANDON, None, 3).unwrap();
+        assert_eq!(scans.len(), 3);
+        assert!(scans.iter().all(|scan| scan.accounts.len() == 3));
+        assert_eq!(scans[0].scheme, DerivationScheme::Bip44);
+        assert_eq!(
+            scans[0].accounts[0].address,
+            "0x9858effd232b4033e47d90003d41ec34ecaeda94"
+        );
+        assert_eq!(scans[1].accounts[2].derivation_path, "m/44'/60'/2'/0/0");
+    }
 }
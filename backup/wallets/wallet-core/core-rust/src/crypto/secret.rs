@@ -0,0 +1,235 @@
+// Containers for secret material
+// Wiped on drop, never cloned or printed, and kept out of swap with `mlock`
+
+#[cfg(any(test, all(feature = "mlock", target_os = "linux")))]
+use std::collections::BTreeMap;
+use std::fmt;
+use zeroize::Zeroize;
+
+/// Secret text such as a mnemonic, passphrase or exported private key.
+///
+/// Deliberately not `Clone`; `Debug` prints a placeholder instead of the value.
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(value: String) -> Self {
+        memlock::lock(value.as_ptr(), value.capacity());
+        Self(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Zero the buffer in place, keeping its allocation
+    pub(crate) fn wipe(&mut self) {
+        self.0.zeroize();
+    }
+
+    #[cfg(test)]
+    fn buffer(&self) -> (*const u8, usize) {
+        (self.0.as_ptr(), self.0.capacity())
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.wipe();
+        memlock::unlock(self.0.as_ptr(), self.0.capacity());
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(<redacted>)")
+    }
+}
+
+/// Secret bytes such as a BIP-32 seed or a raw private key
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    pub fn new(value: Vec<u8>) -> Self {
+        memlock::lock(value.as_ptr(), value.capacity());
+        Self(value)
+    }
+
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Zero the buffer in place, keeping its allocation
+    pub(crate) fn wipe(&mut self) {
+        self.0.zeroize();
+    }
+
+    #[cfg(test)]
+    pub(crate) fn buffer(&self) -> (*const u8, usize) {
+        (self.0.as_ptr(), self.0.capacity())
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.wipe();
+        memlock::unlock(self.0.as_ptr(), self.0.capacity());
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes(<{} bytes redacted>)", self.0.len())
+    }
+}
+
+/// Best-effort page locking. Failures (e.g. RLIMIT_MEMLOCK) are ignored: the
+/// secret is still wiped on drop, it just may reach swap.
+#[cfg(all(feature = "mlock", target_os = "linux"))]
+mod memlock {
+    use super::PageCounts;
+    use std::sync::Mutex;
+
+    static PAGES: Mutex<PageCounts> = Mutex::new(PageCounts::new());
+
+    fn page_size() -> usize {
+        // SAFETY: sysconf has no preconditions
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    pub(super) fn lock(ptr: *const u8, len: usize) {
+        let size = page_size();
+        let mut pages = PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        for page in pages.acquire(ptr as usize, len, size) {
+            // SAFETY: the page holds part of a live allocation owned by the caller
+            unsafe {
+                libc::mlock(page as *const libc::c_void, size);
+            }
+        }
+    }
+
+    pub(super) fn unlock(ptr: *const u8, len: usize) {
+        let size = page_size();
+        let mut pages = PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        for page in pages.release(ptr as usize, len, size) {
+            // SAFETY: as above; called before the allocation is freed
+            unsafe {
+                libc::munlock(page as *const libc::c_void, size);
+            }
+        }
+    }
+}
+
+#[cfg(not(all(feature = "mlock", target_os = "linux")))]
+mod memlock {
+    pub(super) fn lock(_ptr: *const u8, _len: usize) {}
+
+    pub(super) fn unlock(_ptr: *const u8, _len: usize) {}
+}
+
+/// How many live secrets overlap each locked page. `munlock` is not
+/// reference-counted, so a page is only unlocked once the last one is gone.
+#[cfg(any(test, all(feature = "mlock", target_os = "linux")))]
+struct PageCounts(BTreeMap<usize, usize>);
+
+#[cfg(any(test, all(feature = "mlock", target_os = "linux")))]
+impl PageCounts {
+    const fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    /// Pages of the range that were not locked yet
+    fn acquire(&mut self, ptr: usize, len: usize, page_size: usize) -> Vec<usize> {
+        pages(ptr, len, page_size)
+            .filter(|page| {
+                let count = self.0.entry(*page).or_insert(0);
+                *count += 1;
+                *count == 1
+            })
+            .collect()
+    }
+
+    /// Pages of the range that no other secret still needs
+    fn release(&mut self, ptr: usize, len: usize, page_size: usize) -> Vec<usize> {
+        pages(ptr, len, page_size)
+            .filter(|page| match self.0.get_mut(page) {
+                Some(count) if *count > 1 => {
+                    *count -= 1;
+                    false
+                }
+                Some(_) => {
+                    self.0.remove(page);
+                    true
+                }
+                None => false,
+            })
+            .collect()
+    }
+}
+
+/// Start addresses of the pages spanned by `len` bytes at `ptr`
+#[cfg(any(test, all(feature = "mlock", target_os = "linux")))]
+fn pages(ptr: usize, len: usize, page_size: usize) -> impl Iterator<Item = usize> {
+    let first = ptr - ptr % page_size;
+    let end = if len == 0 { first } else { ptr + len };
+    (first..end).step_by(page_size)
+}
+
+/// Whether `len` bytes at `ptr` are all zero
+#[cfg(test)]
+pub(crate) fn is_wiped((ptr, len): (*const u8, usize)) -> bool {
+    // SAFETY: callers pass a buffer that is still allocated
+    unsafe { std::slice::from_raw_parts(ptr, len) }
+        .iter()
+        .all(|&b| b == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wipe_zeroes_whole_buffer() {
+        let mut phrase = String::with_capacity(64);
+        phrase.push_str("legal winner thank year wave sausage");
+        let mut secret = SecretString::new(phrase);
+        let buffer = secret.buffer();
+        assert!(!is_wiped(buffer));
+
+        secret.wipe();
+        // Spare capacity included, so nothing lingers past the old length
+        assert!(is_wiped(buffer));
+        assert_eq!(secret.expose(), "");
+
+        let mut bytes = SecretBytes::new(vec![0xAB; 32]);
+        let buffer = bytes.buffer();
+        bytes.wipe();
+        assert!(is_wiped(buffer));
+    }
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let secret = SecretString::new("hunter2".to_string());
+        assert_eq!(format!("{:?}", secret), "SecretString(<redacted>)");
+        let bytes = SecretBytes::new(vec![7; 16]);
+        assert!(!format!("{:?}", bytes).contains('7'));
+    }
+
+    #[test]
+    fn test_shared_pages_stay_locked_until_last_release() {
+        let mut counts = PageCounts::new();
+        // Two secrets on page 0x1000; the second also spills onto 0x2000
+        assert_eq!(counts.acquire(0x1010, 0x20, 0x1000), vec![0x1000]);
+        assert_eq!(counts.acquire(0x1ff0, 0x20, 0x1000), vec![0x2000]);
+
+        assert_eq!(counts.release(0x1010, 0x20, 0x1000), Vec::<usize>::new());
+        assert_eq!(counts.release(0x1ff0, 0x20, 0x1000), vec![0x1000, 0x2000]);
+        assert!(counts.acquire(0x1010, 0, 0x1000).is_empty());
+        assert!(counts.0.is_empty());
+    }
+}
@@ -0,0 +1,825 @@
+// SLIP-39 Shamir secret sharing for master secrets
+// Splits a secret into groups of mnemonic shares and recovers it from a quorum
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::{error::*, types::Slip39Group};
+
+const WORDLIST: &str = include_str!("slip39_wordlist.txt");
+
+const RADIX_BITS: usize = 10;
+const ID_LENGTH_BITS: usize = 15;
+const CHECKSUM_WORDS: usize = 3;
+/// Identifier, extendable flag and iteration exponent; then the share parameters
+const HEADER_WORDS: usize = 4;
+const MIN_SECRET_BYTES: usize = 16;
+const MAX_SHARE_COUNT: u8 = 16;
+
+const BASE_ITERATION_COUNT: u32 = 10000;
+/// 20000 PBKDF2 iterations, as in the reference implementation
+pub const DEFAULT_ITERATION_EXPONENT: u8 = 1;
+const ROUND_COUNT: u8 = 4;
+
+const DIGEST_LENGTH: usize = 4;
+const DIGEST_INDEX: u8 = 254;
+const SECRET_INDEX: u8 = 255;
+
+lazy_static::lazy_static! {
+    static ref WORDS: Vec<&'static str> = WORDLIST.split_whitespace().collect();
+    static ref GF256: (Vec<u8>, Vec<u8>) = gf256_tables();
+}
+
+/// A single decoded share
+#[derive(Zeroize, ZeroizeOnDrop)]
+struct Share {
+    identifier: u16,
+    extendable: bool,
+    iteration_exponent: u8,
+    group_index: u8,
+    group_threshold: u8,
+    group_count: u8,
+    member_index: u8,
+    member_threshold: u8,
+    value: Vec<u8>,
+}
+
+impl Share {
+    fn from_mnemonic(mnemonic: &str) -> Result<Self> {
+        let words = mnemonic
+            .split_whitespace()
+            .map(|word| {
+                let word = word.to_lowercase();
+                WORDS
+                    .binary_search(&word.as_str())
+                    .map(|index| index as u16)
+                    .map_err(|_| CoreError::InvalidShare)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let value_words = words
+            .len()
+            .checked_sub(HEADER_WORDS + CHECKSUM_WORDS)
+            .ok_or(CoreError::InvalidShare)?;
+        if value_words * RADIX_BITS < MIN_SECRET_BYTES * 8 {
+            return Err(CoreError::InvalidShare);
+        }
+
+        let extendable = (words[1] >> 4) & 1 == 1;
+        if rs1024_polymod(customization(extendable), &words) != 1 {
+            return Err(CoreError::InvalidShare);
+        }
+
+        let id_exp = (words[0] as u32) << RADIX_BITS | words[1] as u32;
+        let params = (words[2] as u32) << RADIX_BITS | words[3] as u32;
+        let nibble = |shift: u32| ((params >> shift) & 0xf) as u8;
+
+        let share = Self {
+            identifier: (id_exp >> 5) as u16,
+            extendable,
+            iteration_exponent: (id_exp & 0xf) as u8,
+            group_index: nibble(16),
+            group_threshold: nibble(12) + 1,
+            group_count: nibble(8) + 1,
+            member_index: nibble(4),
+            member_threshold: nibble(0) + 1,
+            value: words_to_bytes(&words[HEADER_WORDS..words.len() - CHECKSUM_WORDS])?,
+        };
+
+        if share.group_threshold > share.group_count || share.group_index >= share.group_count {
+            return Err(CoreError::InvalidShare);
+        }
+        Ok(share)
+    }
+
+    fn to_mnemonic(&self) -> String {
+        let id_exp = (self.identifier as u32) << 5
+            | (self.extendable as u32) << 4
+            | self.iteration_exponent as u32;
+        let params = (self.group_index as u32) << 16
+            | ((self.group_threshold - 1) as u32) << 12
+            | ((self.group_count - 1) as u32) << 8
+            | (self.member_index as u32) << 4
+            | (self.member_threshold - 1) as u32;
+
+        let mut words = vec![
+            (id_exp >> RADIX_BITS) as u16,
+            (id_exp & 0x3ff) as u16,
+            (params >> RADIX_BITS) as u16,
+            (params & 0x3ff) as u16,
+        ];
+        words.extend(bytes_to_words(&self.value));
+
+        let checksum = rs1024_polymod(
+            customization(self.extendable),
+            &[words.as_slice(), &[0; CHECKSUM_WORDS]].concat(),
+        ) ^ 1;
+        words.extend(
+            (0..CHECKSUM_WORDS)
+                .rev()
+                .map(|i| ((checksum >> (RADIX_BITS * i)) & 0x3ff) as u16),
+        );
+
+        words
+            .iter()
+            .map(|&index| WORDS[index as usize])
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Split `master_secret` into mnemonic shares, one list per group.
+///
+/// Any `group_threshold` groups, each with `member_threshold` of its shares,
+/// recover the secret. `passphrase` encrypts it first and is needed again on
+/// recovery; a wrong one yields a different, equally valid-looking secret.
+pub fn generate_mnemonics(
+    group_threshold: u8,
+    groups: &[Slip39Group],
+    master_secret: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+) -> Result<Vec<Vec<String>>> {
+    if master_secret.len() < MIN_SECRET_BYTES || !master_secret.len().is_multiple_of(2) {
+        return Err(CoreError::InvalidInput);
+    }
+    if group_threshold == 0
+        || groups.len() > MAX_SHARE_COUNT as usize
+        || group_threshold as usize > groups.len()
+        || iteration_exponent > 0xf
+    {
+        return Err(CoreError::InvalidInput);
+    }
+    for group in groups {
+        if group.member_threshold == 0
+            || group.member_threshold > group.member_count
+            || group.member_count > MAX_SHARE_COUNT
+            // A 1-of-n group would just be n copies of the same share
+            || (group.member_threshold == 1 && group.member_count > 1)
+        {
+            return Err(CoreError::InvalidInput);
+        }
+    }
+    validate_passphrase(passphrase)?;
+
+    let mut id_bytes = [0u8; 2];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut id_bytes);
+    let identifier = u16::from_be_bytes(id_bytes) >> (16 - ID_LENGTH_BITS);
+    // New backups are extendable: more share sets can be made for the same secret
+    let extendable = true;
+
+    let encrypted = encrypt(
+        master_secret,
+        passphrase,
+        iteration_exponent,
+        identifier,
+        extendable,
+    )?;
+    let group_secrets = split_secret(group_threshold, groups.len() as u8, &encrypted)?;
+
+    groups
+        .iter()
+        .zip(group_secrets.iter())
+        .map(|(group, (group_index, group_secret))| {
+            let members = split_secret(group.member_threshold, group.member_count, group_secret)?;
+            Ok(members
+                .iter()
+                .map(|(member_index, value)| {
+                    Share {
+                        identifier,
+                        extendable,
+                        iteration_exponent,
+                        group_index: *group_index,
+                        group_threshold,
+                        group_count: groups.len() as u8,
+                        member_index: *member_index,
+                        member_threshold: group.member_threshold,
+                        value: value.to_vec(),
+                    }
+                    .to_mnemonic()
+                })
+                .collect())
+        })
+        .collect()
+}
+
+/// Recover the master secret from enough mnemonic shares
+pub fn combine_mnemonics(mnemonics: &[String], passphrase: &str) -> Result<Zeroizing<Vec<u8>>> {
+    validate_passphrase(passphrase)?;
+
+    let shares = mnemonics
+        .iter()
+        .map(|mnemonic| Share::from_mnemonic(mnemonic))
+        .collect::<Result<Vec<_>>>()?;
+    let first = shares.first().ok_or(CoreError::InvalidShare)?;
+
+    // Every share must come from the same split
+    let consistent = shares.iter().all(|share| {
+        share.identifier == first.identifier
+            && share.extendable == first.extendable
+            && share.iteration_exponent == first.iteration_exponent
+            && share.group_threshold == first.group_threshold
+            && share.group_count == first.group_count
+            && share.value.len() == first.value.len()
+    });
+    if !consistent {
+        return Err(CoreError::InvalidShare);
+    }
+
+    let mut groups: BTreeMap<u8, Vec<&Share>> = BTreeMap::new();
+    for share in &shares {
+        let members = groups.entry(share.group_index).or_default();
+        if members
+            .iter()
+            .any(|other| other.member_threshold != share.member_threshold)
+        {
+            return Err(CoreError::InvalidShare);
+        }
+        match members
+            .iter()
+            .find(|other| other.member_index == share.member_index)
+        {
+            Some(other) if other.value != share.value => return Err(CoreError::InvalidShare),
+            Some(_) => {}
+            None => members.push(share),
+        }
+    }
+
+    // Groups below their member threshold contribute nothing
+    let group_secrets = groups
+        .iter()
+        .filter(|(_, members)| members.len() >= members[0].member_threshold as usize)
+        .take(first.group_threshold as usize)
+        .map(|(&group_index, members)| {
+            let threshold = members[0].member_threshold;
+            let points: Vec<(u8, &[u8])> = members
+                .iter()
+                .take(threshold as usize)
+                .map(|share| (share.member_index, share.value.as_slice()))
+                .collect();
+            Ok((group_index, recover_secret(threshold, &points)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if group_secrets.len() < first.group_threshold as usize {
+        return Err(CoreError::InvalidShare);
+    }
+
+    let points: Vec<(u8, &[u8])> = group_secrets
+        .iter()
+        .map(|(index, secret)| (*index, secret.as_slice()))
+        .collect();
+    let encrypted = recover_secret(first.group_threshold, &points)?;
+
+    decrypt(
+        &encrypted,
+        passphrase,
+        first.iteration_exponent,
+        first.identifier,
+        first.extendable,
+    )
+}
+
+fn validate_passphrase(passphrase: &str) -> Result<()> {
+    if passphrase.bytes().all(|b| (32..=126).contains(&b)) {
+        Ok(())
+    } else {
+        Err(CoreError::InvalidInput)
+    }
+}
+
+fn customization(extendable: bool) -> &'static [u8] {
+    if extendable {
+        b"shamir_extendable"
+    } else {
+        b"shamir"
+    }
+}
+
+/// Reed-Solomon checksum over GF(1024), as in the SLIP-39 reference
+fn rs1024_polymod(customization: &[u8], words: &[u16]) -> u32 {
+    const GEN: [u32; 10] = [
+        0xE0E040, 0x1C1C080, 0x3838100, 0x7070200, 0xE0E0009, 0x1C0C2412, 0x38086C24, 0x3090FC48,
+        0x21B1F890, 0x3F3F120,
+    ];
+
+    let values = customization
+        .iter()
+        .map(|&b| b as u32)
+        .chain(words.iter().map(|&w| w as u32));
+    let mut chk = 1u32;
+    for value in values {
+        let b = chk >> 20;
+        chk = (chk & 0xFFFFF) << 10 ^ value;
+        for (i, generator) in GEN.iter().enumerate() {
+            if (b >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+/// Big-endian bits of `bytes`, left-padded with zeros to whole words
+fn bytes_to_words(bytes: &[u8]) -> Vec<u16> {
+    let bit_len = bytes.len() * 8;
+    let padding = (RADIX_BITS - bit_len % RADIX_BITS) % RADIX_BITS;
+    let bits: Vec<bool> = std::iter::repeat_n(false, padding)
+        .chain(
+            bytes
+                .iter()
+                .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1)),
+        )
+        .collect();
+
+    bits.chunks(RADIX_BITS)
+        .map(|chunk| chunk.iter().fold(0u16, |acc, &bit| acc << 1 | bit as u16))
+        .collect()
+}
+
+/// Inverse of `bytes_to_words`; the padding must be at most 8 zero bits
+fn words_to_bytes(words: &[u16]) -> Result<Vec<u8>> {
+    let padding = words.len() * RADIX_BITS % 16;
+    if padding > 8 {
+        return Err(CoreError::InvalidShare);
+    }
+
+    let bits: Vec<bool> = words
+        .iter()
+        .flat_map(|word| (0..RADIX_BITS).rev().map(move |i| (word >> i) & 1 == 1))
+        .collect();
+    if bits[..padding].iter().any(|&bit| bit) {
+        return Err(CoreError::InvalidShare);
+    }
+
+    Ok(bits[padding..]
+        .chunks(8)
+        .map(|chunk| chunk.iter().fold(0u8, |acc, &bit| acc << 1 | bit as u8))
+        .collect())
+}
+
+/// Exponent and logarithm tables of GF(256) with the Rijndael polynomial
+fn gf256_tables() -> (Vec<u8>, Vec<u8>) {
+    let mut exp = vec![0u8; 255];
+    let mut log = vec![0u8; 256];
+    let mut poly: u16 = 1;
+    for (i, slot) in exp.iter_mut().enumerate() {
+        *slot = poly as u8;
+        log[poly as usize] = i as u8;
+        // Multiply by the generator 3
+        poly = (poly << 1) ^ poly;
+        if poly & 0x100 != 0 {
+            poly ^= 0x11b;
+        }
+    }
+    (exp, log)
+}
+
+/// Lagrange interpolation at `x` of the polynomials through `points`
+fn interpolate(points: &[(u8, &[u8])], x: u8) -> Zeroizing<Vec<u8>> {
+    if let Some((_, value)) = points.iter().find(|(px, _)| *px == x) {
+        return Zeroizing::new(value.to_vec());
+    }
+
+    let (exp, log) = &*GF256;
+    let log_of = |v: u8| log[v as usize] as i32;
+    let log_prod: i32 = points.iter().map(|(px, _)| log_of(px ^ x)).sum();
+
+    let mut result = Zeroizing::new(vec![0u8; points[0].1.len()]);
+    for (px, value) in points {
+        let others: i32 = points
+            .iter()
+            .filter(|(other, _)| other != px)
+            .map(|(other, _)| log_of(px ^ other))
+            .sum();
+        let log_basis = (log_prod - log_of(px ^ x) - others).rem_euclid(255);
+
+        for (out, &byte) in result.iter_mut().zip(value.iter()) {
+            if byte != 0 {
+                *out ^= exp[((log_of(byte) + log_basis) % 255) as usize];
+            }
+        }
+    }
+    result
+}
+
+fn secret_digest(random_part: &[u8], secret: &[u8]) -> Result<Vec<u8>> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(random_part).map_err(|_| CoreError::InternalError)?;
+    mac.update(secret);
+    Ok(mac.finalize().into_bytes()[..DIGEST_LENGTH].to_vec())
+}
+
+/// Shamir split into `count` points, any `threshold` of which recover `secret`
+fn split_secret(threshold: u8, count: u8, secret: &[u8]) -> Result<Vec<(u8, Zeroizing<Vec<u8>>)>> {
+    if threshold == 1 {
+        return Ok((0..count)
+            .map(|index| (index, Zeroizing::new(secret.to_vec())))
+            .collect());
+    }
+
+    let random_bytes = |len: usize| {
+        let mut bytes = Zeroizing::new(vec![0u8; len]);
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut bytes);
+        bytes
+    };
+
+    let mut shares: Vec<(u8, Zeroizing<Vec<u8>>)> = (0..threshold - 2)
+        .map(|index| (index, random_bytes(secret.len())))
+        .collect();
+
+    // The digest point lets recovery detect a wrong set of shares
+    let random_part = random_bytes(secret.len() - DIGEST_LENGTH);
+    let mut digest_share = Zeroizing::new(secret_digest(&random_part, secret)?);
+    digest_share.extend_from_slice(&random_part);
+
+    let mut base: Vec<(u8, &[u8])> = shares
+        .iter()
+        .map(|(index, value)| (*index, value.as_slice()))
+        .collect();
+    base.push((DIGEST_INDEX, &digest_share));
+    base.push((SECRET_INDEX, secret));
+
+    let interpolated: Vec<_> = (threshold - 2..count)
+        .map(|index| (index, interpolate(&base, index)))
+        .collect();
+    shares.extend(interpolated);
+    Ok(shares)
+}
+
+fn recover_secret(threshold: u8, points: &[(u8, &[u8])]) -> Result<Zeroizing<Vec<u8>>> {
+    if threshold == 1 {
+        return Ok(Zeroizing::new(points[0].1.to_vec()));
+    }
+
+    let secret = interpolate(points, SECRET_INDEX);
+    let digest_share = interpolate(points, DIGEST_INDEX);
+    let (digest, random_part) = digest_share.split_at(DIGEST_LENGTH);
+    if digest != secret_digest(random_part, &secret)?.as_slice() {
+        return Err(CoreError::InvalidShare);
+    }
+    Ok(secret)
+}
+
+/// One Feistel round key: PBKDF2 of the round index and passphrase
+fn round_function(
+    round: u8,
+    passphrase: &str,
+    iteration_exponent: u8,
+    salt: &[u8],
+    r: &[u8],
+) -> Zeroizing<Vec<u8>> {
+    let mut password = Zeroizing::new(vec![round]);
+    password.extend_from_slice(passphrase.as_bytes());
+    let iterations = (BASE_ITERATION_COUNT << iteration_exponent) / ROUND_COUNT as u32;
+
+    let mut output = Zeroizing::new(vec![0u8; r.len()]);
+    pbkdf2::pbkdf2_hmac::<Sha256>(&password, &[salt, r].concat(), iterations, &mut output);
+    output
+}
+
+fn feistel_salt(identifier: u16, extendable: bool) -> Vec<u8> {
+    if extendable {
+        Vec::new()
+    } else {
+        [b"shamir".as_slice(), &identifier.to_be_bytes()].concat()
+    }
+}
+
+fn feistel(
+    input: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+    rounds: impl Iterator<Item = u8>,
+) -> Result<Zeroizing<Vec<u8>>> {
+    if !input.len().is_multiple_of(2) {
+        return Err(CoreError::InvalidShare);
+    }
+
+    let salt = feistel_salt(identifier, extendable);
+    let half = input.len() / 2;
+    let mut l = Zeroizing::new(input[..half].to_vec());
+    let mut r = Zeroizing::new(input[half..].to_vec());
+    for round in rounds {
+        let f = round_function(round, passphrase, iteration_exponent, &salt, &r);
+        let next_r: Vec<u8> = l.iter().zip(f.iter()).map(|(a, b)| a ^ b).collect();
+        l = std::mem::replace(&mut r, Zeroizing::new(next_r));
+    }
+
+    let mut output = Zeroizing::new(r.to_vec());
+    output.extend_from_slice(&l);
+    Ok(output)
+}
+
+fn encrypt(
+    master_secret: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+) -> Result<Zeroizing<Vec<u8>>> {
+    feistel(
+        master_secret,
+        passphrase,
+        iteration_exponent,
+        identifier,
+        extendable,
+        0..ROUND_COUNT,
+    )
+}
+
+fn decrypt(
+    encrypted: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+) -> Result<Zeroizing<Vec<u8>>> {
+    feistel(
+        encrypted,
+        passphrase,
+        iteration_exponent,
+        identifier,
+        extendable,
+        (0..ROUND_COUNT).rev(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mnemonics(list: &[&str]) -> Vec<String> {
+        list.iter().map(|m| m.to_string()).collect()
+    }
+
+    // Trezor SLIP-39 test vectors
+    const VECTOR_SINGLE: &str = "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard";
+    const VECTOR_2_OF_3: [&str; 2] = [
+        "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+        "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking",
+    ];
+
+    #[test]
+    fn test_wordlist() {
+        assert_eq!(WORDS.len(), 1 << RADIX_BITS);
+        assert!(WORDS.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_trezor_vector_without_sharing() {
+        let secret = combine_mnemonics(&mnemonics(&[VECTOR_SINGLE]), "TREZOR").unwrap();
+        assert_eq!(
+            hex::encode(secret.as_slice()),
+            "bb54aac4b89dc868ba37d9cc21b2cece"
+        );
+    }
+
+    #[test]
+    fn test_trezor_vector_invalid_checksum() {
+        let tampered = VECTOR_SINGLE.replace("decision keyboard", "decision kidney");
+        assert!(matches!(
+            combine_mnemonics(&mnemonics(&[&tampered]), "TREZOR"),
+            Err(CoreError::InvalidShare)
+        ));
+    }
+
+    #[test]
+    fn test_trezor_vector_2_of_3() {
+        let secret = combine_mnemonics(&mnemonics(&VECTOR_2_OF_3), "TREZOR").unwrap();
+        assert_eq!(
+            hex::encode(secret.as_slice()),
+            "b43ceb7e57a0ea8766221624d01b0864"
+        );
+
+        // One share is below the threshold
+        assert!(combine_mnemonics(&mnemonics(&VECTOR_2_OF_3[..1]), "TREZOR").is_err());
+    }
+
+    /// Trezor's `vectors.json`: description, mnemonics and the master secret
+    /// they combine to under the passphrase "TREZOR", or "" when they must be
+    /// refused
+    const TREZOR_VECTORS: &[(&str, &[&str], &str)] = &[
+        (
+            "Valid mnemonic without sharing (128 bits)",
+            &[VECTOR_SINGLE],
+            "bb54aac4b89dc868ba37d9cc21b2cece",
+        ),
+        (
+            "Mnemonic with invalid padding (128 bits)",
+            &["duckling enlarge academic academic email result length solution fridge kidney coal piece deal husband erode duke ajar music cargo fitness"],
+            "",
+        ),
+        (
+            "Basic sharing 2-of-3 (128 bits)",
+            &VECTOR_2_OF_3,
+            "b43ceb7e57a0ea8766221624d01b0864",
+        ),
+        (
+            "Mnemonics with different identifiers (128 bits)",
+            &[
+                "adequate smoking academic acid debut wine petition glen cluster slow rhyme slow simple epidemic rumor junk tracks treat olympic tolerate",
+                "adequate stay academic agency agency formal party ting frequent learn upstairs remember smear leaf damage anatomy ladle market hush corner",
+            ],
+            "",
+        ),
+        (
+            "Mnemonics with different iteration exponents (128 bits)",
+            &[
+                "peasant leaves academic acid desert exact olympic math alive axle trial tackle drug deny decent smear dominant desert bucket remind",
+                "peasant leader academic agency cultural blessing percent network envelope medal junk primary human pumps jacket fragment payroll ticket evoke voice",
+            ],
+            "",
+        ),
+        (
+            "Mnemonic with greater group threshold than group count (128 bits)",
+            &["music husband acrobat acid artist finance center either graduate swimming object bike medical clothes station aspect spider maiden bulb welcome"],
+            "",
+        ),
+        (
+            "Mnemonics with duplicate member indices (128 bits)",
+            &[
+                "device stay academic always dive coal antenna adult black exceed stadium herald advance soldier busy dryer daughter evaluate minister laser",
+                "device stay academic always dwarf afraid robin gravity crunch adjust soul branch walnut coastal dream costume scholar mortgage mountain pumps",
+            ],
+            "",
+        ),
+        (
+            "Mnemonics with mismatching member thresholds (128 bits)",
+            &[
+                "hour painting academic academic device formal evoke guitar random modern justice filter withdraw trouble identify mailman insect general cover oven",
+                "hour painting academic agency artist again daisy capital beaver fiber much enjoy suitable symbolic identify photo editor romp float echo",
+            ],
+            "",
+        ),
+        (
+            "Mnemonics giving an invalid digest (128 bits)",
+            &[
+                "guilt walnut academic acid deliver remove equip listen vampire tactics nylon rhythm failure husband fatigue alive blind enemy teaspoon rebound",
+                "guilt walnut academic agency brave hamster hobo declare herd taste alpha slim criminal mild arcade formal romp branch pink ambition",
+            ],
+            "",
+        ),
+        (
+            "Threshold number of groups, but insufficient members in one group (128 bits)",
+            &[
+                "eraser senior beard romp adorn nuclear spill corner cradle style ancient family general leader ambition exchange unusual garlic promise voice",
+                "eraser senior ceramic snake clay various huge numb argue hesitate auction category timber browser greatest hanger petition script leaf pickup",
+                "eraser senior ceramic shaft dynamic become junior wrist silver peasant force math alto coal amazing segment yelp velvet image paces",
+            ],
+            "",
+        ),
+        (
+            "Threshold number of groups and members in each group (128 bits)",
+            &[
+                "eraser senior beard romp adorn nuclear spill corner cradle style ancient family general leader ambition exchange unusual garlic promise voice",
+                "eraser senior ceramic snake clay various huge numb argue hesitate auction category timber browser greatest hanger petition script leaf pickup",
+                "eraser senior ceramic shaft dynamic become junior wrist silver peasant force math alto coal amazing segment yelp velvet image paces",
+                "eraser senior ceramic round column hawk trust auction smug shame alive greatest sheriff living perfect corner chest sled fumes adequate",
+                "eraser senior decision smug corner ruin rescue cubic angel tackle skin skunk program roster trash rumor slush angel flea amazing",
+            ],
+            "7c3397a292a5941682d7a4ae2d898d11",
+        ),
+        (
+            "Valid extendable mnemonic without sharing (128 bits)",
+            &[VECTOR_EXTENDABLE],
+            "1679b4516e0ee5954351d288a838f45e",
+        ),
+        (
+            "Valid extendable mnemonic without sharing (256 bits)",
+            &["impulse calcium academic academic alcohol sugar lyrics pajamas column facility finance tension extend space birthday rainbow swimming purple syndrome facility trial warn duration snapshot shadow hormone rhyme public spine counter easy hawk album"],
+            "8340611602fe91af634a5f4608377b5235fa2d757c51d720c0c7656249a3035f",
+        ),
+        (
+            "Extendable basic sharing 2-of-3 (256 bits)",
+            &VECTOR_EXTENDABLE_2_OF_3,
+            "8dc652d6d6cd370d8c963141f6d79ba440300f25c467302c1d966bff8f62300d",
+        ),
+        (
+            "Extendable basic sharing 2-of-3, one share (256 bits)",
+            &[VECTOR_EXTENDABLE_2_OF_3[0]],
+            "",
+        ),
+    ];
+
+    const VECTOR_EXTENDABLE: &str = "testify swimming academic academic column loyalty smear include exotic bedroom exotic wrist lobe cover grief golden smart junior estimate learn";
+    const VECTOR_EXTENDABLE_2_OF_3: [&str; 2] = [
+        "western apart academic always artist resident briefing sugar woman oven coding club ajar merit pecan answer prisoner artist fraction amount desktop mild false necklace muscle photo wealthy alpha category unwrap spew losing making",
+        "western apart academic acid answer ancient auction flip image penalty oasis beaver multiple thunder problem switch alive heat inherit superior teaspoon explain blanket pencil numb lend punish endless aunt garlic humidity kidney observe",
+    ];
+
+    #[test]
+    fn test_trezor_vectors() {
+        for (description, list, secret) in TREZOR_VECTORS {
+            let result = combine_mnemonics(&mnemonics(list), "TREZOR");
+            match *secret {
+                "" => assert!(result.is_err(), "{}", description),
+                secret => assert_eq!(
+                    hex::encode(result.expect(description).as_slice()),
+                    secret,
+                    "{}",
+                    description
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_group_round_trip() {
+        let secret =
+            hex::decode("0c94be6ab9a5bb2f6c0d6a1f7f9a5b10c5a7e2b4d6f80912a3b4c5d6e7f8091a")
+                .unwrap();
+        let groups = [
+            Slip39Group {
+                member_threshold: 1,
+                member_count: 1,
+            },
+            Slip39Group {
+                member_threshold: 2,
+                member_count: 3,
+            },
+            Slip39Group {
+                member_threshold: 3,
+                member_count: 5,
+            },
+        ];
+        let shares = generate_mnemonics(2, &groups, &secret, "family", 0).unwrap();
+        assert_eq!(
+            shares.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![1, 3, 5]
+        );
+        assert!(shares.iter().flatten().all(|m| m.split(' ').count() == 33));
+
+        // The single share plus two members of the second group
+        let quorum = vec![
+            shares[0][0].clone(),
+            shares[1][2].clone(),
+            shares[1][0].clone(),
+        ];
+        assert_eq!(
+            combine_mnemonics(&quorum, "family").unwrap().as_slice(),
+            secret
+        );
+
+        // Two members of the second group and three of the third
+        let quorum: Vec<String> = shares[1][1..]
+            .iter()
+            .chain(&shares[2][..3])
+            .cloned()
+            .collect();
+        assert_eq!(
+            combine_mnemonics(&quorum, "family").unwrap().as_slice(),
+            secret
+        );
+
+        // An incomplete third group does not count towards the group threshold
+        let short: Vec<String> = shares[1][..2]
+            .iter()
+            .chain(&shares[2][..2])
+            .cloned()
+            .collect();
+        assert!(matches!(
+            combine_mnemonics(&short, "family"),
+            Err(CoreError::InvalidShare)
+        ));
+
+        // A different passphrase decrypts to a different secret
+        let other = combine_mnemonics(&quorum, "").unwrap();
+        assert_ne!(other.as_slice(), secret);
+    }
+
+    #[test]
+    fn test_shares_from_different_splits_are_rejected() {
+        let secret = [7u8; 16];
+        let groups = [Slip39Group {
+            member_threshold: 2,
+            member_count: 2,
+        }];
+        let first = generate_mnemonics(1, &groups, &secret, "", 0).unwrap();
+        let second = generate_mnemonics(1, &groups, &secret, "", 0).unwrap();
+
+        let mixed = vec![first[0][0].clone(), second[0][1].clone()];
+        assert!(combine_mnemonics(&mixed, "").is_err());
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        let secret = [1u8; 16];
+        let one_of_two = [Slip39Group {
+            member_threshold: 1,
+            member_count: 2,
+        }];
+        assert!(generate_mnemonics(1, &one_of_two, &secret, "", 0).is_err());
+
+        let group = [Slip39Group {
+            member_threshold: 2,
+            member_count: 3,
+        }];
+        assert!(generate_mnemonics(2, &group, &secret, "", 0).is_err());
+        assert!(generate_mnemonics(1, &group, &secret[..15], "", 0).is_err());
+        assert!(generate_mnemonics(1, &group, &secret, "pässword", 0).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+// Account discovery with gap-limit scanning
+// Derives sequential accounts and checks them for on-chain activity over JSON-RPC
+
+use crate::rpc::JsonRpcClient;
+use crate::storage::Keyring;
+use crate::{error::*, tron, types::*};
+use ethers_core::types::U256;
+use serde_json::json;
+use std::collections::HashMap;
+
+/// Scan a wallet for used accounts, stopping each scheme after
+/// `options.gap_limit` consecutive unused ones.
+///
+/// Used accounts under the wallet's own layout are derived into the wallet;
+/// those found under other schemes are only reported.
+pub fn discover_accounts(
+    keyring: &Keyring,
+    wallet_id: &str,
+    client: &JsonRpcClient,
+    options: &DiscoveryOptions,
+) -> Result<Vec<DiscoveredAccount>> {
+    if options.gap_limit == 0 {
+        return Err(CoreError::InvalidInput);
+    }
+
+    let (own_scheme, watch_only) = keyring.with_wallet(wallet_id, |wallet| {
+        Ok((wallet.scheme().clone(), wallet.is_watch_only()))
+    })?;
+
+    // TRON and watch-only wallets have a single fixed layout
+    let schemes = if options.chain == Chain::Tron || watch_only || options.schemes.is_empty() {
+        vec![own_scheme.clone()]
+    } else {
+        options.schemes.clone()
+    };
+
+    let mut discovered = Vec::new();
+    for scheme in schemes {
+        let found = scan_scheme(keyring, wallet_id, client, options, &scheme)?;
+
+        if scheme == own_scheme || options.chain == Chain::Tron || watch_only {
+            keyring.with_wallet_mut(wallet_id, |wallet| {
+                for item in &found {
+                    wallet.derive_chain_account(options.chain, item.account.index)?;
+                }
+                Ok(())
+            })?;
+        }
+
+        let reported_scheme = match options.chain {
+            Chain::Evm if !watch_only => Some(scheme),
+            _ => None,
+        };
+        discovered.extend(found.into_iter().map(|item| DiscoveredAccount {
+            scheme: reported_scheme.clone(),
+            ..item
+        }));
+    }
+
+    Ok(discovered)
+}
+
+fn scan_scheme(
+    keyring: &Keyring,
+    wallet_id: &str,
+    client: &JsonRpcClient,
+    options: &DiscoveryOptions,
+    scheme: &DerivationScheme,
+) -> Result<Vec<DiscoveredAccount>> {
+    let mut found = Vec::new();
+    let mut next = 0u32;
+    let mut last_used: Option<u32> = None;
+
+    loop {
+        // Keep scanning until `gap_limit` accounts past the last used one
+        let window_end = last_used.map_or(0, |index| index + 1) + options.gap_limit;
+        if next >= window_end {
+            break;
+        }
+
+        let accounts = keyring.with_wallet(wallet_id, |wallet| {
+            wallet.preview_accounts(options.chain, scheme, next, window_end - next)
+        })?;
+        if accounts.is_empty() {
+            break;
+        }
+        next += accounts.len() as u32;
+
+        let activity = query_activity(client, options.chain, &accounts)?;
+        for (account, (balance, nonce)) in accounts.into_iter().zip(activity) {
+            if balance.is_zero() && nonce == 0 {
+                continue;
+            }
+
+            last_used = Some(account.index);
+            found.push(DiscoveredAccount {
+                account,
+                scheme: None,
+                balance: balance.to_string(),
+                nonce,
+            });
+        }
+    }
+
+    Ok(found)
+}
+
+/// Balance and nonce of each account, in one batched request
+fn query_activity(
+    client: &JsonRpcClient,
+    chain: Chain,
+    accounts: &[Account],
+) -> Result<Vec<(U256, u64)>> {
+    let mut requests = Vec::new();
+    for (i, account) in accounts.iter().enumerate() {
+        let address = match chain {
+            Chain::Evm => account.address.clone(),
+            Chain::Tron => tron::address_to_hex(&account.address)?,
+        };
+
+        requests.push(RpcRequest {
+            method: "eth_getBalance".to_string(),
+            params: json!([address, "latest"]).to_string(),
+            id: Some(format!("balance-{}", i)),
+        });
+        if chain == Chain::Evm {
+            requests.push(RpcRequest {
+                method: "eth_getTransactionCount".to_string(),
+                params: json!([address, "latest"]).to_string(),
+                id: Some(format!("nonce-{}", i)),
+            });
+        }
+    }
+
+    // Batch responses may come back in any order
+    let responses: HashMap<String, RpcResponse> = client
+        .batch_call(requests)?
+        .into_iter()
+        .filter_map(|response| {
+            let id = response.id.as_deref()?.trim_matches('"').to_string();
+            Some((id, response))
+        })
+        .collect();
+
+    let quantity = |id: String| -> Result<U256> {
+        let response = responses.get(&id).ok_or(CoreError::RpcError)?;
+        if response.error.is_some() {
+            return Err(CoreError::RpcError);
+        }
+        let value = response.result.as_deref().ok_or(CoreError::RpcError)?;
+        U256::from_str_radix(value.trim_matches('"').trim_start_matches("0x"), 16)
+            .map_err(|_| CoreError::RpcError)
+    };
+
+    (0..accounts.len())
+        .map(|i| {
+            let balance = quantity(format!("balance-{}", i))?;
+            let nonce = match chain {
+                Chain::Evm => u64::try_from(quantity(format!("nonce-{}", i))?)
+                    .map_err(|_| CoreError::RpcError)?,
+                Chain::Tron => 0,
+            };
+            Ok((balance, nonce))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Wallet;
+    use crate::rpc::test_server;
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    const ABANDON: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    /// Node where `funded` addresses hold 1 wei and `active` ones have sent a tx
+    fn spawn_node(funded: Vec<String>, active: Vec<String>) -> (String, Arc<Mutex<usize>>) {
+        let calls = Arc::new(Mutex::new(0));
+        let counter = calls.clone();
+
+        let url = test_server::spawn(move |request: &Value| {
+            *counter.lock().unwrap() += 1;
+            let address = request["params"][0].as_str().unwrap().to_lowercase();
+            let hit = match request["method"].as_str().unwrap() {
+                "eth_getBalance" => funded.contains(&address),
+                "eth_getTransactionCount" => active.contains(&address),
+                other => return Err(format!("unexpected method {}", other)),
+            };
+            Ok(json!(if hit { "0x1" } else { "0x0" }))
+        });
+
+        (url, calls)
+    }
+
+    fn preview(wallet: &Wallet, chain: Chain, scheme: &DerivationScheme, index: u32) -> String {
+        wallet.preview_accounts(chain, scheme, index, 1).unwrap()[0]
+            .address
+            .clone()
+    }
+
+    #[test]
+    fn test_gap_limit_discovery() {
+        let keyring = Keyring::new();
+        let wallet = Wallet::from_mnemonic(ABANDON, None).unwrap();
+        let wallet_id = wallet.id.clone();
+        let bip44 = DerivationScheme::Bip44;
+        let used_nonce = preview(&wallet, Chain::Evm, &bip44, 0);
+        let used_balance = preview(&wallet, Chain::Evm, &bip44, 3);
+        // Beyond the gap after index 3, so never reached
+        let unreachable = preview(&wallet, Chain::Evm, &bip44, 10);
+        keyring.insert(wallet).unwrap();
+
+        let (url, calls) = spawn_node(vec![used_balance.clone(), unreachable], vec![used_nonce]);
+        let options = DiscoveryOptions {
+            gap_limit: 5,
+            ..Default::default()
+        };
+        let found =
+            discover_accounts(&keyring, &wallet_id, &JsonRpcClient::new(url), &options).unwrap();
+
+        let indices: Vec<u32> = found.iter().map(|d| d.account.index).collect();
+        assert_eq!(indices, vec![0, 3]);
+        assert_eq!(found[0].nonce, 1);
+        assert_eq!(found[1].balance, "1");
+        assert_eq!(found[1].scheme, Some(DerivationScheme::Bip44));
+
+        // Indices 0 through 8 were checked, balance and nonce each
+        assert_eq!(*calls.lock().unwrap(), 18);
+
+        // The used account is now part of the wallet
+        assert_eq!(
+            keyring
+                .with_wallet(&wallet_id, |w| w.get_address(Chain::Evm, 3))
+                .unwrap(),
+            used_balance
+        );
+    }
+
+    #[test]
+    fn test_discovery_across_schemes() {
+        let keyring = Keyring::new();
+        let wallet = Wallet::from_mnemonic(ABANDON, None).unwrap();
+        let wallet_id = wallet.id.clone();
+        let mew_address = preview(&wallet, Chain::Evm, &DerivationScheme::LegacyMew, 1);
+        keyring.insert(wallet).unwrap();
+
+        let (url, _) = spawn_node(vec![mew_address.clone()], vec![]);
+        let options = DiscoveryOptions {
+            schemes: DerivationScheme::known(),
+            gap_limit: 3,
+            ..Default::default()
+        };
+        let found =
+            discover_accounts(&keyring, &wallet_id, &JsonRpcClient::new(url), &options).unwrap();
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].scheme, Some(DerivationScheme::LegacyMew));
+        assert_eq!(found[0].account.address, mew_address);
+        assert_eq!(found[0].account.derivation_path, "m/44'/60'/0'/1");
+
+        // Accounts under another scheme are reported, not added
+        let stored = keyring
+            .with_wallet(&wallet_id, |w| Ok(w.to_wallet_data().accounts.len()))
+            .unwrap();
+        assert_eq!(stored, 1);
+    }
+
+    #[test]
+    fn test_tron_discovery() {
+        let keyring = Keyring::new();
+        let wallet = Wallet::from_mnemonic(ABANDON, None).unwrap();
+        let wallet_id = wallet.id.clone();
+        let tron_address = preview(&wallet, Chain::Tron, &DerivationScheme::Bip44, 2);
+        keyring.insert(wallet).unwrap();
+
+        let (url, _) = spawn_node(vec![tron::address_to_hex(&tron_address).unwrap()], vec![]);
+        let options = DiscoveryOptions {
+            chain: Chain::Tron,
+            gap_limit: 3,
+            ..Default::default()
+        };
+        let found =
+            discover_accounts(&keyring, &wallet_id, &JsonRpcClient::new(url), &options).unwrap();
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].account.address, tron_address);
+        assert_eq!(found[0].account.chain, Chain::Tron);
+        assert_eq!(found[0].scheme, None);
+        assert!(keyring
+            .with_wallet(&wallet_id, |w| w.get_address(Chain::Tron, 2))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_rpc_error_fails_discovery() {
+        let keyring = Keyring::new();
+        let wallet = Wallet::from_mnemonic(ABANDON, None).unwrap();
+        let wallet_id = wallet.id.clone();
+        keyring.insert(wallet).unwrap();
+
+        let url = test_server::spawn(|_| Err("node is syncing".to_string()));
+        let result = discover_accounts(
+            &keyring,
+            &wallet_id,
+            &JsonRpcClient::new(url),
+            &DiscoveryOptions::default(),
+        );
+        assert!(matches!(result, Err(CoreError::RpcError)));
+    }
+}
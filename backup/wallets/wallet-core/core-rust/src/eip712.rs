@@ -0,0 +1,493 @@
+// EIP-712 typed structured data hashing
+// Encodes `eth_signTypedData_v4` payloads: domain separator, encodeType with
+// nested structs, fixed and dynamic arrays, dynamic bytes and strings
+
+use crate::error::*;
+use ethers_core::{
+    types::{I256, U256},
+    utils::keccak256,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+const DOMAIN_TYPE: &str = "EIP712Domain";
+
+#[derive(Debug, Clone, Deserialize)]
+struct TypedField {
+    name: String,
+    #[serde(rename = "type")]
+    field_type: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TypedData {
+    types: HashMap<String, Vec<TypedField>>,
+    primary_type: String,
+    #[serde(default)]
+    domain: Value,
+    #[serde(default)]
+    message: Value,
+}
+
+/// Hashes of a typed data payload, as shown to the user before signing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypedDataHash {
+    pub domain_separator: [u8; 32],
+    /// `None` when the primary type is the domain itself
+    pub message_hash: Option<[u8; 32]>,
+    pub digest: [u8; 32],
+}
+
+/// Compute the EIP-712 signing digest of an `eth_signTypedData_v4` JSON payload
+pub fn hash_typed_data(typed_data_json: &str) -> Result<TypedDataHash> {
+    let mut typed_data = parse_typed_data(typed_data_json)?;
+
+    // Some dApps omit the domain type and only send the domain object
+    if !typed_data.types.contains_key(DOMAIN_TYPE) {
+        let domain_fields = infer_domain_fields(&typed_data.domain);
+        typed_data
+            .types
+            .insert(DOMAIN_TYPE.to_string(), domain_fields);
+    }
+
+    let domain_separator = hash_struct(DOMAIN_TYPE, &typed_data.domain, &typed_data.types)?;
+
+    let message_hash = if typed_data.primary_type == DOMAIN_TYPE {
+        None
+    } else {
+        Some(hash_struct(
+            &typed_data.primary_type,
+            &typed_data.message,
+            &typed_data.types,
+        )?)
+    };
+
+    let mut preimage = Vec::with_capacity(66);
+    preimage.extend_from_slice(&[0x19, 0x01]);
+    preimage.extend_from_slice(&domain_separator);
+    if let Some(message_hash) = message_hash {
+        preimage.extend_from_slice(&message_hash);
+    }
+
+    Ok(TypedDataHash {
+        domain_separator,
+        message_hash,
+        digest: keccak256(preimage),
+    })
+}
+
+fn parse_typed_data(typed_data_json: &str) -> Result<TypedData> {
+    let value: Value = serde_json::from_str(typed_data_json)?;
+
+    // ethers.js and older MetaMask builds send the payload JSON-stringified twice
+    let value = match value {
+        Value::String(inner) => serde_json::from_str(&inner)?,
+        other => other,
+    };
+
+    Ok(serde_json::from_value(value)?)
+}
+
+fn infer_domain_fields(domain: &Value) -> Vec<TypedField> {
+    [
+        ("name", "string"),
+        ("version", "string"),
+        ("chainId", "uint256"),
+        ("verifyingContract", "address"),
+        ("salt", "bytes32"),
+    ]
+    .iter()
+    .filter(|(name, _)| domain.get(name).is_some_and(|v| !v.is_null()))
+    .map(|(name, field_type)| TypedField {
+        name: name.to_string(),
+        field_type: field_type.to_string(),
+    })
+    .collect()
+}
+
+/// `encodeType`: the primary type followed by its dependencies sorted by name
+fn encode_type(primary_type: &str, types: &HashMap<String, Vec<TypedField>>) -> Result<String> {
+    let mut dependencies = BTreeSet::new();
+    collect_dependencies(primary_type, types, &mut dependencies);
+    dependencies.remove(primary_type);
+
+    let mut encoded = String::new();
+    for type_name in std::iter::once(primary_type).chain(dependencies.iter().map(String::as_str)) {
+        let fields = types.get(type_name).ok_or(CoreError::InvalidInput)?;
+        let members = fields
+            .iter()
+            .map(|f| format!("{} {}", f.field_type, f.name))
+            .collect::<Vec<_>>()
+            .join(",");
+        encoded.push_str(&format!("{}({})", type_name, members));
+    }
+    Ok(encoded)
+}
+
+fn collect_dependencies(
+    type_name: &str,
+    types: &HashMap<String, Vec<TypedField>>,
+    found: &mut BTreeSet<String>,
+) {
+    let base = base_type(type_name);
+    if found.contains(base) {
+        return;
+    }
+    if let Some(fields) = types.get(base) {
+        found.insert(base.to_string());
+        for field in fields {
+            collect_dependencies(&field.field_type, types, found);
+        }
+    }
+}
+
+/// Strip every array suffix: `Person[][2]` -> `Person`
+fn base_type(type_name: &str) -> &str {
+    type_name.split('[').next().unwrap_or(type_name)
+}
+
+fn hash_struct(
+    type_name: &str,
+    data: &Value,
+    types: &HashMap<String, Vec<TypedField>>,
+) -> Result<[u8; 32]> {
+    let fields = types.get(type_name).ok_or(CoreError::InvalidInput)?;
+
+    let mut encoded = Vec::with_capacity(32 * (fields.len() + 1));
+    encoded.extend_from_slice(&keccak256(encode_type(type_name, types)?));
+
+    for field in fields {
+        let value = data.get(&field.name).unwrap_or(&Value::Null);
+        encoded.extend_from_slice(&encode_value(&field.field_type, value, types)?);
+    }
+
+    Ok(keccak256(encoded))
+}
+
+/// Encode a single member value into its 32-byte word
+fn encode_value(
+    field_type: &str,
+    value: &Value,
+    types: &HashMap<String, Vec<TypedField>>,
+) -> Result<[u8; 32]> {
+    // Arrays: keccak of the concatenated encodings of the elements
+    if let Some(open) = field_type.rfind('[') {
+        let element_type = &field_type[..open];
+        let length = field_type[open + 1..]
+            .strip_suffix(']')
+            .ok_or(CoreError::InvalidInput)?;
+        let items = value.as_array().ok_or(CoreError::InvalidInput)?;
+
+        if !length.is_empty() && length.parse::<usize>().ok() != Some(items.len()) {
+            return Err(CoreError::InvalidInput);
+        }
+
+        let mut encoded = Vec::with_capacity(32 * items.len());
+        for item in items {
+            encoded.extend_from_slice(&encode_value(element_type, item, types)?);
+        }
+        return Ok(keccak256(encoded));
+    }
+
+    // Nested structs are replaced by their hashStruct; a missing one is zero
+    if types.contains_key(field_type) {
+        if value.is_null() {
+            return Ok([0u8; 32]);
+        }
+        return hash_struct(field_type, value, types);
+    }
+
+    if value.is_null() {
+        return Err(CoreError::InvalidInput);
+    }
+
+    match field_type {
+        "string" => {
+            let s = value.as_str().ok_or(CoreError::InvalidInput)?;
+            Ok(keccak256(s.as_bytes()))
+        }
+        "bytes" => Ok(keccak256(parse_bytes(value)?)),
+        "bool" => {
+            let flag = match value {
+                Value::Bool(b) => *b,
+                Value::String(s) if s == "true" => true,
+                Value::String(s) if s == "false" => false,
+                _ => return Err(CoreError::InvalidInput),
+            };
+            Ok(u256_word(U256::from(flag as u8)))
+        }
+        "address" => {
+            let bytes = parse_bytes(value)?;
+            if bytes.len() != 20 {
+                return Err(CoreError::InvalidAddress);
+            }
+            let mut word = [0u8; 32];
+            word[12..].copy_from_slice(&bytes);
+            Ok(word)
+        }
+        t if t.starts_with("bytes") => {
+            let size: usize = t[5..].parse().map_err(|_| CoreError::InvalidInput)?;
+            let bytes = parse_bytes(value)?;
+            if size == 0 || size > 32 || bytes.len() > size {
+                return Err(CoreError::InvalidInput);
+            }
+            // bytesN is right-padded
+            let mut word = [0u8; 32];
+            word[..bytes.len()].copy_from_slice(&bytes);
+            Ok(word)
+        }
+        t if t.starts_with("uint") => {
+            let bits = int_bits(&t[4..])?;
+            let n = parse_uint(value)?;
+            if n.bits() > bits {
+                return Err(CoreError::InvalidInput);
+            }
+            Ok(u256_word(n))
+        }
+        // intN is sign-extended to 256 bits (two's complement)
+        t if t.starts_with("int") => {
+            let bits = int_bits(&t[3..])?;
+            let n = parse_int(value)?;
+            if bits < 256 {
+                let bound = I256::from_raw(U256::one() << (bits - 1));
+                if n < -bound || n >= bound {
+                    return Err(CoreError::InvalidInput);
+                }
+            }
+            Ok(u256_word(n.into_raw()))
+        }
+        _ => Err(CoreError::InvalidInput),
+    }
+}
+
+/// Width N of a `uintN`/`intN` suffix; a bare `uint`/`int` is 256
+fn int_bits(suffix: &str) -> Result<usize> {
+    if suffix.is_empty() {
+        return Ok(256);
+    }
+    match suffix.parse::<usize>() {
+        Ok(bits) if (8..=256).contains(&bits) && bits.is_multiple_of(8) => Ok(bits),
+        _ => Err(CoreError::InvalidInput),
+    }
+}
+
+fn u256_word(value: U256) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    value.to_big_endian(&mut word);
+    word
+}
+
+fn parse_bytes(value: &Value) -> Result<Vec<u8>> {
+    let s = value.as_str().ok_or(CoreError::InvalidInput)?;
+    hex::decode(s.trim_start_matches("0x")).map_err(|_| CoreError::InvalidInput)
+}
+
+fn parse_uint(value: &Value) -> Result<U256> {
+    match value {
+        Value::Number(n) => n.as_u64().map(U256::from).ok_or(CoreError::InvalidInput),
+        Value::String(s) => match s.strip_prefix("0x") {
+            Some(hex_value) => {
+                U256::from_str_radix(hex_value, 16).map_err(|_| CoreError::InvalidInput)
+            }
+            None => U256::from_dec_str(s).map_err(|_| CoreError::InvalidInput),
+        },
+        _ => Err(CoreError::InvalidInput),
+    }
+}
+
+fn parse_int(value: &Value) -> Result<I256> {
+    match value {
+        Value::Number(n) => n.as_i64().map(I256::from).ok_or(CoreError::InvalidInput),
+        Value::String(s) if s.starts_with("0x") => Ok(I256::from_raw(parse_uint(value)?)),
+        Value::String(s) => I256::from_dec_str(s).map_err(|_| CoreError::InvalidInput),
+        _ => Err(CoreError::InvalidInput),
+    }
+}
+
+/// Example from the EIP-712 specification, also signed in the signer tests
+#[cfg(test)]
+pub(crate) const MAIL_TYPED_DATA: &str = r#"{
+    "types": {
+        "EIP712Domain": [
+            {"name": "name", "type": "string"},
+            {"name": "version", "type": "string"},
+            {"name": "chainId", "type": "uint256"},
+            {"name": "verifyingContract", "type": "address"}
+        ],
+        "Person": [
+            {"name": "name", "type": "string"},
+            {"name": "wallet", "type": "address"}
+        ],
+        "Mail": [
+            {"name": "from", "type": "Person"},
+            {"name": "to", "type": "Person"},
+            {"name": "contents", "type": "string"}
+        ]
+    },
+    "primaryType": "Mail",
+    "domain": {
+        "name": "Ether Mail",
+        "version": "1",
+        "chainId": 1,
+        "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+    },
+    "message": {
+        "from": {"name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"},
+        "to": {"name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"},
+        "contents": "Hello, Bob!"
+    }
+}"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_type_orders_dependencies() {
+        let typed_data = parse_typed_data(MAIL_TYPED_DATA).unwrap();
+        assert_eq!(
+            encode_type("Mail", &typed_data.types).unwrap(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+    }
+
+    #[test]
+    fn test_hash_typed_data_eip712_vector() {
+        let hash = hash_typed_data(MAIL_TYPED_DATA).unwrap();
+        assert_eq!(
+            hex::encode(hash.domain_separator),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+        assert_eq!(
+            hex::encode(hash.message_hash.unwrap()),
+            "c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e"
+        );
+        assert_eq!(
+            hex::encode(hash.digest),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+    }
+
+    #[test]
+    fn test_hash_typed_data_with_arrays() {
+        // signTypedData_v4 vector from MetaMask eth-sig-util
+        let typed_data = r#"{
+            "types": {
+                "EIP712Domain": [
+                    {"name": "name", "type": "string"},
+                    {"name": "version", "type": "string"},
+                    {"name": "chainId", "type": "uint256"},
+                    {"name": "verifyingContract", "type": "address"}
+                ],
+                "Group": [
+                    {"name": "name", "type": "string"},
+                    {"name": "members", "type": "Person[]"}
+                ],
+                "Mail": [
+                    {"name": "from", "type": "Person"},
+                    {"name": "to", "type": "Person[]"},
+                    {"name": "contents", "type": "string"}
+                ],
+                "Person": [
+                    {"name": "name", "type": "string"},
+                    {"name": "wallets", "type": "address[]"}
+                ]
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "chainId": 1,
+                "name": "Ether Mail",
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC",
+                "version": "1"
+            },
+            "message": {
+                "contents": "Hello, Bob!",
+                "from": {
+                    "name": "Cow",
+                    "wallets": [
+                        "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826",
+                        "0xDeaDbeefdEAdbeefdEadbEEFdeadbeEFdEaDbeeF"
+                    ]
+                },
+                "to": [{
+                    "name": "Bob",
+                    "wallets": [
+                        "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB",
+                        "0xB0BdaBea57B0BDABeA57b0bdABEA57b0BDabEa57",
+                        "0xB0B0b0b0b0b0B000000000000000000000000000"
+                    ]
+                }]
+            }
+        }"#;
+
+        let typed = parse_typed_data(typed_data).unwrap();
+        assert_eq!(
+            encode_type("Group", &typed.types).unwrap(),
+            "Group(string name,Person[] members)Person(string name,address[] wallets)"
+        );
+
+        let hash = hash_typed_data(typed_data).unwrap();
+        assert_eq!(
+            hex::encode(hash.digest),
+            "a85c2e2b118698e88db68a8105b794a8cc7cec074e89ef991cb4f5f533819cc2"
+        );
+    }
+
+    #[test]
+    fn test_stringified_payload_and_inferred_domain() {
+        let value: Value = serde_json::from_str(MAIL_TYPED_DATA).unwrap();
+        let mut without_domain_type = value.clone();
+        without_domain_type["types"]
+            .as_object_mut()
+            .unwrap()
+            .remove("EIP712Domain");
+
+        let stringified = Value::String(without_domain_type.to_string()).to_string();
+        assert_eq!(
+            hash_typed_data(&stringified).unwrap(),
+            hash_typed_data(MAIL_TYPED_DATA).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_atomic_encodings() {
+        let types = HashMap::new();
+        assert_eq!(
+            encode_value("int8", &Value::String("-1".to_string()), &types).unwrap(),
+            [0xff; 32]
+        );
+        assert_eq!(
+            encode_value("bytes4", &Value::String("0xdeadbeef".to_string()), &types).unwrap()[..5],
+            [0xde, 0xad, 0xbe, 0xef, 0x00]
+        );
+        assert_eq!(
+            encode_value("uint256", &Value::String("0x10".to_string()), &types).unwrap()[31],
+            0x10
+        );
+        assert!(encode_value("uint256[2]", &serde_json::json!([1]), &types).is_err());
+        assert!(encode_value("address", &Value::String("0x12".to_string()), &types).is_err());
+    }
+
+    #[test]
+    fn test_rejects_malformed_types_and_out_of_range_integers() {
+        let types = HashMap::new();
+        let number = |n: i64| serde_json::json!(n);
+        assert!(matches!(
+            encode_value("uint256[", &serde_json::json!([1]), &types),
+            Err(CoreError::InvalidInput)
+        ));
+        assert!(encode_value("uint256[1", &serde_json::json!([1]), &types).is_err());
+
+        assert!(encode_value("uint8", &number(255), &types).is_ok());
+        assert!(encode_value("uint8", &number(300), &types).is_err());
+        assert!(encode_value("uint7", &number(1), &types).is_err());
+        assert!(encode_value("uint264", &number(1), &types).is_err());
+        assert!(encode_value("int8", &number(-128), &types).is_ok());
+        assert!(encode_value("int8", &number(127), &types).is_ok());
+        assert!(encode_value("int8", &number(128), &types).is_err());
+        assert!(encode_value("int8", &number(-129), &types).is_err());
+        assert!(encode_value("int", &number(i64::MIN), &types).is_ok());
+    }
+}
@@ -0,0 +1,661 @@
+// Offline signing envelopes
+// A watch-only wallet packs what it needs signed into a request, an
+// air-gapped wallet holding the key answers with a response, and the online
+// side checks the response against its request before broadcasting
+
+use crate::audit::SigningAction;
+use crate::policy::SpendRequest;
+use crate::signer::EcdsaSignature;
+use crate::storage::Keyring;
+use crate::{aa, config, crypto, eip712, evm, tron};
+use crate::{error::*, types::*};
+use ethers_core::utils::keccak256;
+use rand::RngCore;
+
+/// Envelope format written by this build
+pub const ENVELOPE_VERSION: u32 = 1;
+
+/// Request for the already derived account `account_index` of `wallet_id`,
+/// which may be watch-only. An empty `from` in transaction params is filled
+/// with the account.
+pub(crate) fn create_request(
+    keyring: &Keyring,
+    wallet_id: &str,
+    account_index: u32,
+    mut payload: SigningPayload,
+    origin: Option<String>,
+) -> Result<SigningRequest> {
+    let chain = payload_chain(&payload);
+    let account = keyring.with_wallet(wallet_id, |wallet| {
+        wallet
+            .to_wallet_data()
+            .accounts
+            .into_iter()
+            .find(|a| a.chain == chain && a.index == account_index)
+            .ok_or(CoreError::InvalidInput)
+    })?;
+
+    match &mut payload {
+        SigningPayload::EvmTransaction { params } => fill_from(&mut params.from, &account)?,
+        SigningPayload::TronTransaction { params } => fill_from(&mut params.from, &account)?,
+        _ => {}
+    }
+    let display = display_hints(&payload, &account.address, origin)?;
+
+    let mut request_id = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut request_id);
+    Ok(SigningRequest {
+        version: ENVELOPE_VERSION,
+        request_id: hex::encode(request_id),
+        chain,
+        chain_id: payload_chain_id(&payload),
+        account: account.address,
+        account_index,
+        derivation_path: account.derivation_path,
+        display,
+        payload,
+        created_at: now()?,
+    })
+}
+
+/// Sign `request` with whichever wallet in the keyring holds its account.
+/// The spending policy and audit log apply as for any other signature.
+/// Display hints other than those `parse_request` works out are refused.
+pub(crate) fn sign_request(keyring: &Keyring, request: &SigningRequest) -> Result<SigningResponse> {
+    if request.display != checked_hints(request)? {
+        return Err(CoreError::InvalidEnvelope);
+    }
+    let (wallet_id, index) = find_signing_account(keyring, request.chain, &request.account)?;
+    let (signature, signed_tx, tx_hash) = match &request.payload {
+        SigningPayload::EvmTransaction { params } => {
+            let tx = evm::sign_transaction(keyring, wallet_id, index, params.clone())?;
+            let signature = evm::signed_transaction_signature(params, &tx.signed_tx)?;
+            (signature.to_hex(), Some(tx.signed_tx), Some(tx.hash))
+        }
+        SigningPayload::TypedData { typed_data_json } => (
+            evm::sign_typed_data(keyring, wallet_id, index, typed_data_json.clone())?,
+            None,
+            None,
+        ),
+        SigningPayload::PersonalMessage { chain, message } => {
+            let signature = match chain {
+                Chain::Evm => evm::sign_message(keyring, wallet_id, index, message.clone())?,
+                Chain::Tron => tron::sign_message(keyring, wallet_id, index, message.clone())?,
+            };
+            (signature, None, None)
+        }
+        SigningPayload::UserOperation { params } => (
+            aa::sign_user_operation(keyring, wallet_id, index, params.clone())?,
+            None,
+            None,
+        ),
+        SigningPayload::TronTransaction { params } => {
+            let tx = tron::sign_transaction(keyring, wallet_id, index, params.clone())?;
+            let (_, signature) = tron::signed_transaction_signature(params, &tx.signed_tx)?;
+            (
+                hex::encode(signature.to_rsv()),
+                Some(tx.signed_tx),
+                Some(tx.txid),
+            )
+        }
+    };
+
+    Ok(SigningResponse {
+        version: ENVELOPE_VERSION,
+        request_id: request.request_id.clone(),
+        account: request.account.clone(),
+        signature,
+        signed_tx,
+        tx_hash,
+    })
+}
+
+/// Check that `response` answers `request`: same id and account, a
+/// signature by that account over the requested payload, and for
+/// transactions exactly the requested transaction
+pub(crate) fn accept_response(
+    request: &SigningRequest,
+    mut response: SigningResponse,
+) -> Result<SigningResponse> {
+    if response.request_id != request.request_id
+        || !same_address(request.chain, &response.account, &request.account)
+    {
+        return Err(CoreError::EnvelopeMismatch);
+    }
+
+    let claimed = EcdsaSignature::from_hex(&response.signature).ok();
+    let signed_tx = response.signed_tx.clone();
+    let (digest, signature) = match &request.payload {
+        SigningPayload::EvmTransaction { params } => {
+            let signed_tx = signed_tx.ok_or(CoreError::EnvelopeMismatch)?;
+            let signature = evm::signed_transaction_signature(params, &signed_tx)
+                .map_err(|_| CoreError::EnvelopeMismatch)?;
+            let raw_tx = evm::parse_data(Some(&signed_tx))?;
+            response.tx_hash = Some(format!("0x{}", hex::encode(keccak256(&raw_tx))));
+            (evm::transaction_sighash(params)?, signature)
+        }
+        SigningPayload::TronTransaction { params } => {
+            let signed_tx = signed_tx.ok_or(CoreError::EnvelopeMismatch)?;
+            let (txid, signature) = tron::signed_transaction_signature(params, &signed_tx)
+                .map_err(|_| CoreError::EnvelopeMismatch)?;
+            response.tx_hash = Some(hex::encode(txid));
+            (txid, signature)
+        }
+        SigningPayload::TypedData { typed_data_json } => (
+            eip712::hash_typed_data(typed_data_json)?.digest,
+            claimed.ok_or(CoreError::EnvelopeMismatch)?,
+        ),
+        SigningPayload::PersonalMessage { chain, message } => {
+            let digest = match chain {
+                Chain::Evm => evm::personal_message_hash(message.as_bytes()),
+                Chain::Tron => tron::message_hash(message),
+            };
+            (digest, claimed.ok_or(CoreError::EnvelopeMismatch)?)
+        }
+        SigningPayload::UserOperation { params } => (
+            aa::compute_userop_hash(params)?,
+            claimed.ok_or(CoreError::EnvelopeMismatch)?,
+        ),
+    };
+
+    let public_key = signature
+        .recover(&digest)
+        .map_err(|_| CoreError::EnvelopeMismatch)?;
+    let signer = match request.chain {
+        Chain::Evm => crypto::public_key_to_address(&public_key),
+        Chain::Tron => crypto::public_key_to_tron_address(&public_key),
+    };
+    if !same_address(request.chain, &signer, &request.account) {
+        return Err(CoreError::EnvelopeMismatch);
+    }
+    Ok(response)
+}
+
+/// Parse a request and replace its display hints with ones worked out from
+/// the payload, so nothing the requester wrote is shown but the origin. A
+/// chain or chain id that disagrees with the payload is refused.
+pub(crate) fn parse_request(envelope: &str) -> Result<SigningRequest> {
+    let mut request: SigningRequest =
+        serde_json::from_str(envelope).map_err(|_| CoreError::InvalidEnvelope)?;
+    check_version(request.version)?;
+    request.display = checked_hints(&request)?;
+    Ok(request)
+}
+
+pub(crate) fn parse_response(envelope: &str) -> Result<SigningResponse> {
+    let response: SigningResponse =
+        serde_json::from_str(envelope).map_err(|_| CoreError::InvalidEnvelope)?;
+    check_version(response.version)?;
+    Ok(response)
+}
+
+fn check_version(version: u32) -> Result<()> {
+    match version {
+        1..=ENVELOPE_VERSION => Ok(()),
+        _ => Err(CoreError::InvalidEnvelope),
+    }
+}
+
+fn payload_chain(payload: &SigningPayload) -> Chain {
+    match payload {
+        SigningPayload::PersonalMessage { chain, .. } => *chain,
+        SigningPayload::TronTransaction { .. } => Chain::Tron,
+        _ => Chain::Evm,
+    }
+}
+
+fn payload_chain_id(payload: &SigningPayload) -> Option<u64> {
+    match payload {
+        SigningPayload::EvmTransaction { params } => Some(params.chain_id),
+        SigningPayload::TypedData { typed_data_json } => {
+            let value: serde_json::Value = serde_json::from_str(typed_data_json).ok()?;
+            let chain_id = &value["domain"]["chainId"];
+            chain_id.as_u64().or_else(|| {
+                let chain_id = chain_id.as_str()?;
+                match chain_id.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                    None => chain_id.parse().ok(),
+                }
+            })
+        }
+        SigningPayload::UserOperation { params } => params.chain_id,
+        _ => None,
+    }
+}
+
+/// Hints for `request` from its payload, after checking that its chain and
+/// chain id are the payload's
+fn checked_hints(request: &SigningRequest) -> Result<DisplayHints> {
+    if request.chain != payload_chain(&request.payload)
+        || request.chain_id != payload_chain_id(&request.payload)
+    {
+        return Err(CoreError::InvalidEnvelope);
+    }
+    display_hints(
+        &request.payload,
+        &request.account,
+        request.display.origin.clone(),
+    )
+}
+
+fn display_hints(
+    payload: &SigningPayload,
+    account: &str,
+    origin: Option<String>,
+) -> Result<DisplayHints> {
+    let (title, action) = describe(payload, account)?;
+    let chain_id = payload_chain_id(payload);
+    Ok(DisplayHints {
+        title: title.to_string(),
+        summary: action.summary,
+        network: network_name(payload_chain(payload), chain_id),
+        origin,
+    })
+}
+
+/// Title for the signing screen and the payload described as the audit log
+/// would; also rejects payloads that could not be signed
+fn describe(payload: &SigningPayload, account: &str) -> Result<(&'static str, SigningAction)> {
+    Ok(match payload {
+        SigningPayload::EvmTransaction { params } => {
+            evm::transaction_sighash(params)?;
+            (
+                "Send transaction",
+                SigningAction::transaction(SpendRequest::evm(account, params)?),
+            )
+        }
+        SigningPayload::TypedData { typed_data_json } => {
+            eip712::hash_typed_data(typed_data_json)?;
+            (
+                "Sign typed data",
+                SigningAction::typed_data(account, typed_data_json),
+            )
+        }
+        SigningPayload::PersonalMessage { chain, message } => (
+            "Sign message",
+            SigningAction::message(*chain, message.as_bytes()),
+        ),
+        SigningPayload::UserOperation { params } => (
+            "Sign user operation",
+            SigningAction::user_operation(SpendRequest::user_op(account, params)?),
+        ),
+        SigningPayload::TronTransaction { params } => (
+            "Send TRON transaction",
+            SigningAction::transaction(SpendRequest::tron(account, params)?),
+        ),
+    })
+}
+
+fn fill_from(from: &mut String, account: &Account) -> Result<()> {
+    if from.is_empty() {
+        *from = account.address.clone();
+    } else if !from.eq_ignore_ascii_case(&account.address) {
+        return Err(CoreError::InvalidAddress);
+    }
+    Ok(())
+}
+
+/// EVM hex compares regardless of checksum casing; TRON base58 is exact
+fn same_address(chain: Chain, a: &str, b: &str) -> bool {
+    match chain {
+        Chain::Evm => a.eq_ignore_ascii_case(b),
+        Chain::Tron => a == b,
+    }
+}
+
+fn network_name(chain: Chain, chain_id: Option<u64>) -> Option<String> {
+    match (chain, chain_id) {
+        (Chain::Tron, _) => Some("TRON".to_string()),
+        (Chain::Evm, Some(config::NOR_CHAIN_ID)) => Some(config::NOR_CHAIN_NAME.to_string()),
+        (Chain::Evm, Some(chain_id)) => Some(format!("EVM chain {}", chain_id)),
+        (Chain::Evm, None) => None,
+    }
+}
+
+/// Wallet id and index of the derived account `address`, among wallets
+/// that can sign
+pub(crate) fn find_signing_account(
+    keyring: &Keyring,
+    chain: Chain,
+    address: &str,
+) -> Result<(String, u32)> {
+    for wallet_id in keyring.wallet_ids()? {
+        let index = keyring.with_wallet(&wallet_id, |wallet| {
+            if wallet.is_watch_only() {
+                return Ok(None);
+            }
+            Ok(wallet
+                .to_wallet_data()
+                .accounts
+                .into_iter()
+                .find(|a| a.chain == chain && same_address(chain, &a.address, address))
+                .map(|a| a.index))
+        })?;
+        if let Some(index) = index {
+            return Ok((wallet_id, index));
+        }
+    }
+    Err(CoreError::InvalidInput)
+}
+
+fn now() -> Result<u64> {
+    Ok(std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|_| CoreError::InternalError)?
+        .as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Wallet;
+
+    /// An offline keyring holding a seed and an online one watching its
+    /// accounts by address. Returns the keyrings and the EVM and TRON watch
+    /// wallet ids.
+    fn devices() -> (Keyring, Keyring, String, String) {
+        let offline = Keyring::new();
+        let mut wallet = Wallet::from_entropy(&[23u8; 16], None).unwrap();
+        wallet.derive_chain_account(Chain::Tron, 0).unwrap();
+        let addresses = [
+            wallet.get_address(Chain::Evm, 0).unwrap(),
+            wallet.get_address(Chain::Tron, 0).unwrap(),
+        ];
+        offline.insert(wallet).unwrap();
+
+        let online = Keyring::new();
+        let evm = Wallet::from_addresses(Chain::Evm, &addresses[..1]).unwrap();
+        let tron = Wallet::from_addresses(Chain::Tron, &addresses[1..]).unwrap();
+        let ids = (evm.id.clone(), tron.id.clone());
+        online.insert(evm).unwrap();
+        online.insert(tron).unwrap();
+        (offline, online, ids.0, ids.1)
+    }
+
+    /// Through JSON both ways, as it would travel
+    fn round_trip(offline: &Keyring, request: &SigningRequest) -> SigningResponse {
+        let request = parse_request(&serde_json::to_string(request).unwrap()).unwrap();
+        let response = sign_request(offline, &request).unwrap();
+        parse_response(&serde_json::to_string(&response).unwrap()).unwrap()
+    }
+
+    fn transfer() -> EvmTxParams {
+        EvmTxParams {
+            to: "0x3535353535353535353535353535353535353535".to_string(),
+            value: "1000".to_string(),
+            gas_limit: 21000,
+            gas_price: "1000000000".to_string(),
+            chain_id: config::NOR_CHAIN_ID,
+            ..Default::default()
+        }
+    }
+
+    fn tron_transfer() -> TronTxParams {
+        TronTxParams {
+            from: String::new(),
+            to: "TJCnKsPa7y5okkXvQAidZBzqx3QyQ6sxMW".to_string(),
+            amount: 5,
+            contract_address: None,
+            data: None,
+            fee_limit: 0,
+            ref_block_id: format!("{:064x}", 1),
+            expiration: 60000,
+            timestamp: 1,
+        }
+    }
+
+    #[test]
+    fn test_every_payload_round_trips() {
+        let (offline, online, evm_id, tron_id) = devices();
+        let typed_data = serde_json::json!({
+            "types": {
+                "EIP712Domain": [{"name": "name", "type": "string"}, {"name": "chainId", "type": "uint256"}],
+                "Mail": [{"name": "contents", "type": "string"}]
+            },
+            "primaryType": "Mail",
+            "domain": {"name": "Ether Mail", "chainId": "0x1"},
+            "message": {"contents": "Hello"}
+        });
+        let user_op = UserOpParams {
+            sender: "0x3535353535353535353535353535353535353535".to_string(),
+            nonce: "0x0".to_string(),
+            init_code: "0x".to_string(),
+            call_data: "0x".to_string(),
+            call_gas_limit: "0x1".to_string(),
+            verification_gas_limit: "0x1".to_string(),
+            pre_verification_gas: "0x1".to_string(),
+            max_fee_per_gas: "0x1".to_string(),
+            max_priority_fee_per_gas: "0x1".to_string(),
+            paymaster_and_data: "0x".to_string(),
+            signature: "0x".to_string(),
+            chain_id: Some(65001),
+        };
+
+        let payloads = [
+            (
+                &evm_id,
+                SigningPayload::EvmTransaction { params: transfer() },
+            ),
+            (
+                &evm_id,
+                SigningPayload::TypedData {
+                    typed_data_json: typed_data.to_string(),
+                },
+            ),
+            (
+                &evm_id,
+                SigningPayload::PersonalMessage {
+                    chain: Chain::Evm,
+                    message: "login".to_string(),
+                },
+            ),
+            (
+                &tron_id,
+                SigningPayload::PersonalMessage {
+                    chain: Chain::Tron,
+                    message: "login".to_string(),
+                },
+            ),
+            (&evm_id, SigningPayload::UserOperation { params: user_op }),
+            (
+                &tron_id,
+                SigningPayload::TronTransaction {
+                    params: tron_transfer(),
+                },
+            ),
+        ];
+        for (wallet_id, payload) in payloads {
+            let request = create_request(&online, wallet_id, 0, payload, None).unwrap();
+            let response = round_trip(&offline, &request);
+            let accepted = accept_response(&request, response.clone()).unwrap();
+            assert_eq!(accepted.tx_hash, response.tx_hash);
+        }
+    }
+
+    #[test]
+    fn test_request_context_and_hints() {
+        let (_, online, evm_id, _) = devices();
+        let request = create_request(
+            &online,
+            &evm_id,
+            0,
+            SigningPayload::EvmTransaction { params: transfer() },
+            Some("app.example".to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(request.chain_id, Some(config::NOR_CHAIN_ID));
+        let address = request.account.clone();
+        assert_eq!(
+            online
+                .with_wallet(&evm_id, |w| w.get_address(Chain::Evm, 0))
+                .unwrap(),
+            address
+        );
+        assert_eq!(request.display.network.as_deref(), Some("Nor Chain"));
+        assert_eq!(request.display.origin.as_deref(), Some("app.example"));
+        assert!(request.display.summary.starts_with("send 1000 to 0x3535"));
+        match &request.payload {
+            SigningPayload::EvmTransaction { params } => assert_eq!(params.from, address),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let mut foreign = transfer();
+        foreign.from = "0x5757575757575757575757575757575757575757".to_string();
+        assert!(matches!(
+            create_request(
+                &online,
+                &evm_id,
+                0,
+                SigningPayload::EvmTransaction { params: foreign },
+                None
+            ),
+            Err(CoreError::InvalidAddress)
+        ));
+
+        let mut newer: serde_json::Value = serde_json::to_value(&request).unwrap();
+        newer["version"] = (ENVELOPE_VERSION + 1).into();
+        assert!(matches!(
+            parse_request(&newer.to_string()),
+            Err(CoreError::InvalidEnvelope)
+        ));
+    }
+
+    #[test]
+    fn test_display_hints_come_from_the_payload() {
+        let (offline, online, evm_id, _) = devices();
+        let request = create_request(
+            &online,
+            &evm_id,
+            0,
+            SigningPayload::EvmTransaction { params: transfer() },
+            Some("app.example".to_string()),
+        )
+        .unwrap();
+
+        let mut forged = request.clone();
+        forged.display.title = "Sign in".to_string();
+        forged.display.summary = "nothing leaves your wallet".to_string();
+        forged.display.network = Some("Testnet".to_string());
+        let parsed = parse_request(&serde_json::to_string(&forged).unwrap()).unwrap();
+        assert_eq!(parsed.display, request.display);
+        assert!(matches!(
+            sign_request(&offline, &forged),
+            Err(CoreError::InvalidEnvelope)
+        ));
+
+        let mut other_chain = request.clone();
+        other_chain.chain_id = Some(1);
+        assert!(matches!(
+            parse_request(&serde_json::to_string(&other_chain).unwrap()),
+            Err(CoreError::InvalidEnvelope)
+        ));
+        other_chain.chain_id = request.chain_id;
+        other_chain.chain = Chain::Tron;
+        assert!(matches!(
+            parse_request(&serde_json::to_string(&other_chain).unwrap()),
+            Err(CoreError::InvalidEnvelope)
+        ));
+    }
+
+    #[test]
+    fn test_mismatched_responses_are_refused() {
+        let (offline, online, evm_id, _) = devices();
+        let request = create_request(
+            &online,
+            &evm_id,
+            0,
+            SigningPayload::EvmTransaction { params: transfer() },
+            None,
+        )
+        .unwrap();
+        let response = round_trip(&offline, &request);
+
+        let mut other_id = response.clone();
+        other_id.request_id = "00".to_string();
+        assert!(matches!(
+            accept_response(&request, other_id),
+            Err(CoreError::EnvelopeMismatch)
+        ));
+
+        // A valid signature, but over a different transaction
+        let mut swapped = transfer();
+        swapped.value = "999999".to_string();
+        let other = create_request(
+            &online,
+            &evm_id,
+            0,
+            SigningPayload::EvmTransaction { params: swapped },
+            None,
+        )
+        .unwrap();
+        let mut other_tx = response.clone();
+        other_tx.signed_tx = round_trip(&offline, &other).signed_tx;
+        assert!(matches!(
+            accept_response(&request, other_tx),
+            Err(CoreError::EnvelopeMismatch)
+        ));
+
+        // A message signed by a different key
+        let request = create_request(
+            &online,
+            &evm_id,
+            0,
+            SigningPayload::PersonalMessage {
+                chain: Chain::Evm,
+                message: "login".to_string(),
+            },
+            None,
+        )
+        .unwrap();
+        let mut forged = round_trip(&offline, &request);
+        let stranger = secp256k1::SecretKey::from_slice(&[9u8; 32]).unwrap();
+        forged.signature =
+            crate::signer::sign_with_key(&stranger, &evm::personal_message_hash(b"login"))
+                .unwrap()
+                .to_hex();
+        assert!(matches!(
+            accept_response(&request, forged),
+            Err(CoreError::EnvelopeMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_tron_transactions_are_refused() {
+        let (offline, online, _, tron_id) = devices();
+        let request = create_request(
+            &online,
+            &tron_id,
+            0,
+            SigningPayload::TronTransaction {
+                params: tron_transfer(),
+            },
+            None,
+        )
+        .unwrap();
+        let response = round_trip(&offline, &request);
+        let signed: serde_json::Value =
+            serde_json::from_str(response.signed_tx.as_deref().unwrap()).unwrap();
+
+        // Bytes that would broadcast differently from what was signed, with
+        // the JSON fields and signature left as they were
+        let raw_data = signed["raw_data_hex"].as_str().unwrap();
+        let mut tampered = signed.clone();
+        tampered["raw_data_hex"] = format!("{}00", raw_data).into();
+        let mut extra_bytes = response.clone();
+        extra_bytes.signed_tx = Some(tampered.to_string());
+        assert!(matches!(
+            accept_response(&request, extra_bytes),
+            Err(CoreError::EnvelopeMismatch)
+        ));
+
+        let mut tampered = signed.clone();
+        tampered["raw_data"]["contract"][0]["parameter"]["value"]["amount"] = 500.into();
+        let mut other_amount = response.clone();
+        other_amount.signed_tx = Some(tampered.to_string());
+        assert!(matches!(
+            accept_response(&request, other_amount),
+            Err(CoreError::EnvelopeMismatch)
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::types::PolicyViolation;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, CoreError>;
@@ -8,6 +9,15 @@ pub enum CoreError {
     #[error("Invalid mnemonic phrase")]
     InvalidMnemonic,
 
+    #[error("Mnemonic contains a word not in the wordlist")]
+    UnknownMnemonicWord,
+
+    #[error("Mnemonic has an invalid word count")]
+    InvalidMnemonicLength,
+
+    #[error("Mnemonic checksum does not match")]
+    InvalidMnemonicChecksum,
+
     #[error("Invalid private key")]
     InvalidPrivateKey,
 
@@ -31,11 +41,70 @@ pub enum CoreError {
 
     #[error("Internal error")]
     InternalError,
+
+    #[error("Vault is locked")]
+    VaultLocked,
+
+    #[error("Invalid password")]
+    InvalidPassword,
+
+    #[error("Password confirmation required for this operation")]
+    ReauthenticationRequired,
+
+    #[error("Storage error")]
+    StorageError,
+
+    #[error("Wallet is watch-only and cannot sign")]
+    WatchOnly,
+
+    #[error("Signer does not support this operation")]
+    UnsupportedBySigner,
+
+    #[error("Request was rejected on the hardware wallet")]
+    DeviceRejected,
+
+    #[error("Hardware wallet error")]
+    DeviceError,
+
+    #[error("Blocked by policy rule `{}`: {}", .0.rule_id, .0.reason)]
+    PolicyViolation(PolicyViolation),
+
+    #[error("Spending policy is malformed or from a newer version")]
+    InvalidPolicy,
+
+    #[error("Audit log failed verification")]
+    AuditLogTampered,
+
+    #[error("Signing envelope is malformed or from a newer version")]
+    InvalidEnvelope,
+
+    #[error("Signed response does not match the request")]
+    EnvelopeMismatch,
+
+    #[error("QR code data is not a valid UR or registry type")]
+    InvalidUr,
+
+    #[error("Wallet is already imported")]
+    DuplicateWallet,
+
+    #[error("Invalid SLIP-39 share")]
+    InvalidShare,
+
+    #[error("Backup file is damaged or not a Nor backup")]
+    InvalidBackup,
+
+    #[error("Backup was made by a newer version of the app")]
+    UnsupportedBackupVersion,
 }
 
 impl From<bip39::Error> for CoreError {
-    fn from(_: bip39::Error) -> Self {
-        CoreError::InvalidMnemonic
+    fn from(e: bip39::Error) -> Self {
+        match e {
+            bip39::Error::UnknownWord(_) => CoreError::UnknownMnemonicWord,
+            bip39::Error::BadWordCount(_) => CoreError::InvalidMnemonicLength,
+            bip39::Error::InvalidChecksum => CoreError::InvalidMnemonicChecksum,
+            _ => CoreError::InvalidMnemonic,
+        }
     }
 }
 
@@ -51,8 +120,14 @@ impl From<reqwest::Error> for CoreError {
     }
 }
 
+impl From<std::io::Error> for CoreError {
+    fn from(_: std::io::Error) -> Self {
+        CoreError::StorageError
+    }
+}
+
 impl From<serde_json::Error> for CoreError {
-    fn from(e: serde_json::Error) -> Self {
+    fn from(_: serde_json::Error) -> Self {
         CoreError::InvalidInput
     }
 }
@@ -1,17 +1,22 @@
+use crate::audit::SigningAction;
+use crate::policy::SpendRequest;
+use crate::signer::{EcdsaSignature, LocalSigner, Signer};
+use crate::{crypto, eip712, storage::Keyring};
 use crate::{error::*, types::*};
-use crate::storage;
 use ethers_core::{
-    types::{transaction::eip2718::TypedTransaction, Eip1559TransactionRequest, Signature},
-    utils::keccak256,
+    types::{
+        transaction::{eip2718::TypedTransaction, eip2930::AccessList},
+        Address, Bytes, Eip1559TransactionRequest, Eip2930TransactionRequest, NameOrAddress,
+        Signature, TransactionRequest, H256, U256,
+    },
+    utils::{keccak256, rlp},
 };
-use rlp::RlpStream;
-use secp256k1::{Message, Secp256k1, ecdsa::RecoverableSignature, ecdsa::RecoveryId};
 
 pub fn build_transaction(params: EvmTxParams) -> Result<EvmTransaction> {
-    let tx_hash = compute_tx_hash(&params)?;
+    let tx = to_typed_transaction(&params)?;
 
     Ok(EvmTransaction {
-        hash: format!("0x{}", hex::encode(tx_hash)),
+        hash: format!("0x{}", hex::encode(tx.sighash())),
         signed_tx: String::new(),
         block_hash: None,
         block_number: None,
@@ -19,82 +24,145 @@ pub fn build_transaction(params: EvmTxParams) -> Result<EvmTransaction> {
     })
 }
 
+/// Sign a transaction and return the broadcastable raw transaction.
+///
+/// `signed_tx` is the EIP-2718 encoding accepted by `eth_sendRawTransaction`
+/// (plain RLP for legacy transactions) and `hash` is the keccak256 of it,
+/// i.e. the on-chain transaction hash.
 pub fn sign_transaction(
+    keyring: &Keyring,
     wallet_id: String,
     account_index: u32,
     params: EvmTxParams,
+) -> Result<EvmTransaction> {
+    let signer = LocalSigner::new(keyring, &wallet_id, Chain::Evm, account_index);
+    sign_transaction_audited(keyring, &signer, params)
+}
+
+/// [`sign_transaction_with`], subject to the keyring's spending policy and
+/// recorded in its audit log
+pub(crate) fn sign_transaction_audited(
+    keyring: &Keyring,
+    signer: &dyn Signer,
+    params: EvmTxParams,
+) -> Result<EvmTransaction> {
+    let action = SigningAction::transaction(SpendRequest::evm(&signer.address()?, &params)?);
+    keyring.sign_audited(signer, action, |signer| {
+        sign_transaction_with(signer, params)
+    })
+}
+
+/// [`sign_transaction`] with any signer
+pub fn sign_transaction_with(signer: &dyn Signer, params: EvmTxParams) -> Result<EvmTransaction> {
+    let sig = signer.sign_transaction(&params)?;
+    signed_transaction(&params, &sig)
+}
+
+/// The transaction `params` describe, carrying `sig`
+pub(crate) fn signed_transaction(
+    params: &EvmTxParams,
+    sig: &EcdsaSignature,
+) -> Result<EvmTransaction> {
+    let tx = to_typed_transaction(params)?;
+    let signature = Signature {
+        r: U256::from_big_endian(&sig.r),
+        s: U256::from_big_endian(&sig.s),
+        v: transaction_v(&tx, sig, params.chain_id),
+    };
+
+    let raw_tx = tx.rlp_signed(&signature);
+
+    Ok(EvmTransaction {
+        hash: format!("0x{}", hex::encode(keccak256(&raw_tx))),
+        signed_tx: format!("0x{}", hex::encode(&raw_tx)),
+        block_hash: None,
+        block_number: None,
+        timestamp: None,
+    })
+}
+
+pub fn sign_message(
+    keyring: &Keyring,
+    wallet_id: String,
+    account_index: u32,
+    message: String,
 ) -> Result<String> {
-    // Get wallet and secret key
-    let wallet = storage::get_wallet(&wallet_id)?;
-    let secret_key = wallet.get_secret_key(account_index)?;
-    
-    // Build transaction hash
-    let tx_hash = compute_tx_hash(&params)?;
-    let message = Message::from_digest_slice(&tx_hash)?;
-    
-    // Sign with secp256k1 (recoverable signature)
-    let secp = Secp256k1::new();
-    let recoverable_sig = secp.sign_ecdsa_recoverable(&message, &secret_key);
-    let (recovery_id, compact_sig) = recoverable_sig.serialize_compact();
-    
-    // Serialize signature (r, s, v) - compact_sig is 64 bytes (32 r + 32 s)
-    let mut sig_bytes = [0u8; 65];
-    sig_bytes[0..32].copy_from_slice(&compact_sig[0..32]); // r
-    sig_bytes[32..64].copy_from_slice(&compact_sig[32..64]); // s
-    sig_bytes[64] = recovery_id.to_i32() as u8 + 27; // Ethereum v = recovery_id + 27
-    
-    Ok(format!("0x{}", hex::encode(sig_bytes)))
-}
-
-pub fn sign_message(wallet_id: String, account_index: u32, message: String) -> Result<String> {
-    // Get wallet and secret key
-    let wallet = storage::get_wallet(&wallet_id)?;
-    let secret_key = wallet.get_secret_key(account_index)?;
-    
-    // EIP-191 personal_sign implementation
-    let prefix = format!("\x19Ethereum Signed Message:\n{}", message.len());
-    let prefixed_message = format!("{}{}", prefix, message);
-    let hash = keccak256(prefixed_message.as_bytes());
-    
-    // Sign the hash (recoverable signature)
-    let message_obj = Message::from_digest_slice(&hash)?;
-    let secp = Secp256k1::new();
-    let recoverable_sig = secp.sign_ecdsa_recoverable(&message_obj, &secret_key);
-    let (recovery_id, compact_sig) = recoverable_sig.serialize_compact();
-    
-    // Serialize signature - compact_sig is 64 bytes (32 r + 32 s)
-    let mut sig_bytes = [0u8; 65];
-    sig_bytes[0..32].copy_from_slice(&compact_sig[0..32]); // r
-    sig_bytes[32..64].copy_from_slice(&compact_sig[32..64]); // s
-    sig_bytes[64] = recovery_id.to_i32() as u8 + 27;
-    
-    Ok(format!("0x{}", hex::encode(sig_bytes)))
+    let signer = LocalSigner::new(keyring, &wallet_id, Chain::Evm, account_index);
+    sign_message_audited(keyring, &signer, message.as_bytes())
 }
 
+/// [`sign_message_with`], recorded in the keyring's audit log
+pub(crate) fn sign_message_audited(
+    keyring: &Keyring,
+    signer: &dyn Signer,
+    message: &[u8],
+) -> Result<String> {
+    let action = SigningAction::message(Chain::Evm, message);
+    keyring.sign_audited(signer, action, |signer| sign_message_with(signer, message))
+}
+
+/// EIP-191 `personal_sign` with any signer
+pub fn sign_message_with(signer: &dyn Signer, message: &[u8]) -> Result<String> {
+    Ok(signer.sign_message(message)?.to_hex())
+}
+
+/// Sign an `eth_signTypedData_v4` payload with the account key (EIP-712)
 pub fn sign_typed_data(
-    _wallet_id: String,
-    _account_index: u32,
+    keyring: &Keyring,
+    wallet_id: String,
+    account_index: u32,
     typed_data_json: String,
 ) -> Result<String> {
-    // TODO: Implement EIP-712 signing
-    // For now, hash the JSON
-    let hash = keccak256(typed_data_json.as_bytes());
-    Ok(format!("0x{}", hex::encode(hash)))
+    let signer = LocalSigner::new(keyring, &wallet_id, Chain::Evm, account_index);
+    sign_typed_data_audited(keyring, &signer, &typed_data_json)
 }
 
+/// [`sign_typed_data_with`], recorded in the keyring's audit log
+pub(crate) fn sign_typed_data_audited(
+    keyring: &Keyring,
+    signer: &dyn Signer,
+    typed_data_json: &str,
+) -> Result<String> {
+    let action = SigningAction::typed_data(&signer.address()?, typed_data_json);
+    keyring.sign_audited(signer, action, |signer| {
+        sign_typed_data_with(signer, typed_data_json)
+    })
+}
+
+/// [`sign_typed_data`] with any signer
+pub fn sign_typed_data_with(signer: &dyn Signer, typed_data_json: &str) -> Result<String> {
+    Ok(signer.sign_typed_data(typed_data_json)?.to_hex())
+}
+
+/// EIP-712 digest of a typed data payload, for display before signing
+pub fn hash_typed_data(typed_data_json: String) -> Result<String> {
+    let hash = eip712::hash_typed_data(&typed_data_json)?;
+    Ok(format!("0x{}", hex::encode(hash.digest)))
+}
+
+/// Recover the address that produced an EIP-191 `personal_sign` signature
 pub fn recover_signer(message: String, signature: String) -> Result<String> {
-    let signature = signature.trim_start_matches("0x");
-    if signature.len() != 130 {
-        return Err(CoreError::SigningError);
-    }
+    recover_address(&personal_message_hash(message.as_bytes()), &signature)
+}
 
-    // Parse signature components
-    let r = &signature[0..64];
-    let s = &signature[64..128];
-    let v = &signature[128..130];
+/// Recover the address that signed a raw 32-byte digest
+pub fn recover_digest_signer(digest: String, signature: String) -> Result<String> {
+    let digest_bytes =
+        hex::decode(digest.trim_start_matches("0x")).map_err(|_| CoreError::InvalidInput)?;
+    let digest: [u8; 32] = digest_bytes
+        .try_into()
+        .map_err(|_| CoreError::InvalidInput)?;
+    recover_address(&digest, &signature)
+}
+
+/// Check that `signature` is a `personal_sign` signature of `message` by `address`
+pub fn verify_message(address: String, message: String, signature: String) -> Result<bool> {
+    let expected = parse_address(&address)?;
 
-    // TODO: Implement actual recovery
-    Ok(format!("0x{}{}{}", r, s, v))
+    match recover_signer(message, signature) {
+        Ok(recovered) => Ok(parse_address(&recovered)? == expected),
+        Err(_) => Ok(false),
+    }
 }
 
 pub fn estimate_gas(params: EvmTxParams, rpc_url: String) -> Result<GasEstimate> {
@@ -116,35 +184,344 @@ pub fn estimate_gas(params: EvmTxParams, rpc_url: String) -> Result<GasEstimate>
     let gas_hex = gas_hex.trim_matches('"').trim_start_matches("0x");
     let gas_limit = u64::from_str_radix(gas_hex, 16).map_err(|_| CoreError::RpcError)?;
 
+    // EIP-1559 transactions pay at most max_fee_per_gas per unit of gas
+    let max_fee = params
+        .max_fee_per_gas
+        .clone()
+        .unwrap_or_else(|| params.gas_price.clone());
+    let total_cost = U256::from(gas_limit) * parse_quantity(&max_fee).unwrap_or_default();
+
     Ok(GasEstimate {
         gas_limit: gas_limit.to_string(),
         gas_price: params.gas_price.clone(),
-        max_fee: params.gas_price.clone(),
-        total_cost: (gas_limit as u128 * params.gas_price.parse::<u128>().unwrap_or(0)).to_string(),
+        max_fee,
+        total_cost: total_cost.to_string(),
     })
 }
 
-fn compute_tx_hash(params: &EvmTxParams) -> Result<[u8; 32]> {
-    // Simplified hash computation
-    let mut stream = RlpStream::new();
-    stream.begin_list(9);
-    stream.append(&params.nonce);
-    stream.append(&params.gas_price.parse::<u64>().unwrap_or(0));
-    stream.append(&params.gas_limit);
-    stream.append(&params.to);
-    stream.append(&params.value.parse::<u64>().unwrap_or(0));
-    stream.append(&params.data.as_ref().map(|s| s.as_str()).unwrap_or("0x"));
-    stream.append(&params.chain_id);
-    stream.append(&0u8);
-    stream.append(&0u8);
+/// Resolve the envelope type: an explicit `tx_type` wins, otherwise the
+/// presence of 1559 fee fields or an access list selects the typed envelope.
+pub fn resolve_tx_type(params: &EvmTxParams) -> EvmTxType {
+    if let Some(tx_type) = params.tx_type {
+        return tx_type;
+    }
+    if params.max_fee_per_gas.is_some() || params.max_priority_fee_per_gas.is_some() {
+        EvmTxType::Eip1559
+    } else if params.access_list.is_some() {
+        EvmTxType::Eip2930
+    } else {
+        EvmTxType::Legacy
+    }
+}
+
+/// EIP-191 personal_sign hash
+pub(crate) fn personal_message_hash(message: &[u8]) -> [u8; 32] {
+    keccak256(personal_message(message))
+}
+
+/// `message` behind the EIP-191 prefix, the preimage of its hash
+pub(crate) fn personal_message(message: &[u8]) -> Vec<u8> {
+    let mut prefixed = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    prefixed.extend_from_slice(message);
+    prefixed
+}
+
+fn recover_address(digest: &[u8; 32], signature: &str) -> Result<String> {
+    let public_key = EcdsaSignature::from_hex(signature)?.recover(digest)?;
+    Ok(crypto::public_key_to_address(&public_key))
+}
+
+/// Signature of `signed_tx`, which must be exactly the transaction `params`
+/// describe. The signer is not checked.
+pub(crate) fn signed_transaction_signature(
+    params: &EvmTxParams,
+    signed_tx: &str,
+) -> Result<EcdsaSignature> {
+    let raw_tx = parse_data(Some(signed_tx))?;
+    let (_, signature) = TypedTransaction::decode_signed(&rlp::Rlp::new(&raw_tx))
+        .map_err(|_| CoreError::InvalidTransaction)?;
+    if to_typed_transaction(params)?
+        .rlp_signed(&signature)
+        .as_ref()
+        != raw_tx.as_ref()
+    {
+        return Err(CoreError::InvalidTransaction);
+    }
 
-    let encoded = stream.out();
-    Ok(keccak256(&encoded))
+    let mut r = [0u8; 32];
+    let mut s = [0u8; 32];
+    signature.r.to_big_endian(&mut r);
+    signature.s.to_big_endian(&mut s);
+    EcdsaSignature::from_parts(r, s, signature.v)
+}
+
+/// Submit a signed transaction. Returns its hash.
+pub fn broadcast_transaction(signed_tx: String, rpc_url: String) -> Result<String> {
+    let client = crate::rpc::JsonRpcClient::new(rpc_url);
+    let params = serde_json::json!([signed_tx]).to_string();
+    let response = client.call("eth_sendRawTransaction".to_string(), params)?;
+
+    let hash = response.result.ok_or(CoreError::RpcError)?;
+    Ok(hash.trim_matches('"').to_string())
+}
+
+/// Legacy transactions use EIP-155 replay protection, typed ones the y-parity
+pub(crate) fn transaction_v(tx: &TypedTransaction, sig: &EcdsaSignature, chain_id: u64) -> u64 {
+    match tx {
+        TypedTransaction::Legacy(_) => sig.eip155_v(chain_id),
+        _ => sig.y_parity(),
+    }
+}
+
+/// Params of an unsigned transaction in its signing encoding, EIP-155 RLP
+/// for legacy transactions or an EIP-2718 typed envelope. Fails unless the
+/// params encode back to exactly `unsigned`.
+pub(crate) fn params_from_unsigned(unsigned: &[u8], from: &str) -> Result<EvmTxParams> {
+    let invalid = |_| CoreError::InvalidTransaction;
+    let mut params = match unsigned.first() {
+        Some(0x01) => {
+            let tx: Eip2930TransactionRequest = rlp::decode(&unsigned[1..]).map_err(invalid)?;
+            EvmTxParams {
+                tx_type: Some(EvmTxType::Eip2930),
+                access_list: Some(access_list_items(&tx.access_list)),
+                ..legacy_params(&tx.tx)?
+            }
+        }
+        Some(0x02) => {
+            let tx: Eip1559TransactionRequest = rlp::decode(&unsigned[1..]).map_err(invalid)?;
+            EvmTxParams {
+                to: recipient_string(tx.to.as_ref())?,
+                value: tx.value.unwrap_or_default().to_string(),
+                data: Some(format!("0x{}", hex::encode(tx.data.unwrap_or_default()))),
+                gas_limit: to_u64(tx.gas)?,
+                gas_price: "0".to_string(),
+                nonce: to_u64(tx.nonce)?,
+                chain_id: tx.chain_id.ok_or(CoreError::InvalidTransaction)?.as_u64(),
+                tx_type: Some(EvmTxType::Eip1559),
+                max_fee_per_gas: Some(tx.max_fee_per_gas.unwrap_or_default().to_string()),
+                max_priority_fee_per_gas: Some(
+                    tx.max_priority_fee_per_gas.unwrap_or_default().to_string(),
+                ),
+                access_list: Some(access_list_items(&tx.access_list)),
+                ..Default::default()
+            }
+        }
+        Some(0xc0..) => {
+            let tx = TransactionRequest::decode_unsigned_rlp(&rlp::Rlp::new(unsigned))
+                .map_err(invalid)?;
+            EvmTxParams {
+                tx_type: Some(EvmTxType::Legacy),
+                ..legacy_params(&tx)?
+            }
+        }
+        _ => return Err(CoreError::InvalidTransaction),
+    };
+    params.from = from.to_string();
+
+    if to_typed_transaction(&params)?.rlp().as_ref() != unsigned {
+        return Err(CoreError::InvalidTransaction);
+    }
+    Ok(params)
+}
+
+fn legacy_params(tx: &TransactionRequest) -> Result<EvmTxParams> {
+    Ok(EvmTxParams {
+        to: recipient_string(tx.to.as_ref())?,
+        value: tx.value.unwrap_or_default().to_string(),
+        data: Some(format!(
+            "0x{}",
+            hex::encode(tx.data.clone().unwrap_or_default())
+        )),
+        gas_limit: to_u64(tx.gas)?,
+        gas_price: tx.gas_price.unwrap_or_default().to_string(),
+        nonce: to_u64(tx.nonce)?,
+        // Pre-EIP-155 encodings have no chain id and are refused
+        chain_id: tx.chain_id.ok_or(CoreError::InvalidTransaction)?.as_u64(),
+        ..Default::default()
+    })
+}
+
+fn recipient_string(to: Option<&NameOrAddress>) -> Result<String> {
+    match to {
+        None => Ok(String::new()),
+        Some(NameOrAddress::Address(address)) => Ok(format!("{:?}", address)),
+        Some(NameOrAddress::Name(_)) => Err(CoreError::InvalidTransaction),
+    }
+}
+
+fn to_u64(value: Option<U256>) -> Result<u64> {
+    value
+        .unwrap_or_default()
+        .try_into()
+        .map_err(|_| CoreError::InvalidTransaction)
+}
+
+fn access_list_items(access_list: &AccessList) -> Vec<AccessListItem> {
+    access_list
+        .0
+        .iter()
+        .map(|item| AccessListItem {
+            address: format!("{:?}", item.address),
+            storage_keys: item
+                .storage_keys
+                .iter()
+                .map(|key| format!("{:?}", key))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Digest a transaction signature covers
+pub(crate) fn transaction_sighash(params: &EvmTxParams) -> Result<[u8; 32]> {
+    Ok(to_typed_transaction(params)?.sighash().to_fixed_bytes())
+}
+
+pub(crate) fn to_typed_transaction(params: &EvmTxParams) -> Result<TypedTransaction> {
+    let to = parse_recipient(&params.to)?.map(NameOrAddress::Address);
+    let from = parse_address(&params.from).ok();
+    let value = parse_quantity(&params.value)?;
+    let data = parse_data(params.data.as_deref())?;
+
+    let tx_type = resolve_tx_type(params);
+    match tx_type {
+        EvmTxType::Legacy | EvmTxType::Eip2930 => {
+            let tx = TransactionRequest {
+                from,
+                to,
+                gas: Some(params.gas_limit.into()),
+                gas_price: Some(parse_quantity(&params.gas_price)?),
+                value: Some(value),
+                data: Some(data),
+                nonce: Some(params.nonce.into()),
+                chain_id: Some(params.chain_id.into()),
+            };
+
+            if tx_type == EvmTxType::Legacy {
+                Ok(TypedTransaction::Legacy(tx))
+            } else {
+                let access_list = parse_access_list(params.access_list.as_deref())?;
+                Ok(TypedTransaction::Eip2930(Eip2930TransactionRequest::new(
+                    tx,
+                    access_list,
+                )))
+            }
+        }
+        EvmTxType::Eip1559 => {
+            let max_fee_per_gas = params
+                .max_fee_per_gas
+                .as_deref()
+                .ok_or(CoreError::InvalidTransaction)?;
+            let max_priority_fee_per_gas =
+                params.max_priority_fee_per_gas.as_deref().unwrap_or("0");
+
+            Ok(TypedTransaction::Eip1559(Eip1559TransactionRequest {
+                from,
+                to,
+                gas: Some(params.gas_limit.into()),
+                value: Some(value),
+                data: Some(data),
+                nonce: Some(params.nonce.into()),
+                access_list: parse_access_list(params.access_list.as_deref())?,
+                max_priority_fee_per_gas: Some(parse_quantity(max_priority_fee_per_gas)?),
+                max_fee_per_gas: Some(parse_quantity(max_fee_per_gas)?),
+                chain_id: Some(params.chain_id.into()),
+            }))
+        }
+    }
+}
+
+/// Parse a decimal or `0x`-prefixed hex quantity into a U256
+pub(crate) fn parse_quantity(value: &str) -> Result<U256> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(U256::zero());
+    }
+
+    match value.strip_prefix("0x") {
+        Some("") => Ok(U256::zero()),
+        Some(hex_value) => {
+            U256::from_str_radix(hex_value, 16).map_err(|_| CoreError::InvalidTransaction)
+        }
+        None => U256::from_dec_str(value).map_err(|_| CoreError::InvalidTransaction),
+    }
+}
+
+pub(crate) fn parse_address(address: &str) -> Result<Address> {
+    let bytes =
+        hex::decode(address.trim_start_matches("0x")).map_err(|_| CoreError::InvalidAddress)?;
+    if bytes.len() != 20 {
+        return Err(CoreError::InvalidAddress);
+    }
+    Ok(Address::from_slice(&bytes))
+}
+
+/// An empty recipient means contract creation
+fn parse_recipient(to: &str) -> Result<Option<Address>> {
+    if to.is_empty() || to == "0x" {
+        return Ok(None);
+    }
+    parse_address(to).map(Some)
+}
+
+fn parse_access_list(items: Option<&[AccessListItem]>) -> Result<AccessList> {
+    let mut access_list = Vec::new();
+    for item in items.unwrap_or_default() {
+        let mut storage_keys = Vec::with_capacity(item.storage_keys.len());
+        for key in &item.storage_keys {
+            let bytes = hex::decode(key.trim_start_matches("0x"))
+                .map_err(|_| CoreError::InvalidTransaction)?;
+            if bytes.len() != 32 {
+                return Err(CoreError::InvalidTransaction);
+            }
+            storage_keys.push(H256::from_slice(&bytes));
+        }
+
+        access_list.push(ethers_core::types::transaction::eip2930::AccessListItem {
+            address: parse_address(&item.address)?,
+            storage_keys,
+        });
+    }
+    Ok(AccessList(access_list))
+}
+
+pub(crate) fn parse_data(data: Option<&str>) -> Result<Bytes> {
+    let data = data.unwrap_or("0x").trim_start_matches("0x");
+    hex::decode(data)
+        .map(Bytes::from)
+        .map_err(|_| CoreError::InvalidTransaction)
+}
+
+/// Transaction for the signer backend tests, legacy or EIP-1559, with
+/// `data_len` bytes of call data
+#[cfg(test)]
+pub(crate) fn test_tx_params(from: String, eip1559: bool, data_len: usize) -> EvmTxParams {
+    EvmTxParams {
+        from,
+        to: "0x3535353535353535353535353535353535353535".to_string(),
+        value: "1000".to_string(),
+        data: Some(format!("0x{}", "ab".repeat(data_len))),
+        gas_limit: 100000,
+        gas_price: "1000000000".to_string(),
+        max_fee_per_gas: eip1559.then(|| "2000000000".to_string()),
+        max_priority_fee_per_gas: eip1559.then(|| "1000000000".to_string()),
+        nonce: 7,
+        chain_id: 65001,
+        ..Default::default()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::Wallet;
+
+    fn store_key(private_key: &str) -> (Keyring, String) {
+        let keyring = Keyring::new();
+        let wallet = Wallet::from_private_key(private_key).unwrap();
+        let wallet_id = wallet.id.clone();
+        keyring.insert(wallet).unwrap();
+        (keyring, wallet_id)
+    }
 
     #[test]
     fn test_build_transaction() {
@@ -157,9 +534,371 @@ mod tests {
             gas_price: "20000000000".to_string(),
             nonce: 0,
             chain_id: 1,
+            ..Default::default()
         };
 
         let tx = build_transaction(params).unwrap();
         assert!(tx.hash.starts_with("0x"));
     }
+
+    #[test]
+    fn test_sign_transaction_eip155_vector() {
+        // Example from the EIP-155 specification
+        let (keyring, wallet_id) =
+            store_key("0x4646464646464646464646464646464646464646464646464646464646464646");
+
+        let params = EvmTxParams {
+            from: "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F".to_string(),
+            to: "0x3535353535353535353535353535353535353535".to_string(),
+            value: "1000000000000000000".to_string(),
+            data: None,
+            gas_limit: 21000,
+            gas_price: "20000000000".to_string(),
+            nonce: 9,
+            chain_id: 1,
+            ..Default::default()
+        };
+
+        let unsigned = build_transaction(params.clone()).unwrap();
+        assert_eq!(
+            unsigned.hash,
+            "0xdaf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+
+        let tx = sign_transaction(&keyring, wallet_id, 0, params).unwrap();
+        assert_eq!(
+            tx.signed_tx,
+            "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000\
+             8025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f\
+             761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+        assert_eq!(
+            tx.hash,
+            "0x33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788"
+        );
+    }
+
+    #[test]
+    fn test_signed_transaction_recovers_sender() {
+        let (keyring, wallet_id) =
+            store_key("0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318");
+
+        let params = EvmTxParams {
+            from: "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23".to_string(),
+            to: "0xF0109fC8DF283027b6285cc889F5aA624EaC1F55".to_string(),
+            value: "0x3b9aca00".to_string(),
+            data: Some("0xa9059cbb".to_string()),
+            gas_limit: 21000,
+            gas_price: "20000000000".to_string(),
+            nonce: 0,
+            chain_id: 65001,
+            ..Default::default()
+        };
+
+        let tx = sign_transaction(&keyring, wallet_id, 0, params).unwrap();
+        let raw = hex::decode(tx.signed_tx.trim_start_matches("0x")).unwrap();
+        let (decoded, signature) =
+            TransactionRequest::decode_signed_rlp(&rlp::Rlp::new(&raw)).unwrap();
+
+        assert_eq!(
+            format!("{:?}", signature.recover(decoded.sighash()).unwrap()),
+            "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23"
+        );
+        assert_eq!(decoded.data.unwrap().as_ref(), &[0xa9, 0x05, 0x9c, 0xbb]);
+        assert_eq!(tx.hash, format!("0x{}", hex::encode(keccak256(&raw))));
+    }
+
+    #[test]
+    fn test_sign_transaction_contract_creation() {
+        let (keyring, wallet_id) =
+            store_key("0x4646464646464646464646464646464646464646464646464646464646464646");
+
+        let params = EvmTxParams {
+            from: String::new(),
+            to: String::new(),
+            value: "0".to_string(),
+            data: Some("0x6080".to_string()),
+            gas_limit: 100000,
+            gas_price: "1".to_string(),
+            nonce: 0,
+            chain_id: 65001,
+            ..Default::default()
+        };
+
+        let tx = sign_transaction(&keyring, wallet_id, 0, params).unwrap();
+        let raw = hex::decode(tx.signed_tx.trim_start_matches("0x")).unwrap();
+        let (decoded, signature) =
+            TransactionRequest::decode_signed_rlp(&rlp::Rlp::new(&raw)).unwrap();
+
+        assert!(decoded.to.is_none());
+        assert_eq!(decoded.chain_id, Some(65001u64.into()));
+        assert!(signature.v == 65001 * 2 + 35 || signature.v == 65001 * 2 + 36);
+    }
+
+    fn decode_signed(raw_hex: &str) -> (TypedTransaction, Signature) {
+        let raw = hex::decode(raw_hex.trim_start_matches("0x")).unwrap();
+        TypedTransaction::decode_signed(&rlp::Rlp::new(&raw)).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_tx_type() {
+        let mut params = EvmTxParams::default();
+        assert_eq!(resolve_tx_type(&params), EvmTxType::Legacy);
+
+        params.access_list = Some(vec![]);
+        assert_eq!(resolve_tx_type(&params), EvmTxType::Eip2930);
+
+        params.max_fee_per_gas = Some("100".to_string());
+        assert_eq!(resolve_tx_type(&params), EvmTxType::Eip1559);
+
+        params.tx_type = Some(EvmTxType::Legacy);
+        assert_eq!(resolve_tx_type(&params), EvmTxType::Legacy);
+    }
+
+    #[test]
+    fn test_sign_eip1559_transaction() {
+        let (keyring, wallet_id) =
+            store_key("0x4646464646464646464646464646464646464646464646464646464646464646");
+
+        let params = EvmTxParams {
+            from: "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F".to_string(),
+            to: "0x3535353535353535353535353535353535353535".to_string(),
+            value: "1000000000000000000".to_string(),
+            gas_limit: 21000,
+            nonce: 3,
+            chain_id: 65001,
+            max_fee_per_gas: Some("30000000000".to_string()),
+            max_priority_fee_per_gas: Some("0x3b9aca00".to_string()),
+            ..Default::default()
+        };
+
+        let tx = sign_transaction(&keyring, wallet_id, 0, params).unwrap();
+        assert!(tx.signed_tx.starts_with("0x02"));
+
+        let (decoded, signature) = decode_signed(&tx.signed_tx);
+        let inner = decoded.as_eip1559_ref().unwrap();
+        assert_eq!(inner.max_fee_per_gas, Some(U256::from(30_000_000_000u64)));
+        assert_eq!(
+            inner.max_priority_fee_per_gas,
+            Some(U256::from(1_000_000_000u64))
+        );
+        assert!(signature.v <= 1);
+        assert_eq!(
+            format!("{:?}", signature.recover(decoded.sighash()).unwrap()),
+            "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"
+        );
+
+        let raw = hex::decode(tx.signed_tx.trim_start_matches("0x")).unwrap();
+        assert_eq!(tx.hash, format!("0x{}", hex::encode(keccak256(&raw))));
+    }
+
+    #[test]
+    fn test_sign_eip2930_transaction() {
+        let (keyring, wallet_id) =
+            store_key("0x4646464646464646464646464646464646464646464646464646464646464646");
+
+        let params = EvmTxParams {
+            to: "0x3535353535353535353535353535353535353535".to_string(),
+            value: "0".to_string(),
+            gas_limit: 30000,
+            gas_price: "20000000000".to_string(),
+            chain_id: 1,
+            access_list: Some(vec![AccessListItem {
+                address: "0x3535353535353535353535353535353535353535".to_string(),
+                storage_keys: vec![format!("0x{}", "00".repeat(31) + "01")],
+            }]),
+            ..Default::default()
+        };
+
+        let tx = sign_transaction(&keyring, wallet_id, 0, params).unwrap();
+        assert!(tx.signed_tx.starts_with("0x01"));
+
+        let (decoded, signature) = decode_signed(&tx.signed_tx);
+        let access_list = decoded.access_list().unwrap();
+        assert_eq!(access_list.0.len(), 1);
+        assert_eq!(access_list.0[0].storage_keys[0], H256::from_low_u64_be(1));
+        assert_eq!(
+            format!("{:?}", signature.recover(decoded.sighash()).unwrap()),
+            "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"
+        );
+    }
+
+    #[test]
+    fn test_eip1559_requires_max_fee() {
+        let params = EvmTxParams {
+            tx_type: Some(EvmTxType::Eip1559),
+            ..Default::default()
+        };
+        assert!(build_transaction(params).is_err());
+    }
+
+    #[test]
+    fn test_sign_typed_data_eip712_vector() {
+        // Example from the EIP-712 specification, signed with keccak256("cow")
+        let (keyring, wallet_id) =
+            store_key("0xc85ef7d79691fe79573b1a7064c19c1a9819ebdbd1faaab1a8ec92344438aaf4");
+
+        let typed_data = r#"{
+            "types": {
+                "EIP712Domain": [
+                    {"name": "name", "type": "string"},
+                    {"name": "version", "type": "string"},
+                    {"name": "chainId", "type": "uint256"},
+                    {"name": "verifyingContract", "type": "address"}
+                ],
+                "Person": [
+                    {"name": "name", "type": "string"},
+                    {"name": "wallet", "type": "address"}
+                ],
+                "Mail": [
+                    {"name": "from", "type": "Person"},
+                    {"name": "to", "type": "Person"},
+                    {"name": "contents", "type": "string"}
+                ]
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "name": "Ether Mail",
+                "version": "1",
+                "chainId": 1,
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+            },
+            "message": {
+                "from": {"name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"},
+                "to": {"name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"},
+                "contents": "Hello, Bob!"
+            }
+        }"#;
+
+        assert_eq!(
+            hash_typed_data(typed_data.to_string()).unwrap(),
+            "0xbe609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+
+        let signature = sign_typed_data(&keyring, wallet_id, 0, typed_data.to_string()).unwrap();
+        assert_eq!(
+            signature,
+            "0x4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d\
+             07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b91562\
+             1c"
+        );
+    }
+
+    #[test]
+    fn test_sign_typed_data_rejects_malformed_json() {
+        let (keyring, wallet_id) =
+            store_key("0xc85ef7d79691fe79573b1a7064c19c1a9819ebdbd1faaab1a8ec92344438aaf4");
+        assert!(sign_typed_data(&keyring, wallet_id, 0, "{\"types\": {}}".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_sign_message_round_trip() {
+        let (keyring, wallet_id) =
+            store_key("0x4646464646464646464646464646464646464646464646464646464646464646");
+        let address = "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F".to_string();
+        let message = "Sign in to NorChain".to_string();
+
+        let signature = sign_message(&keyring, wallet_id, 0, message.clone()).unwrap();
+        assert_eq!(
+            recover_signer(message.clone(), signature.clone()).unwrap(),
+            address.to_lowercase()
+        );
+        assert!(verify_message(address.clone(), message.clone(), signature.clone()).unwrap());
+        assert!(!verify_message(address, "Another message".to_string(), signature).unwrap());
+    }
+
+    #[test]
+    fn test_recover_accepts_all_v_encodings() {
+        // EIP-712 specification vector: keccak256("cow") signing the Mail digest
+        let digest = "0xbe609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2";
+        let r_s = "4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d\
+                   07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b91562";
+        let cow = "0xcd2a3d9f938e13cd947ec05abc7fe734df8dd826";
+
+        for v in ["1c", "01"] {
+            let signature = format!("0x{}{}", r_s, v);
+            assert_eq!(
+                recover_digest_signer(digest.to_string(), signature).unwrap(),
+                cow
+            );
+        }
+
+        // EIP-2098 compact form: y-parity 1 folded into the top bit of s
+        let mut compact = hex::decode(r_s).unwrap();
+        compact[32] |= 0x80;
+        let compact = format!("0x{}", hex::encode(compact));
+        assert_eq!(
+            recover_digest_signer(digest.to_string(), compact).unwrap(),
+            cow
+        );
+    }
+
+    #[test]
+    fn test_recover_eip2098_vectors() {
+        // Examples from the EIP-2098 specification: both encodings carry the same signature
+        let vectors = [
+            (
+                "68a020a209d3d56c46f38cc50a33f704f4a9a10a59377f8dd762ac66910e9b90",
+                "7e865ad05c4035ab5792787d4a0297a43617ae897930a6fe4d822b8faea52064",
+                "1b",
+                "68a020a209d3d56c46f38cc50a33f704f4a9a10a59377f8dd762ac66910e9b90\
+                 7e865ad05c4035ab5792787d4a0297a43617ae897930a6fe4d822b8faea52064",
+            ),
+            (
+                "9328da16089fcba9bececa81663203989f2df5fe1faa6291a45381c81bd17f76",
+                "139c6d6b623b42da56557e5e734a43dc83345ddfadec52cbe24d0cc64f550793",
+                "1c",
+                "9328da16089fcba9bececa81663203989f2df5fe1faa6291a45381c81bd17f76\
+                 939c6d6b623b42da56557e5e734a43dc83345ddfadec52cbe24d0cc64f550793",
+            ),
+        ];
+
+        for (r, s, v, compact) in vectors {
+            let full = recover_signer("Hello World".to_string(), format!("{}{}{}", r, s, v));
+            let short = recover_signer("Hello World".to_string(), compact.to_string());
+            assert_eq!(full.unwrap(), short.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_recover_rejects_malformed_signatures() {
+        assert!(recover_signer("hi".to_string(), "0x1234".to_string()).is_err());
+        assert!(recover_signer("hi".to_string(), format!("0x{}05", "11".repeat(64))).is_err());
+        assert!(verify_message(
+            "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F".to_string(),
+            "hi".to_string(),
+            "0x1234".to_string()
+        )
+        .is_ok_and(|valid| !valid));
+        assert!(verify_message(
+            "not-an-address".to_string(),
+            "hi".to_string(),
+            String::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_quantity_rejects_garbage() {
+        assert_eq!(parse_quantity("0x10").unwrap(), U256::from(16));
+        assert_eq!(parse_quantity("16").unwrap(), U256::from(16));
+        assert!(parse_quantity("sixteen").is_err());
+        assert!(parse_address("0x1234").is_err());
+    }
+
+    #[test]
+    fn test_broadcast_transaction() {
+        let url = crate::rpc::test_server::spawn(|request| {
+            assert_eq!(request["method"], "eth_sendRawTransaction");
+            match request["params"][0].as_str() {
+                Some("0xf86c") => Ok(serde_json::json!("0xabcd")),
+                _ => Err("rlp: expected input list".to_string()),
+            }
+        });
+        assert_eq!(
+            broadcast_transaction("0xf86c".to_string(), url.clone()).unwrap(),
+            "0xabcd"
+        );
+        assert!(broadcast_transaction("0x00".to_string(), url).is_err());
+    }
 }
@@ -1,7 +1,10 @@
 // FFI bindings for Swift and Kotlin
 // Uses cbindgen to generate C headers
 
-use crate::{crypto::Wallet, get_nor_chain_id, get_nor_chain_rpc, init_logger, LogLevel, storage};
+use crate::{
+    crypto::Wallet, error::CoreError, get_nor_chain_id, get_nor_chain_rpc, init_logger,
+    storage::WALLET_STORAGE, LogLevel,
+};
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 
@@ -31,18 +34,7 @@ pub extern "C" fn nor_wallet_create() -> NorString {
     rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut entropy);
 
     match Wallet::from_entropy(&entropy, None) {
-        Ok(wallet) => {
-            let wallet_data = wallet.to_wallet_data();
-            let wallet_id = wallet_data.id.clone();
-
-            // Store wallet in global storage
-            let _ = storage::store_wallet(wallet_id, wallet);
-
-            match serde_json::to_string(&wallet_data) {
-                Ok(json) => NorString::from_string(json),
-                Err(_) => NorString::from_string("{}".to_string()),
-            }
-        }
+        Ok(wallet) => store_wallet(wallet),
         Err(_) => NorString::from_string("{}".to_string()),
     }
 }
@@ -63,18 +55,7 @@ pub extern "C" fn nor_wallet_from_mnemonic(mnemonic: *const c_char) -> NorString
     };
 
     match Wallet::from_mnemonic(mnemonic_str, None) {
-        Ok(wallet) => {
-            let wallet_data = wallet.to_wallet_data();
-            let wallet_id = wallet_data.id.clone();
-
-            // Store wallet in global storage
-            let _ = storage::store_wallet(wallet_id, wallet);
-
-            match serde_json::to_string(&wallet_data) {
-                Ok(json) => NorString::from_string(json),
-                Err(_) => NorString::from_string("{}".to_string()),
-            }
-        }
+        Ok(wallet) => store_wallet(wallet),
         Err(_) => NorString::from_string("{}".to_string()),
     }
 }
@@ -95,17 +76,41 @@ pub extern "C" fn nor_wallet_from_private_key(private_key: *const c_char) -> Nor
     };
 
     match Wallet::from_private_key(pk_str) {
-        Ok(wallet) => {
-            let wallet_data = wallet.to_wallet_data();
-            match serde_json::to_string(&wallet_data) {
-                Ok(json) => NorString::from_string(json),
-                Err(_) => NorString::from_string("{}".to_string()),
-            }
-        }
+        Ok(wallet) => store_wallet(wallet),
         Err(_) => NorString::from_string("{}".to_string()),
     }
 }
 
+/// Store a wallet in the global keyring and return its data as JSON
+/// Re-importing a wallet returns the existing one, marked "duplicate": true
+fn store_wallet(wallet: Wallet) -> NorString {
+    let wallet_data = wallet.to_wallet_data();
+
+    let json = match WALLET_STORAGE.insert(wallet) {
+        Ok(()) => serde_json::to_value(&wallet_data).ok(),
+        Err(CoreError::DuplicateWallet) => WALLET_STORAGE
+            .find_by_fingerprint(&wallet_data.fingerprint)
+            .ok()
+            .flatten()
+            .and_then(|id| {
+                WALLET_STORAGE
+                    .with_wallet(&id, |existing| Ok(existing.to_wallet_data()))
+                    .ok()
+            })
+            .and_then(|existing| serde_json::to_value(existing).ok())
+            .map(|mut value| {
+                value["duplicate"] = serde_json::Value::Bool(true);
+                value
+            }),
+        Err(_) => None,
+    };
+
+    match json {
+        Some(json) => NorString::from_string(json.to_string()),
+        None => NorString::from_string("{}".to_string()),
+    }
+}
+
 /// Get Nor Chain RPC URL
 #[no_mangle]
 pub extern "C" fn nor_get_chain_rpc() -> NorString {
@@ -189,6 +194,10 @@ pub extern "C" fn nor_sign_transaction(
         gas_price: gp.to_string(),
         nonce,
         chain_id,
+        tx_type: None,
+        max_fee_per_gas: None,
+        max_priority_fee_per_gas: None,
+        access_list: None,
     };
 
     match crate::evm::build_transaction(params) {
@@ -197,7 +206,44 @@ pub extern "C" fn nor_sign_transaction(
     }
 }
 
+/// Sign an EVM transaction with a key held by a remote signing service
+/// config_json is a RemoteSignerConfig and params_json an EvmTxParams object
+/// Returns the signed EvmTransaction as JSON, or "{}" on error
+#[no_mangle]
+pub extern "C" fn nor_remote_sign_transaction(
+    config_json: *const c_char,
+    params_json: *const c_char,
+) -> NorString {
+    if config_json.is_null() || params_json.is_null() {
+        return NorString::from_string("{}".to_string());
+    }
+
+    let (config, params) = unsafe {
+        match (
+            CStr::from_ptr(config_json).to_str(),
+            CStr::from_ptr(params_json).to_str(),
+        ) {
+            (Ok(config), Ok(params)) => (config, params),
+            _ => return NorString::from_string("{}".to_string()),
+        }
+    };
+
+    let (config, params) = match (serde_json::from_str(config), serde_json::from_str(params)) {
+        (Ok(config), Ok(params)) => (config, params),
+        _ => return NorString::from_string("{}".to_string()),
+    };
+
+    match crate::EvmManager::with_keyring(WALLET_STORAGE.clone())
+        .sign_transaction_remote(config, params)
+        .and_then(|tx| Ok(serde_json::to_string(&tx)?))
+    {
+        Ok(json) => NorString::from_string(json),
+        Err(_) => NorString::from_string("{}".to_string()),
+    }
+}
+
 /// Get mnemonic for a wallet by ID
+/// Call nor_session_authorize first
 /// Returns the mnemonic phrase as a string
 #[no_mangle]
 pub extern "C" fn nor_wallet_get_mnemonic(wallet_id: *const c_char) -> NorString {
@@ -212,14 +258,98 @@ pub extern "C" fn nor_wallet_get_mnemonic(wallet_id: *const c_char) -> NorString
         }
     };
 
-    if let Ok(wallet) = storage::get_wallet(id) {
-        match wallet.export_mnemonic() {
-            Ok(mnemonic) => return NorString::from_string(mnemonic),
-            Err(_) => return NorString::from_string("".to_string()),
+    match WALLET_STORAGE.with_sensitive_wallet(id, |wallet| wallet.export_mnemonic()) {
+        Ok(mnemonic) => NorString::from_string(mnemonic.expose().to_string()),
+        Err(_) => NorString::from_string("".to_string()),
+    }
+}
+
+/// All wallets in display order
+/// Returns a JSON array of wallets, or "[]" on error
+#[no_mangle]
+pub extern "C" fn nor_wallet_list() -> NorString {
+    let manager = crate::WalletManager::with_keyring(WALLET_STORAGE.clone());
+    match manager
+        .list_wallets()
+        .and_then(|wallets| Ok(serde_json::to_string(&wallets)?))
+    {
+        Ok(json) => NorString::from_string(json),
+        Err(_) => NorString::from_string("[]".to_string()),
+    }
+}
+
+/// Replace a wallet's label, color, sort order and hidden flag
+/// metadata_json is a WalletMetadata object
+/// Returns true on success
+#[no_mangle]
+pub extern "C" fn nor_wallet_set_metadata(
+    wallet_id: *const c_char,
+    metadata_json: *const c_char,
+) -> bool {
+    if wallet_id.is_null() || metadata_json.is_null() {
+        return false;
+    }
+
+    let (id, json) = unsafe {
+        match (
+            CStr::from_ptr(wallet_id).to_str(),
+            CStr::from_ptr(metadata_json).to_str(),
+        ) {
+            (Ok(id), Ok(json)) => (id, json),
+            _ => return false,
         }
+    };
+    let metadata = match serde_json::from_str(json) {
+        Ok(metadata) => metadata,
+        Err(_) => return false,
+    };
+
+    WALLET_STORAGE
+        .with_wallet_mut(id, |wallet| {
+            wallet.set_metadata(metadata);
+            Ok(())
+        })
+        .is_ok()
+}
+
+/// Replace the metadata of a derived account
+/// chain is "Evm" or "Tron"; metadata_json is an AccountMetadata object
+/// Returns true on success
+#[no_mangle]
+pub extern "C" fn nor_account_set_metadata(
+    wallet_id: *const c_char,
+    chain: *const c_char,
+    index: u32,
+    metadata_json: *const c_char,
+) -> bool {
+    if wallet_id.is_null() || chain.is_null() || metadata_json.is_null() {
+        return false;
     }
 
-    NorString::from_string("".to_string())
+    let (id, chain, json) = unsafe {
+        match (
+            CStr::from_ptr(wallet_id).to_str(),
+            CStr::from_ptr(chain).to_str(),
+            CStr::from_ptr(metadata_json).to_str(),
+        ) {
+            (Ok(id), Ok(chain), Ok(json)) => (id, chain, json),
+            _ => return false,
+        }
+    };
+    let chain = match serde_json::from_value(serde_json::Value::String(chain.to_string())) {
+        Ok(chain) => chain,
+        Err(_) => return false,
+    };
+    let metadata = match serde_json::from_str(json) {
+        Ok(metadata) => metadata,
+        Err(_) => return false,
+    };
+
+    WALLET_STORAGE
+        .with_wallet_mut(id, |wallet| {
+            wallet.set_account_metadata(chain, index, metadata)
+        })
+        .is_ok()
 }
 
 /// Get balance for an address via RPC
@@ -234,3 +364,503 @@ pub extern "C" fn nor_get_balance(address: *const c_char, rpc_url: *const c_char
         Err(_) => NorString::from_string("0".to_string()),
     }
 }
+
+/// Create an encrypted vault file holding the current wallets
+/// Returns true on success
+#[no_mangle]
+pub extern "C" fn nor_vault_create(path: *const c_char, password: *const c_char) -> bool {
+    if path.is_null() || password.is_null() {
+        return false;
+    }
+
+    let (path, password) = unsafe {
+        match (
+            CStr::from_ptr(path).to_str(),
+            CStr::from_ptr(password).to_str(),
+        ) {
+            (Ok(p), Ok(pw)) => (p, pw),
+            _ => return false,
+        }
+    };
+
+    WALLET_STORAGE.create_vault(path, password).is_ok()
+}
+
+/// Unlock a vault file and load its wallets
+/// Returns true on success
+#[no_mangle]
+pub extern "C" fn nor_vault_unlock(path: *const c_char, password: *const c_char) -> bool {
+    if path.is_null() || password.is_null() {
+        return false;
+    }
+
+    let (path, password) = unsafe {
+        match (
+            CStr::from_ptr(path).to_str(),
+            CStr::from_ptr(password).to_str(),
+        ) {
+            (Ok(p), Ok(pw)) => (p, pw),
+            _ => return false,
+        }
+    };
+
+    WALLET_STORAGE.unlock_vault(path, password).is_ok()
+}
+
+/// Lock the vault and wipe wallets from memory
+#[no_mangle]
+pub extern "C" fn nor_vault_lock() -> bool {
+    WALLET_STORAGE.lock().is_ok()
+}
+
+/// Re-encrypt the vault under a new password
+#[no_mangle]
+pub extern "C" fn nor_vault_change_password(
+    old_password: *const c_char,
+    new_password: *const c_char,
+) -> bool {
+    if old_password.is_null() || new_password.is_null() {
+        return false;
+    }
+
+    let (old, new) = unsafe {
+        match (
+            CStr::from_ptr(old_password).to_str(),
+            CStr::from_ptr(new_password).to_str(),
+        ) {
+            (Ok(o), Ok(n)) => (o, n),
+            _ => return false,
+        }
+    };
+
+    WALLET_STORAGE.change_password(old, new).is_ok()
+}
+
+/// Encrypted backup bundle of every wallet plus the app's settings
+/// settings_json is a BackupSettings object and may be null
+/// Returns the bundle JSON, or an empty string on error
+#[no_mangle]
+pub extern "C" fn nor_backup_export(
+    password: *const c_char,
+    settings_json: *const c_char,
+) -> NorString {
+    if password.is_null() {
+        return NorString::from_string("".to_string());
+    }
+
+    let password = match unsafe { CStr::from_ptr(password).to_str() } {
+        Ok(pw) => pw,
+        Err(_) => return NorString::from_string("".to_string()),
+    };
+    let settings = if settings_json.is_null() {
+        crate::types::BackupSettings::default()
+    } else {
+        let parsed = unsafe { CStr::from_ptr(settings_json).to_str() }
+            .ok()
+            .and_then(|json| serde_json::from_str(json).ok());
+        match parsed {
+            Some(settings) => settings,
+            None => return NorString::from_string("".to_string()),
+        }
+    };
+
+    match WALLET_STORAGE.export_backup(password, settings) {
+        Ok(bundle) => NorString::from_string(bundle),
+        Err(_) => NorString::from_string("".to_string()),
+    }
+}
+
+/// Restore a backup bundle into the wallet store
+/// Returns a JSON BackupRestore, or "{}" on error
+#[no_mangle]
+pub extern "C" fn nor_backup_import(bundle: *const c_char, password: *const c_char) -> NorString {
+    if bundle.is_null() || password.is_null() {
+        return NorString::from_string("{}".to_string());
+    }
+
+    let (bundle, password) = unsafe {
+        match (
+            CStr::from_ptr(bundle).to_str(),
+            CStr::from_ptr(password).to_str(),
+        ) {
+            (Ok(b), Ok(pw)) => (b, pw),
+            _ => return NorString::from_string("{}".to_string()),
+        }
+    };
+
+    match WALLET_STORAGE
+        .import_backup(bundle, password)
+        .and_then(|restore| Ok(serde_json::to_string(&restore)?))
+    {
+        Ok(json) => NorString::from_string(json),
+        Err(_) => NorString::from_string("{}".to_string()),
+    }
+}
+
+/// Confirm the vault password, allowing exports for the session's sensitive window
+/// Returns true on success
+#[no_mangle]
+pub extern "C" fn nor_session_authorize(password: *const c_char) -> bool {
+    if password.is_null() {
+        return false;
+    }
+
+    let password = unsafe {
+        match CStr::from_ptr(password).to_str() {
+            Ok(pw) => pw,
+            Err(_) => return false,
+        }
+    };
+
+    WALLET_STORAGE.authorize_sensitive(password).is_ok()
+}
+
+/// Lock the vault, wipe wallets from memory and revoke export authorization
+#[no_mangle]
+pub extern "C" fn nor_session_lock_all() -> bool {
+    WALLET_STORAGE.lock_all().is_ok()
+}
+
+/// Set the idle timeout (0 disables it) and the export window, in milliseconds
+/// Loosening either one needs nor_session_authorize first
+#[no_mangle]
+pub extern "C" fn nor_session_set_policy(idle_timeout_ms: u64, sensitive_window_ms: u64) -> bool {
+    WALLET_STORAGE
+        .set_session_policy(crate::types::SessionPolicy {
+            idle_timeout_ms,
+            sensitive_window_ms,
+        })
+        .is_ok()
+}
+
+/// Lock the vault if the session is idle; call from a periodic timer
+/// Returns true if it locked
+#[no_mangle]
+pub extern "C" fn nor_session_tick() -> bool {
+    WALLET_STORAGE.enforce_idle_timeout().unwrap_or(false)
+}
+
+/// Returns a JSON SessionStatus, or "{}" on error
+#[no_mangle]
+pub extern "C" fn nor_session_status() -> NorString {
+    match WALLET_STORAGE
+        .session_status()
+        .and_then(|status| Ok(serde_json::to_string(&status)?))
+    {
+        Ok(json) => NorString::from_string(json),
+        Err(_) => NorString::from_string("{}".to_string()),
+    }
+}
+
+/// Replace the spending policy checked before every signature
+/// Call nor_session_authorize first
+/// Returns true on success
+#[no_mangle]
+pub extern "C" fn nor_policy_set(policy_json: *const c_char) -> bool {
+    if policy_json.is_null() {
+        return false;
+    }
+
+    let json = unsafe {
+        match CStr::from_ptr(policy_json).to_str() {
+            Ok(json) => json,
+            Err(_) => return false,
+        }
+    };
+
+    WALLET_STORAGE.set_spending_policy(json).is_ok()
+}
+
+/// Remove the spending policy
+/// Call nor_session_authorize first
+#[no_mangle]
+pub extern "C" fn nor_policy_clear() -> bool {
+    WALLET_STORAGE.clear_spending_policy().is_ok()
+}
+
+/// Returns the spending policy as JSON, or "" when none is set
+#[no_mangle]
+pub extern "C" fn nor_policy_get() -> NorString {
+    match WALLET_STORAGE.spending_policy() {
+        Ok(Some(json)) => NorString::from_string(json),
+        _ => NorString::from_string("".to_string()),
+    }
+}
+
+/// Keep the signing audit log in the JSON lines file at path
+/// Needs an unlocked vault; call before signing anything
+/// Returns true on success
+#[no_mangle]
+pub extern "C" fn nor_audit_open(path: *const c_char) -> bool {
+    if path.is_null() {
+        return false;
+    }
+
+    let path = unsafe {
+        match CStr::from_ptr(path).to_str() {
+            Ok(path) => path,
+            Err(_) => return false,
+        }
+    };
+
+    WALLET_STORAGE.open_audit_log(path).is_ok()
+}
+
+/// Returns a JSON AuditVerification, or "{}" on error
+#[no_mangle]
+pub extern "C" fn nor_audit_verify() -> NorString {
+    match WALLET_STORAGE
+        .verify_audit_log()
+        .and_then(|verification| Ok(serde_json::to_string(&verification)?))
+    {
+        Ok(json) => NorString::from_string(json),
+        Err(_) => NorString::from_string("{}".to_string()),
+    }
+}
+
+/// Returns the audit log as JSON lines, or "" on error
+#[no_mangle]
+pub extern "C" fn nor_audit_export() -> NorString {
+    match WALLET_STORAGE.export_audit_log() {
+        Ok(jsonl) => NorString::from_string(jsonl),
+        Err(_) => NorString::from_string("".to_string()),
+    }
+}
+
+/// Sign an offline signing request envelope with the wallet holding its account
+/// Returns the response envelope JSON, or "{}" on error
+#[no_mangle]
+pub extern "C" fn nor_envelope_sign(request_json: *const c_char) -> NorString {
+    if request_json.is_null() {
+        return NorString::from_string("{}".to_string());
+    }
+
+    let request = unsafe {
+        match CStr::from_ptr(request_json).to_str() {
+            Ok(request) => request,
+            Err(_) => return NorString::from_string("{}".to_string()),
+        }
+    };
+
+    match crate::WalletManager::with_keyring(WALLET_STORAGE.clone())
+        .sign_signing_request(request.to_string())
+    {
+        Ok(json) => NorString::from_string(json),
+        Err(_) => NorString::from_string("{}".to_string()),
+    }
+}
+
+/// Check a response envelope against the request envelope it answers
+/// Returns the verified response JSON, or "{}" if it does not match
+#[no_mangle]
+pub extern "C" fn nor_envelope_accept(
+    request_json: *const c_char,
+    response_json: *const c_char,
+) -> NorString {
+    if request_json.is_null() || response_json.is_null() {
+        return NorString::from_string("{}".to_string());
+    }
+
+    let (request, response) = unsafe {
+        match (
+            CStr::from_ptr(request_json).to_str(),
+            CStr::from_ptr(response_json).to_str(),
+        ) {
+            (Ok(request), Ok(response)) => (request, response),
+            _ => return NorString::from_string("{}".to_string()),
+        }
+    };
+
+    match crate::WalletManager::with_keyring(WALLET_STORAGE.clone())
+        .accept_signing_response(request.to_string(), response.to_string())
+        .and_then(|response| Ok(serde_json::to_string(&response)?))
+    {
+        Ok(json) => NorString::from_string(json),
+        Err(_) => NorString::from_string("{}".to_string()),
+    }
+}
+
+/// Answer a scanned single-part `ur:eth-sign-request` as a QR signer
+/// Returns the `ur:eth-signature` string, or an empty string on error
+#[no_mangle]
+pub extern "C" fn nor_ur_sign(request_ur: *const c_char) -> NorString {
+    if request_ur.is_null() {
+        return NorString::from_string("".to_string());
+    }
+
+    let request_ur = unsafe {
+        match CStr::from_ptr(request_ur).to_str() {
+            Ok(request_ur) => request_ur,
+            Err(_) => return NorString::from_string("".to_string()),
+        }
+    };
+
+    let manager = crate::EvmManager::with_keyring(WALLET_STORAGE.clone());
+    let signature = crate::ur::decode(request_ur)
+        .and_then(|payload| manager.decode_eth_sign_request(payload))
+        .and_then(|request| manager.sign_eth_sign_request(request))
+        .and_then(|signature| manager.encode_eth_signature(signature))
+        .and_then(|payload| crate::ur::encode(&payload));
+    match signature {
+        Ok(signature_ur) => NorString::from_string(signature_ur),
+        Err(_) => NorString::from_string("".to_string()),
+    }
+}
+
+/// Recover a wallet from SLIP-39 shares, one mnemonic per line
+/// passphrase may be null
+/// Returns a JSON string containing wallet data
+#[no_mangle]
+pub extern "C" fn nor_wallet_from_slip39(
+    mnemonics: *const c_char,
+    passphrase: *const c_char,
+) -> NorString {
+    if mnemonics.is_null() {
+        return NorString::from_string("{}".to_string());
+    }
+
+    let mnemonics_str = unsafe {
+        match CStr::from_ptr(mnemonics).to_str() {
+            Ok(s) => s,
+            Err(_) => return NorString::from_string("{}".to_string()),
+        }
+    };
+    let passphrase_str = if passphrase.is_null() {
+        ""
+    } else {
+        match unsafe { CStr::from_ptr(passphrase).to_str() } {
+            Ok(s) => s,
+            Err(_) => return NorString::from_string("{}".to_string()),
+        }
+    };
+
+    let shares: Vec<String> = mnemonics_str
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    match Wallet::from_slip39(&shares, passphrase_str) {
+        Ok(wallet) => store_wallet(wallet),
+        Err(_) => NorString::from_string("{}".to_string()),
+    }
+}
+
+/// Split a wallet's seed into SLIP-39 shares
+/// groups_json: [{"member_threshold":2,"member_count":3}, ...]; passphrase may be null
+/// Returns a JSON array with one array of mnemonics per group
+#[no_mangle]
+pub extern "C" fn nor_wallet_export_slip39(
+    wallet_id: *const c_char,
+    group_threshold: u8,
+    groups_json: *const c_char,
+    passphrase: *const c_char,
+) -> NorString {
+    if wallet_id.is_null() || groups_json.is_null() {
+        return NorString::from_string("[]".to_string());
+    }
+
+    let (id, groups_str) = unsafe {
+        match (
+            CStr::from_ptr(wallet_id).to_str(),
+            CStr::from_ptr(groups_json).to_str(),
+        ) {
+            (Ok(id), Ok(groups)) => (id, groups),
+            _ => return NorString::from_string("[]".to_string()),
+        }
+    };
+    let passphrase_str = if passphrase.is_null() {
+        ""
+    } else {
+        match unsafe { CStr::from_ptr(passphrase).to_str() } {
+            Ok(s) => s,
+            Err(_) => return NorString::from_string("[]".to_string()),
+        }
+    };
+
+    let groups: Vec<crate::types::Slip39Group> = match serde_json::from_str(groups_str) {
+        Ok(groups) => groups,
+        Err(_) => return NorString::from_string("[]".to_string()),
+    };
+
+    let shares = WALLET_STORAGE.with_sensitive_wallet(id, |wallet| {
+        wallet.to_slip39(
+            group_threshold,
+            &groups,
+            passphrase_str,
+            crate::crypto::slip39::DEFAULT_ITERATION_EXPONENT,
+        )
+    });
+    match shares.and_then(|shares| Ok(serde_json::to_string(&shares)?)) {
+        Ok(json) => NorString::from_string(json),
+        Err(_) => NorString::from_string("[]".to_string()),
+    }
+}
+
+/// Check a mnemonic word by word
+/// language is a snake_case name such as "english", or null to detect it
+/// Returns a JSON MnemonicValidation
+#[no_mangle]
+pub extern "C" fn nor_mnemonic_validate(
+    mnemonic: *const c_char,
+    language: *const c_char,
+) -> NorString {
+    if mnemonic.is_null() {
+        return NorString::from_string("{}".to_string());
+    }
+
+    let mnemonic_str = unsafe {
+        match CStr::from_ptr(mnemonic).to_str() {
+            Ok(s) => s,
+            Err(_) => return NorString::from_string("{}".to_string()),
+        }
+    };
+    let language = if language.is_null() {
+        None
+    } else {
+        match unsafe { CStr::from_ptr(language).to_str() }.map(parse_language) {
+            Ok(Some(language)) => Some(language),
+            _ => return NorString::from_string("{}".to_string()),
+        }
+    };
+
+    let validation = crate::crypto::mnemonic::validate(mnemonic_str, language);
+    match serde_json::to_string(&validation) {
+        Ok(json) => NorString::from_string(json),
+        Err(_) => NorString::from_string("{}".to_string()),
+    }
+}
+
+/// Suggest up to `limit` wordlist entries starting with `prefix`
+/// Returns a JSON array of words
+#[no_mangle]
+pub extern "C" fn nor_mnemonic_suggest(
+    prefix: *const c_char,
+    language: *const c_char,
+    limit: u32,
+) -> NorString {
+    if prefix.is_null() || language.is_null() {
+        return NorString::from_string("[]".to_string());
+    }
+
+    let (prefix, language) = unsafe {
+        match (
+            CStr::from_ptr(prefix).to_str(),
+            CStr::from_ptr(language).to_str().map(parse_language),
+        ) {
+            (Ok(p), Ok(Some(l))) => (p, l),
+            _ => return NorString::from_string("[]".to_string()),
+        }
+    };
+
+    let words = crate::crypto::mnemonic::suggest_words(prefix, language, limit);
+    match serde_json::to_string(&words) {
+        Ok(json) => NorString::from_string(json),
+        Err(_) => NorString::from_string("[]".to_string()),
+    }
+}
+
+fn parse_language(name: &str) -> Option<crate::types::MnemonicLanguage> {
+    serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+}
@@ -0,0 +1,467 @@
+// Web3 Secret Storage (keystore V3) support
+// Compatible with the JSON files written by Geth, MetaMask and MyEtherWallet
+
+use crate::error::*;
+use aes::cipher::{KeyIvInit, StreamCipher};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Keccak};
+use zeroize::Zeroizing;
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+
+const KEYSTORE_VERSION: u32 = 3;
+const CIPHER: &str = "aes-128-ctr";
+const PRF: &str = "hmac-sha256";
+const DKLEN: u32 = 32;
+/// Upper bounds on parameters read from untrusted files
+const MAX_DKLEN: u32 = 64;
+const MAX_PBKDF2_ROUNDS: u32 = 10_000_000;
+/// Each unit of scrypt's `p` is another pass over the `n` table; Geth writes 1
+const MAX_SCRYPT_PARALLELISM: u32 = 16;
+
+/// Key derivation parameters as stored in `crypto.kdfparams`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kdf", content = "kdfparams", rename_all = "lowercase")]
+pub enum KeystoreKdf {
+    Scrypt {
+        dklen: u32,
+        n: u32,
+        r: u32,
+        p: u32,
+        salt: String,
+    },
+    Pbkdf2 {
+        dklen: u32,
+        c: u32,
+        prf: String,
+        salt: String,
+    },
+}
+
+impl KeystoreKdf {
+    /// Scrypt with Geth's standard cost (n = 2^18, r = 8, p = 1)
+    pub fn scrypt() -> Self {
+        Self::scrypt_with_cost(1 << 18, 8, 1)
+    }
+
+    pub fn scrypt_with_cost(n: u32, r: u32, p: u32) -> Self {
+        KeystoreKdf::Scrypt {
+            dklen: DKLEN,
+            n,
+            r,
+            p,
+            salt: random_hex(32),
+        }
+    }
+
+    pub fn pbkdf2(iterations: u32) -> Self {
+        KeystoreKdf::Pbkdf2 {
+            dklen: DKLEN,
+            c: iterations,
+            prf: PRF.to_string(),
+            salt: random_hex(32),
+        }
+    }
+
+    fn derive_key(&self, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+        match self {
+            KeystoreKdf::Scrypt {
+                dklen,
+                n,
+                r,
+                p,
+                salt,
+            } => {
+                if *dklen < DKLEN
+                    || *dklen > MAX_DKLEN
+                    || !n.is_power_of_two()
+                    || *n < 2
+                    || *r == 0
+                    || *p == 0
+                    || *p > MAX_SCRYPT_PARALLELISM
+                {
+                    return Err(CoreError::InvalidInput);
+                }
+                let salt = hex::decode(salt).map_err(|_| CoreError::InvalidInput)?;
+
+                let mut key = Zeroizing::new(vec![0u8; *dklen as usize]);
+                scrypt(password.as_bytes(), &salt, *n, *r, *p, &mut key)?;
+                Ok(key)
+            }
+            KeystoreKdf::Pbkdf2 {
+                dklen,
+                c,
+                prf,
+                salt,
+            } => {
+                if *dklen < DKLEN
+                    || *dklen > MAX_DKLEN
+                    || *c == 0
+                    || *c > MAX_PBKDF2_ROUNDS
+                    || prf != PRF
+                {
+                    return Err(CoreError::InvalidInput);
+                }
+                let salt = hex::decode(salt).map_err(|_| CoreError::InvalidInput)?;
+
+                let mut key = Zeroizing::new(vec![0u8; *dklen as usize]);
+                pbkdf2::pbkdf2_hmac::<sha2::Sha256>(password.as_bytes(), &salt, *c, &mut key);
+                Ok(key)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CipherParams {
+    iv: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CryptoSection {
+    cipher: String,
+    cipherparams: CipherParams,
+    ciphertext: String,
+    #[serde(flatten)]
+    kdf: KeystoreKdf,
+    mac: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    // Older Geth and MyEtherWallet files capitalize this key
+    #[serde(alias = "Crypto")]
+    crypto: CryptoSection,
+}
+
+/// Decrypt a keystore V3 JSON document, returning the raw private key
+pub fn decrypt_keystore(json: &str, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let file: KeystoreFile = serde_json::from_str(json)?;
+    let crypto = &file.crypto;
+    if file.version != KEYSTORE_VERSION || crypto.cipher != CIPHER {
+        return Err(CoreError::InvalidInput);
+    }
+
+    let iv = hex::decode(&crypto.cipherparams.iv).map_err(|_| CoreError::InvalidInput)?;
+    let ciphertext = hex::decode(&crypto.ciphertext).map_err(|_| CoreError::InvalidInput)?;
+    let mac = hex::decode(&crypto.mac).map_err(|_| CoreError::InvalidInput)?;
+    if iv.len() != 16 {
+        return Err(CoreError::InvalidInput);
+    }
+
+    let derived = crypto.kdf.derive_key(password)?;
+    if keystore_mac(&derived, &ciphertext).as_slice() != mac.as_slice() {
+        return Err(CoreError::InvalidPassword);
+    }
+
+    let mut key = Zeroizing::new(ciphertext);
+    Aes128Ctr::new(derived[..16].into(), iv.as_slice().into()).apply_keystream(&mut key);
+    Ok(key)
+}
+
+/// Encrypt a private key into a keystore V3 JSON document
+pub fn encrypt_keystore(
+    private_key: &[u8],
+    address: &str,
+    password: &str,
+    kdf: KeystoreKdf,
+) -> Result<String> {
+    let mut iv = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut iv);
+
+    let derived = kdf.derive_key(password)?;
+    let mut ciphertext = private_key.to_vec();
+    Aes128Ctr::new(derived[..16].into(), (&iv).into()).apply_keystream(&mut ciphertext);
+    let mac = keystore_mac(&derived, &ciphertext);
+
+    let file = KeystoreFile {
+        version: KEYSTORE_VERSION,
+        id: uuid::Uuid::new_v4().to_string(),
+        address: Some(address.trim_start_matches("0x").to_lowercase()),
+        crypto: CryptoSection {
+            cipher: CIPHER.to_string(),
+            cipherparams: CipherParams {
+                iv: hex::encode(iv),
+            },
+            ciphertext: hex::encode(ciphertext),
+            kdf,
+            mac: hex::encode(mac),
+        },
+    };
+
+    Ok(serde_json::to_string(&file)?)
+}
+
+/// keccak256(derived_key[16..32] ++ ciphertext)
+fn keystore_mac(derived_key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut mac = [0u8; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(&derived_key[16..32]);
+    hasher.update(ciphertext);
+    hasher.finalize(&mut mac);
+    mac
+}
+
+/// scrypt as implemented by Geth.
+///
+/// The RustCrypto crate enforces RFC 7914's `n < 2^(16 * r)`, which rejects
+/// files in the wild (including the spec's own test vector) that use `r = 1`.
+fn scrypt(password: &[u8], salt: &[u8], n: u32, r: u32, p: u32, out: &mut [u8]) -> Result<()> {
+    let block_len = 128 * r as usize;
+    let memory = block_len
+        .checked_mul(n as usize)
+        .filter(|&m| m <= MAX_SCRYPT_MEMORY)
+        .ok_or(CoreError::InvalidInput)?;
+
+    let b_len = block_len
+        .checked_mul(p as usize)
+        .filter(|&m| m <= MAX_SCRYPT_MEMORY)
+        .ok_or(CoreError::InvalidInput)?;
+
+    let mut b = Zeroizing::new(vec![0u8; b_len]);
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(password, salt, 1, &mut b);
+
+    let mut v = Zeroizing::new(vec![0u32; memory / 4]);
+    for chunk in b.chunks_mut(block_len) {
+        ro_mix(chunk, &mut v, n as usize);
+    }
+
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(password, &b, 1, out);
+    Ok(())
+}
+
+/// Bounds both the `128 * r * n` scratch table and the `128 * r * p` blocks
+const MAX_SCRYPT_MEMORY: usize = 1 << 30;
+
+fn ro_mix(block: &mut [u8], v: &mut [u32], n: usize) {
+    let words = block.len() / 4;
+    let mut x = Zeroizing::new(vec![0u32; words]);
+    let mut y = Zeroizing::new(vec![0u32; words]);
+    for (word, bytes) in x.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().expect("4-byte chunk"));
+    }
+
+    for i in 0..n {
+        v[i * words..(i + 1) * words].copy_from_slice(&x);
+        block_mix(&x, &mut y);
+        std::mem::swap(&mut x, &mut y);
+    }
+    for _ in 0..n {
+        // Integerify: first word of the last 64-byte block
+        let j = x[words - 16] as usize & (n - 1);
+        for (a, b) in x.iter_mut().zip(&v[j * words..(j + 1) * words]) {
+            *a ^= b;
+        }
+        block_mix(&x, &mut y);
+        std::mem::swap(&mut x, &mut y);
+    }
+
+    for (bytes, word) in block.chunks_exact_mut(4).zip(x.iter()) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+}
+
+fn block_mix(input: &[u32], output: &mut [u32]) {
+    let blocks = input.len() / 16;
+    let mut x = [0u32; 16];
+    x.copy_from_slice(&input[(blocks - 1) * 16..]);
+
+    for i in 0..blocks {
+        for (a, b) in x.iter_mut().zip(&input[i * 16..(i + 1) * 16]) {
+            *a ^= b;
+        }
+        salsa20_8(&mut x);
+        // Even blocks go to the first half, odd blocks to the second
+        let dst = (i / 2 + (i % 2) * blocks / 2) * 16;
+        output[dst..dst + 16].copy_from_slice(&x);
+    }
+}
+
+fn salsa20_8(b: &mut [u32; 16]) {
+    let mut x = *b;
+    for _ in 0..4 {
+        for &(t, a, c, shift) in &SALSA_QUARTER_ROUNDS {
+            x[t] ^= x[a].wrapping_add(x[c]).rotate_left(shift);
+        }
+    }
+    for (out, mixed) in b.iter_mut().zip(x.iter()) {
+        *out = out.wrapping_add(*mixed);
+    }
+}
+
+// One Salsa20 double round as (target, a, b, rotation) steps
+const SALSA_QUARTER_ROUNDS: [(usize, usize, usize, u32); 32] = [
+    (4, 0, 12, 7),
+    (8, 4, 0, 9),
+    (12, 8, 4, 13),
+    (0, 12, 8, 18),
+    (9, 5, 1, 7),
+    (13, 9, 5, 9),
+    (1, 13, 9, 13),
+    (5, 1, 13, 18),
+    (14, 10, 6, 7),
+    (2, 14, 10, 9),
+    (6, 2, 14, 13),
+    (10, 6, 2, 18),
+    (3, 15, 11, 7),
+    (7, 3, 15, 9),
+    (11, 7, 3, 13),
+    (15, 11, 7, 18),
+    (1, 0, 3, 7),
+    (2, 1, 0, 9),
+    (3, 2, 1, 13),
+    (0, 3, 2, 18),
+    (6, 5, 4, 7),
+    (7, 6, 5, 9),
+    (4, 7, 6, 13),
+    (5, 4, 7, 18),
+    (11, 10, 9, 7),
+    (8, 11, 10, 9),
+    (9, 8, 11, 13),
+    (10, 9, 8, 18),
+    (12, 15, 14, 7),
+    (13, 12, 15, 9),
+    (14, 13, 12, 13),
+    (15, 14, 13, 18),
+];
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from the Web3 Secret Storage Definition
+    const PASSWORD: &str = "testpassword";
+    const PRIVATE_KEY: &str = "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
+
+    const PBKDF2_VECTOR: &str = r#"{
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+            "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+            "kdf": "pbkdf2",
+            "kdfparams": {
+                "c": 262144,
+                "dklen": 32,
+                "prf": "hmac-sha256",
+                "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+            },
+            "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+        },
+        "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version": 3
+    }"#;
+
+    const SCRYPT_VECTOR: &str = r#"{
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": { "iv": "83dbcc02d8ccb40e466191a123791e0e" },
+            "ciphertext": "d172bf743a674da9cdad04534d56926ef8358534d458fffccd4e6ad2fbde479c",
+            "kdf": "scrypt",
+            "kdfparams": {
+                "dklen": 32,
+                "n": 262144,
+                "p": 8,
+                "r": 1,
+                "salt": "ab0c7876052600dd703518d6fc3fe8984592145b591fc8fb5c6d43190334ba19"
+            },
+            "mac": "2103ac29920d71da29f15d75b4a16dbe95cfd7ff8faea1056c33131d846e3097"
+        },
+        "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version": 3
+    }"#;
+
+    #[test]
+    fn test_pbkdf2_vector() {
+        let key = decrypt_keystore(PBKDF2_VECTOR, PASSWORD).unwrap();
+        assert_eq!(hex::encode(key.as_slice()), PRIVATE_KEY);
+    }
+
+    #[test]
+    fn test_scrypt_vector() {
+        let key = decrypt_keystore(SCRYPT_VECTOR, PASSWORD).unwrap();
+        assert_eq!(hex::encode(key.as_slice()), PRIVATE_KEY);
+    }
+
+    #[test]
+    fn test_scrypt_rfc7914_vector() {
+        let mut out = [0u8; 64];
+        scrypt(b"password", b"NaCl", 1024, 8, 16, &mut out).unwrap();
+        assert_eq!(
+            hex::encode(out),
+            "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162\
+             2eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640"
+        );
+    }
+
+    #[test]
+    fn test_wrong_password_fails_mac() {
+        assert!(matches!(
+            decrypt_keystore(PBKDF2_VECTOR, "wrongpassword"),
+            Err(CoreError::InvalidPassword)
+        ));
+    }
+
+    #[test]
+    fn test_capitalized_crypto_key() {
+        let json = PBKDF2_VECTOR.replace("\"crypto\"", "\"Crypto\"");
+        let key = decrypt_keystore(&json, PASSWORD).unwrap();
+        assert_eq!(hex::encode(key.as_slice()), PRIVATE_KEY);
+    }
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        let private_key = hex::decode(PRIVATE_KEY).unwrap();
+
+        for kdf in [
+            KeystoreKdf::scrypt_with_cost(1 << 10, 8, 1),
+            KeystoreKdf::pbkdf2(1000),
+        ] {
+            let json = encrypt_keystore(&private_key, "0xAbCd", PASSWORD, kdf.clone()).unwrap();
+            let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(value["version"], 3);
+            assert_eq!(value["address"], "abcd");
+            assert_eq!(value["crypto"]["cipher"], "aes-128-ctr");
+
+            let key = decrypt_keystore(&json, PASSWORD).unwrap();
+            assert_eq!(key.as_slice(), private_key.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_rejects_unsupported_cipher() {
+        let json = PBKDF2_VECTOR.replace("aes-128-ctr", "aes-128-cbc");
+        assert!(matches!(
+            decrypt_keystore(&json, PASSWORD),
+            Err(CoreError::InvalidInput)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_unbounded_kdf_params() {
+        // Each would allocate gigabytes or spin for hours before the MAC check
+        for json in [
+            SCRYPT_VECTOR.replace("\"p\": 8", "\"p\": 4294967295"),
+            // Within the memory bounds, but millions of passes over the table
+            SCRYPT_VECTOR.replace("\"p\": 8", "\"p\": 8388608"),
+            SCRYPT_VECTOR.replace("\"dklen\": 32", "\"dklen\": 4294967295"),
+            PBKDF2_VECTOR.replace("\"c\": 262144", "\"c\": 4294967295"),
+            PBKDF2_VECTOR.replace("\"c\": 262144", "\"c\": 0"),
+        ] {
+            assert!(matches!(
+                decrypt_keystore(&json, PASSWORD),
+                Err(CoreError::InvalidInput)
+            ));
+        }
+    }
+}
@@ -0,0 +1,476 @@
+// Ledger Ethereum app over APDU
+// The app talks ISO 7816 APDUs over whatever link the platform offers (USB
+// HID, BLE); the app supplies that link as a `LedgerTransport`. What the
+// device signs is never taken on its word: the signature is recovered over
+// our own digest and must come from the address the app reported
+
+use std::str::FromStr;
+
+use crate::crypto::public_key_to_address;
+use crate::signer::{EcdsaSignature, Signer};
+use crate::{eip712, evm};
+use crate::{error::*, types::*};
+use bip32::DerivationPath;
+use ethers_core::utils::rlp;
+use secp256k1::PublicKey;
+
+const CLA: u8 = 0xe0;
+const INS_GET_ADDRESS: u8 = 0x02;
+const INS_SIGN_TRANSACTION: u8 = 0x04;
+const INS_SIGN_PERSONAL_MESSAGE: u8 = 0x08;
+const INS_SIGN_EIP712_HASHED: u8 = 0x0c;
+
+const P1_FIRST_CHUNK: u8 = 0x00;
+const P1_MORE_CHUNKS: u8 = 0x80;
+
+const SW_OK: u16 = 0x9000;
+const SW_DENIED: u16 = 0x6985;
+
+/// Chunk size used by Ledger's own libraries; APDUs carry at most 255 bytes
+const CHUNK_SIZE: usize = 150;
+/// Deepest path the app accepts
+const MAX_PATH_DEPTH: usize = 10;
+
+/// Carries APDUs to a Ledger device and back. `exchange` returns the
+/// response data followed by the two status word bytes.
+pub trait LedgerTransport: Send + Sync {
+    fn exchange(&self, apdu: Vec<u8>) -> Result<Vec<u8>>;
+}
+
+/// Signs with the Ethereum app of a Ledger device, for the account at one
+/// derivation path
+pub struct LedgerSigner {
+    transport: Box<dyn LedgerTransport>,
+    path: Vec<u8>,
+    public_key: PublicKey,
+    address: String,
+}
+
+impl LedgerSigner {
+    /// Reads the account at `derivation_path` from the device
+    pub fn new(transport: Box<dyn LedgerTransport>, derivation_path: &str) -> Result<Self> {
+        let path = encode_path(derivation_path)?;
+        let (public_key, address) = get_address(transport.as_ref(), &path, false)?;
+        Ok(Self {
+            transport,
+            path,
+            public_key,
+            address,
+        })
+    }
+
+    /// Has the device show the address for the user to compare
+    pub fn confirm_address(&self) -> Result<String> {
+        let (_, address) = get_address(self.transport.as_ref(), &self.path, true)?;
+        match address == self.address {
+            true => Ok(address),
+            false => Err(CoreError::DeviceError),
+        }
+    }
+
+    /// Sends `payload` in chunks, the first one prefixed with the path. A
+    /// chunk never ends at `split_limit` or later except at the end of the
+    /// payload.
+    fn send_chunked(&self, ins: u8, payload: &[u8], split_limit: usize) -> Result<Vec<u8>> {
+        let mut first = self.path.clone();
+        let mut offset = 0;
+        loop {
+            let room = CHUNK_SIZE - if offset == 0 { first.len() } else { 0 };
+            let mut end = (offset + room).min(payload.len());
+            if end >= split_limit {
+                end = payload.len();
+            }
+            let mut data = match offset {
+                0 => std::mem::take(&mut first),
+                _ => Vec::new(),
+            };
+            data.extend_from_slice(&payload[offset..end]);
+            let p1 = if offset == 0 {
+                P1_FIRST_CHUNK
+            } else {
+                P1_MORE_CHUNKS
+            };
+            let response = exchange(self.transport.as_ref(), ins, p1, 0x00, &data)?;
+            offset = end;
+            if offset == payload.len() {
+                return Ok(response);
+            }
+        }
+    }
+
+    /// The device answers v || r || s, with v cut to one byte for legacy
+    /// transactions; the recovery id comes from checking against our address
+    fn verified(&self, response: &[u8], digest: &[u8; 32]) -> Result<EcdsaSignature> {
+        if response.len() < 65 {
+            return Err(CoreError::DeviceError);
+        }
+        let r: [u8; 32] = response[1..33].try_into().unwrap();
+        let s: [u8; 32] = response[33..65].try_into().unwrap();
+        for recovery_id in [0, 1] {
+            let signature = EcdsaSignature { r, s, recovery_id };
+            if let Ok(public_key) = signature.recover(digest) {
+                if public_key == self.public_key {
+                    return Ok(signature);
+                }
+            }
+        }
+        Err(CoreError::SigningError)
+    }
+}
+
+impl Signer for LedgerSigner {
+    /// The Ethereum app does not sign bare digests
+    fn sign_digest(&self, _digest: &[u8; 32]) -> Result<EcdsaSignature> {
+        Err(CoreError::UnsupportedBySigner)
+    }
+
+    fn public_key(&self) -> Result<PublicKey> {
+        Ok(self.public_key)
+    }
+
+    fn address(&self) -> Result<String> {
+        Ok(self.address.clone())
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<EcdsaSignature> {
+        let length = u32::try_from(message.len()).map_err(|_| CoreError::InvalidInput)?;
+        let mut payload = length.to_be_bytes().to_vec();
+        payload.extend_from_slice(message);
+        let response = self.send_chunked(INS_SIGN_PERSONAL_MESSAGE, &payload, usize::MAX)?;
+        self.verified(&response, &evm::personal_message_hash(message))
+    }
+
+    /// Blind-signs the two EIP-712 hashes; the device shows them, not the
+    /// fields
+    fn sign_typed_data(&self, typed_data_json: &str) -> Result<EcdsaSignature> {
+        let hash = eip712::hash_typed_data(typed_data_json)?;
+        // The app needs a message hash, which domain-only payloads lack
+        let message_hash = hash.message_hash.ok_or(CoreError::UnsupportedBySigner)?;
+        let mut data = self.path.clone();
+        data.extend_from_slice(&hash.domain_separator);
+        data.extend_from_slice(&message_hash);
+        let response = exchange(
+            self.transport.as_ref(),
+            INS_SIGN_EIP712_HASHED,
+            0x00,
+            0x00,
+            &data,
+        )?;
+        self.verified(&response, &hash.digest)
+    }
+
+    fn sign_transaction(&self, params: &EvmTxParams) -> Result<EcdsaSignature> {
+        let unsigned = evm::to_typed_transaction(params)?.rlp();
+        let response =
+            self.send_chunked(INS_SIGN_TRANSACTION, &unsigned, eip155_offset(&unsigned)?)?;
+        self.verified(&response, &evm::transaction_sighash(params)?)
+    }
+}
+
+/// Start of the EIP-155 `chain_id, 0, 0` fields of a legacy transaction;
+/// the app needs them in the same chunk. Typed transactions have no limit.
+fn eip155_offset(unsigned: &[u8]) -> Result<usize> {
+    let tx = rlp::Rlp::new(unsigned);
+    if !tx.is_list() || tx.item_count().map_err(|_| CoreError::InvalidTransaction)? != 9 {
+        return Ok(usize::MAX);
+    }
+    let (_, offset) = tx
+        .at_with_offset(6)
+        .map_err(|_| CoreError::InvalidTransaction)?;
+    Ok(offset)
+}
+
+/// Public key and lowercase `0x` address at `path`, optionally confirmed on
+/// the device screen
+fn get_address(
+    transport: &dyn LedgerTransport,
+    path: &[u8],
+    confirm: bool,
+) -> Result<(PublicKey, String)> {
+    let response = exchange(transport, INS_GET_ADDRESS, confirm as u8, 0x00, path)?;
+    // public key length || public key || address length || address as ASCII hex
+    let key_len = *response.first().ok_or(CoreError::DeviceError)? as usize;
+    let key = response.get(1..1 + key_len).ok_or(CoreError::DeviceError)?;
+    let public_key = PublicKey::from_slice(key).map_err(|_| CoreError::DeviceError)?;
+    let address_len = *response.get(1 + key_len).ok_or(CoreError::DeviceError)? as usize;
+    let start = 2 + key_len;
+    let reported = response
+        .get(start..start + address_len)
+        .ok_or(CoreError::DeviceError)?;
+
+    let address = public_key_to_address(&public_key);
+    let reported = String::from_utf8_lossy(reported).to_lowercase();
+    if reported.trim_start_matches("0x") != address.trim_start_matches("0x") {
+        return Err(CoreError::DeviceError);
+    }
+    Ok((public_key, address))
+}
+
+fn exchange(
+    transport: &dyn LedgerTransport,
+    ins: u8,
+    p1: u8,
+    p2: u8,
+    data: &[u8],
+) -> Result<Vec<u8>> {
+    let length = u8::try_from(data.len()).map_err(|_| CoreError::InvalidInput)?;
+    let mut apdu = vec![CLA, ins, p1, p2, length];
+    apdu.extend_from_slice(data);
+
+    let mut response = transport.exchange(apdu)?;
+    if response.len() < 2 {
+        return Err(CoreError::DeviceError);
+    }
+    let status = response.split_off(response.len() - 2);
+    match u16::from_be_bytes([status[0], status[1]]) {
+        SW_OK => Ok(response),
+        SW_DENIED => Err(CoreError::DeviceRejected),
+        _ => Err(CoreError::DeviceError),
+    }
+}
+
+/// Path as the app takes it: depth, then each index big-endian
+fn encode_path(derivation_path: &str) -> Result<Vec<u8>> {
+    let path = DerivationPath::from_str(derivation_path).map_err(|_| CoreError::InvalidInput)?;
+    let depth = path.iter().count();
+    if depth == 0 || depth > MAX_PATH_DEPTH {
+        return Err(CoreError::InvalidInput);
+    }
+    let mut encoded = vec![depth as u8];
+    for child in path.iter() {
+        encoded.extend_from_slice(&child.0.to_be_bytes());
+    }
+    Ok(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::sign_with_key;
+    use crate::storage::Keyring;
+    use ethers_core::utils::keccak256;
+    use secp256k1::SecretKey;
+    use std::sync::{Arc, Mutex};
+
+    const PATH: &str = "m/44'/60'/0'/0/0";
+
+    #[derive(Default)]
+    struct DeviceState {
+        apdus: Vec<Vec<u8>>,
+        pending: Vec<u8>,
+        last_chunk_start: usize,
+        reject: bool,
+        // Key the device signs with, when it differs from the one it reports
+        impostor: Option<SecretKey>,
+    }
+
+    /// In-memory stand-in for the Ethereum app: reassembles chunked
+    /// commands the way the device does and answers with `key`
+    #[derive(Clone)]
+    struct MockLedger {
+        key: SecretKey,
+        state: Arc<Mutex<DeviceState>>,
+    }
+
+    impl MockLedger {
+        fn new() -> Self {
+            Self {
+                key: SecretKey::from_slice(&[0x46; 32]).unwrap(),
+                state: Arc::default(),
+            }
+        }
+
+        fn address(&self) -> String {
+            public_key_to_address(&PublicKey::from_secret_key_global(&self.key))
+        }
+
+        fn apdus(&self) -> Vec<Vec<u8>> {
+            self.state.lock().unwrap().apdus.clone()
+        }
+
+        fn answer(state: &DeviceState, key: &SecretKey, digest: [u8; 32], v: u8) -> Vec<u8> {
+            let signature = sign_with_key(state.impostor.as_ref().unwrap_or(key), &digest).unwrap();
+            let mut response = vec![v + signature.recovery_id];
+            response.extend_from_slice(&signature.r);
+            response.extend_from_slice(&signature.s);
+            response.extend_from_slice(&[0x90, 0x00]);
+            response
+        }
+    }
+
+    impl LedgerTransport for MockLedger {
+        fn exchange(&self, apdu: Vec<u8>) -> Result<Vec<u8>> {
+            let mut state = self.state.lock().unwrap();
+            state.apdus.push(apdu.clone());
+            assert_eq!(apdu[0], CLA);
+            assert_eq!(apdu[4] as usize, apdu.len() - 5);
+            let (ins, p1, data) = (apdu[1], apdu[2], &apdu[5..]);
+            if state.reject && ins != INS_GET_ADDRESS {
+                return Ok(vec![0x69, 0x85]);
+            }
+            // Strip the path from first chunks
+            let body = match p1 {
+                P1_FIRST_CHUNK if ins != INS_GET_ADDRESS => &data[1 + data[0] as usize * 4..],
+                _ => data,
+            };
+            if p1 == P1_FIRST_CHUNK {
+                state.pending.clear();
+            }
+            state.last_chunk_start = state.pending.len();
+            state.pending.extend_from_slice(body);
+            let pending = state.pending.clone();
+
+            match ins {
+                INS_GET_ADDRESS => {
+                    let public_key = PublicKey::from_secret_key_global(&self.key);
+                    let mut response = vec![65];
+                    response.extend_from_slice(&public_key.serialize_uncompressed());
+                    let address = self.address();
+                    response.push(40);
+                    response.extend_from_slice(&address.as_bytes()[2..]);
+                    response.extend_from_slice(&[0x90, 0x00]);
+                    Ok(response)
+                }
+                INS_SIGN_TRANSACTION => {
+                    let legacy = pending[0] >= 0xc0;
+                    let list = if legacy { &pending[..] } else { &pending[1..] };
+                    let info = rlp::Rlp::new(list).payload_info();
+                    match info {
+                        Ok(info) if info.header_len + info.value_len == list.len() => {}
+                        _ => return Ok(vec![0x90, 0x00]),
+                    }
+                    if legacy {
+                        // The EIP-155 fields must not be split off
+                        if state.last_chunk_start >= eip155_offset(&pending).unwrap() {
+                            return Ok(vec![0x6a, 0x80]);
+                        }
+                        let chain_id: u64 = rlp::Rlp::new(list).val_at(6).unwrap();
+                        let v = (chain_id * 2 + 35) as u8;
+                        return Ok(Self::answer(&state, &self.key, keccak256(&pending), v));
+                    }
+                    Ok(Self::answer(&state, &self.key, keccak256(&pending), 0))
+                }
+                INS_SIGN_PERSONAL_MESSAGE => {
+                    let length = u32::from_be_bytes(pending[..4].try_into().unwrap()) as usize;
+                    if pending.len() < 4 + length {
+                        return Ok(vec![0x90, 0x00]);
+                    }
+                    let digest = evm::personal_message_hash(&pending[4..]);
+                    Ok(Self::answer(&state, &self.key, digest, 27))
+                }
+                INS_SIGN_EIP712_HASHED => {
+                    let mut preimage = vec![0x19, 0x01];
+                    preimage.extend_from_slice(&pending);
+                    Ok(Self::answer(&state, &self.key, keccak256(preimage), 27))
+                }
+                _ => Ok(vec![0x6d, 0x00]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_address_apdu() {
+        let device = MockLedger::new();
+        let signer = LedgerSigner::new(Box::new(device.clone()), PATH).unwrap();
+        assert_eq!(signer.address().unwrap(), device.address());
+        assert_eq!(
+            hex::encode(&device.apdus()[0]),
+            "e002000015058000002c8000003c800000000000000000000000"
+        );
+
+        assert_eq!(signer.confirm_address().unwrap(), device.address());
+        assert_eq!(device.apdus()[1][2], 0x01);
+
+        for path in ["m", "44'/60'", "m/44'/x", "m/0/0/0/0/0/0/0/0/0/0/0"] {
+            assert!(LedgerSigner::new(Box::new(device.clone()), path).is_err());
+        }
+    }
+
+    #[test]
+    fn test_signatures_match_the_device_key() {
+        let device = MockLedger::new();
+        let signer = LedgerSigner::new(Box::new(device.clone()), PATH).unwrap();
+        let address = signer.address().unwrap();
+
+        let message = "login ".repeat(60);
+        let signature = evm::sign_message_with(&signer, message.as_bytes()).unwrap();
+        assert!(evm::verify_message(address.clone(), message, signature).unwrap());
+        let hash = eip712::hash_typed_data(eip712::MAIL_TYPED_DATA).unwrap();
+        assert_eq!(
+            signer.sign_typed_data(eip712::MAIL_TYPED_DATA).unwrap(),
+            sign_with_key(&device.key, &hash.digest).unwrap()
+        );
+        assert!(matches!(
+            signer.sign_digest(&[1u8; 32]),
+            Err(CoreError::UnsupportedBySigner)
+        ));
+
+        // Chunk boundaries fall everywhere across the EIP-155 fields
+        for data_len in (0..160).step_by(7).chain([300, 1000]) {
+            for eip1559 in [false, true] {
+                let params = evm::test_tx_params(address.clone(), eip1559, data_len);
+                let tx = evm::sign_transaction_with(&signer, params.clone()).unwrap();
+                assert_eq!(
+                    evm::signed_transaction_signature(&params, &tx.signed_tx).unwrap(),
+                    sign_with_key(&device.key, &evm::transaction_sighash(&params).unwrap())
+                        .unwrap()
+                );
+            }
+        }
+        assert!(device.apdus().iter().all(|apdu| apdu.len() <= 5 + 255));
+    }
+
+    #[test]
+    fn test_rejection_and_foreign_signatures() {
+        let device = MockLedger::new();
+        let signer = LedgerSigner::new(Box::new(device.clone()), PATH).unwrap();
+        let params = evm::test_tx_params(signer.address().unwrap(), true, 0);
+
+        device.state.lock().unwrap().reject = true;
+        assert!(matches!(
+            signer.sign_transaction(&params),
+            Err(CoreError::DeviceRejected)
+        ));
+
+        device.state.lock().unwrap().reject = false;
+        device.state.lock().unwrap().impostor = Some(SecretKey::from_slice(&[0x47; 32]).unwrap());
+        assert!(matches!(
+            signer.sign_message(b"login"),
+            Err(CoreError::SigningError)
+        ));
+        assert!(matches!(
+            signer.sign_transaction(&params),
+            Err(CoreError::SigningError)
+        ));
+    }
+
+    #[test]
+    fn test_evm_manager_signs_through_ledger() {
+        let keyring = Keyring::new();
+        let manager = crate::EvmManager::with_keyring(keyring.clone());
+        let device = MockLedger::new();
+
+        let address = manager
+            .ledger_address(Box::new(device.clone()), PATH.to_string(), false)
+            .unwrap();
+        let params = evm::test_tx_params(address.clone(), false, 40);
+        let tx = manager
+            .sign_transaction_ledger(Box::new(device.clone()), PATH.to_string(), params.clone())
+            .unwrap();
+        assert!(evm::signed_transaction_signature(&params, &tx.signed_tx).is_ok());
+
+        let signature = manager
+            .sign_typed_data_ledger(
+                Box::new(device),
+                PATH.to_string(),
+                eip712::MAIL_TYPED_DATA.to_string(),
+            )
+            .unwrap();
+        assert!(signature.starts_with("0x"));
+
+        // Both signatures went through the audit log like any other
+        let log = keyring.export_audit_log().unwrap();
+        assert_eq!(log.lines().count(), 2);
+        assert!(log.contains(&address));
+    }
+}
@@ -2,25 +2,49 @@
 // Provides cryptographic operations, wallet management, and blockchain interactions
 
 mod aa;
+mod audit;
+mod backup;
 mod config;
 mod crypto;
+mod discovery;
+mod eip712;
+mod envelope;
 mod error;
 mod evm;
 mod ffi;
+mod keystore;
+mod ledger;
 mod network;
+mod policy;
+mod remote_signer;
 mod rpc;
+mod session;
+mod signer;
 mod simulation;
 mod storage;
 mod tron;
 mod types;
+mod ur;
+mod vault;
 
-pub use config::{NetworkConfig, NOR_CHAIN_ID, NOR_CHAIN_RPC_URL};
+pub use config::{
+    NetworkConfig, DEFAULT_GAS_LIMIT, DEFAULT_GAS_PRICE, NOR_CHAIN_ID, NOR_CHAIN_RPC_URL,
+};
+pub use backup::BACKUP_VERSION;
+pub use envelope::ENVELOPE_VERSION;
 pub use error::{CoreError, Result};
+pub use keystore::KeystoreKdf;
+pub use ledger::{LedgerSigner, LedgerTransport};
 pub use network::{NetworkInfo, NetworkManager};
+pub use policy::POLICY_VERSION;
+pub use remote_signer::RemoteSigner;
+pub use signer::{EcdsaSignature, LocalSigner, Signer};
+pub use storage::Keyring;
+pub use ur::{UrDecoder, UrEncoder};
+pub use vault::{KdfParams, VAULT_VERSION};
 pub use types::*;
 
 use tracing::Level;
-use tracing_subscriber;
 
 // Get Nor Chain RPC URL
 pub fn get_nor_chain_rpc() -> String {
@@ -82,22 +106,29 @@ pub fn init_logger(level: LogLevel) {
 
 // Wallet Manager implementation
 pub struct WalletManager {
-    wallets: std::sync::Arc<std::sync::RwLock<std::collections::HashMap<String, crypto::Wallet>>>,
+    keyring: Keyring,
 }
 
 impl WalletManager {
+    /// Manager backed by the process-wide keyring
     pub fn new() -> Result<Self> {
-        Ok(Self {
-            wallets: std::sync::Arc::new(std::sync::RwLock::new(std::collections::HashMap::new())),
-        })
+        Ok(Self::with_keyring(Keyring::global()))
+    }
+
+    pub fn with_keyring(keyring: Keyring) -> Self {
+        Self { keyring }
+    }
+
+    /// The keyring this manager stores wallets in, to share with other managers
+    pub fn keyring(&self) -> Keyring {
+        self.keyring.clone()
     }
 
     pub fn create_wallet(&self, entropy: Vec<u8>, passphrase: Option<String>) -> Result<Wallet> {
         let wallet = crypto::Wallet::from_entropy(&entropy, passphrase.as_deref())?;
         let wallet_data = wallet.to_wallet_data();
 
-        let mut wallets = self.wallets.write().unwrap();
-        wallets.insert(wallet_data.id.clone(), wallet);
+        self.keyring.insert(wallet)?;
 
         Ok(wallet_data)
     }
@@ -110,38 +141,328 @@ impl WalletManager {
         let wallet = crypto::Wallet::from_mnemonic(&mnemonic, passphrase.as_deref())?;
         let wallet_data = wallet.to_wallet_data();
 
-        let mut wallets = self.wallets.write().unwrap();
-        wallets.insert(wallet_data.id.clone(), wallet);
+        self.keyring.insert(wallet)?;
+
+        Ok(wallet_data)
+    }
+
+    pub fn create_wallet_with_scheme(
+        &self,
+        entropy: Vec<u8>,
+        passphrase: Option<String>,
+        scheme: DerivationScheme,
+    ) -> Result<Wallet> {
+        let wallet =
+            crypto::Wallet::from_entropy_with_scheme(&entropy, passphrase.as_deref(), scheme)?;
+        let wallet_data = wallet.to_wallet_data();
+
+        self.keyring.insert(wallet)?;
+
+        Ok(wallet_data)
+    }
+
+    pub fn import_from_mnemonic_with_scheme(
+        &self,
+        mnemonic: String,
+        passphrase: Option<String>,
+        scheme: DerivationScheme,
+    ) -> Result<Wallet> {
+        let wallet =
+            crypto::Wallet::from_mnemonic_with_scheme(&mnemonic, passphrase.as_deref(), scheme)?;
+        let wallet_data = wallet.to_wallet_data();
+
+        self.keyring.insert(wallet)?;
 
         Ok(wallet_data)
     }
 
+    /// Create a wallet whose mnemonic is written in `language`
+    pub fn create_wallet_in_language(
+        &self,
+        entropy: Vec<u8>,
+        passphrase: Option<String>,
+        language: MnemonicLanguage,
+    ) -> Result<Wallet> {
+        let wallet = crypto::Wallet::from_entropy_in(
+            language,
+            &entropy,
+            passphrase.as_deref(),
+            DerivationScheme::Bip44,
+        )?;
+        let wallet_data = wallet.to_wallet_data();
+
+        self.keyring.insert(wallet)?;
+
+        Ok(wallet_data)
+    }
+
+    /// Check a mnemonic word by word; `language` is detected when omitted
+    pub fn validate_mnemonic(
+        &self,
+        mnemonic: String,
+        language: Option<MnemonicLanguage>,
+    ) -> MnemonicValidation {
+        crypto::mnemonic::validate(&mnemonic, language)
+    }
+
+    /// Wordlist entries starting with `prefix`, for seed entry autocomplete
+    pub fn suggest_mnemonic_words(
+        &self,
+        prefix: String,
+        language: MnemonicLanguage,
+        limit: u32,
+    ) -> Vec<String> {
+        crypto::mnemonic::suggest_words(&prefix, language, limit)
+    }
+
+    /// Derive the first `count` addresses of a mnemonic under every known
+    /// scheme, without importing it
+    pub fn scan_derivation_schemes(
+        &self,
+        mnemonic: String,
+        passphrase: Option<String>,
+        count: u32,
+    ) -> Result<Vec<SchemeAccounts>> {
+        crypto::scan_derivation_schemes(&mnemonic, passphrase.as_deref(), count)
+    }
+
     pub fn import_from_private_key(&self, private_key: String) -> Result<Wallet> {
         let wallet = crypto::Wallet::from_private_key(&private_key)?;
         let wallet_data = wallet.to_wallet_data();
 
-        let mut wallets = self.wallets.write().unwrap();
-        wallets.insert(wallet_data.id.clone(), wallet);
+        self.keyring.insert(wallet)?;
+
+        Ok(wallet_data)
+    }
+
+    /// Split a wallet's seed into SLIP-39 mnemonic shares, one list per group.
+    /// `passphrase` encrypts the shares and is needed again to recover them.
+    pub fn export_slip39_shares(
+        &self,
+        wallet_id: String,
+        group_threshold: u8,
+        groups: Vec<Slip39Group>,
+        passphrase: Option<String>,
+    ) -> Result<Vec<Vec<String>>> {
+        let passphrase = zeroize::Zeroizing::new(passphrase.unwrap_or_default());
+        self.keyring.with_sensitive_wallet(&wallet_id, |wallet| {
+            wallet.to_slip39(
+                group_threshold,
+                &groups,
+                &passphrase,
+                crypto::slip39::DEFAULT_ITERATION_EXPONENT,
+            )
+        })
+    }
+
+    /// Recover a wallet from a quorum of SLIP-39 mnemonic shares
+    pub fn import_from_slip39(
+        &self,
+        mnemonics: Vec<String>,
+        passphrase: Option<String>,
+    ) -> Result<Wallet> {
+        let passphrase = zeroize::Zeroizing::new(passphrase.unwrap_or_default());
+        let wallet = crypto::Wallet::from_slip39(&mnemonics, &passphrase)?;
+        let wallet_data = wallet.to_wallet_data();
+
+        self.keyring.insert(wallet)?;
+
+        Ok(wallet_data)
+    }
+
+    /// Import a Web3 Secret Storage (keystore V3) JSON file
+    pub fn import_from_keystore(&self, keystore_json: String, password: String) -> Result<Wallet> {
+        let key = keystore::decrypt_keystore(&keystore_json, &password)?;
+        let key_hex = zeroize::Zeroizing::new(hex::encode(key.as_slice()));
+        self.import_from_private_key(key_hex.to_string())
+    }
+
+    /// Export one account as keystore V3 JSON, encrypted with scrypt
+    pub fn export_keystore(
+        &self,
+        wallet_id: String,
+        account_index: u32,
+        password: String,
+    ) -> Result<String> {
+        self.export_keystore_with_kdf(wallet_id, account_index, password, KeystoreKdf::scrypt())
+    }
+
+    pub fn export_keystore_with_kdf(
+        &self,
+        wallet_id: String,
+        account_index: u32,
+        password: String,
+        kdf: KeystoreKdf,
+    ) -> Result<String> {
+        self.keyring.with_sensitive_wallet(&wallet_id, |wallet| {
+            wallet.with_secret_key(Chain::Evm, account_index, |secret_key| {
+                let public_key = secp256k1::PublicKey::from_secret_key_global(secret_key);
+                let address = crypto::public_key_to_address(&public_key);
+                let secret_bytes = zeroize::Zeroizing::new(secret_key.secret_bytes());
+                keystore::encrypt_keystore(secret_bytes.as_slice(), &address, &password, kdf)
+            })
+        })
+    }
+
+    /// Watch-only wallet deriving addresses from an account-level xpub.
+    /// `child_template` defaults to the BIP44 external chain, `0/{index}`.
+    /// `master_fingerprint`, as [`WalletManager::export_xpub`] gives it, lets
+    /// QR signers find the seed the xpub belongs to.
+    pub fn import_watch_only_xpub(
+        &self,
+        xpub: String,
+        chain: Chain,
+        child_template: Option<String>,
+        master_fingerprint: Option<u32>,
+    ) -> Result<Wallet> {
+        let template = child_template.unwrap_or_else(|| "0/{index}".to_string());
+        let wallet = crypto::Wallet::from_xpub(&xpub, chain, &template, master_fingerprint)?;
+        let wallet_data = wallet.to_wallet_data();
+
+        self.keyring.insert(wallet)?;
 
         Ok(wallet_data)
     }
 
+    /// Watch-only wallet over a fixed list of addresses
+    pub fn import_watch_only_addresses(
+        &self,
+        chain: Chain,
+        addresses: Vec<String>,
+    ) -> Result<Wallet> {
+        let wallet = crypto::Wallet::from_addresses(chain, &addresses)?;
+        let wallet_data = wallet.to_wallet_data();
+
+        self.keyring.insert(wallet)?;
+
+        Ok(wallet_data)
+    }
+
+    pub fn is_watch_only(&self, wallet_id: String) -> Result<bool> {
+        self.keyring
+            .with_wallet(&wallet_id, |wallet| Ok(wallet.is_watch_only()))
+    }
+
+    pub fn export_xpub(&self, wallet_id: String, chain: Chain) -> Result<AccountXpub> {
+        self.keyring
+            .with_wallet(&wallet_id, |wallet| wallet.export_xpub(chain))
+    }
+
+    /// Find accounts with a balance or transactions, scanning until
+    /// `options.gap_limit` consecutive unused ones
+    pub fn discover_accounts(
+        &self,
+        wallet_id: String,
+        rpc_url: String,
+        options: DiscoveryOptions,
+    ) -> Result<Vec<DiscoveredAccount>> {
+        let client = rpc::JsonRpcClient::new(rpc_url);
+        discovery::discover_accounts(&self.keyring, &wallet_id, &client, &options)
+    }
+
+    pub fn get_wallet(&self, wallet_id: String) -> Result<Wallet> {
+        self.keyring
+            .with_wallet(&wallet_id, |wallet| Ok(wallet.to_wallet_data()))
+    }
+
+    /// Every wallet, by `metadata.sort_order` and then creation time
+    pub fn list_wallets(&self) -> Result<Vec<Wallet>> {
+        let mut wallets = self
+            .keyring
+            .wallet_ids()?
+            .into_iter()
+            .map(|id| self.get_wallet(id))
+            .collect::<Result<Vec<_>>>()?;
+        wallets.sort_by_key(|w| (w.metadata.sort_order, w.created_at));
+        Ok(wallets)
+    }
+
+    pub fn set_wallet_metadata(
+        &self,
+        wallet_id: String,
+        metadata: WalletMetadata,
+    ) -> Result<Wallet> {
+        self.keyring.with_wallet_mut(&wallet_id, |wallet| {
+            wallet.set_metadata(metadata);
+            Ok(wallet.to_wallet_data())
+        })
+    }
+
+    /// Label, order or hide an account; it must already be derived
+    pub fn set_account_metadata(
+        &self,
+        wallet_id: String,
+        chain: Chain,
+        index: u32,
+        metadata: AccountMetadata,
+    ) -> Result<Account> {
+        self.keyring.with_wallet_mut(&wallet_id, |wallet| {
+            wallet.set_account_metadata(chain, index, metadata)?;
+            wallet.derive_chain_account(chain, index)
+        })
+    }
+
+    /// Give the wallets sort orders following their position in `wallet_ids`
+    pub fn reorder_wallets(&self, wallet_ids: Vec<String>) -> Result<()> {
+        if !wallet_ids.iter().all(|id| self.keyring.contains(id)) {
+            return Err(CoreError::InvalidInput);
+        }
+        for (position, wallet_id) in wallet_ids.iter().enumerate() {
+            self.keyring.with_wallet_mut(wallet_id, |wallet| {
+                let mut metadata = wallet.metadata().clone();
+                metadata.sort_order = position as u32;
+                wallet.set_metadata(metadata);
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// The wallet holding the key material with this fingerprint, if any
+    pub fn find_wallet_by_fingerprint(&self, fingerprint: String) -> Result<Option<Wallet>> {
+        match self.keyring.find_by_fingerprint(&fingerprint)? {
+            Some(id) => self.get_wallet(id).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Fingerprint a mnemonic would have once imported, to check for an
+    /// existing copy before importing it
+    pub fn mnemonic_fingerprint(
+        &self,
+        mnemonic: String,
+        passphrase: Option<String>,
+    ) -> Result<String> {
+        let wallet = crypto::Wallet::from_mnemonic(&mnemonic, passphrase.as_deref())?;
+        Ok(wallet.fingerprint())
+    }
+
+    pub fn get_derivation_scheme(&self, wallet_id: String) -> Result<DerivationScheme> {
+        self.keyring
+            .with_wallet(&wallet_id, |wallet| Ok(wallet.scheme().clone()))
+    }
+
+    pub fn remove_wallet(&self, wallet_id: String) -> Result<bool> {
+        self.keyring.remove(&wallet_id)
+    }
+
+    /// Needs a recent `authorize_sensitive`, and so a vault (as do the other
+    /// exports of secret material)
     pub fn export_mnemonic(&self, wallet_id: String) -> Result<String> {
-        let wallets = self.wallets.read().unwrap();
-        let wallet = wallets.get(&wallet_id).ok_or(CoreError::InvalidInput)?;
-        wallet.export_mnemonic()
+        self.keyring
+            .with_sensitive_wallet(&wallet_id, |wallet| wallet.export_mnemonic())
+            .map(|mnemonic| mnemonic.expose().to_string())
     }
 
     pub fn export_private_key(&self, wallet_id: String, account_index: u32) -> Result<String> {
-        let wallets = self.wallets.read().unwrap();
-        let wallet = wallets.get(&wallet_id).ok_or(CoreError::InvalidInput)?;
-        wallet.export_private_key(account_index)
+        self.keyring
+            .with_sensitive_wallet(&wallet_id, |wallet| wallet.export_private_key(account_index))
+            .map(|key| key.expose().to_string())
     }
 
     pub fn derive_account(&self, wallet_id: String, index: u32) -> Result<Account> {
-        let mut wallets = self.wallets.write().unwrap();
-        let wallet = wallets.get_mut(&wallet_id).ok_or(CoreError::InvalidInput)?;
-        wallet.derive_account(index)
+        self.keyring
+            .with_wallet_mut(&wallet_id, |wallet| wallet.derive_account(index))
     }
 
     pub fn derive_accounts(
@@ -150,23 +471,190 @@ impl WalletManager {
         start_index: u32,
         count: u32,
     ) -> Result<Vec<Account>> {
-        let mut wallets = self.wallets.write().unwrap();
-        let wallet = wallets.get_mut(&wallet_id).ok_or(CoreError::InvalidInput)?;
+        self.keyring.with_wallet_mut(&wallet_id, |wallet| {
+            let mut accounts = Vec::new();
+            for i in start_index..(start_index + count) {
+                accounts.push(wallet.derive_account(i)?);
+            }
+            Ok(accounts)
+        })
+    }
 
-        let mut accounts = Vec::new();
-        for i in start_index..(start_index + count) {
-            accounts.push(wallet.derive_account(i)?);
-        }
-        Ok(accounts)
+    /// Derive an account for a specific chain, e.g. a TRON account at
+    /// `m/44'/195'/0'/0/index` from the same mnemonic
+    pub fn derive_chain_account(
+        &self,
+        wallet_id: String,
+        chain: Chain,
+        index: u32,
+    ) -> Result<Account> {
+        self.keyring
+            .with_wallet_mut(&wallet_id, |wallet| wallet.derive_chain_account(chain, index))
+    }
+
+    pub fn derive_chain_accounts(
+        &self,
+        wallet_id: String,
+        chain: Chain,
+        start_index: u32,
+        count: u32,
+    ) -> Result<Vec<Account>> {
+        self.keyring.with_wallet_mut(&wallet_id, |wallet| {
+            let mut accounts = Vec::new();
+            for i in start_index..(start_index + count) {
+                accounts.push(wallet.derive_chain_account(chain, i)?);
+            }
+            Ok(accounts)
+        })
+    }
+
+    /// Persist the wallets to an encrypted vault file and keep it in sync
+    pub fn create_vault(&self, path: String, password: String) -> Result<()> {
+        self.keyring.create_vault(path, &password)
+    }
+
+    /// Load wallets from an existing vault file. Returns the wallet ids.
+    pub fn unlock_vault(&self, path: String, password: String) -> Result<Vec<String>> {
+        self.keyring.unlock_vault(path, &password)
+    }
+
+    pub fn lock(&self) -> Result<()> {
+        self.keyring.lock()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.keyring.is_locked()
+    }
+
+    pub fn change_password(&self, old_password: String, new_password: String) -> Result<()> {
+        self.keyring.change_password(&old_password, &new_password)
+    }
+
+    /// Encrypted bundle of all wallets and the app's `settings`, to restore on
+    /// another device
+    pub fn export_backup(&self, password: String, settings: BackupSettings) -> Result<String> {
+        self.keyring.export_backup(&password, settings)
+    }
+
+    pub fn import_backup(&self, bundle: String, password: String) -> Result<BackupRestore> {
+        self.keyring.import_backup(&bundle, &password)
+    }
+
+    /// Confirm the vault password before exporting mnemonics or keys
+    pub fn authorize_sensitive(&self, password: String) -> Result<()> {
+        self.keyring.authorize_sensitive(&password)
+    }
+
+    /// Lock the vault and revoke export authorization
+    pub fn lock_all(&self) -> Result<()> {
+        self.keyring.lock_all()
+    }
+
+    /// Loosening either limit needs a recent `authorize_sensitive`
+    pub fn set_session_policy(&self, policy: SessionPolicy) -> Result<()> {
+        self.keyring.set_session_policy(policy)
+    }
+
+    pub fn session_status(&self) -> Result<SessionStatus> {
+        self.keyring.session_status()
+    }
+
+    /// Lock if idle past the timeout; call periodically. Returns whether it locked.
+    pub fn enforce_idle_timeout(&self) -> Result<bool> {
+        self.keyring.enforce_idle_timeout()
+    }
+
+    /// Limits checked before every transaction, user operation or typed data
+    /// is signed.
+    /// Needs a recent `authorize_sensitive`.
+    pub fn set_spending_policy(&self, policy_json: String) -> Result<()> {
+        self.keyring.set_spending_policy(&policy_json)
+    }
+
+    pub fn clear_spending_policy(&self) -> Result<()> {
+        self.keyring.clear_spending_policy()
+    }
+
+    pub fn spending_policy(&self) -> Result<Option<String>> {
+        self.keyring.spending_policy()
+    }
+
+    /// Keep the signing audit log in a JSON lines file, continuing any
+    /// entries already in it. Needs an unlocked vault, which keeps the key
+    /// entries are hashed with. Call before signing anything.
+    pub fn open_audit_log(&self, path: String) -> Result<()> {
+        self.keyring.open_audit_log(path)
+    }
+
+    pub fn verify_audit_log(&self) -> Result<AuditVerification> {
+        self.keyring.verify_audit_log()
+    }
+
+    /// Check an exported log's hash chain. Entries from a log file are keyed
+    /// with a secret in the vault, so this needs it unlocked.
+    pub fn verify_audit_export(&self, jsonl: String) -> Result<AuditVerification> {
+        self.keyring.verify_audit_export(&jsonl)
+    }
+
+    pub fn export_audit_log(&self) -> Result<String> {
+        self.keyring.export_audit_log()
+    }
+
+    /// Envelope asking an offline wallet to sign `payload` for an account of
+    /// this one, typically watch-only. Returns the request JSON.
+    pub fn create_signing_request(
+        &self,
+        wallet_id: String,
+        account_index: u32,
+        payload: SigningPayload,
+        origin: Option<String>,
+    ) -> Result<String> {
+        let request =
+            envelope::create_request(&self.keyring, &wallet_id, account_index, payload, origin)?;
+        Ok(serde_json::to_string(&request)?)
+    }
+
+    /// Read a request envelope, e.g. to show its display hints before signing.
+    /// The hints are worked out from the payload, not taken from the envelope.
+    pub fn parse_signing_request(&self, request: String) -> Result<SigningRequest> {
+        envelope::parse_request(&request)
+    }
+
+    /// Sign a request envelope with the wallet holding its account. Returns
+    /// the response JSON.
+    pub fn sign_signing_request(&self, request: String) -> Result<String> {
+        let request = envelope::parse_request(&request)?;
+        let response = envelope::sign_request(&self.keyring, &request)?;
+        Ok(serde_json::to_string(&response)?)
+    }
+
+    /// Check a response envelope against the request it answers; its
+    /// `signed_tx` is then safe to broadcast
+    pub fn accept_signing_response(
+        &self,
+        request: String,
+        response: String,
+    ) -> Result<SigningResponse> {
+        envelope::accept_response(
+            &envelope::parse_request(&request)?,
+            envelope::parse_response(&response)?,
+        )
     }
 }
 
 // EVM Manager implementation
-pub struct EvmManager;
+pub struct EvmManager {
+    keyring: Keyring,
+}
 
 impl EvmManager {
+    /// Manager signing with the process-wide keyring
     pub fn new() -> Result<Self> {
-        Ok(Self)
+        Ok(Self::with_keyring(Keyring::global()))
+    }
+
+    pub fn with_keyring(keyring: Keyring) -> Self {
+        Self { keyring }
     }
 
     pub fn build_transaction(&self, params: EvmTxParams) -> Result<EvmTransaction> {
@@ -178,9 +666,18 @@ impl EvmManager {
         wallet_id: String,
         account_index: u32,
         params: EvmTxParams,
-    ) -> Result<String> {
-        // This would need access to WalletManager - to be refactored
-        evm::sign_transaction(wallet_id, account_index, params)
+    ) -> Result<EvmTransaction> {
+        evm::sign_transaction(&self.keyring, wallet_id, account_index, params)
+    }
+
+    /// Sign with a signer other than the keyring, e.g. a hardware device.
+    /// The spending policy and audit log apply all the same.
+    pub fn sign_transaction_with(
+        &self,
+        signer: &dyn Signer,
+        params: EvmTxParams,
+    ) -> Result<EvmTransaction> {
+        evm::sign_transaction_audited(&self.keyring, signer, params)
     }
 
     pub fn sign_message(
@@ -189,7 +686,11 @@ impl EvmManager {
         account_index: u32,
         message: String,
     ) -> Result<String> {
-        evm::sign_message(wallet_id, account_index, message)
+        evm::sign_message(&self.keyring, wallet_id, account_index, message)
+    }
+
+    pub fn sign_message_with(&self, signer: &dyn Signer, message: String) -> Result<String> {
+        evm::sign_message_audited(&self.keyring, signer, message.as_bytes())
     }
 
     pub fn sign_typed_data(
@@ -198,24 +699,179 @@ impl EvmManager {
         account_index: u32,
         typed_data_json: String,
     ) -> Result<String> {
-        evm::sign_typed_data(wallet_id, account_index, typed_data_json)
+        evm::sign_typed_data(&self.keyring, wallet_id, account_index, typed_data_json)
+    }
+
+    pub fn sign_typed_data_with(
+        &self,
+        signer: &dyn Signer,
+        typed_data_json: String,
+    ) -> Result<String> {
+        evm::sign_typed_data_audited(&self.keyring, signer, &typed_data_json)
+    }
+
+    /// Sign with an account whose key is held by a remote signing service
+    pub fn sign_transaction_remote(
+        &self,
+        config: RemoteSignerConfig,
+        params: EvmTxParams,
+    ) -> Result<EvmTransaction> {
+        self.sign_transaction_with(&RemoteSigner::new(config)?, params)
+    }
+
+    pub fn sign_message_remote(
+        &self,
+        config: RemoteSignerConfig,
+        message: String,
+    ) -> Result<String> {
+        self.sign_message_with(&RemoteSigner::new(config)?, message)
+    }
+
+    pub fn sign_typed_data_remote(
+        &self,
+        config: RemoteSignerConfig,
+        typed_data_json: String,
+    ) -> Result<String> {
+        self.sign_typed_data_with(&RemoteSigner::new(config)?, typed_data_json)
+    }
+
+    /// Address of the Ledger account at `derivation_path`, shown on the
+    /// device for the user to compare when `confirm` is set
+    pub fn ledger_address(
+        &self,
+        transport: Box<dyn LedgerTransport>,
+        derivation_path: String,
+        confirm: bool,
+    ) -> Result<String> {
+        let signer = LedgerSigner::new(transport, &derivation_path)?;
+        match confirm {
+            true => signer.confirm_address(),
+            false => signer.address(),
+        }
+    }
+
+    /// Sign with the Ledger account at `derivation_path`; the user approves
+    /// on the device
+    pub fn sign_transaction_ledger(
+        &self,
+        transport: Box<dyn LedgerTransport>,
+        derivation_path: String,
+        params: EvmTxParams,
+    ) -> Result<EvmTransaction> {
+        self.sign_transaction_with(&LedgerSigner::new(transport, &derivation_path)?, params)
+    }
+
+    pub fn sign_message_ledger(
+        &self,
+        transport: Box<dyn LedgerTransport>,
+        derivation_path: String,
+        message: String,
+    ) -> Result<String> {
+        self.sign_message_with(&LedgerSigner::new(transport, &derivation_path)?, message)
+    }
+
+    pub fn sign_typed_data_ledger(
+        &self,
+        transport: Box<dyn LedgerTransport>,
+        derivation_path: String,
+        typed_data_json: String,
+    ) -> Result<String> {
+        self.sign_typed_data_with(
+            &LedgerSigner::new(transport, &derivation_path)?,
+            typed_data_json,
+        )
+    }
+
+    pub fn hash_typed_data(&self, typed_data_json: String) -> Result<String> {
+        evm::hash_typed_data(typed_data_json)
     }
 
     pub fn recover_signer(&self, message: String, signature: String) -> Result<String> {
         evm::recover_signer(message, signature)
     }
 
+    pub fn recover_digest_signer(&self, digest: String, signature: String) -> Result<String> {
+        evm::recover_digest_signer(digest, signature)
+    }
+
+    pub fn verify_message(
+        &self,
+        address: String,
+        message: String,
+        signature: String,
+    ) -> Result<bool> {
+        evm::verify_message(address, message, signature)
+    }
+
     pub fn estimate_gas(&self, params: EvmTxParams, rpc_url: String) -> Result<GasEstimate> {
         evm::estimate_gas(params, rpc_url)
     }
+
+    /// Send a signed transaction, e.g. from an offline signing response
+    pub fn broadcast_transaction(&self, signed_tx: String, rpc_url: String) -> Result<String> {
+        evm::broadcast_transaction(signed_tx, rpc_url)
+    }
+
+    /// ERC-4527 request a QR signer needs to answer a signing request
+    /// envelope; show it with a [`UrEncoder`]. Requests from an xpub watch
+    /// wallet need the xpub's `account_path`, e.g. `m/44'/60'/0'`.
+    pub fn eth_sign_request_for(
+        &self,
+        request: String,
+        account_path: Option<String>,
+    ) -> Result<EthSignRequest> {
+        ur::registry::request_from_envelope(
+            &self.keyring,
+            &envelope::parse_request(&request)?,
+            account_path.as_deref(),
+        )
+    }
+
+    /// Response to a signing request envelope from a QR signer's answer,
+    /// checked as [`WalletManager::accept_signing_response`] checks any
+    pub fn accept_eth_signature(
+        &self,
+        request: String,
+        signature: EthSignature,
+    ) -> Result<SigningResponse> {
+        ur::registry::response_from_signature(&envelope::parse_request(&request)?, &signature)
+    }
+
+    /// Answer a scanned ERC-4527 request as a QR signer, with the keyring
+    /// account at its address
+    pub fn sign_eth_sign_request(&self, request: EthSignRequest) -> Result<EthSignature> {
+        ur::registry::sign(&self.keyring, &request)
+    }
+
+    pub fn encode_eth_sign_request(&self, request: EthSignRequest) -> Result<UrPayload> {
+        ur::registry::encode_sign_request(&request)
+    }
+
+    pub fn decode_eth_sign_request(&self, payload: UrPayload) -> Result<EthSignRequest> {
+        ur::registry::decode_sign_request(&payload)
+    }
+
+    pub fn encode_eth_signature(&self, signature: EthSignature) -> Result<UrPayload> {
+        ur::registry::encode_signature(&signature)
+    }
+
+    pub fn decode_eth_signature(&self, payload: UrPayload) -> Result<EthSignature> {
+        ur::registry::decode_signature(&payload)
+    }
 }
 
 // Account Abstraction Manager
-pub struct AaManager;
+pub struct AaManager {
+    keyring: Keyring,
+}
 
 impl AaManager {
     pub fn new() -> Result<Self> {
-        Ok(Self)
+        Ok(Self::with_keyring(Keyring::global()))
+    }
+
+    pub fn with_keyring(keyring: Keyring) -> Self {
+        Self { keyring }
     }
 
     pub fn create_account(
@@ -237,7 +893,15 @@ impl AaManager {
         account_index: u32,
         params: UserOpParams,
     ) -> Result<String> {
-        aa::sign_user_operation(wallet_id, account_index, params)
+        aa::sign_user_operation(&self.keyring, wallet_id, account_index, params)
+    }
+
+    pub fn sign_user_operation_with(
+        &self,
+        signer: &dyn Signer,
+        params: UserOpParams,
+    ) -> Result<String> {
+        aa::sign_user_operation_audited(&self.keyring, signer, params)
     }
 
     pub fn estimate_user_op_gas(
@@ -254,11 +918,17 @@ impl AaManager {
 }
 
 // TRON Manager
-pub struct TronManager;
+pub struct TronManager {
+    keyring: Keyring,
+}
 
 impl TronManager {
     pub fn new() -> Result<Self> {
-        Ok(Self)
+        Ok(Self::with_keyring(Keyring::global()))
+    }
+
+    pub fn with_keyring(keyring: Keyring) -> Self {
+        Self { keyring }
     }
 
     pub fn build_transaction(&self, params: TronTxParams) -> Result<TronTransaction> {
@@ -270,8 +940,16 @@ impl TronManager {
         wallet_id: String,
         account_index: u32,
         params: TronTxParams,
-    ) -> Result<String> {
-        tron::sign_transaction(wallet_id, account_index, params)
+    ) -> Result<TronTransaction> {
+        tron::sign_transaction(&self.keyring, wallet_id, account_index, params)
+    }
+
+    pub fn sign_transaction_with(
+        &self,
+        signer: &dyn Signer,
+        params: TronTxParams,
+    ) -> Result<TronTransaction> {
+        tron::sign_transaction_audited(&self.keyring, signer, params)
     }
 
     pub fn sign_message(
@@ -280,12 +958,20 @@ impl TronManager {
         account_index: u32,
         message: String,
     ) -> Result<String> {
-        tron::sign_message(wallet_id, account_index, message)
+        tron::sign_message(&self.keyring, wallet_id, account_index, message)
+    }
+
+    pub fn sign_message_with(&self, signer: &dyn Signer, message: String) -> Result<String> {
+        tron::sign_message_audited(&self.keyring, signer, &message)
     }
 
     pub fn validate_address(&self, address: String) -> Result<bool> {
         tron::validate_address(address)
     }
+
+    pub fn broadcast_transaction(&self, signed_tx: String, node_url: String) -> Result<String> {
+        tron::broadcast_transaction(signed_tx, node_url)
+    }
 }
 
 // RPC Client
@@ -381,4 +1067,40 @@ mod tests {
         assert!(!wallet.id.is_empty());
         assert_eq!(wallet.accounts.len(), 1);
     }
+
+    #[test]
+    fn test_managers_share_keyring() {
+        let keyring = Keyring::new();
+        let wallet_manager = WalletManager::with_keyring(keyring.clone());
+        let evm_manager = EvmManager::with_keyring(keyring.clone());
+        let tron_manager = TronManager::with_keyring(keyring);
+
+        let wallet = wallet_manager.create_wallet(vec![3u8; 16], None).unwrap();
+        let account = &wallet.accounts[0];
+
+        let signature = evm_manager
+            .sign_message(wallet.id.clone(), 0, "hello".to_string())
+            .unwrap();
+        assert_eq!(
+            evm_manager
+                .recover_signer("hello".to_string(), signature)
+                .unwrap(),
+            account.address
+        );
+        assert!(tron_manager
+            .sign_message(wallet.id.clone(), 0, "hello".to_string())
+            .is_err());
+        wallet_manager
+            .derive_chain_account(wallet.id.clone(), Chain::Tron, 0)
+            .unwrap();
+        assert!(tron_manager
+            .sign_message(wallet.id.clone(), 0, "hello".to_string())
+            .is_ok());
+
+        // A manager on another keyring cannot see the wallet
+        let other = EvmManager::with_keyring(Keyring::new());
+        assert!(other
+            .sign_message(wallet.id, 0, "hello".to_string())
+            .is_err());
+    }
 }
@@ -55,7 +55,7 @@ impl NetworkManager {
     }
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct NetworkInfo {
     pub rpc_url: String,
     pub chain_id: u64,
@@ -0,0 +1,1103 @@
+// Spending policies
+// Declarative rules checked against every transaction, user operation and
+// EIP-712 payload before a signature is produced, whichever signer produces it
+
+use crate::{error::*, types::*};
+use crate::{evm, tron};
+use ethers_core::types::U256;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Policy format written by this build
+pub const POLICY_VERSION: u32 = 1;
+
+/// `execute(address,uint256,bytes)`, the call ERC-4337 accounts make on a
+/// user operation's behalf
+const EXECUTE_SELECTOR: [u8; 4] = [0xb6, 0x1d, 0x27, 0xf6];
+/// `executeBatch(address[],bytes[])`, one valueless call per target
+const EXECUTE_BATCH_SELECTOR: [u8; 4] = [0x18, 0xdf, 0xb3, 0xc7];
+/// `executeBatch(address[],uint256[],bytes[])`; an empty value array means none
+const EXECUTE_BATCH_VALUES_SELECTOR: [u8; 4] = [0x47, 0xe1, 0xda, 0x2a];
+/// ERC-20 calls whose first address argument receives tokens or allowance
+const TRANSFER_SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+const APPROVE_SELECTOR: [u8; 4] = [0x09, 0x5e, 0xa7, 0xb3];
+/// `transferFrom(address,address,uint256)`, whose recipient is the second argument
+const TRANSFER_FROM_SELECTOR: [u8; 4] = [0x23, 0xb8, 0x72, 0xdd];
+
+/// A set of rules, e.g.
+///
+/// ```json
+/// { "version": 1, "rules": [
+///   { "id": "daily", "type": "daily_limit", "chain": "Evm", "max_value": "1000000000000000000" },
+///   { "id": "known", "type": "recipient_allowlist", "addresses": ["0x35…35"] } ] }
+/// ```
+///
+/// Every rule must pass. Values are in the chain's smallest unit, decimal or
+/// `0x` hex, and count the native value only: ERC-20 and TRC-20 amounts are
+/// not converted, so token spending is bounded with recipient and method
+/// rules instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SpendingPolicy {
+    pub version: u32,
+    pub rules: Vec<PolicyRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PolicyRule {
+    pub id: String,
+    /// Signing accounts the rule applies to; all when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<String>,
+    /// Chain the rule applies to; all when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<Chain>,
+    #[serde(flatten)]
+    pub kind: RuleKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum RuleKind {
+    /// Most native value a single transaction may move; token amounts count as 0
+    TransactionLimit {
+        max_value: String,
+    },
+    /// Most native value an account may move per UTC day; token amounts
+    /// count as 0
+    DailyLimit {
+        max_value: String,
+    },
+    /// Only these recipients, including ERC-20 transfer and approval targets
+    RecipientAllowlist {
+        addresses: Vec<String>,
+    },
+    RecipientDenylist {
+        addresses: Vec<String>,
+    },
+    /// Calls to `contracts` (any contract when empty) may only use these
+    /// 4-byte selectors; contract creation is refused
+    ContractMethods {
+        selectors: Vec<String>,
+        #[serde(default)]
+        contracts: Vec<String>,
+    },
+    /// Only these chains and EVM chain ids; an empty list allows any
+    ChainAllowlist {
+        #[serde(default)]
+        chains: Vec<Chain>,
+        #[serde(default)]
+        chain_ids: Vec<u64>,
+    },
+}
+
+impl RuleKind {
+    fn name(&self) -> &'static str {
+        match self {
+            RuleKind::TransactionLimit { .. } => "transaction_limit",
+            RuleKind::DailyLimit { .. } => "daily_limit",
+            RuleKind::RecipientAllowlist { .. } => "recipient_allowlist",
+            RuleKind::RecipientDenylist { .. } => "recipient_denylist",
+            RuleKind::ContractMethods { .. } => "contract_methods",
+            RuleKind::ChainAllowlist { .. } => "chain_allowlist",
+        }
+    }
+}
+
+impl SpendingPolicy {
+    /// Parse and validate policy JSON
+    pub fn parse(json: &str) -> Result<Self> {
+        let policy: Self = serde_json::from_str(json).map_err(|_| CoreError::InvalidPolicy)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.version == 0 || self.version > POLICY_VERSION {
+            return Err(CoreError::InvalidPolicy);
+        }
+
+        let mut ids = std::collections::HashSet::new();
+        for rule in &self.rules {
+            if rule.id.is_empty() || !ids.insert(rule.id.as_str()) {
+                return Err(CoreError::InvalidPolicy);
+            }
+            let mut addresses: Vec<&String> = rule.accounts.iter().collect();
+            match &rule.kind {
+                RuleKind::TransactionLimit { max_value } | RuleKind::DailyLimit { max_value } => {
+                    parse_value(max_value)?;
+                }
+                RuleKind::RecipientAllowlist { addresses: list }
+                | RuleKind::RecipientDenylist { addresses: list } => addresses.extend(list),
+                RuleKind::ContractMethods {
+                    selectors,
+                    contracts,
+                } => {
+                    for selector in selectors {
+                        parse_selector(selector)?;
+                    }
+                    addresses.extend(contracts);
+                }
+                RuleKind::ChainAllowlist { .. } => {}
+            }
+            for address in addresses {
+                if normalize(address).is_empty() {
+                    return Err(CoreError::InvalidPolicy);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a signature would authorize, in the terms rules are written in
+#[derive(Debug, Clone)]
+pub(crate) struct SpendRequest {
+    pub chain: Chain,
+    /// None where the signed payload does not commit to one
+    pub chain_id: Option<u64>,
+    /// Account producing the signature
+    pub from: String,
+    /// One per call made, in order; a batched user operation makes several
+    pub calls: Vec<SpendCall>,
+    /// Everyone who ends up with value or allowance
+    pub recipients: Vec<String>,
+    /// Set, describing the payload, when the policy cannot tell what it
+    /// authorizes; every rule that applies then refuses it
+    pub unreadable: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SpendCall {
+    /// Called contract or plain recipient; None for contract creation
+    pub to: Option<String>,
+    pub value: U256,
+    pub data: Vec<u8>,
+}
+
+impl SpendRequest {
+    pub fn evm(from: &str, params: &EvmTxParams) -> Result<Self> {
+        let to = match params.to.as_str() {
+            "" | "0x" => None,
+            to => Some(to.to_string()),
+        };
+        let data = evm::parse_data(params.data.as_deref())?.to_vec();
+        Ok(Self {
+            chain: Chain::Evm,
+            chain_id: Some(params.chain_id),
+            from: from.to_string(),
+            recipients: evm_recipients(to.as_deref(), &data),
+            calls: vec![SpendCall {
+                to,
+                value: evm::parse_quantity(&params.value)?,
+                data,
+            }],
+            unreadable: None,
+        })
+    }
+
+    /// A contract call signs no `to`, so the TRC-20 recipient comes from the
+    /// call data like on EVM chains
+    pub fn tron(from: &str, params: &TronTxParams) -> Result<Self> {
+        let value = u64::try_from(params.amount).map_err(|_| CoreError::InvalidTransaction)?;
+        let (to, data) = match &params.contract_address {
+            Some(contract) => (
+                contract.clone(),
+                evm::parse_data(params.data.as_deref())?.to_vec(),
+            ),
+            None => (params.to.clone(), Vec::new()),
+        };
+        let mut recipients = vec![to.clone()];
+        if let Some(recipient) = token_recipient(&data) {
+            recipients.push(tron::address_from_hex(&recipient)?);
+        }
+        Ok(Self {
+            chain: Chain::Tron,
+            chain_id: None,
+            from: from.to_string(),
+            calls: vec![SpendCall {
+                to: Some(to),
+                value: value.into(),
+                data,
+            }],
+            recipients,
+            unreadable: None,
+        })
+    }
+
+    /// The account's `execute` and `executeBatch` calls are unpacked so rules
+    /// see the real targets; any other call data is treated as a call on the
+    /// account itself
+    pub fn user_op(from: &str, params: &UserOpParams) -> Result<Self> {
+        let call_data = evm::parse_data(Some(&params.call_data))?.to_vec();
+        let calls = decode_account_calls(&call_data).unwrap_or_else(|| {
+            vec![SpendCall {
+                to: Some(params.sender.clone()),
+                value: U256::zero(),
+                data: call_data,
+            }]
+        });
+        Ok(Self {
+            chain: Chain::Evm,
+            chain_id: params.chain_id,
+            from: from.to_string(),
+            recipients: calls
+                .iter()
+                .flat_map(|call| evm_recipients(call.to.as_deref(), &call.data))
+                .collect(),
+            calls,
+            unreadable: None,
+        })
+    }
+
+    /// EIP-2612 and DAI permits and Permit2's permits grant token allowances
+    /// without a transaction, so they are checked as the `approve` calls they
+    /// amount to. Any other typed data is unreadable to the policy.
+    pub fn typed_data(from: &str, typed_data_json: &str) -> Self {
+        let typed_data: Value = serde_json::from_str(typed_data_json).unwrap_or_default();
+        let primary_type = typed_data["primaryType"].as_str().unwrap_or_default();
+        let chain_id = json_quantity(&typed_data["domain"]["chainId"])
+            .filter(|id| id.bits() <= 64)
+            .map(|id| id.as_u64());
+
+        let calls = primary_type
+            .starts_with("Permit")
+            .then(|| permit_approvals(&typed_data["domain"], &typed_data["message"]))
+            .flatten();
+        let unreadable = calls
+            .is_none()
+            .then(|| format!("typed data {:?}", primary_type));
+        let calls = calls.unwrap_or_default();
+        Self {
+            chain: Chain::Evm,
+            chain_id,
+            from: from.to_string(),
+            recipients: calls
+                .iter()
+                .flat_map(|call| evm_recipients(call.to.as_deref(), &call.data))
+                .collect(),
+            calls,
+            unreadable,
+        }
+    }
+
+    /// Native value moved by all calls together
+    pub fn value(&self) -> U256 {
+        self.calls
+            .iter()
+            .fold(U256::zero(), |total, call| total.saturating_add(call.value))
+    }
+}
+
+/// The called address plus the recipient of an ERC-20 transfer or approval
+fn evm_recipients(to: Option<&str>, data: &[u8]) -> Vec<String> {
+    let mut recipients: Vec<String> = to.into_iter().map(str::to_string).collect();
+    recipients.extend(token_recipient(data));
+    recipients
+}
+
+/// Hex address receiving tokens or allowance in a token call
+fn token_recipient(data: &[u8]) -> Option<String> {
+    let argument = match data.get(..4)? {
+        s if s == TRANSFER_SELECTOR || s == APPROVE_SELECTOR => 0,
+        s if s == TRANSFER_FROM_SELECTOR => 1,
+        _ => return None,
+    };
+    address_argument(data, argument)
+}
+
+/// What the vault keeps of a policy engine, so limits survive a restart
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PolicyState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<SpendingPolicy>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spent: Vec<DailySpend>,
+}
+
+/// Value an account moved on one UTC day under a daily limit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct DailySpend {
+    pub rule_id: String,
+    pub account: String,
+    pub day: u64,
+    pub value: U256,
+}
+
+/// Active policy plus what each account has spent under its daily limits
+#[derive(Default)]
+pub(crate) struct PolicyEngine {
+    policy: Option<SpendingPolicy>,
+    /// (rule id, account) to (UTC day, value spent that day)
+    spent: HashMap<(String, String), (u64, U256)>,
+}
+
+impl PolicyEngine {
+    /// Rebuild an engine from vault state, re-validating its policy
+    pub fn restore(state: PolicyState) -> Result<Self> {
+        if let Some(policy) = &state.policy {
+            policy.validate()?;
+        }
+        let spent = state
+            .spent
+            .into_iter()
+            .map(|s| ((s.rule_id, normalize(&s.account)), (s.day, s.value)))
+            .collect();
+        Ok(Self {
+            policy: state.policy,
+            spent,
+        })
+    }
+
+    pub fn state(&self) -> PolicyState {
+        let mut spent: Vec<DailySpend> = self
+            .spent
+            .iter()
+            .map(|((rule_id, account), (day, value))| DailySpend {
+                rule_id: rule_id.clone(),
+                account: account.clone(),
+                day: *day,
+                value: *value,
+            })
+            .collect();
+        spent.sort_by(|a, b| (&a.rule_id, &a.account).cmp(&(&b.rule_id, &b.account)));
+        PolicyState {
+            policy: self.policy.clone(),
+            spent,
+        }
+    }
+
+    pub fn policy(&self) -> Option<&SpendingPolicy> {
+        self.policy.as_ref()
+    }
+
+    /// Replace the policy. Spending so far still counts against a daily
+    /// limit that keeps its id.
+    pub fn set_policy(&mut self, policy: Option<SpendingPolicy>) {
+        self.policy = policy;
+    }
+
+    /// The first rule `request` breaks, if any
+    pub fn check(&self, request: &SpendRequest, day: u64) -> Result<()> {
+        let Some(policy) = &self.policy else {
+            return Ok(());
+        };
+        for rule in policy.rules.iter().filter(|rule| applies(rule, request)) {
+            if let Some(reason) = self.violation(rule, request, day)? {
+                return Err(CoreError::PolicyViolation(PolicyViolation {
+                    rule_id: rule.id.clone(),
+                    rule_type: rule.kind.name().to_string(),
+                    reason,
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Count a signed request against the daily limits it falls under
+    pub fn record(&mut self, request: &SpendRequest, day: u64) {
+        let Some(policy) = &self.policy else {
+            return;
+        };
+        for rule in policy.rules.iter().filter(|rule| applies(rule, request)) {
+            if let RuleKind::DailyLimit { .. } = rule.kind {
+                let key = (rule.id.clone(), normalize(&request.from));
+                let entry = self.spent.entry(key).or_insert((day, U256::zero()));
+                if entry.0 != day {
+                    *entry = (day, U256::zero());
+                }
+                entry.1 = entry.1.saturating_add(request.value());
+            }
+        }
+    }
+
+    /// Check `request` and count it in one step, so concurrent requests
+    /// cannot both fit under the same limit
+    pub fn reserve(&mut self, request: &SpendRequest, day: u64) -> Result<()> {
+        self.check(request, day)?;
+        self.record(request, day);
+        Ok(())
+    }
+
+    /// Undo `reserve` for a request that was never signed
+    pub fn release(&mut self, request: &SpendRequest, day: u64) {
+        let Some(policy) = &self.policy else {
+            return;
+        };
+        for rule in policy.rules.iter().filter(|rule| applies(rule, request)) {
+            let key = (rule.id.clone(), normalize(&request.from));
+            if let Some((spent_day, spent)) = self.spent.get_mut(&key) {
+                if *spent_day == day {
+                    *spent = spent.saturating_sub(request.value());
+                }
+            }
+        }
+    }
+
+    fn spent_today(&self, rule_id: &str, account: &str, day: u64) -> U256 {
+        match self.spent.get(&(rule_id.to_string(), normalize(account))) {
+            Some((spent_day, value)) if *spent_day == day => *value,
+            _ => U256::zero(),
+        }
+    }
+
+    fn violation(
+        &self,
+        rule: &PolicyRule,
+        request: &SpendRequest,
+        day: u64,
+    ) -> Result<Option<String>> {
+        if let Some(payload) = &request.unreadable {
+            return Ok(Some(format!(
+                "{} cannot be checked against this rule",
+                payload
+            )));
+        }
+        let reason = match &rule.kind {
+            RuleKind::TransactionLimit { max_value } => {
+                let max = parse_value(max_value)?;
+                let value = request.value();
+                (value > max).then(|| format!("value {} exceeds the limit of {}", value, max))
+            }
+            RuleKind::DailyLimit { max_value } => {
+                let max = parse_value(max_value)?;
+                let value = request.value();
+                let spent = self.spent_today(&rule.id, &request.from, day);
+                (spent.saturating_add(value) > max).then(|| {
+                    format!(
+                        "value {} with {} already spent today exceeds the daily limit of {}",
+                        value, spent, max
+                    )
+                })
+            }
+            RuleKind::RecipientAllowlist { addresses } => request
+                .recipients
+                .iter()
+                .find(|recipient| !contains(addresses, recipient))
+                .map(|recipient| format!("recipient {} is not on the allowlist", recipient)),
+            RuleKind::RecipientDenylist { addresses } => request
+                .recipients
+                .iter()
+                .find(|recipient| contains(addresses, recipient))
+                .map(|recipient| format!("recipient {} is on the denylist", recipient)),
+            RuleKind::ContractMethods {
+                selectors,
+                contracts,
+            } => {
+                let mut violation = None;
+                for call in &request.calls {
+                    violation = contract_call_violation(call, selectors, contracts)?;
+                    if violation.is_some() {
+                        break;
+                    }
+                }
+                violation
+            }
+            RuleKind::ChainAllowlist { chains, chain_ids } => {
+                if !chains.is_empty() && !chains.contains(&request.chain) {
+                    Some(format!("chain {:?} is not allowed", request.chain))
+                } else if chain_ids.is_empty() || request.chain != Chain::Evm {
+                    None
+                } else {
+                    match request.chain_id {
+                        Some(id) if chain_ids.contains(&id) => None,
+                        Some(id) => Some(format!("chain id {} is not allowed", id)),
+                        None => Some("the payload does not commit to a chain id".to_string()),
+                    }
+                }
+            }
+        };
+        Ok(reason)
+    }
+}
+
+fn contract_call_violation(
+    call: &SpendCall,
+    selectors: &[String],
+    contracts: &[String],
+) -> Result<Option<String>> {
+    let Some(to) = &call.to else {
+        return Ok(Some("contract creation is not allowed".to_string()));
+    };
+    if call.data.is_empty() || (!contracts.is_empty() && !contains(contracts, to)) {
+        return Ok(None);
+    }
+
+    let Some(selector) = call.data.get(..4) else {
+        return Ok(Some(format!("call data to {} has no method selector", to)));
+    };
+    for allowed in selectors {
+        if parse_selector(allowed)? == selector {
+            return Ok(None);
+        }
+    }
+    Ok(Some(format!(
+        "method 0x{} is not allowed on {}",
+        hex::encode(selector),
+        to
+    )))
+}
+
+fn applies(rule: &PolicyRule, request: &SpendRequest) -> bool {
+    rule.chain.is_none_or(|chain| chain == request.chain)
+        && (rule.accounts.is_empty() || contains(&rule.accounts, &request.from))
+}
+
+fn contains(list: &[String], address: &str) -> bool {
+    let address = normalize(address);
+    list.iter().any(|entry| normalize(entry) == address)
+}
+
+/// EVM hex addresses compare case-insensitively, TRON base58 ones exactly
+fn normalize(address: &str) -> String {
+    let address = address.trim();
+    match address.starts_with("0x") || address.starts_with("0X") {
+        true => address.to_lowercase(),
+        false => address.to_string(),
+    }
+}
+
+fn parse_value(value: &str) -> Result<U256> {
+    evm::parse_quantity(value).map_err(|_| CoreError::InvalidPolicy)
+}
+
+fn parse_selector(selector: &str) -> Result<[u8; 4]> {
+    hex::decode(selector.trim_start_matches("0x"))
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(CoreError::InvalidPolicy)
+}
+
+/// The address in ABI argument `index` of a call
+fn address_argument(data: &[u8], index: usize) -> Option<String> {
+    let word = data.get(4 + index * 32..4 + (index + 1) * 32)?;
+    Some(format!("0x{}", hex::encode(&word[12..])))
+}
+
+/// `approve(spender, amount)` on each token a permit message covers: the
+/// signing domain's own token for EIP-2612 and DAI (`allowed` meaning an
+/// unlimited allowance), the listed ones for Permit2
+fn permit_approvals(domain: &Value, message: &Value) -> Option<Vec<SpendCall>> {
+    let spender = message["spender"].as_str()?;
+    let grants = match message.get("details").or_else(|| message.get("permitted")) {
+        Some(Value::Array(grants)) => grants.iter().collect(),
+        Some(grant) => vec![grant],
+        None => {
+            let amount = match message.get("allowed") {
+                Some(allowed) => match allowed.as_bool()? {
+                    true => U256::MAX,
+                    false => U256::zero(),
+                },
+                None => json_quantity(message.get("value")?)?,
+            };
+            return Some(vec![approval(
+                domain["verifyingContract"].as_str()?,
+                spender,
+                amount,
+            )?]);
+        }
+    };
+    grants
+        .into_iter()
+        .map(|grant| {
+            let amount = json_quantity(&grant["amount"])?;
+            approval(grant["token"].as_str()?, spender, amount)
+        })
+        .collect()
+}
+
+fn approval(token: &str, spender: &str, amount: U256) -> Option<SpendCall> {
+    let spender = hex::decode(spender.trim_start_matches("0x"))
+        .ok()
+        .filter(|spender| spender.len() == 20)?;
+    let mut data = APPROVE_SELECTOR.to_vec();
+    data.extend_from_slice(&[0; 12]);
+    data.extend_from_slice(&spender);
+    let mut word = [0u8; 32];
+    amount.to_big_endian(&mut word);
+    data.extend_from_slice(&word);
+    Some(SpendCall {
+        to: Some(token.to_string()),
+        value: U256::zero(),
+        data,
+    })
+}
+
+/// A typed data integer, which JSON carries as a number or a string
+fn json_quantity(value: &Value) -> Option<U256> {
+    match value {
+        Value::Number(number) => number.as_u64().map(U256::from),
+        Value::String(value) => evm::parse_quantity(value).ok(),
+        _ => None,
+    }
+}
+
+/// The calls an account's `execute` or `executeBatch` makes
+fn decode_account_calls(call_data: &[u8]) -> Option<Vec<SpendCall>> {
+    let selector = call_data.get(..4)?;
+    let args = &call_data[4..];
+    if selector == EXECUTE_SELECTOR {
+        return Some(vec![SpendCall {
+            to: Some(address_argument(call_data, 0)?),
+            value: U256::from_big_endian(args.get(32..64)?),
+            data: abi_bytes(args, 64)?,
+        }]);
+    }
+
+    let (targets, values, data) = if selector == EXECUTE_BATCH_SELECTOR {
+        (abi_words(args, 0)?, Vec::new(), abi_bytes_array(args, 32)?)
+    } else if selector == EXECUTE_BATCH_VALUES_SELECTOR {
+        (
+            abi_words(args, 0)?,
+            abi_words(args, 32)?,
+            abi_bytes_array(args, 64)?,
+        )
+    } else {
+        return None;
+    };
+    if data.len() != targets.len() || !(values.is_empty() || values.len() == targets.len()) {
+        return None;
+    }
+    let calls = targets
+        .iter()
+        .zip(data)
+        .enumerate()
+        .map(|(i, (target, data))| SpendCall {
+            to: Some(format!("0x{}", hex::encode(&target[12..]))),
+            value: values
+                .get(i)
+                .map_or(U256::zero(), |value| U256::from_big_endian(value)),
+            data,
+        })
+        .collect();
+    Some(calls)
+}
+
+/// ABI word at byte `at` of `args`, read as an offset or length
+fn abi_usize(args: &[u8], at: usize) -> Option<usize> {
+    let word = U256::from_big_endian(args.get(at..at.checked_add(32)?)?);
+    if word.bits() > 64 {
+        return None;
+    }
+    usize::try_from(word.low_u64()).ok()
+}
+
+/// (length, content start) of the dynamic argument whose offset is at `head`
+fn abi_dynamic(args: &[u8], head: usize) -> Option<(usize, usize)> {
+    let offset = abi_usize(args, head)?;
+    Some((abi_usize(args, offset)?, offset.checked_add(32)?))
+}
+
+fn abi_bytes(args: &[u8], head: usize) -> Option<Vec<u8>> {
+    let (length, start) = abi_dynamic(args, head)?;
+    Some(args.get(start..start.checked_add(length)?)?.to_vec())
+}
+
+/// A static array of 32-byte words
+fn abi_words(args: &[u8], head: usize) -> Option<Vec<&[u8]>> {
+    let (length, start) = abi_dynamic(args, head)?;
+    (0..length)
+        .map(|i| args.get(start + i * 32..start + (i + 1) * 32))
+        .collect()
+}
+
+/// A `bytes[]`, whose element offsets count from the first of them
+fn abi_bytes_array(args: &[u8], head: usize) -> Option<Vec<Vec<u8>>> {
+    let (length, start) = abi_dynamic(args, head)?;
+    let elements = args.get(start..)?;
+    (0..length).map(|i| abi_bytes(elements, i * 32)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FROM: &str = "0x9858EfFD232B4033E47d90003D41EC34EcaEda94";
+    const FRIEND: &str = "0x3535353535353535353535353535353535353535";
+    const TOKEN: &str = "0x4646464646464646464646464646464646464646";
+
+    fn engine(rules: serde_json::Value) -> PolicyEngine {
+        let json = serde_json::json!({ "version": 1, "rules": rules }).to_string();
+        let mut engine = PolicyEngine::default();
+        engine.set_policy(Some(SpendingPolicy::parse(&json).unwrap()));
+        engine
+    }
+
+    fn transfer(to: &str, value: &str, data: Option<String>) -> SpendRequest {
+        let params = EvmTxParams {
+            to: to.to_string(),
+            value: value.to_string(),
+            data,
+            chain_id: 65001,
+            ..Default::default()
+        };
+        SpendRequest::evm(FROM, &params).unwrap()
+    }
+
+    fn erc20_transfer(recipient: &str) -> String {
+        format!(
+            "0xa9059cbb{:0>64}{:064x}",
+            recipient.trim_start_matches("0x"),
+            1000
+        )
+    }
+
+    fn violated_rule(result: Result<()>) -> String {
+        match result {
+            Err(CoreError::PolicyViolation(violation)) => violation.rule_id,
+            other => panic!("expected a violation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_policies() {
+        let parse = |json: serde_json::Value| SpendingPolicy::parse(&json.to_string());
+        assert!(parse(serde_json::json!({"version": 1, "rules": []})).is_ok());
+        for bad in [
+            serde_json::json!({"version": 2, "rules": []}),
+            serde_json::json!({"rules": []}),
+            serde_json::json!({"version": 1, "rules": [{"id": "a", "type": "nope"}]}),
+            serde_json::json!({"version": 1, "rules": [
+                {"id": "a", "type": "transaction_limit", "max_value": "ten"}]}),
+            serde_json::json!({"version": 1, "rules": [
+                {"id": "a", "type": "contract_methods", "selectors": ["0x1234"]}]}),
+            serde_json::json!({"version": 1, "rules": [
+                {"id": "a", "type": "chain_allowlist"},
+                {"id": "a", "type": "chain_allowlist"}]}),
+        ] {
+            assert!(matches!(parse(bad), Err(CoreError::InvalidPolicy)));
+        }
+    }
+
+    #[test]
+    fn test_value_limits() {
+        let mut engine = engine(serde_json::json!([
+            {"id": "per-tx", "type": "transaction_limit", "max_value": "600"},
+            {"id": "daily", "type": "daily_limit", "max_value": "0x3e8"},
+        ]));
+
+        assert_eq!(
+            violated_rule(engine.check(&transfer(FRIEND, "601", None), 10)),
+            "per-tx"
+        );
+        let request = transfer(FRIEND, "600", None);
+        engine.check(&request, 10).unwrap();
+        engine.record(&request, 10);
+        assert_eq!(violated_rule(engine.check(&request, 10)), "daily");
+        engine.check(&transfer(FRIEND, "400", None), 10).unwrap();
+
+        // A reservation holds its share until released
+        let rest = transfer(FRIEND, "400", None);
+        engine.reserve(&rest, 10).unwrap();
+        assert_eq!(violated_rule(engine.reserve(&rest, 10)), "daily");
+        engine.release(&rest, 10);
+        engine.reserve(&rest, 10).unwrap();
+
+        // Totals survive a round trip through the vault state
+        let json = serde_json::to_string(&engine.state()).unwrap();
+        let restored = PolicyEngine::restore(serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(restored.state(), engine.state());
+        assert_eq!(
+            violated_rule(restored.check(&transfer(FRIEND, "1", None), 10)),
+            "daily"
+        );
+
+        // A new UTC day starts from zero
+        engine.check(&request, 11).unwrap();
+    }
+
+    #[test]
+    fn test_rules_scope_to_accounts_and_chains() {
+        let engine = engine(serde_json::json!([
+            {"id": "other", "type": "transaction_limit", "max_value": "0",
+             "accounts": ["0x0000000000000000000000000000000000000001"]},
+            {"id": "tron", "type": "transaction_limit", "max_value": "0", "chain": "Tron"},
+            {"id": "mine", "type": "transaction_limit", "max_value": "5",
+             "accounts": [FROM.to_lowercase()]},
+        ]));
+        assert_eq!(
+            violated_rule(engine.check(&transfer(FRIEND, "6", None), 0)),
+            "mine"
+        );
+        engine.check(&transfer(FRIEND, "5", None), 0).unwrap();
+    }
+
+    #[test]
+    fn test_recipient_lists_see_token_transfers() {
+        let allowlist = engine(serde_json::json!([
+            {"id": "known", "type": "recipient_allowlist", "addresses": [FRIEND, TOKEN]},
+        ]));
+        allowlist.check(&transfer(FRIEND, "1", None), 0).unwrap();
+        allowlist
+            .check(&transfer(TOKEN, "0", Some(erc20_transfer(FRIEND))), 0)
+            .unwrap();
+        let stranger = "0x5757575757575757575757575757575757575757";
+        assert_eq!(
+            violated_rule(
+                allowlist.check(&transfer(TOKEN, "0", Some(erc20_transfer(stranger))), 0)
+            ),
+            "known"
+        );
+
+        // Hex addresses match regardless of checksum casing
+        let denylist = engine(serde_json::json!([
+            {"id": "blocked", "type": "recipient_denylist", "addresses": [FROM.to_lowercase()]},
+        ]));
+        assert_eq!(
+            violated_rule(denylist.check(&transfer(FROM, "1", None), 0)),
+            "blocked"
+        );
+        denylist.check(&transfer(FRIEND, "1", None), 0).unwrap();
+    }
+
+    #[test]
+    fn test_trc20_recipient_comes_from_call_data() {
+        let contract = tron::address_from_hex(TOKEN).unwrap();
+        let friend = tron::address_from_hex(FRIEND).unwrap();
+        let allowlist = engine(serde_json::json!([
+            {"id": "known", "type": "recipient_allowlist", "addresses": [contract, friend]},
+        ]));
+        let trc20 = |recipient: &str| TronTxParams {
+            from: String::new(),
+            to: friend.clone(),
+            amount: 0,
+            contract_address: Some(contract.clone()),
+            data: Some(erc20_transfer(recipient)),
+            fee_limit: 0,
+            ref_block_id: format!("{:064x}", 1),
+            expiration: 60000,
+            timestamp: 1,
+        };
+        let check = |recipient: &str| {
+            allowlist.check(&SpendRequest::tron(&friend, &trc20(recipient)).unwrap(), 0)
+        };
+        check(FRIEND).unwrap();
+
+        // `to` is not signed for contract calls, so naming a friend there
+        // does not cover a transfer to someone else
+        let stranger = "0x5757575757575757575757575757575757575757";
+        assert_eq!(violated_rule(check(stranger)), "known");
+    }
+
+    fn typed_data(primary_type: &str, message: serde_json::Value) -> SpendRequest {
+        let typed_data = serde_json::json!({
+            "primaryType": primary_type,
+            "domain": {"name": "Token", "chainId": "65001", "verifyingContract": TOKEN},
+            "message": message,
+        });
+        SpendRequest::typed_data(FROM, &typed_data.to_string())
+    }
+
+    #[test]
+    fn test_permits_are_checked_as_approvals() {
+        let stranger = "0x5757575757575757575757575757575757575757";
+        let allowlist = engine(serde_json::json!([
+            {"id": "known", "type": "recipient_allowlist", "addresses": [FRIEND, TOKEN]},
+            {"id": "chains", "type": "chain_allowlist", "chain_ids": [65001]},
+        ]));
+        let permit = |spender: &str| {
+            typed_data(
+                "Permit",
+                serde_json::json!({"owner": FROM, "spender": spender, "value": 1000,
+                                   "nonce": 0, "deadline": 1}),
+            )
+        };
+        allowlist.check(&permit(FRIEND), 0).unwrap();
+        assert_eq!(
+            violated_rule(allowlist.check(&permit(stranger), 0)),
+            "known"
+        );
+
+        let mut other_chain = permit(FRIEND);
+        other_chain.chain_id = Some(1);
+        assert_eq!(violated_rule(allowlist.check(&other_chain, 0)), "chains");
+
+        // DAI's permit grants everything or nothing
+        let dai = typed_data(
+            "Permit",
+            serde_json::json!({"holder": FROM, "spender": stranger, "nonce": 0,
+                               "expiry": 0, "allowed": true}),
+        );
+        assert_eq!(dai.calls[0].data[36..], [0xff; 32]);
+        assert_eq!(violated_rule(allowlist.check(&dai, 0)), "known");
+
+        // Permit2 names each token; any of them may be off the list
+        let permit2 = typed_data(
+            "PermitBatch",
+            serde_json::json!({"spender": FRIEND, "sigDeadline": 1, "details": [
+                {"token": TOKEN, "amount": "1", "expiration": 1, "nonce": 0},
+                {"token": stranger, "amount": "1", "expiration": 1, "nonce": 0}]}),
+        );
+        assert_eq!(permit2.calls.len(), 2);
+        assert_eq!(violated_rule(allowlist.check(&permit2, 0)), "known");
+
+        let methods = engine(serde_json::json!([
+            {"id": "methods", "type": "contract_methods", "selectors": ["0xa9059cbb"]},
+        ]));
+        assert_eq!(violated_rule(methods.check(&permit(FRIEND), 0)), "methods");
+    }
+
+    #[test]
+    fn test_unreadable_typed_data_is_refused_by_any_rule() {
+        let mail = typed_data("Mail", serde_json::json!({"contents": "hi"}));
+        assert!(mail.unreadable.is_some());
+        PolicyEngine::default().check(&mail, 0).unwrap();
+
+        let chains = engine(serde_json::json!([
+            {"id": "chains", "type": "chain_allowlist", "chains": ["Evm"]},
+        ]));
+        assert_eq!(violated_rule(chains.check(&mail, 0)), "chains");
+
+        // A permit without a spender is not one the policy understands
+        let broken = typed_data("Permit", serde_json::json!({"value": 1}));
+        assert!(broken.unreadable.is_some());
+
+        // Rules scoped to another chain do not apply
+        let tron_only = engine(serde_json::json!([
+            {"id": "tron", "type": "transaction_limit", "max_value": "0", "chain": "Tron"},
+        ]));
+        tron_only.check(&mail, 0).unwrap();
+    }
+
+    #[test]
+    fn test_contract_methods() {
+        let engine = engine(serde_json::json!([
+            {"id": "methods", "type": "contract_methods", "selectors": ["0xa9059cbb"], "contracts": [TOKEN]},
+        ]));
+        engine
+            .check(&transfer(TOKEN, "0", Some(erc20_transfer(FRIEND))), 0)
+            .unwrap();
+        // Plain transfers and other contracts are out of scope
+        engine.check(&transfer(TOKEN, "1", None), 0).unwrap();
+        engine
+            .check(&transfer(FRIEND, "0", Some("0xdeadbeef".to_string())), 0)
+            .unwrap();
+
+        let approve = format!("0x095ea7b3{:0>64}{:064x}", &FRIEND[2..], 1);
+        let result = engine.check(&transfer(TOKEN, "0", Some(approve)), 0);
+        match result {
+            Err(CoreError::PolicyViolation(violation)) => {
+                assert_eq!(violation.rule_type, "contract_methods");
+                assert!(violation.reason.contains("0x095ea7b3"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(engine
+            .check(&transfer("", "0", Some("0x6080".to_string())), 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_chain_allowlist() {
+        let engine = engine(serde_json::json!([
+            {"id": "chains", "type": "chain_allowlist", "chains": ["Evm"], "chain_ids": [65001]},
+        ]));
+        engine.check(&transfer(FRIEND, "1", None), 0).unwrap();
+
+        let mut other_chain = transfer(FRIEND, "1", None);
+        other_chain.chain_id = Some(1);
+        assert!(engine.check(&other_chain, 0).is_err());
+
+        let tron = TronTxParams {
+            from: String::new(),
+            to: "TM2TmqauSEiRxUPCoW8U8C6WKbW1m5BnCQ".to_string(),
+            amount: 1,
+            contract_address: None,
+            data: None,
+            fee_limit: 0,
+            ref_block_id: format!("{:064x}", 1),
+            expiration: 60000,
+            timestamp: 1,
+        };
+        let request = SpendRequest::tron("TLsV52sRDL79HXGGm9yzwKibb6BeruhUzy", &tron).unwrap();
+        assert_eq!(violated_rule(engine.check(&request, 0)), "chains");
+    }
+
+    fn user_op(call_data: String) -> UserOpParams {
+        UserOpParams {
+            sender: TOKEN.to_string(),
+            nonce: "0".to_string(),
+            init_code: "0x".to_string(),
+            call_data,
+            call_gas_limit: "0".to_string(),
+            verification_gas_limit: "0".to_string(),
+            pre_verification_gas: "0".to_string(),
+            max_fee_per_gas: "0".to_string(),
+            max_priority_fee_per_gas: "0".to_string(),
+            paymaster_and_data: "0x".to_string(),
+            signature: "0x".to_string(),
+            chain_id: Some(65001),
+        }
+    }
+
+    #[test]
+    fn test_user_op_execute_is_unpacked() {
+        // execute(FRIEND, 7, 0x) on the sender account
+        let call_data = format!(
+            "0xb61d27f6{:0>64}{:064x}{:064x}{:064x}",
+            &FRIEND[2..],
+            7,
+            0x60,
+            0
+        );
+        let request = SpendRequest::user_op(FROM, &user_op(call_data)).unwrap();
+        assert_eq!(request.calls.len(), 1);
+        assert_eq!(request.calls[0].to.as_deref(), Some(FRIEND));
+        assert_eq!(request.value(), U256::from(7));
+        assert!(request.calls[0].data.is_empty());
+
+        let request = SpendRequest::user_op(FROM, &user_op("0x12345678".to_string())).unwrap();
+        assert_eq!(request.calls[0].to.as_deref(), Some(TOKEN));
+    }
+
+    #[test]
+    fn test_user_op_batch_checks_every_call() {
+        // executeBatch([FRIEND, TOKEN], [7, 0], [0x, transfer(stranger, 1000)])
+        let stranger = "0x5757575757575757575757575757575757575757";
+        let transfer_data = erc20_transfer(stranger);
+        let call_data = format!(
+            "0x47e1da2a{:064x}{:064x}{:064x}\
+             {:064x}{:0>64}{:0>64}{:064x}{:064x}{:064x}\
+             {:064x}{:064x}{:064x}{:064x}{:064x}{:0<192}",
+            0x60,
+            0xc0,
+            0x120,
+            2,
+            &FRIEND[2..],
+            &TOKEN[2..],
+            2,
+            7,
+            0,
+            2,
+            0x40,
+            0x60,
+            0,
+            68,
+            &transfer_data[2..],
+        );
+        let mut params = user_op(call_data);
+        let request = SpendRequest::user_op(FROM, &params).unwrap();
+        assert_eq!(request.calls.len(), 2);
+        assert_eq!(request.calls[1].to.as_deref(), Some(TOKEN));
+        assert_eq!(request.value(), U256::from(7));
+        assert!(request.recipients.iter().any(|r| r == stranger));
+
+        let allowlist = engine(serde_json::json!([
+            {"id": "known", "type": "recipient_allowlist", "addresses": [FRIEND, TOKEN]},
+        ]));
+        assert_eq!(violated_rule(allowlist.check(&request, 0)), "known");
+        let limit = engine(serde_json::json!([
+            {"id": "per-tx", "type": "transaction_limit", "max_value": "6"},
+        ]));
+        assert_eq!(violated_rule(limit.check(&request, 0)), "per-tx");
+
+        // Chain id rules need the operation to name its chain
+        let chains = engine(serde_json::json!([
+            {"id": "chains", "type": "chain_allowlist", "chain_ids": [65001]},
+        ]));
+        chains.check(&request, 0).unwrap();
+        params.chain_id = None;
+        let request = SpendRequest::user_op(FROM, &params).unwrap();
+        assert_eq!(violated_rule(chains.check(&request, 0)), "chains");
+    }
+}
@@ -0,0 +1,379 @@
+// Remote signing backend
+// Keys stay in a separate signing service reached over JSON-RPC or the
+// Web3Signer REST API; every signature it returns is checked locally against
+// the digest we expect before it is used
+
+use crate::crypto::public_key_to_address;
+use crate::rpc::JsonRpcClient;
+use crate::signer::{EcdsaSignature, Signer};
+use crate::{eip712, evm};
+use crate::{error::*, types::*};
+use ethers_core::types::{transaction::eip2718::TypedTransaction, Address, Bytes};
+use ethers_core::utils::keccak256;
+use secp256k1::PublicKey;
+use serde_json::{json, Value};
+
+/// Signs through a remote service holding the account's key
+pub struct RemoteSigner {
+    client: JsonRpcClient,
+    api: RemoteSignerApi,
+    address: String,
+    public_key: Option<PublicKey>,
+}
+
+impl RemoteSigner {
+    pub fn new(config: RemoteSignerConfig) -> Result<Self> {
+        let public_key = config
+            .public_key
+            .as_deref()
+            .map(parse_public_key)
+            .transpose()?;
+
+        let address = match (&public_key, &config.address) {
+            (Some(key), Some(address)) => {
+                let derived = public_key_to_address(key);
+                if !same_address(&derived, address)? {
+                    return Err(CoreError::InvalidAddress);
+                }
+                derived
+            }
+            (Some(key), None) => public_key_to_address(key),
+            (None, Some(address)) => {
+                evm::parse_address(address)?;
+                address.to_lowercase()
+            }
+            (None, None) => return Err(CoreError::InvalidInput),
+        };
+        // Web3Signer addresses keys by public key
+        if config.api == RemoteSignerApi::Web3Signer && public_key.is_none() {
+            return Err(CoreError::InvalidInput);
+        }
+
+        Ok(Self {
+            client: JsonRpcClient::new(config.url),
+            api: config.api,
+            address,
+            public_key,
+        })
+    }
+
+    /// JSON-RPC call whose result is parsed from the response
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let response = self.client.call(method.to_string(), params.to_string())?;
+        if response.error.is_some() {
+            return Err(CoreError::RpcError);
+        }
+        let result = response.result.ok_or(CoreError::RpcError)?;
+        serde_json::from_str(&result).map_err(|_| CoreError::RpcError)
+    }
+
+    /// Web3Signer signs keccak256 of `data`, so callers pass the preimage
+    fn sign_preimage(&self, data: &[u8]) -> Result<EcdsaSignature> {
+        let public_key = self.public_key.ok_or(CoreError::UnsupportedBySigner)?;
+        let identifier = format!(
+            "0x{}",
+            hex::encode(&public_key.serialize_uncompressed()[1..])
+        );
+        let body = json!({ "data": format!("0x{}", hex::encode(data)) });
+
+        let response = self
+            .client
+            .post(&format!("/api/v1/eth1/sign/{}", identifier), &body)?;
+        // Plain text by default; JSON when the service is set up that way
+        let signature = match serde_json::from_str::<Value>(&response) {
+            Ok(Value::String(signature)) => signature,
+            Ok(Value::Object(object)) => object
+                .get("signature")
+                .and_then(Value::as_str)
+                .ok_or(CoreError::RpcError)?
+                .to_string(),
+            _ => response.trim().to_string(),
+        };
+        EcdsaSignature::from_hex(&signature)
+    }
+
+    /// Reject a signature that is not this account's over `digest`
+    fn verified(&self, signature: EcdsaSignature, digest: &[u8; 32]) -> Result<EcdsaSignature> {
+        let signer = public_key_to_address(&signature.recover(digest)?);
+        match same_address(&signer, &self.address)? {
+            true => Ok(signature),
+            false => Err(CoreError::SigningError),
+        }
+    }
+}
+
+impl Signer for RemoteSigner {
+    /// Signing services only sign prefixed messages, typed data and
+    /// transactions, never arbitrary digests
+    fn sign_digest(&self, _digest: &[u8; 32]) -> Result<EcdsaSignature> {
+        Err(CoreError::UnsupportedBySigner)
+    }
+
+    fn public_key(&self) -> Result<PublicKey> {
+        self.public_key.ok_or(CoreError::UnsupportedBySigner)
+    }
+
+    fn address(&self) -> Result<String> {
+        Ok(self.address.clone())
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<EcdsaSignature> {
+        let digest = evm::personal_message_hash(message);
+        let signature = match self.api {
+            RemoteSignerApi::JsonRpc => {
+                let data = format!("0x{}", hex::encode(message));
+                let result = self.call("eth_sign", json!([self.address, data]))?;
+                EcdsaSignature::from_hex(result.as_str().ok_or(CoreError::RpcError)?)?
+            }
+            RemoteSignerApi::Web3Signer => self.sign_preimage(&evm::personal_message(message))?,
+        };
+        self.verified(signature, &digest)
+    }
+
+    fn sign_typed_data(&self, typed_data_json: &str) -> Result<EcdsaSignature> {
+        let hash = eip712::hash_typed_data(typed_data_json)?;
+        let signature = match self.api {
+            RemoteSignerApi::JsonRpc => {
+                let result = self.call(
+                    "eth_signTypedData_v4",
+                    json!([self.address, typed_data_json]),
+                )?;
+                EcdsaSignature::from_hex(result.as_str().ok_or(CoreError::RpcError)?)?
+            }
+            RemoteSignerApi::Web3Signer => {
+                let mut preimage = vec![0x19, 0x01];
+                preimage.extend_from_slice(&hash.domain_separator);
+                if let Some(message_hash) = hash.message_hash {
+                    preimage.extend_from_slice(&message_hash);
+                }
+                self.sign_preimage(&preimage)?
+            }
+        };
+        self.verified(signature, &hash.digest)
+    }
+
+    fn sign_transaction(&self, params: &EvmTxParams) -> Result<EcdsaSignature> {
+        let mut tx = evm::to_typed_transaction(params)?;
+        let sighash = tx.sighash().to_fixed_bytes();
+        let signature = match self.api {
+            RemoteSignerApi::JsonRpc => {
+                tx.set_from(evm::parse_address(&self.address)?);
+                // ethers leaves the chain id out of legacy requests
+                let mut request = serde_json::to_value(&tx)?;
+                request["chainId"] = json!(format!("{:#x}", params.chain_id));
+                let result = self.call("eth_signTransaction", json!([request]))?;
+                // A raw transaction, or Geth's `{ raw, tx }`
+                let raw = match &result {
+                    Value::String(raw) => raw.as_str(),
+                    other => other["raw"].as_str().ok_or(CoreError::RpcError)?,
+                };
+                signature_of_raw_transaction(raw)?
+            }
+            RemoteSignerApi::Web3Signer => {
+                let unsigned = tx.rlp();
+                debug_assert_eq!(keccak256(&unsigned), sighash);
+                self.sign_preimage(&unsigned)?
+            }
+        };
+        // The service may have changed fields before signing; the signature
+        // only verifies if it signed exactly the transaction we built
+        self.verified(signature, &sighash)
+    }
+}
+
+/// The signature of a signed EIP-2718 or legacy transaction
+fn signature_of_raw_transaction(raw: &str) -> Result<EcdsaSignature> {
+    let bytes = raw
+        .parse::<Bytes>()
+        .map_err(|_| CoreError::InvalidTransaction)?;
+    let (_, signature) = TypedTransaction::decode_signed(&rlp::Rlp::new(&bytes))
+        .map_err(|_| CoreError::InvalidTransaction)?;
+
+    let mut r = [0u8; 32];
+    let mut s = [0u8; 32];
+    signature.r.to_big_endian(&mut r);
+    signature.s.to_big_endian(&mut s);
+    EcdsaSignature::from_parts(r, s, signature.v)
+}
+
+/// Hex public key, compressed, uncompressed, or uncompressed without the
+/// `04` prefix as Web3Signer lists them
+fn parse_public_key(public_key: &str) -> Result<PublicKey> {
+    let mut bytes =
+        hex::decode(public_key.trim_start_matches("0x")).map_err(|_| CoreError::InvalidInput)?;
+    if bytes.len() == 64 {
+        bytes.insert(0, 0x04);
+    }
+    PublicKey::from_slice(&bytes).map_err(|_| CoreError::InvalidInput)
+}
+
+fn same_address(a: &str, b: &str) -> Result<bool> {
+    let a: Address = evm::parse_address(a)?;
+    Ok(a == evm::parse_address(b)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::test_server;
+    use crate::signer::sign_with_key;
+    use secp256k1::SecretKey;
+
+    const KEY: [u8; 32] = [0x46; 32];
+
+    /// A signing service holding `key`, speaking the `eth_sign*` methods
+    fn json_rpc_service(key: SecretKey) -> String {
+        test_server::spawn(move |request| {
+            let params = &request["params"];
+            match request["method"].as_str().unwrap() {
+                "eth_sign" => {
+                    let data =
+                        hex::decode(params[1].as_str().unwrap().trim_start_matches("0x")).unwrap();
+                    Ok(json!(sign_with_key(
+                        &key,
+                        &evm::personal_message_hash(&data)
+                    )
+                    .unwrap()
+                    .to_hex()))
+                }
+                "eth_signTypedData_v4" => {
+                    let hash = eip712::hash_typed_data(params[1].as_str().unwrap()).unwrap();
+                    Ok(json!(sign_with_key(&key, &hash.digest).unwrap().to_hex()))
+                }
+                "eth_signTransaction" => {
+                    let mut tx: TypedTransaction =
+                        serde_json::from_value(params[0].clone()).unwrap();
+                    let chain_id = params[0]["chainId"].as_str().unwrap();
+                    tx.set_chain_id(u64::from_str_radix(&chain_id[2..], 16).unwrap());
+                    let sig = sign_with_key(&key, &tx.sighash().to_fixed_bytes()).unwrap();
+                    let v = match &tx {
+                        TypedTransaction::Legacy(_) => {
+                            sig.eip155_v(tx.chain_id().unwrap().as_u64())
+                        }
+                        _ => sig.y_parity(),
+                    };
+                    let signature = ethers_core::types::Signature {
+                        r: sig.r.into(),
+                        s: sig.s.into(),
+                        v,
+                    };
+                    let raw = format!("0x{}", hex::encode(tx.rlp_signed(&signature)));
+                    Ok(json!({ "raw": raw, "tx": params[0] }))
+                }
+                other => Err(format!("unknown method {}", other)),
+            }
+        })
+    }
+
+    fn config(url: String, api: RemoteSignerApi, key: &SecretKey) -> RemoteSignerConfig {
+        let public_key = PublicKey::from_secret_key_global(key);
+        RemoteSignerConfig {
+            url,
+            api,
+            address: Some(public_key_to_address(&public_key)),
+            public_key: match api {
+                RemoteSignerApi::JsonRpc => None,
+                RemoteSignerApi::Web3Signer => Some(format!(
+                    "0x{}",
+                    hex::encode(&public_key.serialize_uncompressed()[1..])
+                )),
+            },
+        }
+    }
+
+    #[test]
+    fn test_json_rpc_signer_matches_local_signatures() {
+        let key = SecretKey::from_slice(&KEY).unwrap();
+        let url = json_rpc_service(key);
+        let signer = RemoteSigner::new(config(url, RemoteSignerApi::JsonRpc, &key)).unwrap();
+        let address = signer.address().unwrap();
+
+        let signature = evm::sign_message_with(&signer, b"login").unwrap();
+        assert!(evm::verify_message(address.clone(), "login".to_string(), signature).unwrap());
+        assert!(signer.sign_typed_data(eip712::MAIL_TYPED_DATA).is_ok());
+        assert!(matches!(
+            signer.sign_digest(&[1u8; 32]),
+            Err(CoreError::UnsupportedBySigner)
+        ));
+
+        for eip1559 in [false, true] {
+            let params = evm::test_tx_params(address.clone(), eip1559, 0);
+            let tx = evm::sign_transaction_with(&signer, params.clone()).unwrap();
+            let expected =
+                sign_with_key(&key, &evm::transaction_sighash(&params).unwrap()).unwrap();
+            assert_eq!(
+                signature_of_raw_transaction(&tx.signed_tx).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_signature_from_another_key() {
+        let key = SecretKey::from_slice(&KEY).unwrap();
+        let impostor = SecretKey::from_slice(&[0x47; 32]).unwrap();
+        let url = json_rpc_service(impostor);
+        let signer = RemoteSigner::new(config(url, RemoteSignerApi::JsonRpc, &key)).unwrap();
+
+        assert!(matches!(
+            signer.sign_message(b"login"),
+            Err(CoreError::SigningError)
+        ));
+        let params = evm::test_tx_params(signer.address().unwrap(), true, 0);
+        assert!(matches!(
+            signer.sign_transaction(&params),
+            Err(CoreError::SigningError)
+        ));
+    }
+
+    #[test]
+    fn test_web3signer_signs_preimages() {
+        let key = SecretKey::from_slice(&KEY).unwrap();
+        let cfg = config(String::new(), RemoteSignerApi::Web3Signer, &key);
+        let expected_path = format!("/api/v1/eth1/sign/{}", cfg.public_key.clone().unwrap());
+        let url = test_server::spawn_http(move |path, body| {
+            if path != expected_path {
+                return (404, String::new());
+            }
+            let body: Value = serde_json::from_slice(body).unwrap();
+            let data =
+                hex::decode(body["data"].as_str().unwrap().trim_start_matches("0x")).unwrap();
+            (200, sign_with_key(&key, &keccak256(data)).unwrap().to_hex())
+        });
+        let signer = RemoteSigner::new(RemoteSignerConfig { url, ..cfg }).unwrap();
+
+        let signature = signer.sign_message(b"login").unwrap();
+        assert_eq!(
+            signature,
+            sign_with_key(&key, &evm::personal_message_hash(b"login")).unwrap()
+        );
+        let hash = eip712::hash_typed_data(eip712::MAIL_TYPED_DATA).unwrap();
+        assert_eq!(
+            signer.sign_typed_data(eip712::MAIL_TYPED_DATA).unwrap(),
+            sign_with_key(&key, &hash.digest).unwrap()
+        );
+        let params = evm::test_tx_params(signer.address().unwrap(), false, 0);
+        assert_eq!(
+            signer.sign_transaction(&params).unwrap(),
+            sign_with_key(&key, &evm::transaction_sighash(&params).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let key = SecretKey::from_slice(&KEY).unwrap();
+        let mut cfg = config(String::new(), RemoteSignerApi::Web3Signer, &key);
+        assert!(RemoteSigner::new(cfg.clone()).is_ok());
+
+        cfg.address = Some("0x3535353535353535353535353535353535353535".to_string());
+        assert!(matches!(
+            RemoteSigner::new(cfg.clone()),
+            Err(CoreError::InvalidAddress)
+        ));
+        cfg.address = None;
+        cfg.public_key = None;
+        assert!(RemoteSigner::new(cfg.clone()).is_err());
+        cfg.api = RemoteSignerApi::JsonRpc;
+        assert!(RemoteSigner::new(cfg).is_err());
+    }
+}
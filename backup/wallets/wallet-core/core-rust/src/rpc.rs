@@ -81,6 +81,21 @@ impl JsonRpcClient {
         })
     }
 
+    /// POST a JSON body to `path` under the client's URL, for REST endpoints
+    /// served next to JSON-RPC. Returns the response body.
+    pub fn post(&self, path: &str, body: &Value) -> Result<String> {
+        let url = format!("{}{}", self.rpc_url.trim_end_matches('/'), path);
+        let runtime = tokio::runtime::Runtime::new().map_err(|_| CoreError::InternalError)?;
+
+        runtime.block_on(async {
+            let response = self.client.post(&url).json(body).send().await?;
+            if !response.status().is_success() {
+                return Err(CoreError::RpcError);
+            }
+            Ok(response.text().await?)
+        })
+    }
+
     pub fn get_balance(&self, address: String) -> Result<String> {
         let params = json!([address, "latest"]).to_string();
         let response = self.call("eth_getBalance".to_string(), params)?;
@@ -114,6 +129,90 @@ impl JsonRpcClient {
     }
 }
 
+/// Minimal HTTP and JSON-RPC servers for tests
+#[cfg(test)]
+pub(crate) mod test_server {
+    use serde_json::{json, Value};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// JSON-RPC server; `handler` maps a request object to its result, or to
+    /// an error message
+    pub(crate) fn spawn<F>(handler: F) -> String
+    where
+        F: Fn(&Value) -> std::result::Result<Value, String> + Send + 'static,
+    {
+        spawn_http(move |_, body| {
+            let respond = |request: &Value| match handler(request) {
+                Ok(result) => json!({"jsonrpc": "2.0", "id": request["id"], "result": result}),
+                Err(message) => json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": {"code": -32000, "message": message},
+                }),
+            };
+            let request: Value = serde_json::from_slice(body).unwrap_or(Value::Null);
+            let response = match &request {
+                Value::Array(batch) => Value::Array(batch.iter().map(respond).collect()),
+                single => respond(single),
+            };
+            (200, response.to_string())
+        })
+    }
+
+    /// HTTP server; `handler` maps the request path and body to a status and body
+    pub(crate) fn spawn_http<F>(handler: F) -> String
+    where
+        F: Fn(&str, &[u8]) -> (u16, String) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                let _ = reader.read_line(&mut request_line);
+                let path = request_line
+                    .split_whitespace()
+                    .nth(1)
+                    .unwrap_or("/")
+                    .to_string();
+
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap_or(0);
+                        }
+                    }
+                }
+                let mut body = vec![0u8; content_length];
+                if reader.read_exact(&mut body).is_err() {
+                    continue;
+                }
+
+                let (status, response) = handler(&path, &body);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    response.len(),
+                    response
+                );
+            }
+        });
+
+        url
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
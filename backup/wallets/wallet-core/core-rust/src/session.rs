@@ -0,0 +1,89 @@
+// Keyring session state
+// Tracks wallet activity for the idle timeout and the window in which a
+// password proof allows exporting secrets
+
+use crate::types::{SessionPolicy, SessionStatus};
+use std::time::{Duration, Instant};
+
+pub(crate) struct Session {
+    policy: SessionPolicy,
+    last_activity: Instant,
+    sensitive_until: Option<Instant>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            policy: SessionPolicy::default(),
+            last_activity: Instant::now(),
+            sensitive_until: None,
+        }
+    }
+}
+
+impl Session {
+    pub fn policy(&self) -> SessionPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: SessionPolicy) {
+        self.policy = policy;
+        // A shorter window applies to a grant already handed out
+        if let Some(until) = self.sensitive_until {
+            let latest = Instant::now() + Duration::from_millis(policy.sensitive_window_ms);
+            self.sensitive_until = Some(until.min(latest));
+        }
+    }
+
+    /// Fresh session, as after unlocking the vault
+    pub fn start(&mut self) {
+        self.last_activity = Instant::now();
+        self.sensitive_until = None;
+    }
+
+    pub fn end(&mut self) {
+        self.sensitive_until = None;
+    }
+
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle_remaining()
+            .is_some_and(|remaining| remaining.is_zero())
+    }
+
+    pub fn grant_sensitive(&mut self) {
+        self.sensitive_until =
+            Some(Instant::now() + Duration::from_millis(self.policy.sensitive_window_ms));
+    }
+
+    pub fn has_sensitive(&self) -> bool {
+        !self.sensitive_remaining().is_zero()
+    }
+
+    pub fn status(&self, locked: bool) -> SessionStatus {
+        SessionStatus {
+            locked,
+            idle_remaining_ms: match locked {
+                true => None,
+                false => self.idle_remaining().map(|d| d.as_millis() as u64),
+            },
+            sensitive_remaining_ms: self.sensitive_remaining().as_millis() as u64,
+        }
+    }
+
+    fn idle_remaining(&self) -> Option<Duration> {
+        match self.policy.idle_timeout_ms {
+            0 => None,
+            ms => Some(Duration::from_millis(ms).saturating_sub(self.last_activity.elapsed())),
+        }
+    }
+
+    fn sensitive_remaining(&self) -> Duration {
+        self.sensitive_until.map_or(Duration::ZERO, |until| {
+            until.saturating_duration_since(Instant::now())
+        })
+    }
+}
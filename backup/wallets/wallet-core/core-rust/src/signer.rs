@@ -0,0 +1,267 @@
+// Signing backends
+// Chain modules hash what they sign and hand the digest to a `Signer`; where
+// the key lives (keyring, hardware device, remote service) is up to the signer
+
+use crate::storage::Keyring;
+use crate::{eip712, evm};
+use crate::{error::*, types::*};
+use secp256k1::{
+    ecdsa::{RecoverableSignature, RecoveryId},
+    Message, PublicKey, Secp256k1, SecretKey,
+};
+
+/// Produces recoverable secp256k1 signatures for one account
+pub trait Signer: Send + Sync {
+    /// Sign a 32-byte digest as is; callers apply any message prefix first
+    fn sign_digest(&self, digest: &[u8; 32]) -> Result<EcdsaSignature>;
+
+    fn public_key(&self) -> Result<PublicKey>;
+
+    /// Address in the format of the chain the signer was set up for
+    fn address(&self) -> Result<String>;
+
+    /// EIP-191 `personal_sign`. Signers that apply the prefix themselves,
+    /// like remote services and hardware wallets, override this.
+    fn sign_message(&self, message: &[u8]) -> Result<EcdsaSignature> {
+        self.sign_digest(&evm::personal_message_hash(message))
+    }
+
+    /// EIP-712 typed data, as `eth_signTypedData_v4` JSON
+    fn sign_typed_data(&self, typed_data_json: &str) -> Result<EcdsaSignature> {
+        self.sign_digest(&eip712::hash_typed_data(typed_data_json)?.digest)
+    }
+
+    /// An EVM transaction; the signature covers its sighash
+    fn sign_transaction(&self, params: &EvmTxParams) -> Result<EcdsaSignature> {
+        self.sign_digest(&evm::transaction_sighash(params)?)
+    }
+}
+
+/// Recoverable ECDSA signature. Every encoding of `v` lives here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcdsaSignature {
+    pub r: [u8; 32],
+    pub s: [u8; 32],
+    /// 0 or 1, the parity of the nonce point's y coordinate
+    pub recovery_id: u8,
+}
+
+impl EcdsaSignature {
+    /// Parse a 65-byte r || s || v signature (v as 0/1, 27/28 or EIP-155)
+    /// or a 64-byte EIP-2098 compact signature (r || yParityAndS)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut signature = Self {
+            r: [0; 32],
+            s: [0; 32],
+            recovery_id: 0,
+        };
+        match bytes.len() {
+            65 => {
+                signature.recovery_id = recovery_id_from_v(bytes[64] as u64)?;
+                signature.s.copy_from_slice(&bytes[32..64]);
+            }
+            64 => {
+                // The top bit of s carries the y-parity
+                signature.recovery_id = bytes[32] >> 7;
+                signature.s.copy_from_slice(&bytes[32..64]);
+                signature.s[0] &= 0x7f;
+            }
+            _ => return Err(CoreError::SigningError),
+        }
+        signature.r.copy_from_slice(&bytes[..32]);
+        Ok(signature)
+    }
+
+    /// From the `r`, `s` and `v` fields of a signed transaction or JSON signature
+    pub fn from_parts(r: [u8; 32], s: [u8; 32], v: u64) -> Result<Self> {
+        Ok(Self {
+            r,
+            s,
+            recovery_id: recovery_id_from_v(v)?,
+        })
+    }
+
+    /// Hex `0x` r || s || v, the string form of [`EcdsaSignature::from_bytes`]
+    pub fn from_hex(signature: &str) -> Result<Self> {
+        let bytes =
+            hex::decode(signature.trim_start_matches("0x")).map_err(|_| CoreError::SigningError)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// r || s || v with v = 27 + recovery id, as used by `personal_sign`,
+    /// EIP-712, user operations and TRON
+    pub fn to_rsv(&self) -> [u8; 65] {
+        let mut bytes = [0u8; 65];
+        bytes[..32].copy_from_slice(&self.r);
+        bytes[32..64].copy_from_slice(&self.s);
+        bytes[64] = 27 + self.recovery_id;
+        bytes
+    }
+
+    /// [`EcdsaSignature::to_rsv`] as `0x`-prefixed hex
+    pub fn to_hex(&self) -> String {
+        format!("0x{}", hex::encode(self.to_rsv()))
+    }
+
+    /// `v` of a legacy transaction under EIP-155 replay protection
+    pub fn eip155_v(&self, chain_id: u64) -> u64 {
+        chain_id * 2 + 35 + self.recovery_id as u64
+    }
+
+    /// `v` of a typed (EIP-2718) transaction: the bare y-parity
+    pub fn y_parity(&self) -> u64 {
+        self.recovery_id as u64
+    }
+
+    /// Public key that produced this signature over `digest`
+    pub fn recover(&self, digest: &[u8; 32]) -> Result<PublicKey> {
+        let message = Message::from_digest_slice(digest)?;
+        Ok(Secp256k1::verification_only().recover_ecdsa(&message, &self.to_secp()?)?)
+    }
+
+    fn to_secp(self) -> Result<RecoverableSignature> {
+        let mut compact = [0u8; 64];
+        compact[..32].copy_from_slice(&self.r);
+        compact[32..].copy_from_slice(&self.s);
+        let recovery_id = RecoveryId::from_i32(self.recovery_id as i32)?;
+        Ok(RecoverableSignature::from_compact(&compact, recovery_id)?)
+    }
+
+    fn from_secp(signature: &RecoverableSignature) -> Self {
+        let (recovery_id, compact) = signature.serialize_compact();
+        let mut parsed = Self {
+            r: [0; 32],
+            s: [0; 32],
+            recovery_id: recovery_id.to_i32() as u8,
+        };
+        parsed.r.copy_from_slice(&compact[..32]);
+        parsed.s.copy_from_slice(&compact[32..]);
+        parsed
+    }
+}
+
+/// v as 0/1, 27/28 or EIP-155 `chain_id * 2 + 35 + y-parity`
+fn recovery_id_from_v(v: u64) -> Result<u8> {
+    match v {
+        0 | 1 => Ok(v as u8),
+        27 | 28 => Ok((v - 27) as u8),
+        v if v >= 35 => Ok(((v - 35) % 2) as u8),
+        _ => Err(CoreError::SigningError),
+    }
+}
+
+/// Sign with a key held in memory
+pub(crate) fn sign_with_key(secret_key: &SecretKey, digest: &[u8; 32]) -> Result<EcdsaSignature> {
+    let message = Message::from_digest_slice(digest)?;
+    let signature = Secp256k1::signing_only().sign_ecdsa_recoverable(&message, secret_key);
+    Ok(EcdsaSignature::from_secp(&signature))
+}
+
+/// Signs with an account of a keyring wallet. The key is borrowed from the
+/// keyring for each signature and never held by the signer.
+#[derive(Clone)]
+pub struct LocalSigner {
+    keyring: Keyring,
+    wallet_id: String,
+    chain: Chain,
+    account_index: u32,
+}
+
+impl LocalSigner {
+    /// The account must already be derived
+    pub fn new(keyring: &Keyring, wallet_id: &str, chain: Chain, account_index: u32) -> Self {
+        Self {
+            keyring: keyring.clone(),
+            wallet_id: wallet_id.to_string(),
+            chain,
+            account_index,
+        }
+    }
+}
+
+impl Signer for LocalSigner {
+    fn sign_digest(&self, digest: &[u8; 32]) -> Result<EcdsaSignature> {
+        self.keyring.with_wallet(&self.wallet_id, |wallet| {
+            wallet.with_secret_key(self.chain, self.account_index, |key| {
+                sign_with_key(key, digest)
+            })
+        })
+    }
+
+    fn public_key(&self) -> Result<PublicKey> {
+        self.keyring.with_wallet(&self.wallet_id, |wallet| {
+            wallet.get_public_key(self.chain, self.account_index)
+        })
+    }
+
+    fn address(&self) -> Result<String> {
+        self.keyring.with_wallet(&self.wallet_id, |wallet| {
+            wallet.get_address(self.chain, self.account_index)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer(chain: Chain) -> (LocalSigner, String) {
+        let keyring = Keyring::new();
+        let mut wallet = crate::crypto::Wallet::from_entropy(&[21u8; 16], None).unwrap();
+        wallet.derive_chain_account(Chain::Tron, 0).unwrap();
+        let address = wallet.get_address(chain, 0).unwrap();
+        let id = wallet.id.clone();
+        keyring.insert(wallet).unwrap();
+        (LocalSigner::new(&keyring, &id, chain, 0), address)
+    }
+
+    #[test]
+    fn test_local_signer_recovers_to_account() {
+        for chain in [Chain::Evm, Chain::Tron] {
+            let (signer, address) = signer(chain);
+            assert_eq!(signer.address().unwrap(), address);
+            let public_key = signer.public_key().unwrap();
+            let derived = match chain {
+                Chain::Evm => crate::crypto::public_key_to_address(&public_key),
+                Chain::Tron => crate::crypto::public_key_to_tron_address(&public_key),
+            };
+            assert_eq!(derived, address);
+
+            let digest = [7u8; 32];
+            let signature = signer.sign_digest(&digest).unwrap();
+            assert_eq!(signature.recover(&digest).unwrap(), public_key);
+        }
+    }
+
+    #[test]
+    fn test_v_encodings_parse_back() {
+        let (signer, _) = signer(Chain::Evm);
+        let signature = signer.sign_digest(&[3u8; 32]).unwrap();
+
+        let rsv = signature.to_rsv();
+        assert_eq!(rsv[64], 27 + signature.recovery_id);
+        assert_eq!(
+            EcdsaSignature::from_hex(&signature.to_hex()).unwrap(),
+            signature
+        );
+
+        let mut eip155 = rsv;
+        eip155[64] = (signature.eip155_v(1) & 0xff) as u8;
+        assert_eq!(signature.eip155_v(1), 37 + signature.recovery_id as u64);
+        assert_eq!(EcdsaSignature::from_bytes(&eip155).unwrap(), signature);
+
+        let mut parity = rsv;
+        parity[64] = signature.y_parity() as u8;
+        assert_eq!(EcdsaSignature::from_bytes(&parity).unwrap(), signature);
+
+        // EIP-2098: y-parity folded into the top bit of s
+        let mut compact = rsv[..64].to_vec();
+        compact[32] |= signature.recovery_id << 7;
+        assert_eq!(EcdsaSignature::from_bytes(&compact).unwrap(), signature);
+
+        assert!(EcdsaSignature::from_bytes(&rsv[..63]).is_err());
+        let mut bad_v = rsv;
+        bad_v[64] = 5;
+        assert!(EcdsaSignature::from_bytes(&bad_v).is_err());
+    }
+}
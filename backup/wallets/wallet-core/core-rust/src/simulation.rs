@@ -152,8 +152,19 @@ mod tests {
 
     #[test]
     fn test_analyze_transaction() {
-        // This test requires a live RPC endpoint, so we'll skip actual execution
-        // In a real scenario, you'd use a mock RPC server
-        assert!(true);
+        // No live RPC endpoint in tests: an unreachable node must surface as an error
+        let params = EvmTxParams {
+            from: "0x1234567890123456789012345678901234567890".to_string(),
+            to: "0x0987654321098765432109876543210987654321".to_string(),
+            value: "0".to_string(),
+            data: None,
+            gas_limit: 21000,
+            gas_price: "20000000000".to_string(),
+            nonce: 0,
+            chain_id: 1,
+            ..Default::default()
+        };
+
+        assert!(analyze_transaction(params, "http://127.0.0.1:1".to_string()).is_err());
     }
 }
@@ -1,23 +1,773 @@
 // Shared wallet storage module
-// Provides centralized access to wallet storage for all modules
+// Provides the keyring every manager signs through
 
-use crate::crypto::Wallet;
+use crate::audit::{AuditLog, DigestRecorder, SigningAction};
+use crate::backup::{self, BackupPayload, BackupWallet};
+use crate::crypto::{Wallet, WalletRecord};
+use crate::error::{CoreError, Result};
+use crate::policy::{PolicyEngine, SpendingPolicy};
+use crate::session::Session;
+use crate::signer::Signer;
+use crate::types::{
+    AuditVerification, BackupRestore, BackupSettings, PolicyViolation, SessionPolicy, SessionStatus,
+};
+use crate::vault::{KdfParams, Vault, VaultPayload};
 use lazy_static::lazy_static;
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 
 lazy_static! {
-    pub static ref WALLET_STORAGE: Mutex<HashMap<String, Wallet>> = Mutex::new(HashMap::new());
+    /// Process-wide keyring used by managers created with `new()` and by the FFI layer
+    pub static ref WALLET_STORAGE: Keyring = Keyring::new();
 }
 
-pub fn get_wallet(wallet_id: &str) -> Result<Wallet, crate::error::CoreError> {
-    let wallets = WALLET_STORAGE.lock().map_err(|_| crate::error::CoreError::InternalError)?;
-    wallets.get(wallet_id).cloned().ok_or(crate::error::CoreError::InvalidInput)
+/// Handle to a set of in-memory wallets.
+///
+/// Cloning is cheap and every clone refers to the same wallets, so a keyring
+/// can be handed to several managers to let them sign for any wallet the
+/// app created.
+///
+/// With a vault attached, every change is written encrypted to disk and
+/// locking the keyring wipes the wallets from memory until it is unlocked.
+///
+/// A vault also enables the session: the keyring locks itself after the
+/// policy's idle timeout, and exporting secrets needs a recent password proof
+/// from [`Keyring::authorize_sensitive`]. Keyrings without a vault have no
+/// password to check against: they never lock on idle and refuse to export
+/// secrets at all.
+///
+/// A spending policy, once set, is checked before any transaction, user
+/// operation or typed data signed through the keyring's managers, and every
+/// signature they
+/// produce or the policy refuses is recorded in the audit log. The policy and
+/// its daily totals are kept in the vault.
+#[derive(Clone, Default)]
+pub struct Keyring {
+    wallets: Arc<RwLock<HashMap<String, Wallet>>>,
+    vault: Arc<Mutex<Option<Vault>>>,
+    session: Arc<Mutex<Session>>,
+    policy: Arc<Mutex<PolicyEngine>>,
+    audit: Arc<Mutex<AuditLog>>,
 }
 
-pub fn store_wallet(wallet_id: String, wallet: Wallet) -> Result<(), crate::error::CoreError> {
-    let mut wallets = WALLET_STORAGE.lock().map_err(|_| crate::error::CoreError::InternalError)?;
-    wallets.insert(wallet_id, wallet);
-    Ok(())
+impl Keyring {
+    /// Create an empty keyring, independent from the global one
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The process-wide keyring
+    pub fn global() -> Self {
+        WALLET_STORAGE.clone()
+    }
+
+    pub fn contains(&self, wallet_id: &str) -> bool {
+        self.wallets
+            .read()
+            .map(|wallets| wallets.contains_key(wallet_id))
+            .unwrap_or(false)
+    }
+
+    pub fn wallet_ids(&self) -> Result<Vec<String>> {
+        let wallets = self.wallets.read().map_err(|_| CoreError::InternalError)?;
+        Ok(wallets.keys().cloned().collect())
+    }
+
+    /// Remove a wallet, returning whether it was present
+    pub fn remove(&self, wallet_id: &str) -> Result<bool> {
+        self.ensure_writable()?;
+        let removed = {
+            let mut wallets = self.wallets.write().map_err(|_| CoreError::InternalError)?;
+            wallets.remove(wallet_id).is_some()
+        };
+        self.persist()?;
+        Ok(removed)
+    }
+
+    /// Create an encrypted vault at `path` holding the current wallets,
+    /// using Argon2id for the password
+    pub fn create_vault(&self, path: impl AsRef<Path>, password: &str) -> Result<()> {
+        self.create_vault_with_kdf(path, password, KdfParams::argon2id())
+    }
+
+    pub fn create_vault_with_kdf(
+        &self,
+        path: impl AsRef<Path>,
+        password: &str,
+        kdf: KdfParams,
+    ) -> Result<()> {
+        let mut vault = self.vault.lock().map_err(|_| CoreError::InternalError)?;
+        let payload = self.payload()?;
+        *vault = Some(Vault::create(path.as_ref(), password, kdf, &payload)?);
+        self.audit()?.attach_vault(None);
+        self.session()?.start();
+        Ok(())
+    }
+
+    /// Open the vault at `path` and load its wallets and spending policy.
+    /// Returns the wallet ids.
+    pub fn unlock_vault(&self, path: impl AsRef<Path>, password: &str) -> Result<Vec<String>> {
+        let mut vault = self.vault.lock().map_err(|_| CoreError::InternalError)?;
+        let (unlocked, payload) = Vault::unlock(path.as_ref(), password)?;
+
+        let mut restored = Vec::with_capacity(payload.wallets.len());
+        for record in &payload.wallets {
+            restored.push(Wallet::from_record(record)?);
+        }
+        let policy = PolicyEngine::restore(payload.policy.unwrap_or_default())?;
+
+        let mut wallets = self.wallets.write().map_err(|_| CoreError::InternalError)?;
+        let ids = restored.iter().map(|w| w.id.clone()).collect();
+        for wallet in restored {
+            wallets.insert(wallet.id.clone(), wallet);
+        }
+        *self.policy()? = policy;
+        self.audit()?.attach_vault(payload.audit);
+        *vault = Some(unlocked);
+        self.session()?.start();
+        Ok(ids)
+    }
+
+    /// Re-open the attached vault after `lock`
+    pub fn unlock(&self, password: &str) -> Result<Vec<String>> {
+        let path = {
+            let vault = self.vault.lock().map_err(|_| CoreError::InternalError)?;
+            vault
+                .as_ref()
+                .ok_or(CoreError::InvalidInput)?
+                .path()
+                .to_path_buf()
+        };
+        self.unlock_vault(path, password)
+    }
+
+    /// Forget the vault key and wipe every wallet from memory
+    pub fn lock(&self) -> Result<()> {
+        let mut vault = self.vault.lock().map_err(|_| CoreError::InternalError)?;
+        let vault = vault.as_mut().ok_or(CoreError::InvalidInput)?;
+
+        vault.lock();
+        self.wallets
+            .write()
+            .map_err(|_| CoreError::InternalError)?
+            .clear();
+        self.audit()?.detach_vault();
+        self.session()?.end();
+        Ok(())
+    }
+
+    /// Lock the vault and revoke any sensitive authorization. Without a vault
+    /// nothing could unlock the wallets again, so they are dropped from memory.
+    pub fn lock_all(&self) -> Result<()> {
+        if self.has_vault() {
+            return self.lock();
+        }
+        self.session()?.end();
+        self.wallets
+            .write()
+            .map_err(|_| CoreError::InternalError)?
+            .clear();
+        Ok(())
+    }
+
+    /// Lock the vault if the session has been idle past its timeout, returning
+    /// whether it did. Apps call this from a timer so secrets leave memory even
+    /// when nothing touches the keyring.
+    pub fn enforce_idle_timeout(&self) -> Result<bool> {
+        if !self.has_vault() || self.is_locked() || !self.session()?.is_idle() {
+            return Ok(false);
+        }
+        self.lock()?;
+        Ok(true)
+    }
+
+    /// Prove knowledge of the vault password, allowing secret exports for the
+    /// policy's sensitive window
+    pub fn authorize_sensitive(&self, password: &str) -> Result<()> {
+        self.touch()?;
+        {
+            let vault = self.vault.lock().map_err(|_| CoreError::InternalError)?;
+            let vault = vault.as_ref().ok_or(CoreError::InvalidInput)?;
+            if !vault.is_unlocked() {
+                return Err(CoreError::VaultLocked);
+            }
+            vault.verify_password(password)?;
+        }
+        self.session()?.grant_sensitive();
+        Ok(())
+    }
+
+    pub fn session_policy(&self) -> Result<SessionPolicy> {
+        Ok(self.session()?.policy())
+    }
+
+    /// Tightening either limit is always allowed; loosening one needs a live
+    /// `authorize_sensitive` grant
+    pub fn set_session_policy(&self, policy: SessionPolicy) -> Result<()> {
+        let current = self.session_policy()?;
+        let never_locks = |p: &SessionPolicy| p.idle_timeout_ms == 0;
+        let loosens = (never_locks(&policy) && !never_locks(&current))
+            || (!never_locks(&current) && policy.idle_timeout_ms > current.idle_timeout_ms)
+            || policy.sensitive_window_ms > current.sensitive_window_ms;
+        if loosens {
+            self.require_sensitive()?;
+        }
+        self.session()?.set_policy(policy);
+        Ok(())
+    }
+
+    pub fn session_status(&self) -> Result<SessionStatus> {
+        let locked = self.is_locked();
+        Ok(self.session()?.status(locked))
+    }
+
+    /// Replace the spending policy with `policy_json`; see [`SpendingPolicy`].
+    /// Needs a live `authorize_sensitive` grant, like exports.
+    pub fn set_spending_policy(&self, policy_json: &str) -> Result<()> {
+        let policy = SpendingPolicy::parse(policy_json)?;
+        self.require_sensitive()?;
+        self.policy()?.set_policy(Some(policy));
+        self.persist()
+    }
+
+    pub fn clear_spending_policy(&self) -> Result<()> {
+        self.require_sensitive()?;
+        self.policy()?.set_policy(None);
+        self.persist()
+    }
+
+    /// The active policy as JSON
+    pub fn spending_policy(&self) -> Result<Option<String>> {
+        match self.policy()?.policy() {
+            Some(policy) => Ok(Some(serde_json::to_string(policy)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Keep the audit log in the JSON lines file at `path`, continuing the
+    /// entries already there. Needs an unlocked vault, which keeps the key
+    /// the entries are hashed with and the last one written; while it is
+    /// locked nothing can be signed. Open the log before signing anything:
+    /// entries made before then only live in memory.
+    pub fn open_audit_log(&self, path: impl AsRef<Path>) -> Result<()> {
+        if !self.has_vault() {
+            return Err(CoreError::InvalidInput);
+        }
+        {
+            let mut audit = self.audit()?;
+            if audit.has_unsaved_entries() {
+                return Err(CoreError::InvalidInput);
+            }
+            let state = audit.state().ok_or(CoreError::VaultLocked)?;
+            *audit = AuditLog::open(path.as_ref(), state)?;
+        }
+        self.persist()
+    }
+
+    /// Check the log's hash chain and that its file was not changed behind
+    /// the keyring's back
+    pub fn verify_audit_log(&self) -> Result<AuditVerification> {
+        self.audit()?.verify()
+    }
+
+    /// Check an export's hash chain with the log's key
+    pub fn verify_audit_export(&self, jsonl: &str) -> Result<AuditVerification> {
+        self.audit()?.verify_export(jsonl)
+    }
+
+    /// The log as JSON lines, oldest entry first
+    pub fn export_audit_log(&self) -> Result<String> {
+        self.audit()?.export()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.vault
+            .lock()
+            .map(|vault| vault.as_ref().is_some_and(|v| !v.is_unlocked()))
+            .unwrap_or(true)
+    }
+
+    pub fn change_password(&self, old_password: &str, new_password: &str) -> Result<()> {
+        let mut vault = self.vault.lock().map_err(|_| CoreError::InternalError)?;
+        let vault = vault.as_mut().ok_or(CoreError::InvalidInput)?;
+        if !vault.is_unlocked() {
+            return Err(CoreError::VaultLocked);
+        }
+        vault.change_password(old_password, new_password, &self.payload()?)
+    }
+
+    /// Encrypted bundle of every wallet plus `settings`, to move the whole setup
+    /// to another device. Reveals secrets, so a vault keyring needs
+    /// `authorize_sensitive` first.
+    pub fn export_backup(&self, password: &str, settings: BackupSettings) -> Result<String> {
+        self.export_backup_with_kdf(password, settings, KdfParams::argon2id())
+    }
+
+    pub fn export_backup_with_kdf(
+        &self,
+        password: &str,
+        settings: BackupSettings,
+        kdf: KdfParams,
+    ) -> Result<String> {
+        self.require_sensitive()?;
+        let payload = {
+            let wallets = self.wallets.read().map_err(|_| CoreError::InternalError)?;
+            let wallets = wallets
+                .values()
+                .map(|wallet| {
+                    Ok(BackupWallet {
+                        record: wallet.to_record()?,
+                        fingerprint: Some(wallet.fingerprint()),
+                    })
+                })
+                .collect::<Result<_>>()?;
+            BackupPayload { wallets, settings }
+        };
+        backup::seal(&payload, password, kdf)
+    }
+
+    /// Restore a bundle from `export_backup`, or from an older app version.
+    /// Wallets already in the keyring are skipped; if any bundled wallet fails
+    /// its integrity check nothing is added.
+    pub fn import_backup(&self, bundle: &str, password: &str) -> Result<BackupRestore> {
+        self.ensure_writable()?;
+        let (version, payload) = backup::open(bundle, password)?;
+        let wallets = payload
+            .wallets
+            .iter()
+            .map(backup::restore_wallet)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut restored = Vec::new();
+        let mut skipped = Vec::new();
+        for wallet in wallets {
+            let id = wallet.id.clone();
+            if self.contains(&id) {
+                skipped.push(id);
+                continue;
+            }
+            match self.insert(wallet) {
+                Ok(()) => restored.push(id),
+                Err(CoreError::DuplicateWallet) => skipped.push(id),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(BackupRestore {
+            version,
+            restored,
+            skipped,
+            settings: payload.settings,
+        })
+    }
+
+    /// Add a wallet; fails with `DuplicateWallet` if its key material is
+    /// already in the keyring
+    pub(crate) fn insert(&self, wallet: Wallet) -> Result<()> {
+        self.ensure_writable()?;
+        {
+            let mut wallets = self.wallets.write().map_err(|_| CoreError::InternalError)?;
+            let fingerprint = wallet.fingerprint();
+            if wallets.values().any(|w| w.fingerprint() == fingerprint) {
+                return Err(CoreError::DuplicateWallet);
+            }
+            wallets.insert(wallet.id.clone(), wallet);
+        }
+        self.persist()
+    }
+
+    /// Id of the wallet with this fingerprint, if present
+    pub fn find_by_fingerprint(&self, fingerprint: &str) -> Result<Option<String>> {
+        let wallets = self.wallets.read().map_err(|_| CoreError::InternalError)?;
+        Ok(wallets
+            .values()
+            .find(|w| w.fingerprint() == fingerprint)
+            .map(|w| w.id.clone()))
+    }
+
+    /// Run `f` with a borrowed wallet, without copying it out of the keyring
+    pub(crate) fn with_wallet<T>(
+        &self,
+        wallet_id: &str,
+        f: impl FnOnce(&Wallet) -> Result<T>,
+    ) -> Result<T> {
+        self.touch()?;
+        let wallets = self.wallets.read().map_err(|_| CoreError::InternalError)?;
+        let wallet = wallets.get(wallet_id).ok_or(CoreError::InvalidInput)?;
+        f(wallet)
+    }
+
+    /// Like `with_wallet`, for operations that reveal secret material. With a
+    /// vault attached this needs a live `authorize_sensitive` grant.
+    pub(crate) fn with_sensitive_wallet<T>(
+        &self,
+        wallet_id: &str,
+        f: impl FnOnce(&Wallet) -> Result<T>,
+    ) -> Result<T> {
+        self.require_sensitive()?;
+        self.with_wallet(wallet_id, f)
+    }
+
+    pub(crate) fn with_wallet_mut<T>(
+        &self,
+        wallet_id: &str,
+        f: impl FnOnce(&mut Wallet) -> Result<T>,
+    ) -> Result<T> {
+        self.ensure_writable()?;
+        let result = {
+            let mut wallets = self.wallets.write().map_err(|_| CoreError::InternalError)?;
+            let wallet = wallets.get_mut(wallet_id).ok_or(CoreError::InvalidInput)?;
+            f(wallet)?
+        };
+        self.persist()?;
+        Ok(result)
+    }
+
+    /// Run `sign` with `signer` if the spending policy allows `action`, count
+    /// it against daily limits and log it. The spend is reserved and saved
+    /// before signing, so the policy is not held across signer round trips,
+    /// and rolled back if no signature comes out. Refusals are logged too; a
+    /// signature whose entry cannot be written is withheld.
+    pub(crate) fn sign_audited<T>(
+        &self,
+        signer: &dyn Signer,
+        action: SigningAction,
+        sign: impl FnOnce(&dyn Signer) -> Result<T>,
+    ) -> Result<T> {
+        let day = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|_| CoreError::InternalError)?
+            .as_secs()
+            / 86_400;
+        let account = signer.address()?;
+
+        if let Some(request) = &action.spend {
+            let reserved = self.policy()?.reserve(request, day);
+            match reserved {
+                Err(CoreError::PolicyViolation(violation)) => {
+                    self.log_signing(&action, account, None, Some(violation.clone()))?;
+                    return Err(CoreError::PolicyViolation(violation));
+                }
+                reserved => reserved?,
+            }
+            if let Err(e) = self.persist() {
+                self.policy()?.release(request, day);
+                return Err(e);
+            }
+        }
+
+        let recorder = DigestRecorder::new(signer);
+        let signed = sign(&recorder).and_then(|signed| {
+            self.log_signing(&action, account, recorder.digest(), None)?;
+            Ok(signed)
+        });
+        if let (Err(_), Some(request)) = (&signed, &action.spend) {
+            self.policy()?.release(request, day);
+            // Failing to save the rollback only over-counts until the next save
+            let _ = self.persist();
+        }
+        signed
+    }
+
+    /// Append to the audit log and, for a log file, save its new head in
+    /// the vault
+    fn log_signing(
+        &self,
+        action: &SigningAction,
+        account: String,
+        digest: Option<[u8; 32]>,
+        violation: Option<PolicyViolation>,
+    ) -> Result<()> {
+        let file_backed = {
+            let mut audit = self.audit()?;
+            audit.append(action, account, digest, violation)?;
+            audit.is_file_backed()
+        };
+        if file_backed {
+            self.persist()?;
+        }
+        Ok(())
+    }
+
+    fn records(&self) -> Result<Vec<WalletRecord>> {
+        let wallets = self.wallets.read().map_err(|_| CoreError::InternalError)?;
+        wallets.values().map(Wallet::to_record).collect()
+    }
+
+    fn ensure_writable(&self) -> Result<()> {
+        self.touch()?;
+        if self.is_locked() {
+            return Err(CoreError::VaultLocked);
+        }
+        Ok(())
+    }
+
+    /// Secrets only leave after a recent password proof. Fails closed without
+    /// a vault, as there is no password to prove.
+    fn require_sensitive(&self) -> Result<()> {
+        self.touch()?;
+        if !self.has_vault() || !self.session()?.has_sensitive() {
+            return Err(CoreError::ReauthenticationRequired);
+        }
+        Ok(())
+    }
+
+    /// Record wallet activity, locking first if the session went idle
+    fn touch(&self) -> Result<()> {
+        if self.enforce_idle_timeout()? {
+            return Err(CoreError::VaultLocked);
+        }
+        self.session()?.touch();
+        Ok(())
+    }
+
+    fn has_vault(&self) -> bool {
+        self.vault
+            .lock()
+            .map(|vault| vault.is_some())
+            .unwrap_or(false)
+    }
+
+    fn session(&self) -> Result<MutexGuard<'_, Session>> {
+        self.session.lock().map_err(|_| CoreError::InternalError)
+    }
+
+    /// Taken after the vault and wallets and never held while signing
+    fn policy(&self) -> Result<MutexGuard<'_, PolicyEngine>> {
+        self.policy.lock().map_err(|_| CoreError::InternalError)
+    }
+
+    fn audit(&self) -> Result<MutexGuard<'_, AuditLog>> {
+        self.audit.lock().map_err(|_| CoreError::InternalError)
+    }
+
+    /// Everything the vault keeps
+    fn payload(&self) -> Result<VaultPayload> {
+        Ok(VaultPayload {
+            wallets: self.records()?,
+            policy: Some(self.policy()?.state()),
+            audit: self.audit()?.state(),
+        })
+    }
+
+    /// Write the wallets, spending policy and audit log head to the attached
+    /// vault, if any.
+    /// The vault lock is held while reading them so saves land in order.
+    fn persist(&self) -> Result<()> {
+        let vault = self.vault.lock().map_err(|_| CoreError::InternalError)?;
+        match vault.as_ref() {
+            Some(vault) => vault.save(&self.payload()?),
+            None => Ok(()),
+        }
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_wallets() {
+        let keyring = Keyring::new();
+        let wallet = Wallet::from_entropy(&[7u8; 16], None).unwrap();
+        let wallet_id = wallet.id.clone();
+
+        keyring.clone().insert(wallet).unwrap();
+
+        assert!(keyring.contains(&wallet_id));
+        assert!(!Keyring::new().contains(&wallet_id));
+        assert!(keyring.remove(&wallet_id).unwrap());
+        assert!(!keyring.contains(&wallet_id));
+    }
+
+    #[test]
+    fn test_rejects_duplicate_key_material() {
+        let keyring = Keyring::new();
+        let wallet = Wallet::from_entropy(&[7u8; 16], None).unwrap();
+        let fingerprint = wallet.fingerprint();
+        let wallet_id = wallet.id.clone();
+        keyring.insert(wallet).unwrap();
+
+        let again = Wallet::from_entropy(&[7u8; 16], None).unwrap();
+        assert!(matches!(
+            keyring.insert(again),
+            Err(CoreError::DuplicateWallet)
+        ));
+        assert_eq!(keyring.wallet_ids().unwrap(), vec![wallet_id.clone()]);
+        assert_eq!(
+            keyring.find_by_fingerprint(&fingerprint).unwrap(),
+            Some(wallet_id)
+        );
+
+        // A passphrase makes it a different wallet
+        let other = Wallet::from_entropy(&[7u8; 16], Some("25th word")).unwrap();
+        assert!(keyring.insert(other).is_ok());
+    }
+
+    #[test]
+    fn test_vault_persists_across_keyrings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallets.vault");
+
+        let keyring = Keyring::new();
+        keyring
+            .create_vault_with_kdf(&path, "hunter2", KdfParams::argon2id_with_cost(256, 1))
+            .unwrap();
+
+        let wallet = Wallet::from_entropy(&[8u8; 16], None).unwrap();
+        let wallet_id = wallet.id.clone();
+        keyring.insert(wallet).unwrap();
+        keyring
+            .with_wallet_mut(&wallet_id, |w| w.derive_account(3))
+            .unwrap();
+
+        // A fresh process sees the same wallet and derived accounts
+        let restored = Keyring::new();
+        assert!(restored.unlock_vault(&path, "wrong").is_err());
+        assert_eq!(
+            restored.unlock_vault(&path, "hunter2").unwrap(),
+            vec![wallet_id.clone()]
+        );
+        assert_eq!(
+            restored
+                .with_wallet(&wallet_id, |w| Ok(w.to_wallet_data().accounts.len()))
+                .unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_lock_wipes_and_blocks_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallets.vault");
+
+        let keyring = Keyring::new();
+        let wallet = Wallet::from_entropy(&[6u8; 16], None).unwrap();
+        let wallet_id = wallet.id.clone();
+        keyring.insert(wallet).unwrap();
+        keyring
+            .create_vault_with_kdf(&path, "pw", KdfParams::pbkdf2(1000))
+            .unwrap();
+
+        keyring.lock().unwrap();
+        assert!(keyring.is_locked());
+        assert!(!keyring.contains(&wallet_id));
+        assert!(matches!(
+            keyring.insert(Wallet::from_entropy(&[1u8; 16], None).unwrap()),
+            Err(CoreError::VaultLocked)
+        ));
+
+        keyring.unlock("pw").unwrap();
+        assert!(keyring.contains(&wallet_id));
+
+        keyring.change_password("pw", "new-pw").unwrap();
+        keyring.lock().unwrap();
+        assert!(keyring.unlock("pw").is_err());
+        keyring.unlock("new-pw").unwrap();
+        assert!(keyring.contains(&wallet_id));
+    }
+
+    fn vault_keyring(dir: &tempfile::TempDir) -> (Keyring, String) {
+        let keyring = Keyring::new();
+        let wallet = Wallet::from_entropy(&[5u8; 16], None).unwrap();
+        let wallet_id = wallet.id.clone();
+        keyring.insert(wallet).unwrap();
+        keyring
+            .create_vault_with_kdf(
+                dir.path().join("wallets.vault"),
+                "pw",
+                KdfParams::pbkdf2(1000),
+            )
+            .unwrap();
+        (keyring, wallet_id)
+    }
+
+    #[test]
+    fn test_idle_timeout_locks_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let (keyring, wallet_id) = vault_keyring(&dir);
+        keyring
+            .set_session_policy(SessionPolicy {
+                idle_timeout_ms: 50,
+                ..SessionPolicy::default()
+            })
+            .unwrap();
+
+        assert!(!keyring.enforce_idle_timeout().unwrap());
+        assert!(keyring
+            .session_status()
+            .unwrap()
+            .idle_remaining_ms
+            .is_some());
+        std::thread::sleep(std::time::Duration::from_millis(80));
+
+        // The next access finds the session expired and locks
+        assert!(matches!(
+            keyring.with_wallet(&wallet_id, |w| Ok(w.id.clone())),
+            Err(CoreError::VaultLocked)
+        ));
+        assert!(keyring.is_locked());
+        assert!(!keyring.contains(&wallet_id));
+        assert!(keyring.session_status().unwrap().locked);
+
+        // Unlocking starts a fresh session
+        keyring.unlock("pw").unwrap();
+        assert!(keyring.with_wallet(&wallet_id, |_| Ok(())).is_ok());
+    }
+
+    #[test]
+    fn test_exports_need_password_proof() {
+        let dir = tempfile::tempdir().unwrap();
+        let (keyring, wallet_id) = vault_keyring(&dir);
+        let export = || keyring.with_sensitive_wallet(&wallet_id, |w| w.export_mnemonic());
+
+        assert!(matches!(export(), Err(CoreError::ReauthenticationRequired)));
+        assert!(keyring.authorize_sensitive("wrong").is_err());
+        keyring.authorize_sensitive("pw").unwrap();
+        assert!(export().is_ok());
+        assert!(keyring.session_status().unwrap().sensitive_remaining_ms > 0);
+
+        // The grant does not survive a lock
+        keyring.lock_all().unwrap();
+        keyring.unlock("pw").unwrap();
+        assert!(matches!(export(), Err(CoreError::ReauthenticationRequired)));
+
+        // Loosening the session policy needs the same proof; tightening does not
+        let never_locks = SessionPolicy {
+            idle_timeout_ms: 0,
+            ..SessionPolicy::default()
+        };
+        assert!(matches!(
+            keyring.set_session_policy(never_locks),
+            Err(CoreError::ReauthenticationRequired)
+        ));
+        keyring.authorize_sensitive("pw").unwrap();
+        keyring.set_session_policy(never_locks).unwrap();
+
+        // The grant runs out
+        keyring
+            .set_session_policy(SessionPolicy {
+                sensitive_window_ms: 0,
+                ..never_locks
+            })
+            .unwrap();
+        assert!(export().is_err());
+        assert!(matches!(
+            keyring.set_session_policy(SessionPolicy::default()),
+            Err(CoreError::ReauthenticationRequired)
+        ));
+
+        // Without a vault there is no password to prove, so exports fail closed
+        let plain = Keyring::new();
+        let wallet = Wallet::from_entropy(&[5u8; 16], None).unwrap();
+        let plain_id = wallet.id.clone();
+        plain.insert(wallet).unwrap();
+        assert!(matches!(
+            plain.with_sensitive_wallet(&plain_id, |w| w.export_mnemonic()),
+            Err(CoreError::ReauthenticationRequired)
+        ));
+        plain.lock_all().unwrap();
+        assert!(!plain.contains(&plain_id));
+    }
+}
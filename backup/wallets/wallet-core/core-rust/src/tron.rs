@@ -1,82 +1,330 @@
 use crate::{error::*, types::*};
-use crate::storage;
-use bs58;
+use crate::audit::SigningAction;
+use crate::policy::SpendRequest;
+use crate::signer::{EcdsaSignature, LocalSigner, Signer};
+use crate::storage::Keyring;
 use sha2::{Digest, Sha256};
-use secp256k1::{Message, Secp256k1, ecdsa::RecoverableSignature, ecdsa::RecoveryId};
 
+/// `protocol.Transaction.Contract.ContractType` values
+const TRANSFER_CONTRACT: u64 = 1;
+const TRIGGER_SMART_CONTRACT: u64 = 31;
+const TYPE_URL_PREFIX: &str = "type.googleapis.com/protocol.";
+
+/// Build a TRX transfer, or a contract call when `contract_address` is set.
+/// The txid is the SHA-256 of the protobuf-encoded `raw_data`.
 pub fn build_transaction(params: TronTxParams) -> Result<TronTransaction> {
-    // Simplified TRON transaction building
-    let raw_data = serde_json::json!({
+    let raw_data = encode_raw_data(&params)?;
+
+    Ok(TronTransaction {
+        txid: hex::encode(Sha256::digest(&raw_data)),
+        raw_data: raw_data_json(&params)?.to_string(),
+        raw_data_hex: hex::encode(raw_data),
+        signed_tx: String::new(),
+    })
+}
+
+/// `protocol.Transaction.raw` for `params`, as nodes hash and verify it
+fn encode_raw_data(params: &TronTxParams) -> Result<Vec<u8>> {
+    let (ref_block_bytes, ref_block_hash) = block_reference(&params.ref_block_id)?;
+    let expiration = non_negative(params.expiration)?;
+    let timestamp = non_negative(params.timestamp)?;
+    if expiration <= timestamp {
+        return Err(CoreError::InvalidTransaction);
+    }
+
+    let mut value = Protobuf::default();
+    value.bytes(1, &address_bytes(&params.from)?);
+    let contract_type = match &params.contract_address {
+        None => {
+            value.bytes(2, &address_bytes(&params.to)?);
+            value.varint(3, non_negative(params.amount)?);
+            TRANSFER_CONTRACT
+        }
+        Some(contract) => {
+            value.bytes(2, &address_bytes(contract)?);
+            value.varint(3, non_negative(params.amount)?);
+            value.bytes(4, &call_data(params)?);
+            TRIGGER_SMART_CONTRACT
+        }
+    };
+
+    let mut any = Protobuf::default();
+    any.bytes(1, type_url(contract_type).as_bytes());
+    any.bytes(2, &value.0);
+    let mut contract = Protobuf::default();
+    contract.varint(1, contract_type);
+    contract.bytes(2, &any.0);
+
+    let mut raw = Protobuf::default();
+    raw.bytes(1, &ref_block_bytes);
+    raw.bytes(4, &ref_block_hash);
+    raw.varint(8, expiration);
+    raw.bytes(11, &contract.0);
+    raw.varint(14, timestamp);
+    raw.varint(18, non_negative(params.fee_limit)?);
+    Ok(raw.0)
+}
+
+/// `raw_data` in the JSON form full nodes return, with hex addresses
+fn raw_data_json(params: &TronTxParams) -> Result<serde_json::Value> {
+    let (ref_block_bytes, ref_block_hash) = block_reference(&params.ref_block_id)?;
+    let owner_address = hex::encode(address_bytes(&params.from)?);
+    let (contract_type, value) = match &params.contract_address {
+        None => (
+            TRANSFER_CONTRACT,
+            serde_json::json!({
+                "amount": params.amount,
+                "owner_address": owner_address,
+                "to_address": hex::encode(address_bytes(&params.to)?),
+            }),
+        ),
+        Some(contract) => (
+            TRIGGER_SMART_CONTRACT,
+            serde_json::json!({
+                "call_value": params.amount,
+                "contract_address": hex::encode(address_bytes(contract)?),
+                "data": hex::encode(call_data(params)?),
+                "owner_address": owner_address,
+            }),
+        ),
+    };
+
+    let mut raw_data = serde_json::json!({
         "contract": [{
             "parameter": {
-                "value": {
-                    "amount": params.amount,
-                    "owner_address": params.from,
-                    "to_address": params.to,
-                },
-                "type_url": "type.googleapis.com/protocol.TransferContract"
+                "value": value,
+                "type_url": type_url(contract_type),
             },
-            "type": "TransferContract"
+            "type": contract_name(contract_type),
         }],
-        "ref_block_bytes": "0000",
-        "ref_block_hash": "0000000000000000",
-        "expiration": 0,
-        "fee_limit": params.fee_limit,
-        "timestamp": std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis(),
+        "ref_block_bytes": hex::encode(ref_block_bytes),
+        "ref_block_hash": hex::encode(ref_block_hash),
+        "expiration": params.expiration,
+        "timestamp": params.timestamp,
     });
+    if params.fee_limit != 0 {
+        raw_data["fee_limit"] = params.fee_limit.into();
+    }
+    Ok(raw_data)
+}
 
-    let txid = compute_txid(&raw_data.to_string())?;
+fn contract_name(contract_type: u64) -> &'static str {
+    match contract_type {
+        TRANSFER_CONTRACT => "TransferContract",
+        _ => "TriggerSmartContract",
+    }
+}
 
-    Ok(TronTransaction {
-        txid: hex::encode(txid),
-        raw_data: raw_data.to_string(),
-        signed_tx: String::new(),
-    })
+fn type_url(contract_type: u64) -> String {
+    format!("{}{}", TYPE_URL_PREFIX, contract_name(contract_type))
+}
+
+/// `ref_block_bytes` and `ref_block_hash` from a block id: bytes 6..8 (the
+/// low half of the block number) and 8..16
+fn block_reference(block_id: &str) -> Result<([u8; 2], [u8; 8])> {
+    let id = hex::decode(block_id.trim_start_matches("0x"))
+        .map_err(|_| CoreError::InvalidTransaction)?;
+    if id.len() != 32 {
+        return Err(CoreError::InvalidTransaction);
+    }
+    Ok((
+        id[6..8].try_into().expect("2 bytes"),
+        id[8..16].try_into().expect("8 bytes"),
+    ))
+}
+
+fn call_data(params: &TronTxParams) -> Result<Vec<u8>> {
+    let data = params.data.as_deref().unwrap_or_default();
+    hex::decode(data.trim_start_matches("0x")).map_err(|_| CoreError::InvalidTransaction)
+}
+
+fn non_negative(value: i64) -> Result<u64> {
+    u64::try_from(value).map_err(|_| CoreError::InvalidTransaction)
 }
 
+/// Protobuf writer for the few message shapes above. Zero and empty fields
+/// are skipped, as proto3 serializers do.
+#[derive(Default)]
+struct Protobuf(Vec<u8>);
+
+impl Protobuf {
+    fn varint(&mut self, field: u32, value: u64) {
+        if value != 0 {
+            self.raw_varint((field as u64) << 3);
+            self.raw_varint(value);
+        }
+    }
+
+    fn bytes(&mut self, field: u32, data: &[u8]) {
+        if !data.is_empty() {
+            self.raw_varint((field as u64) << 3 | 2);
+            self.raw_varint(data.len() as u64);
+            self.0.extend_from_slice(data);
+        }
+    }
+
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+}
+
+/// Sign a transaction with the wallet's TRON account at `account_index`,
+/// which must already be derived. An empty `from` defaults to that account.
 pub fn sign_transaction(
+    keyring: &Keyring,
     wallet_id: String,
     account_index: u32,
     params: TronTxParams,
+) -> Result<TronTransaction> {
+    let signer = LocalSigner::new(keyring, &wallet_id, Chain::Tron, account_index);
+    sign_transaction_audited(keyring, &signer, params)
+}
+
+/// [`sign_transaction_with`], subject to the keyring's spending policy and
+/// recorded in its audit log
+pub(crate) fn sign_transaction_audited(
+    keyring: &Keyring,
+    signer: &dyn Signer,
+    params: TronTxParams,
+) -> Result<TronTransaction> {
+    let action = SigningAction::transaction(SpendRequest::tron(&signer.address()?, &params)?);
+    keyring.sign_audited(signer, action, |signer| sign_transaction_with(signer, params))
+}
+
+/// [`sign_transaction`] with any signer for a TRON account
+pub fn sign_transaction_with(signer: &dyn Signer, mut params: TronTxParams) -> Result<TronTransaction> {
+    let address = signer.address()?;
+
+    if params.from.is_empty() {
+        params.from = address;
+    } else if params.from != address {
+        return Err(CoreError::InvalidAddress);
+    }
+
+    let mut tx = build_transaction(params)?;
+    let txid = hex::decode(&tx.txid).map_err(|_| CoreError::InternalError)?;
+    let signature = sign_digest(signer, &txid)?;
+
+    // Same shape TronWeb hands to `broadcastTransaction`
+    let raw_data: serde_json::Value = serde_json::from_str(&tx.raw_data)?;
+    tx.signed_tx = serde_json::json!({
+        "visible": false,
+        "txID": tx.txid,
+        "raw_data": raw_data,
+        "raw_data_hex": tx.raw_data_hex,
+        "signature": [signature],
+    })
+    .to_string();
+
+    Ok(tx)
+}
+
+pub fn sign_message(
+    keyring: &Keyring,
+    wallet_id: String,
+    account_index: u32,
+    message: String,
 ) -> Result<String> {
-    // Get wallet and secret key
-    let wallet = storage::get_wallet(&wallet_id)?;
-    let secret_key = wallet.get_secret_key(account_index)?;
-    
-    // Build transaction
-    let tx = build_transaction(params)?;
-    
-    // Sign transaction (TRON uses different signing, simplified for now)
-    // In production, this would properly sign the TRON transaction
-    Ok(tx.txid)
-}
-
-pub fn sign_message(wallet_id: String, account_index: u32, message: String) -> Result<String> {
-    // Get wallet and secret key
-    let wallet = storage::get_wallet(&wallet_id)?;
-    let secret_key = wallet.get_secret_key(account_index)?;
-    
-    // TRON message signing with ECDSA
+    let signer = LocalSigner::new(keyring, &wallet_id, Chain::Tron, account_index);
+    sign_message_audited(keyring, &signer, &message)
+}
+
+/// [`sign_message_with`], recorded in the keyring's audit log
+pub(crate) fn sign_message_audited(
+    keyring: &Keyring,
+    signer: &dyn Signer,
+    message: &str,
+) -> Result<String> {
+    let action = SigningAction::message(Chain::Tron, message.as_bytes());
+    keyring.sign_audited(signer, action, |signer| sign_message_with(signer, message))
+}
+
+/// TRON message signing with any signer
+pub fn sign_message_with(signer: &dyn Signer, message: &str) -> Result<String> {
+    sign_digest(signer, &message_hash(message))
+}
+
+/// Digest of a message under TRON's signed message prefix
+pub(crate) fn message_hash(message: &str) -> [u8; 32] {
     let prefix = format!("\x19TRON Signed Message:\n{}", message.len());
     let prefixed_message = format!("{}{}", prefix, message);
-    let hash = Sha256::digest(prefixed_message.as_bytes());
-    
-    // Sign using secp256k1 (recoverable signature)
-    let message_obj = Message::from_digest_slice(&hash)?;
-    let secp = Secp256k1::new();
-    let recoverable_sig = secp.sign_ecdsa_recoverable(&message_obj, &secret_key);
-    let (recovery_id, compact_sig) = recoverable_sig.serialize_compact();
-    
-    // Serialize signature - compact_sig is 64 bytes (32 r + 32 s)
-    let mut sig_bytes = [0u8; 65];
-    sig_bytes[0..32].copy_from_slice(&compact_sig[0..32]); // r
-    sig_bytes[32..64].copy_from_slice(&compact_sig[32..64]); // s
-    sig_bytes[64] = recovery_id.to_i32() as u8 + 27;
-    
-    Ok(hex::encode(sig_bytes))
+    Sha256::digest(prefixed_message.as_bytes()).into()
+}
+
+/// Transaction id and signature of `signed_tx`, after checking that its
+/// `raw_data` is exactly the transaction `params` describe. The signer is
+/// not checked.
+pub(crate) fn signed_transaction_signature(
+    params: &TronTxParams,
+    signed_tx: &str,
+) -> Result<([u8; 32], EcdsaSignature)> {
+    let signed: serde_json::Value =
+        serde_json::from_str(signed_tx).map_err(|_| CoreError::InvalidTransaction)?;
+    let raw_data = signed["raw_data_hex"]
+        .as_str()
+        .and_then(|raw| hex::decode(raw).ok())
+        .ok_or(CoreError::InvalidTransaction)?;
+    if raw_data != encode_raw_data(params)? || signed["raw_data"] != raw_data_json(params)? {
+        return Err(CoreError::InvalidTransaction);
+    }
+
+    let txid: [u8; 32] = Sha256::digest(&raw_data).into();
+    if signed["txID"].as_str() != Some(hex::encode(txid).as_str()) {
+        return Err(CoreError::InvalidTransaction);
+    }
+
+    let signature = signed["signature"][0]
+        .as_str()
+        .ok_or(CoreError::InvalidTransaction)?;
+    Ok((txid, EcdsaSignature::from_hex(signature)?))
+}
+
+/// Submit a signed transaction to a full node's HTTP API. Returns the txid.
+///
+/// The node receives the signed `raw_data` bytes as they are, so nothing is
+/// re-encoded between signing and broadcast.
+pub fn broadcast_transaction(signed_tx: String, node_url: String) -> Result<String> {
+    let signed: serde_json::Value =
+        serde_json::from_str(&signed_tx).map_err(|_| CoreError::InvalidTransaction)?;
+    let raw_data = signed["raw_data_hex"]
+        .as_str()
+        .and_then(|raw| hex::decode(raw).ok())
+        .ok_or(CoreError::InvalidTransaction)?;
+    let signatures = signed["signature"]
+        .as_array()
+        .filter(|signatures| !signatures.is_empty())
+        .ok_or(CoreError::InvalidTransaction)?;
+
+    // protocol.Transaction { raw raw_data = 1; repeated bytes signature = 2; }
+    let mut transaction = Protobuf::default();
+    transaction.bytes(1, &raw_data);
+    for signature in signatures {
+        let signature = signature
+            .as_str()
+            .and_then(|signature| hex::decode(signature).ok())
+            .ok_or(CoreError::InvalidTransaction)?;
+        transaction.bytes(2, &signature);
+    }
+
+    let client = crate::rpc::JsonRpcClient::new(node_url);
+    let body = serde_json::json!({ "transaction": hex::encode(transaction.0) });
+    let response: serde_json::Value =
+        serde_json::from_str(&client.post("/wallet/broadcasthex", &body)?)?;
+
+    match (response["result"].as_bool(), response["txid"].as_str()) {
+        (Some(true), Some(txid)) => Ok(txid.to_string()),
+        _ => Err(CoreError::RpcError),
+    }
+}
+
+/// Signature as unprefixed hex r || s || v, the form TRON nodes expect
+fn sign_digest(signer: &dyn Signer, digest: &[u8]) -> Result<String> {
+    let digest: &[u8; 32] = digest.try_into().map_err(|_| CoreError::InvalidInput)?;
+    Ok(hex::encode(signer.sign_digest(digest)?.to_rsv()))
 }
 
 pub fn validate_address(address: String) -> Result<bool> {
@@ -98,7 +346,7 @@ pub fn validate_address(address: String) -> Result<bool> {
             let addr_bytes = &decoded[0..21];
             let checksum = &decoded[21..25];
 
-            let hash = Sha256::digest(&Sha256::digest(addr_bytes));
+            let hash = Sha256::digest(Sha256::digest(addr_bytes));
             let computed_checksum = &hash[0..4];
 
             Ok(checksum == computed_checksum)
@@ -107,11 +355,32 @@ pub fn validate_address(address: String) -> Result<bool> {
     }
 }
 
-fn compute_txid(raw_data: &str) -> Result<[u8; 32]> {
-    let hash = Sha256::digest(raw_data.as_bytes());
-    let mut txid = [0u8; 32];
-    txid.copy_from_slice(&hash);
-    Ok(txid)
+/// Hex form of a TRON address, as TRON's Ethereum-compatible JSON-RPC expects
+pub(crate) fn address_to_hex(address: &str) -> Result<String> {
+    Ok(format!("0x{}", hex::encode(&address_bytes(address)?[1..])))
+}
+
+/// Base58check form of a 20-byte hex address, as TRC-20 call data holds them
+pub(crate) fn address_from_hex(address: &str) -> Result<String> {
+    let bytes = hex::decode(address.trim_start_matches("0x")).map_err(|_| CoreError::InvalidAddress)?;
+    if bytes.len() != 20 {
+        return Err(CoreError::InvalidAddress);
+    }
+    let mut full = vec![0x41];
+    full.extend_from_slice(&bytes);
+    Ok(bs58::encode(full).with_check().into_string())
+}
+
+/// The 21 bytes behind a base58check address, starting with 0x41
+fn address_bytes(address: &str) -> Result<Vec<u8>> {
+    let decoded = bs58::decode(address)
+        .with_check(None)
+        .into_vec()
+        .map_err(|_| CoreError::InvalidAddress)?;
+    if decoded.len() != 21 || decoded[0] != 0x41 {
+        return Err(CoreError::InvalidAddress);
+    }
+    Ok(decoded)
 }
 
 #[cfg(test)]
@@ -131,17 +400,160 @@ mod tests {
     }
 
     #[test]
-    fn test_build_transaction() {
-        let params = TronTxParams {
+    fn test_address_to_hex() {
+        assert_eq!(
+            address_to_hex("TMVQGm1qAQYVdetCeGRRkTWYYrLXuHK2HC").unwrap(),
+            "0x7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+        assert!(address_to_hex("TMVQGm1qAQYVdetCeGRRkTWYYrLXuHK2HD").is_err());
+    }
+
+    const BLOCK_ID: &str = "00000000036a3c0f8a5e2d7c41b9f0e3c7d1a2b3e4f5061728394a5b6c7d8e9f";
+
+    fn transfer() -> TronTxParams {
+        TronTxParams {
             from: "TLsV52sRDL79HXGGm9yzwKibb6BeruhUzy".to_string(),
-            to: "TM2TmqauSEiRxUPCoW8U8C6WKbW1m5BnCQ".to_string(),
+            to: "TMVQGm1qAQYVdetCeGRRkTWYYrLXuHK2HC".to_string(),
             amount: 1000000,
             contract_address: None,
             data: None,
-            fee_limit: 1000000,
+            fee_limit: 0,
+            ref_block_id: BLOCK_ID.to_string(),
+            expiration: 1700000060000,
+            timestamp: 1700000000000,
+        }
+    }
+
+    // Expected bytes come from a separate encoder written against the
+    // protocol.Transaction definitions in java-tron's Tron.proto
+    #[test]
+    fn test_build_transaction() {
+        let tx = build_transaction(transfer()).unwrap();
+        assert_eq!(
+            tx.raw_data_hex,
+            "0a023c0f22088a5e2d7c41b9f0e340e0a499ffbc315a67080112630a2d747970652e676f6f676c65\
+             617069732e636f6d2f70726f746f636f6c2e5472616e73666572436f6e747261637412320a1541\
+             77944d19c052b73ee2286823aa83f8138cb7032f1215417e5f4552091a69125d5dfcb7b8c26590\
+             29395bdf18c0843d7080d095ffbc31"
+        );
+        assert_eq!(
+            tx.txid,
+            "f0b34ca0108d9ff78ec18f7c4588340b84e7face9e96a691fb0f416c4226230d"
+        );
+
+        let raw_data: serde_json::Value = serde_json::from_str(&tx.raw_data).unwrap();
+        assert_eq!(raw_data["ref_block_bytes"], "3c0f");
+        assert_eq!(raw_data["ref_block_hash"], "8a5e2d7c41b9f0e3");
+        assert_eq!(
+            raw_data["contract"][0]["parameter"]["value"]["to_address"],
+            "417e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+        assert!(raw_data.get("fee_limit").is_none());
+
+        // TRC-20 transfer(to, 5 USDT) through TriggerSmartContract
+        let call = TronTxParams {
+            contract_address: Some("TR7NHqjeKQxGTCi8q8ZY4pL8otSzgjLj6t".to_string()),
+            data: Some(
+                "0xa9059cbb0000000000000000000000007e5f4552091a69125d5dfcb7b8c2659029395bdf\
+                 00000000000000000000000000000000000000000000000000000000004c4b40"
+                    .to_string(),
+            ),
+            amount: 0,
+            fee_limit: 100000000,
+            ..transfer()
+        };
+        assert_eq!(
+            build_transaction(call).unwrap().txid,
+            "e458005ab7cd6bce10bf9bc057aed79ae4ae34ff5915c3137c1c7b7de00d500c"
+        );
+    }
+
+    #[test]
+    fn test_build_transaction_rejects_bad_fields() {
+        for params in [
+            TronTxParams {
+                ref_block_id: "3c0f".to_string(),
+                ..transfer()
+            },
+            TronTxParams {
+                expiration: 0,
+                ..transfer()
+            },
+            TronTxParams {
+                amount: -1,
+                ..transfer()
+            },
+        ] {
+            assert!(matches!(
+                build_transaction(params),
+                Err(CoreError::InvalidTransaction)
+            ));
+        }
+        // Bad base58 checksum
+        let params = TronTxParams {
+            to: "TM2TmqauSEiRxUPCoW8U8C6WKbW1m5BnCQ".to_string(),
+            ..transfer()
         };
+        assert!(matches!(
+            build_transaction(params),
+            Err(CoreError::InvalidAddress)
+        ));
+    }
+
+    #[test]
+    fn test_sign_transaction_with_derived_account() {
+        use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+        use secp256k1::{Message, Secp256k1};
+
+        let keyring = Keyring::new();
+        let mut wallet = crate::crypto::Wallet::from_entropy(&[9u8; 16], None).unwrap();
+        let address = wallet.derive_chain_account(Chain::Tron, 0).unwrap().address;
+        let wallet_id = wallet.id.clone();
+        keyring.insert(wallet).unwrap();
 
-        let tx = build_transaction(params).unwrap();
-        assert!(!tx.txid.is_empty());
+        let params = TronTxParams {
+            from: String::new(),
+            ..transfer()
+        };
+
+        let tx = sign_transaction(&keyring, wallet_id.clone(), 0, params.clone()).unwrap();
+        let signed: serde_json::Value = serde_json::from_str(&tx.signed_tx).unwrap();
+        assert_eq!(signed["txID"], tx.txid);
+        assert_eq!(signed["raw_data_hex"], tx.raw_data_hex);
+        assert_eq!(
+            signed["raw_data"]["contract"][0]["parameter"]["value"]["owner_address"],
+            hex::encode(address_bytes(&address).unwrap())
+        );
+        let signed_params = TronTxParams {
+            from: address.clone(),
+            ..params.clone()
+        };
+        assert!(signed_transaction_signature(&signed_params, &tx.signed_tx).is_ok());
+        let other = TronTxParams {
+            amount: 2,
+            ..signed_params
+        };
+        assert!(signed_transaction_signature(&other, &tx.signed_tx).is_err());
+
+        // The signature recovers to the TRON account's key
+        let sig = hex::decode(signed["signature"][0].as_str().unwrap()).unwrap();
+        let recovery_id = RecoveryId::from_i32(sig[64] as i32 - 27).unwrap();
+        let recoverable = RecoverableSignature::from_compact(&sig[..64], recovery_id).unwrap();
+        let digest = Message::from_digest_slice(&hex::decode(&tx.txid).unwrap()).unwrap();
+        let public_key = Secp256k1::new().recover_ecdsa(&digest, &recoverable).unwrap();
+        assert_eq!(
+            crate::crypto::public_key_to_tron_address(&public_key),
+            address
+        );
+
+        let foreign = TronTxParams {
+            from: "TLsV52sRDL79HXGGm9yzwKibb6BeruhUzy".to_string(),
+            ..params.clone()
+        };
+        assert!(matches!(
+            sign_transaction(&keyring, wallet_id.clone(), 0, foreign),
+            Err(CoreError::InvalidAddress)
+        ));
+        assert!(sign_transaction(&keyring, wallet_id, 1, params).is_err());
     }
 }
@@ -10,31 +10,494 @@ pub enum LogLevel {
     Error,
 }
 
+/// Address format and coin type an account is derived for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Chain {
+    #[default]
+    Evm,
+    Tron,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
     pub address: String,
     pub public_key: String,
     pub index: u32,
     pub derivation_path: String,
+    pub chain: Chain,
+    pub metadata: AccountMetadata,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Wallet {
     pub id: String,
+    /// Stable across devices and re-imports; key sync on this rather than `id`
+    pub fingerprint: String,
+    /// Accounts by `metadata.sort_order`, then in derivation order
     pub accounts: Vec<Account>,
+    /// When the wallet was first created or imported on any device
     pub created_at: std::time::SystemTime,
+    pub metadata: WalletMetadata,
+}
+
+/// How a wallet is shown, persisted with it so every platform agrees
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WalletMetadata {
+    pub label: Option<String>,
+    /// Free-form, e.g. `#5b8def`
+    pub color: Option<String>,
+    /// Position in wallet lists, lowest first
+    pub sort_order: u32,
+    pub hidden: bool,
+}
+
+/// How an account is shown within its wallet
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountMetadata {
+    pub label: Option<String>,
+    pub color: Option<String>,
+    pub sort_order: u32,
+    pub hidden: bool,
+}
+
+/// HD path layout used to derive a wallet's accounts
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "scheme", rename_all = "snake_case")]
+pub enum DerivationScheme {
+    /// `m/44'/60'/0'/0/{index}`, used by MetaMask and most software wallets
+    #[default]
+    Bip44,
+    /// `m/44'/60'/{index}'/0/0`
+    LedgerLive,
+    /// `m/44'/60'/0'/{index}`, used by MyEtherWallet and the Ledger Chrome app
+    LegacyMew,
+    /// Any path with a single `{index}` placeholder, e.g. `m/44'/60'/1'/0/{index}`
+    Custom { template: String },
+}
+
+impl DerivationScheme {
+    pub const INDEX_PLACEHOLDER: &'static str = "{index}";
+
+    /// Every named scheme, in the order a scan reports them
+    pub fn known() -> Vec<DerivationScheme> {
+        vec![
+            DerivationScheme::Bip44,
+            DerivationScheme::LedgerLive,
+            DerivationScheme::LegacyMew,
+        ]
+    }
+
+    pub fn template(&self) -> &str {
+        match self {
+            DerivationScheme::Bip44 => "m/44'/60'/0'/0/{index}",
+            DerivationScheme::LedgerLive => "m/44'/60'/{index}'/0/0",
+            DerivationScheme::LegacyMew => "m/44'/60'/0'/{index}",
+            DerivationScheme::Custom { template } => template,
+        }
+    }
+
+    /// Concrete derivation path for account `index`
+    pub fn path(&self, index: u32) -> String {
+        self.template()
+            .replace(Self::INDEX_PLACEHOLDER, &index.to_string())
+    }
+}
+
+/// Extended public key of one account level, enough to watch its addresses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountXpub {
+    pub xpub: String,
+    pub chain: Chain,
+    /// Hardened path of the xpub, e.g. `m/44'/60'/0'`; empty when re-exported
+    /// from a watch-only wallet
+    pub account_path: String,
+    /// Path below the xpub, e.g. `0/{index}`
+    pub child_template: String,
+    /// BIP-32 fingerprint of the master key, if known
+    pub master_fingerprint: Option<u32>,
+}
+
+/// What `WalletManager::discover_accounts` scans
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryOptions {
+    pub chain: Chain,
+    /// EVM schemes to scan; empty means the wallet's own scheme
+    pub schemes: Vec<DerivationScheme>,
+    /// Consecutive unused accounts after which a scan stops
+    pub gap_limit: u32,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self {
+            chain: Chain::Evm,
+            schemes: Vec::new(),
+            gap_limit: 20,
+        }
+    }
+}
+
+/// How long a keyring session stays open, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionPolicy {
+    /// Lock the vault after this long without wallet access; 0 never locks
+    pub idle_timeout_ms: u64,
+    /// How long one password proof unlocks exports of secret material
+    pub sensitive_window_ms: u64,
+}
+
+impl Default for SessionPolicy {
+    fn default() -> Self {
+        Self {
+            idle_timeout_ms: 5 * 60 * 1000,
+            sensitive_window_ms: 60 * 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionStatus {
+    pub locked: bool,
+    /// Time until the idle timeout locks the vault; None if it never will
+    pub idle_remaining_ms: Option<u64>,
+    /// Time left to export secrets without a new password proof
+    pub sensitive_remaining_ms: u64,
+}
+
+/// Protocol spoken by a remote signing service
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RemoteSignerApi {
+    /// `eth_sign`, `eth_signTypedData_v4` and `eth_signTransaction` over JSON-RPC
+    JsonRpc,
+    /// Web3Signer's REST `/api/v1/eth1/sign/{public key}`
+    Web3Signer,
+}
+
+/// Account held by a remote signing service
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteSignerConfig {
+    pub url: String,
+    pub api: RemoteSignerApi,
+    /// Required for JSON-RPC unless `public_key` is given
+    pub address: Option<String>,
+    /// Uncompressed or compressed hex; required for Web3Signer
+    pub public_key: Option<String>,
+}
+
+/// Why a spending policy refused to let a signature be produced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyViolation {
+    /// `id` of the rule in the policy JSON
+    pub rule_id: String,
+    /// Its `type`, e.g. `daily_limit`
+    pub rule_type: String,
+    pub reason: String,
+}
+
+/// What kind of payload a signature was produced over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SigningOperation {
+    Transaction,
+    /// `personal_sign` or TRON's signed message
+    Message,
+    /// EIP-712
+    TypedData,
+    /// ERC-4337
+    UserOperation,
+}
+
+/// Outcome of the spending policy for a signing request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyDecision {
+    Allowed,
+    Blocked,
+    /// Messages and typed data carry no value for the policy to check
+    NotApplicable,
+}
+
+/// One signing operation in the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the log, from 0
+    pub sequence: u64,
+    /// Unix seconds
+    pub timestamp: u64,
+    pub chain: Chain,
+    pub account: String,
+    pub operation: SigningOperation,
+    /// `0x` hex of the 32 bytes signed; None when the policy blocked it
+    pub digest: Option<String>,
+    /// What was signed, for people reading the log
+    pub summary: String,
+    pub decision: PolicyDecision,
+    pub violation: Option<PolicyViolation>,
+    /// `hash` of the previous entry, zeros for the first
+    pub prev_hash: String,
+    /// SHA-256 of `prev_hash` and the rest of this entry
+    pub hash: String,
 }
 
+/// Result of checking the audit log's hash chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditVerification {
+    pub valid: bool,
+    /// Entries read from the log
+    pub entries: u64,
+    /// Sequence number of the first entry that does not check out
+    pub first_invalid: Option<u64>,
+    pub reason: Option<String>,
+}
+
+/// Payload of an offline signing request
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SigningPayload {
+    EvmTransaction {
+        params: EvmTxParams,
+    },
+    /// `eth_signTypedData_v4` JSON
+    TypedData {
+        typed_data_json: String,
+    },
+    /// `personal_sign` on EVM, TRON's signed message on TRON
+    PersonalMessage {
+        chain: Chain,
+        message: String,
+    },
+    UserOperation {
+        params: UserOpParams,
+    },
+    TronTransaction {
+        params: TronTxParams,
+    },
+}
+
+/// What the signing device shows before asking for confirmation. Worked out
+/// again from the payload when a request is parsed; only the origin is taken
+/// on trust.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayHints {
+    pub title: String,
+    pub summary: String,
+    /// Network name, e.g. "Nor Chain"
+    pub network: Option<String>,
+    /// dApp or site that asked for the signature, as the requester claims
+    pub origin: Option<String>,
+}
+
+/// Envelope asking an offline wallet to sign for one of its accounts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningRequest {
+    pub version: u32,
+    /// Random, echoed back by the response
+    pub request_id: String,
+    pub chain: Chain,
+    /// EVM chain id the payload commits to, if any
+    pub chain_id: Option<u64>,
+    /// Address expected to sign
+    pub account: String,
+    pub account_index: u32,
+    pub derivation_path: String,
+    pub payload: SigningPayload,
+    pub display: DisplayHints,
+    /// Unix seconds
+    pub created_at: u64,
+}
+
+/// Envelope carrying an offline wallet's signature back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningResponse {
+    pub version: u32,
+    pub request_id: String,
+    pub account: String,
+    /// Hex r || s || v in the form the chain's signing call returns
+    pub signature: String,
+    /// Broadcastable transaction, for transaction payloads
+    pub signed_tx: Option<String>,
+    /// Its hash or TRON txid
+    pub tx_hash: Option<String>,
+}
+
+/// Decoded Uniform Resource: its type and CBOR body
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UrPayload {
+    pub ur_type: String,
+    pub cbor: Vec<u8>,
+}
+
+/// What the `sign_data` of an ERC-4527 request holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EthDataType {
+    /// Unsigned legacy transaction, EIP-155 RLP
+    Transaction,
+    /// `eth_signTypedData_v4` JSON
+    TypedData,
+    /// Raw `personal_sign` message bytes
+    PersonalMessage,
+    /// Unsigned EIP-2718 typed transaction
+    TypedTransaction,
+}
+
+/// ERC-4527 `eth-sign-request`, sent by a watch-only wallet to a QR signer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EthSignRequest {
+    /// UUID, hyphenated
+    pub request_id: Option<String>,
+    pub sign_data: Vec<u8>,
+    pub data_type: EthDataType,
+    pub chain_id: Option<u64>,
+    /// e.g. `m/44'/60'/0'/0/0`
+    pub derivation_path: String,
+    /// Master key fingerprint the path starts from
+    pub source_fingerprint: Option<u32>,
+    pub address: Option<String>,
+    pub origin: Option<String>,
+}
+
+/// ERC-4527 `eth-signature`, the QR signer's answer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EthSignature {
+    pub request_id: Option<String>,
+    /// Hex r || s || v, with v as the transaction or message encodes it
+    pub signature: String,
+    pub origin: Option<String>,
+}
+
+/// A token the user added by hand
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomToken {
+    pub chain_id: u64,
+    pub address: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub name: Option<String>,
+}
+
+/// App settings carried in a backup bundle next to the wallets, whose own
+/// labels travel inside them. Missing sections read as empty, so older apps'
+/// bundles stay restorable.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BackupSettings {
+    #[serde(default)]
+    pub networks: Vec<crate::network::NetworkInfo>,
+    #[serde(default)]
+    pub tokens: Vec<CustomToken>,
+}
+
+/// Outcome of restoring a backup bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupRestore {
+    /// Format version the bundle was written with
+    pub version: u32,
+    /// Ids of the wallets added to the keyring
+    pub restored: Vec<String>,
+    /// Ids of bundled wallets whose key material was already present
+    pub skipped: Vec<String>,
+    pub settings: BackupSettings,
+}
+
+/// An account with on-chain activity found during discovery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveredAccount {
+    pub account: Account,
+    /// None for TRON and watch-only wallets, whose layout is fixed
+    pub scheme: Option<DerivationScheme>,
+    pub balance: String,
+    /// Always 0 on TRON, which has no account nonce
+    pub nonce: u64,
+}
+
+/// Addresses a mnemonic yields under one derivation scheme
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemeAccounts {
+    pub scheme: DerivationScheme,
+    pub accounts: Vec<Account>,
+}
+
+/// BIP-39 wordlist a mnemonic is written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MnemonicLanguage {
+    #[default]
+    English,
+    ChineseSimplified,
+    ChineseTraditional,
+    Czech,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Portuguese,
+    Spanish,
+}
+
+/// Outcome of checking a (possibly partly typed) mnemonic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum MnemonicValidation {
+    Valid {
+        language: MnemonicLanguage,
+    },
+    /// `index` is the zero-based position of the first word not in the wordlist
+    UnknownWord {
+        index: u32,
+        word: String,
+    },
+    /// Every word is known but there are not 12, 15, 18, 21 or 24 of them
+    BadWordCount {
+        count: u32,
+    },
+    InvalidChecksum {
+        language: MnemonicLanguage,
+    },
+    /// The words appear in several wordlists and no language was given
+    AmbiguousLanguage {
+        candidates: Vec<MnemonicLanguage>,
+    },
+}
+
+/// One SLIP-39 group: how many of its member shares recover it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Slip39Group {
+    pub member_threshold: u8,
+    pub member_count: u8,
+}
+
+/// EIP-2718 transaction envelope type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvmTxType {
+    Legacy,
+    Eip2930,
+    Eip1559,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessListItem {
+    pub address: String,
+    pub storage_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct EvmTxParams {
     pub from: String,
     pub to: String,
     pub value: String,
     pub data: Option<String>,
     pub gas_limit: u64,
+    /// Used by legacy and EIP-2930 transactions
     pub gas_price: String,
     pub nonce: u64,
     pub chain_id: u64,
+    /// Inferred from the fee fields when not set
+    pub tx_type: Option<EvmTxType>,
+    pub max_fee_per_gas: Option<String>,
+    pub max_priority_fee_per_gas: Option<String>,
+    pub access_list: Option<Vec<AccessListItem>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,6 +530,9 @@ pub struct UserOpParams {
     pub max_priority_fee_per_gas: String,
     pub paymaster_and_data: String,
     pub signature: String,
+    /// Chain the operation is meant for, which the hash then commits to
+    #[serde(default)]
+    pub chain_id: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -129,12 +595,21 @@ pub struct TronTxParams {
     pub contract_address: Option<String>,
     pub data: Option<String>,
     pub fee_limit: i64,
+    /// `blockID` of a recent block, which the transaction is bound to
+    pub ref_block_id: String,
+    /// Milliseconds since the epoch after which nodes reject the transaction
+    pub expiration: i64,
+    /// Creation time in milliseconds since the epoch
+    pub timestamp: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TronTransaction {
     pub txid: String,
+    /// `raw_data` as JSON with hex addresses
     pub raw_data: String,
+    /// Protobuf-encoded `raw_data`; `txid` is its SHA-256
+    pub raw_data_hex: String,
     pub signed_tx: String,
 }
 
@@ -0,0 +1,81 @@
+// Bytewords (BCR-2020-012), minimal style as used in UR strings
+// Each byte maps to a four-letter word; the minimal form keeps its first and
+// last letter. A big-endian CRC-32 of the payload is appended before encoding
+
+use crate::error::*;
+
+const WORDLIST: &str = include_str!("bytewords.txt");
+
+lazy_static::lazy_static! {
+    static ref WORDS: Vec<&'static str> = WORDLIST.split_whitespace().collect();
+    static ref MINIMAL: Vec<[u8; 2]> = WORDS
+        .iter()
+        .map(|word| [word.as_bytes()[0], word.as_bytes()[3]])
+        .collect();
+}
+
+/// CRC-32 (IEEE 802.3, the zlib polynomial)
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+pub(crate) fn encode_minimal(data: &[u8]) -> String {
+    let checksum = crc32(data).to_be_bytes();
+    data.iter()
+        .chain(checksum.iter())
+        .flat_map(|byte| MINIMAL[*byte as usize])
+        .map(char::from)
+        .collect()
+}
+
+/// Decodes case-insensitively and checks the trailing checksum
+pub(crate) fn decode_minimal(text: &str) -> Result<Vec<u8>> {
+    let letters = text.to_ascii_lowercase().into_bytes();
+    if !letters.len().is_multiple_of(2) || letters.len() < 10 {
+        return Err(CoreError::InvalidUr);
+    }
+    let mut bytes = letters
+        .chunks(2)
+        .map(|pair| {
+            MINIMAL
+                .iter()
+                .position(|minimal| minimal == pair)
+                .map(|index| index as u8)
+                .ok_or(CoreError::InvalidUr)
+        })
+        .collect::<Result<Vec<u8>>>()?;
+    let checksum = bytes.split_off(bytes.len() - 4);
+    if crc32(&bytes).to_be_bytes() != checksum[..] {
+        return Err(CoreError::InvalidUr);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytewords_vectors() {
+        assert_eq!(WORDS.len(), 256);
+        assert_eq!(crc32(b"Hello, world!"), 0xebe6c6e6);
+        assert_eq!(crc32(b"Wolf"), 0x598c84dc);
+
+        let data = [0, 1, 2, 128, 255];
+        assert_eq!(encode_minimal(&data), "aeadaolazmjendeoti");
+        assert_eq!(decode_minimal("AEADAOLAZMJENDEOTI").unwrap(), data);
+
+        // Bad checksum, unknown pair, odd length
+        assert!(decode_minimal("aeadaolazmjendeotu").is_err());
+        assert!(decode_minimal("aeadaolazmjendeoxx").is_err());
+        assert!(decode_minimal("aeadaolazmjendeot").is_err());
+    }
+}
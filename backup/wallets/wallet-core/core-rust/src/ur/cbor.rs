@@ -0,0 +1,285 @@
+// Minimal CBOR (RFC 8949) for the UR registry types
+// Only what the registry needs: unsigned and negative integers, byte and text
+// strings, arrays, maps, tags and booleans. Always emits the shortest head;
+// rejects indefinite lengths and floats
+
+use crate::error::*;
+
+/// Nesting limit when decoding untrusted input
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Cbor {
+    Unsigned(u64),
+    /// Encodes -1 - n
+    Negative(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Tag(u64, Box<Cbor>),
+    Bool(bool),
+}
+
+impl Cbor {
+    pub(crate) fn tagged(tag: u64, value: Cbor) -> Self {
+        Cbor::Tag(tag, Box::new(value))
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write(&mut out);
+        out
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Cbor::Unsigned(n) => write_head(out, 0, *n),
+            Cbor::Negative(n) => write_head(out, 1, *n),
+            Cbor::Bytes(bytes) => {
+                write_head(out, 2, bytes.len() as u64);
+                out.extend_from_slice(bytes);
+            }
+            Cbor::Text(text) => {
+                write_head(out, 3, text.len() as u64);
+                out.extend_from_slice(text.as_bytes());
+            }
+            Cbor::Array(items) => {
+                write_head(out, 4, items.len() as u64);
+                for item in items {
+                    item.write(out);
+                }
+            }
+            Cbor::Map(entries) => {
+                write_head(out, 5, entries.len() as u64);
+                for (key, value) in entries {
+                    key.write(out);
+                    value.write(out);
+                }
+            }
+            Cbor::Tag(tag, value) => {
+                write_head(out, 6, *tag);
+                value.write(out);
+            }
+            Cbor::Bool(value) => out.push(if *value { 0xf5 } else { 0xf4 }),
+        }
+    }
+
+    /// Decodes exactly one item spanning all of `data`
+    pub(crate) fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader { data, pos: 0 };
+        let value = reader.item(0)?;
+        if reader.pos != data.len() {
+            return Err(CoreError::InvalidUr);
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn as_unsigned(&self) -> Result<u64> {
+        match self {
+            Cbor::Unsigned(n) => Ok(*n),
+            _ => Err(CoreError::InvalidUr),
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> Result<&[u8]> {
+        match self {
+            Cbor::Bytes(bytes) => Ok(bytes),
+            _ => Err(CoreError::InvalidUr),
+        }
+    }
+
+    pub(crate) fn as_text(&self) -> Result<&str> {
+        match self {
+            Cbor::Text(text) => Ok(text),
+            _ => Err(CoreError::InvalidUr),
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Result<&[Cbor]> {
+        match self {
+            Cbor::Array(items) => Ok(items),
+            _ => Err(CoreError::InvalidUr),
+        }
+    }
+
+    pub(crate) fn as_map(&self) -> Result<&[(Cbor, Cbor)]> {
+        match self {
+            Cbor::Map(entries) => Ok(entries),
+            _ => Err(CoreError::InvalidUr),
+        }
+    }
+
+    pub(crate) fn as_bool(&self) -> Result<bool> {
+        match self {
+            Cbor::Bool(value) => Ok(*value),
+            _ => Err(CoreError::InvalidUr),
+        }
+    }
+
+    /// Content of a `tag`-tagged item
+    pub(crate) fn untag(&self, tag: u64) -> Result<&Cbor> {
+        match self {
+            Cbor::Tag(found, value) if *found == tag => Ok(value),
+            _ => Err(CoreError::InvalidUr),
+        }
+    }
+
+    /// Value under an unsigned integer key of a map
+    pub(crate) fn get(&self, key: u64) -> Result<Option<&Cbor>> {
+        Ok(self
+            .as_map()?
+            .iter()
+            .find(|(k, _)| *k == Cbor::Unsigned(key))
+            .map(|(_, value)| value))
+    }
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < 24 {
+        out.push(major | value as u8);
+    } else if value <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(value as u8);
+    } else if value <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).ok_or(CoreError::InvalidUr)?;
+        let bytes = self.data.get(self.pos..end).ok_or(CoreError::InvalidUr)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn head(&mut self) -> Result<(u8, u8, u64)> {
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let value = match info {
+            0..=23 => info as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            _ => return Err(CoreError::InvalidUr),
+        };
+        Ok((major, info, value))
+    }
+
+    /// Length of a string or container, bounded by the remaining input so a
+    /// forged header cannot trigger a huge allocation
+    fn length(&self, value: u64) -> Result<usize> {
+        let remaining = (self.data.len() - self.pos) as u64;
+        if value > remaining {
+            return Err(CoreError::InvalidUr);
+        }
+        Ok(value as usize)
+    }
+
+    fn item(&mut self, depth: usize) -> Result<Cbor> {
+        if depth > MAX_DEPTH {
+            return Err(CoreError::InvalidUr);
+        }
+        let (major, info, value) = self.head()?;
+        Ok(match major {
+            0 => Cbor::Unsigned(value),
+            1 => Cbor::Negative(value),
+            2 => {
+                let len = self.length(value)?;
+                Cbor::Bytes(self.take(len)?.to_vec())
+            }
+            3 => {
+                let len = self.length(value)?;
+                let text =
+                    std::str::from_utf8(self.take(len)?).map_err(|_| CoreError::InvalidUr)?;
+                Cbor::Text(text.to_string())
+            }
+            4 => {
+                let len = self.length(value)?;
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..len {
+                    items.push(self.item(depth + 1)?);
+                }
+                Cbor::Array(items)
+            }
+            5 => {
+                let len = self.length(value)?;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let key = self.item(depth + 1)?;
+                    entries.push((key, self.item(depth + 1)?));
+                }
+                Cbor::Map(entries)
+            }
+            6 => Cbor::Tag(value, Box::new(self.item(depth + 1)?)),
+            7 if info == 20 => Cbor::Bool(false),
+            7 if info == 21 => Cbor::Bool(true),
+            _ => return Err(CoreError::InvalidUr),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let value = Cbor::Map(vec![
+            (
+                Cbor::Unsigned(1),
+                Cbor::tagged(37, Cbor::Bytes(vec![0xab; 16])),
+            ),
+            (Cbor::Unsigned(2), Cbor::Text("nor".into())),
+            (
+                Cbor::Unsigned(3),
+                Cbor::Array(vec![
+                    Cbor::Unsigned(44),
+                    Cbor::Bool(true),
+                    Cbor::Negative(0),
+                ]),
+            ),
+            (Cbor::Unsigned(4), Cbor::Unsigned(u32::MAX as u64 + 1)),
+        ]);
+        let encoded = value.encode();
+        assert_eq!(&encoded[..4], &[0xa4, 0x01, 0xd8, 0x25]);
+        assert_eq!(Cbor::decode(&encoded).unwrap(), value);
+
+        // Shortest heads (RFC 8949 appendix A)
+        assert_eq!(Cbor::Unsigned(23).encode(), vec![0x17]);
+        assert_eq!(Cbor::Unsigned(24).encode(), vec![0x18, 0x18]);
+        assert_eq!(Cbor::Unsigned(1000).encode(), vec![0x19, 0x03, 0xe8]);
+        assert_eq!(Cbor::Negative(99).encode(), vec![0x38, 0x63]);
+    }
+
+    #[test]
+    fn test_cbor_rejects_malformed() {
+        // Trailing byte, truncated string, forged length, indefinite array, float
+        for data in [
+            &[0x01, 0x02][..],
+            &[0x43, 0x01, 0x02],
+            &[0x5b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            &[0x9f, 0x01, 0xff],
+            &[0xf9, 0x3c, 0x00],
+        ] {
+            assert!(matches!(Cbor::decode(data), Err(CoreError::InvalidUr)));
+        }
+        let nested = [vec![0x81; MAX_DEPTH + 2], vec![0x01]].concat();
+        assert!(Cbor::decode(&nested).is_err());
+    }
+}
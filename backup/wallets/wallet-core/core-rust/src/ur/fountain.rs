@@ -0,0 +1,372 @@
+// Fountain codes for multipart URs (BCR-2020-005)
+// The message is split into equal fragments. Parts 1..=n carry one fragment
+// each; later parts XOR a pseudo-random subset chosen by a generator seeded
+// from the part number and message checksum, so a scanner can finish from any
+// large enough mix of parts, in any order
+
+use std::collections::{BTreeSet, HashMap};
+
+use super::bytewords::crc32;
+use super::cbor::Cbor;
+use crate::error::*;
+use sha2::{Digest, Sha256};
+
+/// Upper bound on fragments per message; keeps sampler tables small
+const MAX_FRAGMENTS: usize = 10_000;
+
+/// Xoshiro256** seeded with the SHA-256 of the seed, as in the reference
+/// implementation
+pub(crate) struct Xoshiro256 {
+    state: [u64; 4],
+}
+
+impl Xoshiro256 {
+    pub(crate) fn new(seed: &[u8]) -> Self {
+        let hash = Sha256::digest(seed);
+        let mut state = [0u64; 4];
+        for (word, chunk) in state.iter_mut().zip(hash.chunks(8)) {
+            *word = u64::from_be_bytes(chunk.try_into().unwrap());
+        }
+        Self { state }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn next_double(&mut self) -> f64 {
+        self.next_u64() as f64 / (u64::MAX as f64 + 1.0)
+    }
+
+    /// Uniform in `low..=high`
+    pub(crate) fn next_int(&mut self, low: u64, high: u64) -> u64 {
+        (self.next_double() * (high - low + 1) as f64) as u64 + low
+    }
+}
+
+/// Walker/Vose alias sampler over the weights `1/1, 1/2, .., 1/n`
+fn choose_degree(fragment_count: usize, rng: &mut Xoshiro256) -> usize {
+    let weights: Vec<f64> = (1..=fragment_count).map(|i| 1.0 / i as f64).collect();
+    let sum: f64 = weights.iter().sum();
+    let n = weights.len();
+    let mut scaled: Vec<f64> = weights.iter().map(|w| w * n as f64 / sum).collect();
+    let (mut small, mut large) = (Vec::new(), Vec::new());
+    for i in (0..n).rev() {
+        if scaled[i] < 1.0 {
+            small.push(i);
+        } else {
+            large.push(i);
+        }
+    }
+    let mut probabilities = vec![0.0; n];
+    let mut aliases = vec![0usize; n];
+    while !small.is_empty() && !large.is_empty() {
+        let a = small.pop().unwrap();
+        let g = large.pop().unwrap();
+        probabilities[a] = scaled[a];
+        aliases[a] = g;
+        scaled[g] += scaled[a] - 1.0;
+        if scaled[g] < 1.0 {
+            small.push(g);
+        } else {
+            large.push(g);
+        }
+    }
+    for i in large.into_iter().chain(small) {
+        probabilities[i] = 1.0;
+    }
+
+    let r1 = rng.next_double();
+    let r2 = rng.next_double();
+    let i = (n as f64 * r1) as usize;
+    let index = if r2 < probabilities[i] { i } else { aliases[i] };
+    index + 1
+}
+
+/// Indexes of the fragments XORed into part `sequence`
+pub(crate) fn choose_fragments(
+    sequence: u32,
+    fragment_count: usize,
+    checksum: u32,
+) -> BTreeSet<usize> {
+    if sequence as usize <= fragment_count {
+        return BTreeSet::from([sequence as usize - 1]);
+    }
+    let seed = [sequence.to_be_bytes(), checksum.to_be_bytes()].concat();
+    let mut rng = Xoshiro256::new(&seed);
+    let degree = choose_degree(fragment_count, &mut rng);
+    let mut remaining: Vec<usize> = (0..fragment_count).collect();
+    let mut chosen = BTreeSet::new();
+    while chosen.len() < degree {
+        let index = rng.next_int(0, remaining.len() as u64 - 1) as usize;
+        chosen.insert(remaining.remove(index));
+    }
+    chosen
+}
+
+/// Smallest fragment length that splits the message into fragments no
+/// longer than `max_fragment_len`
+fn fragment_length(message_len: usize, min_fragment_len: usize, max_fragment_len: usize) -> usize {
+    let max_count = (message_len / min_fragment_len).max(1);
+    let mut length = message_len;
+    for count in 1..=max_count {
+        length = message_len.div_ceil(count);
+        if length <= max_fragment_len {
+            break;
+        }
+    }
+    length
+}
+
+fn xor_into(target: &mut [u8], source: &[u8]) {
+    for (t, s) in target.iter_mut().zip(source) {
+        *t ^= s;
+    }
+}
+
+/// One fountain-coded part, CBOR `[seq, count, message_len, checksum, data]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Part {
+    pub sequence: u32,
+    pub fragment_count: usize,
+    pub message_len: usize,
+    pub checksum: u32,
+    pub data: Vec<u8>,
+}
+
+impl Part {
+    pub(crate) fn to_cbor(&self) -> Vec<u8> {
+        Cbor::Array(vec![
+            Cbor::Unsigned(self.sequence as u64),
+            Cbor::Unsigned(self.fragment_count as u64),
+            Cbor::Unsigned(self.message_len as u64),
+            Cbor::Unsigned(self.checksum as u64),
+            Cbor::Bytes(self.data.clone()),
+        ])
+        .encode()
+    }
+
+    pub(crate) fn from_cbor(data: &[u8]) -> Result<Self> {
+        let cbor = Cbor::decode(data)?;
+        let [sequence, count, message_len, checksum, data] = cbor.as_array()? else {
+            return Err(CoreError::InvalidUr);
+        };
+        let to_u32 = |value: &Cbor| -> Result<u32> {
+            u32::try_from(value.as_unsigned()?).map_err(|_| CoreError::InvalidUr)
+        };
+        let part = Self {
+            sequence: to_u32(sequence)?,
+            fragment_count: to_u32(count)? as usize,
+            message_len: to_u32(message_len)? as usize,
+            checksum: to_u32(checksum)?,
+            data: data.as_bytes()?.to_vec(),
+        };
+        if part.sequence == 0
+            || part.fragment_count == 0
+            || part.fragment_count > MAX_FRAGMENTS
+            || part.data.is_empty()
+            || part.message_len > part.fragment_count * part.data.len()
+        {
+            return Err(CoreError::InvalidUr);
+        }
+        Ok(part)
+    }
+}
+
+pub(crate) struct FountainEncoder {
+    fragments: Vec<Vec<u8>>,
+    message_len: usize,
+    checksum: u32,
+    sequence: u32,
+}
+
+impl FountainEncoder {
+    pub(crate) fn new(message: &[u8], max_fragment_len: usize) -> Result<Self> {
+        if message.is_empty() || max_fragment_len == 0 {
+            return Err(CoreError::InvalidInput);
+        }
+        let length = fragment_length(message.len(), 10.min(max_fragment_len), max_fragment_len);
+        if message.len().div_ceil(length) > MAX_FRAGMENTS {
+            return Err(CoreError::InvalidInput);
+        }
+        let fragments = message
+            .chunks(length)
+            .map(|chunk| {
+                let mut fragment = chunk.to_vec();
+                fragment.resize(length, 0);
+                fragment
+            })
+            .collect();
+        Ok(Self {
+            fragments,
+            message_len: message.len(),
+            checksum: crc32(message),
+            sequence: 0,
+        })
+    }
+
+    pub(crate) fn fragment_count(&self) -> usize {
+        self.fragments.len()
+    }
+
+    pub(crate) fn next_part(&mut self) -> Part {
+        self.sequence = self.sequence.wrapping_add(1).max(1);
+        let indexes = choose_fragments(self.sequence, self.fragments.len(), self.checksum);
+        let mut data = vec![0u8; self.fragments[0].len()];
+        for index in indexes {
+            xor_into(&mut data, &self.fragments[index]);
+        }
+        Part {
+            sequence: self.sequence,
+            fragment_count: self.fragments.len(),
+            message_len: self.message_len,
+            checksum: self.checksum,
+            data,
+        }
+    }
+}
+
+/// Collects parts until the message can be rebuilt
+#[derive(Default)]
+pub(crate) struct FountainDecoder {
+    // (fragment count, message length, checksum, fragment length) of the first part
+    expected: Option<(usize, usize, u32, usize)>,
+    simple: HashMap<usize, Vec<u8>>,
+    mixed: Vec<(BTreeSet<usize>, Vec<u8>)>,
+    message: Option<Vec<u8>>,
+}
+
+impl FountainDecoder {
+    pub(crate) fn message(&self) -> Option<&[u8]> {
+        self.message.as_deref()
+    }
+
+    /// Fraction of fragments recovered so far
+    pub(crate) fn progress(&self) -> f64 {
+        match (&self.message, self.expected) {
+            (Some(_), _) => 1.0,
+            (None, Some((count, ..))) => self.simple.len() as f64 / count as f64,
+            (None, None) => 0.0,
+        }
+    }
+
+    /// Feeds a part; true once the message is complete. Fails on parts of a
+    /// different message or a rebuilt message with a bad checksum.
+    pub(crate) fn receive(&mut self, part: Part) -> Result<bool> {
+        if self.message.is_some() {
+            return Ok(true);
+        }
+        let shape = (
+            part.fragment_count,
+            part.message_len,
+            part.checksum,
+            part.data.len(),
+        );
+        if *self.expected.get_or_insert(shape) != shape {
+            return Err(CoreError::InvalidUr);
+        }
+
+        let indexes = choose_fragments(part.sequence, part.fragment_count, part.checksum);
+        let mut queue = vec![(indexes, part.data)];
+        while let Some((mut indexes, mut data)) = queue.pop() {
+            for index in indexes.clone() {
+                if let Some(fragment) = self.simple.get(&index) {
+                    xor_into(&mut data, fragment);
+                    indexes.remove(&index);
+                }
+            }
+            match indexes.len() {
+                0 => {}
+                1 => {
+                    let index = *indexes.iter().next().unwrap();
+                    // Any stored mix containing the new fragment can shrink now
+                    let (ready, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.mixed)
+                        .into_iter()
+                        .partition(|(mixed, _)| mixed.contains(&index));
+                    self.mixed = waiting;
+                    self.simple.insert(index, data);
+                    queue.extend(ready);
+                }
+                _ => {
+                    if !self.mixed.iter().any(|(mixed, _)| *mixed == indexes) {
+                        self.mixed.push((indexes, data));
+                    }
+                }
+            }
+        }
+
+        if self.simple.len() == part.fragment_count {
+            let mut message: Vec<u8> = (0..part.fragment_count)
+                .flat_map(|index| self.simple[&index].clone())
+                .collect();
+            message.truncate(part.message_len);
+            if crc32(&message) != part.checksum {
+                return Err(CoreError::InvalidUr);
+            }
+            self.message = Some(message);
+        }
+        Ok(self.message.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic test message, as in the reference test suite
+    fn test_message(len: usize) -> Vec<u8> {
+        let mut rng = Xoshiro256::new(b"Wolf");
+        (0..len).map(|_| rng.next_int(0, 255) as u8).collect()
+    }
+
+    #[test]
+    fn test_xoshiro_and_fragment_choice() {
+        let mut rng = Xoshiro256::new(b"Wolf");
+        let first: Vec<u64> = (0..10).map(|_| rng.next_u64() % 100).collect();
+        assert_eq!(first, vec![42, 81, 85, 8, 82, 84, 76, 73, 70, 88]);
+
+        assert_eq!(fragment_length(12345, 1005, 1955), 1764);
+        assert_eq!(fragment_length(12345, 1005, 30000), 12345);
+        assert_eq!(choose_fragments(3, 9, 0), BTreeSet::from([2]));
+        let mixed = choose_fragments(10, 9, crc32(&test_message(256)));
+        assert!(!mixed.is_empty() && mixed.iter().all(|index| *index < 9));
+    }
+
+    #[test]
+    fn test_fountain_round_trip_out_of_order() {
+        let message = test_message(1024);
+        let mut encoder = FountainEncoder::new(&message, 100).unwrap();
+        assert_eq!(encoder.fragment_count(), 11);
+
+        // Drop every third part; mixed parts make up for the gaps
+        let mut decoder = FountainDecoder::default();
+        let mut sent = 0;
+        while decoder.message().is_none() {
+            let part = encoder.next_part();
+            sent += 1;
+            assert!(sent < 200, "decoder never converged");
+            if part.sequence.is_multiple_of(3) {
+                continue;
+            }
+            let part = Part::from_cbor(&part.to_cbor()).unwrap();
+            decoder.receive(part).unwrap();
+        }
+        assert_eq!(decoder.message().unwrap(), &message[..]);
+        assert_eq!(decoder.progress(), 1.0);
+
+        // A part of another message is refused
+        let mut other = FountainEncoder::new(&test_message(1000), 100).unwrap();
+        let mut fresh = FountainDecoder::default();
+        fresh.receive(encoder.next_part()).unwrap();
+        assert!(fresh.receive(other.next_part()).is_err());
+    }
+}
@@ -0,0 +1,589 @@
+// ERC-4527 registry types: `eth-sign-request` and `eth-signature`
+// Talking to a QR signer, a signing envelope becomes an eth-sign-request and
+// the returned eth-signature becomes an envelope response. Acting as one, an
+// eth-sign-request is signed with the keyring like any other payload
+
+use super::cbor::Cbor;
+use crate::envelope;
+use crate::signer::{EcdsaSignature, LocalSigner};
+use crate::storage::Keyring;
+use crate::{error::*, evm, types::*};
+use ethers_core::types::transaction::eip2718::TypedTransaction;
+use uuid::Uuid;
+
+pub(crate) const ETH_SIGN_REQUEST: &str = "eth-sign-request";
+pub(crate) const ETH_SIGNATURE: &str = "eth-signature";
+
+const TAG_UUID: u64 = 37;
+const TAG_KEYPATH: u64 = 304;
+
+pub(crate) fn encode_sign_request(request: &EthSignRequest) -> Result<UrPayload> {
+    let mut map = Vec::new();
+    if let Some(request_id) = &request.request_id {
+        map.push((1, uuid_cbor(request_id)?));
+    }
+    map.push((2, Cbor::Bytes(request.sign_data.clone())));
+    map.push((3, Cbor::Unsigned(data_type_code(request.data_type))));
+    if let Some(chain_id) = request.chain_id {
+        map.push((4, Cbor::Unsigned(chain_id)));
+    }
+    map.push((
+        5,
+        keypath_cbor(&request.derivation_path, request.source_fingerprint)?,
+    ));
+    if let Some(address) = &request.address {
+        let address = evm::parse_address(address)?;
+        map.push((6, Cbor::Bytes(address.as_bytes().to_vec())));
+    }
+    if let Some(origin) = &request.origin {
+        map.push((7, Cbor::Text(origin.clone())));
+    }
+    Ok(payload(ETH_SIGN_REQUEST, map))
+}
+
+pub(crate) fn decode_sign_request(payload: &UrPayload) -> Result<EthSignRequest> {
+    let map = decode_map(payload, ETH_SIGN_REQUEST)?;
+    let (derivation_path, source_fingerprint) =
+        keypath_from_cbor(map.get(5)?.ok_or(CoreError::InvalidUr)?)?;
+    let address = match map.get(6)? {
+        Some(address) => match address.as_bytes()? {
+            bytes if bytes.len() == 20 => Some(format!("0x{}", hex::encode(bytes))),
+            _ => return Err(CoreError::InvalidUr),
+        },
+        None => None,
+    };
+    Ok(EthSignRequest {
+        request_id: map.get(1)?.map(uuid_from_cbor).transpose()?,
+        sign_data: map
+            .get(2)?
+            .ok_or(CoreError::InvalidUr)?
+            .as_bytes()?
+            .to_vec(),
+        data_type: match map.get(3)?.map(Cbor::as_unsigned).transpose()? {
+            // Data type defaults to a legacy transaction
+            None | Some(1) => EthDataType::Transaction,
+            Some(2) => EthDataType::TypedData,
+            Some(3) => EthDataType::PersonalMessage,
+            Some(4) => EthDataType::TypedTransaction,
+            Some(_) => return Err(CoreError::InvalidUr),
+        },
+        chain_id: map.get(4)?.map(Cbor::as_unsigned).transpose()?,
+        derivation_path,
+        source_fingerprint,
+        address,
+        origin: map.get(7)?.map(text).transpose()?,
+    })
+}
+
+pub(crate) fn encode_signature(signature: &EthSignature) -> Result<UrPayload> {
+    let mut map = Vec::new();
+    if let Some(request_id) = &signature.request_id {
+        map.push((1, uuid_cbor(request_id)?));
+    }
+    let bytes = hex::decode(signature.signature.trim_start_matches("0x"))
+        .map_err(|_| CoreError::SigningError)?;
+    map.push((2, Cbor::Bytes(bytes)));
+    if let Some(origin) = &signature.origin {
+        map.push((3, Cbor::Text(origin.clone())));
+    }
+    Ok(payload(ETH_SIGNATURE, map))
+}
+
+pub(crate) fn decode_signature(payload: &UrPayload) -> Result<EthSignature> {
+    let map = decode_map(payload, ETH_SIGNATURE)?;
+    let bytes = map.get(2)?.ok_or(CoreError::InvalidUr)?.as_bytes()?;
+    Ok(EthSignature {
+        request_id: map.get(1)?.map(uuid_from_cbor).transpose()?,
+        signature: format!("0x{}", hex::encode(bytes)),
+        origin: map.get(3)?.map(text).transpose()?,
+    })
+}
+
+/// The eth-sign-request a QR signer needs to answer an envelope request.
+/// Only EVM transactions, typed data and messages have a registry type. The
+/// signer finds its key by master fingerprint, taken from the keyring wallet
+/// holding the account when it knows one, and full derivation path:
+/// accounts of xpub watch wallets need the xpub's `account_path` in front of
+/// theirs, and address-only watch wallets cannot be used.
+pub(crate) fn request_from_envelope(
+    keyring: &Keyring,
+    request: &SigningRequest,
+    account_path: Option<&str>,
+) -> Result<EthSignRequest> {
+    let (sign_data, data_type) = match &request.payload {
+        SigningPayload::EvmTransaction { params } => match evm::to_typed_transaction(params)? {
+            tx @ TypedTransaction::Legacy(_) => (tx.rlp().to_vec(), EthDataType::Transaction),
+            tx => (tx.rlp().to_vec(), EthDataType::TypedTransaction),
+        },
+        SigningPayload::TypedData { typed_data_json } => {
+            (typed_data_json.as_bytes().to_vec(), EthDataType::TypedData)
+        }
+        SigningPayload::PersonalMessage {
+            chain: Chain::Evm,
+            message,
+        } => (message.as_bytes().to_vec(), EthDataType::PersonalMessage),
+        _ => return Err(CoreError::UnsupportedBySigner),
+    };
+    let derivation_path = match (request.derivation_path.strip_prefix("xpub/"), account_path) {
+        (Some(relative), Some(account_path)) => {
+            format!("{}/{}", account_path.trim_end_matches('/'), relative)
+        }
+        _ => request.derivation_path.clone(),
+    };
+    parse_path(&derivation_path)?;
+    Ok(EthSignRequest {
+        request_id: Some(envelope_uuid(&request.request_id)?.hyphenated().to_string()),
+        sign_data,
+        data_type,
+        chain_id: request.chain_id,
+        derivation_path,
+        source_fingerprint: master_fingerprint(keyring, request)?,
+        address: Some(request.account.clone()),
+        origin: request.display.origin.clone(),
+    })
+}
+
+/// Master fingerprint of the keyring wallet, signing or watch-only, holding
+/// the account of `request`
+fn master_fingerprint(keyring: &Keyring, request: &SigningRequest) -> Result<Option<u32>> {
+    for wallet_id in keyring.wallet_ids()? {
+        let fingerprint = keyring.with_wallet(&wallet_id, |wallet| {
+            let holds_account = wallet.to_wallet_data().accounts.iter().any(|a| {
+                a.chain == request.chain && a.address.eq_ignore_ascii_case(&request.account)
+            });
+            Ok(wallet.master_fingerprint().filter(|_| holds_account))
+        })?;
+        if fingerprint.is_some() {
+            return Ok(fingerprint);
+        }
+    }
+    Ok(None)
+}
+
+/// Envelope response for `request` built from a QR signer's answer, checked
+/// as [`envelope::accept_response`] checks any response
+pub(crate) fn response_from_signature(
+    request: &SigningRequest,
+    signature: &EthSignature,
+) -> Result<SigningResponse> {
+    if let Some(request_id) = &signature.request_id {
+        let request_id = Uuid::parse_str(request_id).map_err(|_| CoreError::InvalidUr)?;
+        if request_id != envelope_uuid(&request.request_id)? {
+            return Err(CoreError::EnvelopeMismatch);
+        }
+    }
+    let sig = parse_signature(&signature.signature)?;
+    let (signed_tx, tx_hash) = match &request.payload {
+        SigningPayload::EvmTransaction { params } => {
+            let tx = evm::signed_transaction(params, &sig)?;
+            (Some(tx.signed_tx), Some(tx.hash))
+        }
+        _ => (None, None),
+    };
+    let response = SigningResponse {
+        version: envelope::ENVELOPE_VERSION,
+        request_id: request.request_id.clone(),
+        account: request.account.clone(),
+        signature: sig.to_hex(),
+        signed_tx,
+        tx_hash,
+    };
+    envelope::accept_response(request, response)
+}
+
+/// Answer an eth-sign-request with the keyring account at its address,
+/// which must sit at the requested path below the requested master key.
+/// Transactions are decoded and must re-encode to exactly the requested
+/// bytes, so the spending policy and audit log see what is really signed.
+pub(crate) fn sign(keyring: &Keyring, request: &EthSignRequest) -> Result<EthSignature> {
+    let address = request.address.as_deref().ok_or(CoreError::InvalidInput)?;
+    let (wallet_id, index) = envelope::find_signing_account(keyring, Chain::Evm, address)?;
+    let (account_path, fingerprint) = keyring.with_wallet(&wallet_id, |wallet| {
+        let account_path = wallet
+            .to_wallet_data()
+            .accounts
+            .into_iter()
+            .find(|a| a.chain == Chain::Evm && a.index == index)
+            .map(|a| a.derivation_path);
+        Ok((account_path, wallet.master_fingerprint()))
+    })?;
+    if account_path.map(|path| parse_path(&path)).transpose()?
+        != Some(parse_path(&request.derivation_path)?)
+        || request
+            .source_fingerprint
+            .is_some_and(|requested| Some(requested) != fingerprint)
+    {
+        return Err(CoreError::InvalidAddress);
+    }
+
+    let signature = match request.data_type {
+        EthDataType::Transaction | EthDataType::TypedTransaction => {
+            let params = evm::params_from_unsigned(&request.sign_data, address)?;
+            let legacy = request.sign_data.first().is_some_and(|b| *b >= 0xc0);
+            if legacy != (request.data_type == EthDataType::Transaction)
+                || request.chain_id.is_some_and(|id| id != params.chain_id)
+            {
+                return Err(CoreError::InvalidTransaction);
+            }
+            let tx = evm::sign_transaction(keyring, wallet_id, index, params.clone())?;
+            let sig = evm::signed_transaction_signature(&params, &tx.signed_tx)?;
+            let v = evm::transaction_v(&evm::to_typed_transaction(&params)?, &sig, params.chain_id);
+            let v = v.to_be_bytes();
+            let v = &v[v.iter().position(|b| *b != 0).unwrap_or(7)..];
+            format!(
+                "0x{}{}{}",
+                hex::encode(sig.r),
+                hex::encode(sig.s),
+                hex::encode(v)
+            )
+        }
+        EthDataType::TypedData => {
+            let json = std::str::from_utf8(&request.sign_data).map_err(|_| CoreError::InvalidUr)?;
+            evm::sign_typed_data(keyring, wallet_id, index, json.to_string())?
+        }
+        // Raw bytes, which need not be text
+        EthDataType::PersonalMessage => {
+            let signer = LocalSigner::new(keyring, &wallet_id, Chain::Evm, index);
+            evm::sign_message_audited(keyring, &signer, &request.sign_data)?
+        }
+    };
+    Ok(EthSignature {
+        request_id: request.request_id.clone(),
+        signature,
+        origin: None,
+    })
+}
+
+/// r || s || v with `v` big-endian in as many bytes as it needs
+fn parse_signature(signature: &str) -> Result<EcdsaSignature> {
+    let bytes =
+        hex::decode(signature.trim_start_matches("0x")).map_err(|_| CoreError::SigningError)?;
+    if !(65..=72).contains(&bytes.len()) {
+        return Err(CoreError::SigningError);
+    }
+    let v = bytes[64..].iter().fold(0u64, |v, b| (v << 8) | *b as u64);
+    EcdsaSignature::from_parts(
+        bytes[..32].try_into().unwrap(),
+        bytes[32..64].try_into().unwrap(),
+        v,
+    )
+}
+
+fn data_type_code(data_type: EthDataType) -> u64 {
+    match data_type {
+        EthDataType::Transaction => 1,
+        EthDataType::TypedData => 2,
+        EthDataType::PersonalMessage => 3,
+        EthDataType::TypedTransaction => 4,
+    }
+}
+
+fn payload(ur_type: &str, map: Vec<(u64, Cbor)>) -> UrPayload {
+    let map = map
+        .into_iter()
+        .map(|(key, value)| (Cbor::Unsigned(key), value))
+        .collect();
+    UrPayload {
+        ur_type: ur_type.to_string(),
+        cbor: Cbor::Map(map).encode(),
+    }
+}
+
+fn decode_map(payload: &UrPayload, ur_type: &str) -> Result<Cbor> {
+    if payload.ur_type != ur_type {
+        return Err(CoreError::InvalidUr);
+    }
+    let map = Cbor::decode(&payload.cbor)?;
+    map.as_map()?;
+    Ok(map)
+}
+
+fn text(value: &Cbor) -> Result<String> {
+    value.as_text().map(str::to_string)
+}
+
+/// Envelope request ids are 16 random bytes, a UUID in all but version bits
+fn envelope_uuid(request_id: &str) -> Result<Uuid> {
+    let bytes = hex::decode(request_id).map_err(|_| CoreError::InvalidEnvelope)?;
+    Uuid::from_slice(&bytes).map_err(|_| CoreError::InvalidEnvelope)
+}
+
+fn uuid_cbor(request_id: &str) -> Result<Cbor> {
+    let uuid = Uuid::parse_str(request_id).map_err(|_| CoreError::InvalidInput)?;
+    Ok(Cbor::tagged(
+        TAG_UUID,
+        Cbor::Bytes(uuid.as_bytes().to_vec()),
+    ))
+}
+
+fn uuid_from_cbor(value: &Cbor) -> Result<String> {
+    let uuid =
+        Uuid::from_slice(value.untag(TAG_UUID)?.as_bytes()?).map_err(|_| CoreError::InvalidUr)?;
+    Ok(uuid.hyphenated().to_string())
+}
+
+/// `m/44'/60'/0'/0/0` as (index, hardened) pairs; `h` also marks hardened
+fn parse_path(path: &str) -> Result<Vec<(u32, bool)>> {
+    let rest = path.strip_prefix('m').ok_or(CoreError::InvalidInput)?;
+    if rest.is_empty() {
+        return Ok(Vec::new());
+    }
+    rest.strip_prefix('/')
+        .ok_or(CoreError::InvalidInput)?
+        .split('/')
+        .map(|component| {
+            let (index, hardened) = match component.strip_suffix(['\'', 'h']) {
+                Some(index) => (index, true),
+                None => (component, false),
+            };
+            match index.parse::<u32>() {
+                Ok(index) if index < 1 << 31 => Ok((index, hardened)),
+                _ => Err(CoreError::InvalidInput),
+            }
+        })
+        .collect()
+}
+
+fn keypath_cbor(path: &str, source_fingerprint: Option<u32>) -> Result<Cbor> {
+    let components = parse_path(path)?
+        .into_iter()
+        .flat_map(|(index, hardened)| [Cbor::Unsigned(index as u64), Cbor::Bool(hardened)])
+        .collect();
+    let mut map = vec![(Cbor::Unsigned(1), Cbor::Array(components))];
+    if let Some(fingerprint) = source_fingerprint {
+        map.push((Cbor::Unsigned(2), Cbor::Unsigned(fingerprint as u64)));
+    }
+    Ok(Cbor::tagged(TAG_KEYPATH, Cbor::Map(map)))
+}
+
+/// Wildcards and ranges have no place in a signing path and are refused
+fn keypath_from_cbor(value: &Cbor) -> Result<(String, Option<u32>)> {
+    let keypath = value.untag(TAG_KEYPATH)?;
+    let components = keypath.get(1)?.ok_or(CoreError::InvalidUr)?.as_array()?;
+    if !components.len().is_multiple_of(2) {
+        return Err(CoreError::InvalidUr);
+    }
+    let mut path = "m".to_string();
+    for pair in components.chunks(2) {
+        let index = pair[0].as_unsigned()?;
+        if index >= 1 << 31 {
+            return Err(CoreError::InvalidUr);
+        }
+        let hardened = if pair[1].as_bool()? { "'" } else { "" };
+        path.push_str(&format!("/{}{}", index, hardened));
+    }
+    let fingerprint = match keypath.get(2)? {
+        Some(value) => Some(u32::try_from(value.as_unsigned()?).map_err(|_| CoreError::InvalidUr)?),
+        None => None,
+    };
+    Ok((path, fingerprint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Wallet;
+    use crate::ur::{UrDecoder, UrEncoder};
+
+    fn keyring() -> (Keyring, String) {
+        let keyring = Keyring::new();
+        let wallet = Wallet::from_entropy(&[24u8; 16], None).unwrap();
+        let wallet_id = wallet.id.clone();
+        keyring.insert(wallet).unwrap();
+        (keyring, wallet_id)
+    }
+
+    /// Through an animated QR code, frame by frame
+    fn scan(payload: UrPayload) -> UrPayload {
+        let encoder = UrEncoder::new(payload, 40).unwrap();
+        let decoder = UrDecoder::new();
+        while !decoder.receive(encoder.next_part().unwrap()).unwrap() {}
+        decoder.result().unwrap()
+    }
+
+    #[test]
+    fn test_eth_sign_request_cbor() {
+        let request = EthSignRequest {
+            request_id: Some("9b1deb4d-3b7d-4bad-9bdd-2b0d7b3dcb6d".to_string()),
+            sign_data: vec![0xde, 0xad],
+            data_type: EthDataType::TypedTransaction,
+            chain_id: Some(1),
+            derivation_path: "m/44'/60'/0'/0/0".to_string(),
+            source_fingerprint: Some(0x12345678),
+            address: Some("0x3535353535353535353535353535353535353535".to_string()),
+            origin: Some("metamask".to_string()),
+        };
+        let payload = encode_sign_request(&request).unwrap();
+        assert_eq!(payload.ur_type, "eth-sign-request");
+        let cbor = hex::encode(&payload.cbor);
+        assert!(cbor.starts_with("a701d825509b1deb4d3b7d4bad9bdd2b0d7b3dcb6d0242dead03040401"));
+        // crypto-keypath: tag 304, components [44, true, 60, true, 0, true, 0, false, 0, false]
+        assert!(cbor.contains("05d90130a2018a182cf5183cf500f500f400f4021a12345678"));
+        assert_eq!(decode_sign_request(&payload).unwrap(), request);
+
+        let signature = EthSignature {
+            request_id: request.request_id.clone(),
+            signature: format!("0x{}", "11".repeat(65)),
+            origin: None,
+        };
+        assert_eq!(
+            decode_signature(&encode_signature(&signature).unwrap()).unwrap(),
+            signature
+        );
+
+        // Wrong UR type, wildcard path component
+        assert!(decode_signature(&payload).is_err());
+        let wildcard = Cbor::tagged(
+            TAG_KEYPATH,
+            Cbor::Map(vec![(
+                Cbor::Unsigned(1),
+                Cbor::Array(vec![Cbor::Array(vec![]), Cbor::Bool(false)]),
+            )]),
+        );
+        assert!(keypath_from_cbor(&wildcard).is_err());
+    }
+
+    #[test]
+    fn test_qr_signer_round_trip() {
+        let (offline, wallet_id) = keyring();
+        let legacy = EvmTxParams {
+            to: "0x3535353535353535353535353535353535353535".to_string(),
+            value: "1000".to_string(),
+            data: Some(format!("0x{}", "ab".repeat(300))),
+            gas_limit: 90000,
+            gas_price: "1000000000".to_string(),
+            nonce: 7,
+            chain_id: 1,
+            ..Default::default()
+        };
+        let eip1559 = EvmTxParams {
+            max_fee_per_gas: Some("3000000000".to_string()),
+            max_priority_fee_per_gas: Some("1000000000".to_string()),
+            chain_id: 137,
+            ..legacy.clone()
+        };
+        let payloads = [
+            SigningPayload::EvmTransaction { params: legacy },
+            SigningPayload::EvmTransaction { params: eip1559 },
+            SigningPayload::PersonalMessage {
+                chain: Chain::Evm,
+                message: "login".to_string(),
+            },
+        ];
+
+        for payload in payloads {
+            let request = envelope::create_request(&offline, &wallet_id, 0, payload, None).unwrap();
+            let eth_request = request_from_envelope(&offline, &request, None).unwrap();
+            assert!(eth_request.source_fingerprint.is_some());
+
+            // The signing device sees only the QR code
+            let scanned = decode_sign_request(&scan(encode_sign_request(&eth_request).unwrap()));
+            let answer = sign(&offline, &scanned.unwrap()).unwrap();
+            let answer = decode_signature(&scan(encode_signature(&answer).unwrap())).unwrap();
+
+            let response = response_from_signature(&request, &answer).unwrap();
+            if let SigningPayload::EvmTransaction { params } = &request.payload {
+                let signed_tx = response.signed_tx.unwrap();
+                evm::signed_transaction_signature(params, &signed_tx).unwrap();
+            }
+
+            let mut stranger = answer.clone();
+            stranger.request_id = Some(Uuid::nil().hyphenated().to_string());
+            assert!(matches!(
+                response_from_signature(&request, &stranger),
+                Err(CoreError::EnvelopeMismatch)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_qr_signer_refuses_mismatched_requests() {
+        let (offline, wallet_id) = keyring();
+        let params = EvmTxParams {
+            to: "0x3535353535353535353535353535353535353535".to_string(),
+            value: "1".to_string(),
+            gas_limit: 21000,
+            gas_price: "1".to_string(),
+            chain_id: 1,
+            ..Default::default()
+        };
+        let request = envelope::create_request(
+            &offline,
+            &wallet_id,
+            0,
+            SigningPayload::EvmTransaction { params },
+            None,
+        )
+        .unwrap();
+        let eth_request = request_from_envelope(&offline, &request, None).unwrap();
+
+        let other_path = EthSignRequest {
+            derivation_path: "m/44'/60'/0'/0/1".to_string(),
+            ..eth_request.clone()
+        };
+        assert!(matches!(
+            sign(&offline, &other_path),
+            Err(CoreError::InvalidAddress)
+        ));
+        let other_seed = EthSignRequest {
+            source_fingerprint: eth_request.source_fingerprint.map(|f| f ^ 1),
+            ..eth_request.clone()
+        };
+        assert!(matches!(
+            sign(&offline, &other_seed),
+            Err(CoreError::InvalidAddress)
+        ));
+
+        let wrong_type = EthSignRequest {
+            data_type: EthDataType::TypedTransaction,
+            ..eth_request.clone()
+        };
+        assert!(sign(&offline, &wrong_type).is_err());
+
+        // Bytes that do not re-encode identically, e.g. trailing garbage
+        let mut padded = eth_request.clone();
+        padded.sign_data.push(0);
+        assert!(sign(&offline, &padded).is_err());
+
+        // A different answer than the one asked for
+        let message = sign(
+            &offline,
+            &EthSignRequest {
+                sign_data: b"hello".to_vec(),
+                data_type: EthDataType::PersonalMessage,
+                ..eth_request
+            },
+        )
+        .unwrap();
+        assert!(response_from_signature(&request, &message).is_err());
+    }
+
+    #[test]
+    fn test_qr_signer_signs_binary_messages() {
+        let (offline, wallet_id) = keyring();
+        let address = offline
+            .with_wallet(&wallet_id, |w| w.get_address(Chain::Evm, 0))
+            .unwrap();
+        let message = vec![0xff, 0x00, 0xfe];
+        let signature = sign(
+            &offline,
+            &EthSignRequest {
+                request_id: None,
+                sign_data: message.clone(),
+                data_type: EthDataType::PersonalMessage,
+                chain_id: None,
+                derivation_path: "m/44'/60'/0'/0/0".to_string(),
+                source_fingerprint: None,
+                address: Some(address.clone()),
+                origin: None,
+            },
+        )
+        .unwrap();
+
+        let public_key = parse_signature(&signature.signature)
+            .unwrap()
+            .recover(&evm::personal_message_hash(&message))
+            .unwrap();
+        assert_eq!(crate::crypto::public_key_to_address(&public_key), address);
+    }
+}
@@ -0,0 +1,532 @@
+// Encrypted on-disk vault for wallet secrets
+// Versioned JSON envelope: password KDF (Argon2id or PBKDF2) + AES-256-GCM
+
+use crate::audit::AuditState;
+use crate::crypto::WalletRecord;
+use crate::error::*;
+use crate::policy::PolicyState;
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Version written by this build.
+///
+/// 1. Wallets only
+/// 2. Adds the spending policy and its daily totals, and the audit log key
+///    and head
+pub const VAULT_VERSION: u32 = 2;
+
+pub(crate) const CIPHER: &str = "aes-256-gcm";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+// KDF settings are read back from files that may come from elsewhere, so
+// cost is bounded before any work is done
+const MAX_ARGON2_MEMORY_KIB: u32 = 1 << 20;
+const MAX_ARGON2_PASSES: u32 = 16;
+const MAX_ARGON2_LANES: u32 = 16;
+const MAX_PBKDF2_ROUNDS: u32 = 10_000_000;
+
+/// Password key derivation settings, stored in the clear next to the ciphertext
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "algorithm", rename_all = "kebab-case")]
+pub enum KdfParams {
+    Argon2id {
+        salt: String,
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+    Pbkdf2Sha256 {
+        salt: String,
+        iterations: u32,
+    },
+}
+
+impl KdfParams {
+    /// Argon2id with OWASP-recommended cost (64 MiB, 3 passes) and a fresh salt
+    pub fn argon2id() -> Self {
+        Self::argon2id_with_cost(64 * 1024, 3)
+    }
+
+    pub fn argon2id_with_cost(memory_kib: u32, iterations: u32) -> Self {
+        KdfParams::Argon2id {
+            salt: random_hex(SALT_LEN),
+            memory_kib,
+            iterations,
+            parallelism: 1,
+        }
+    }
+
+    /// PBKDF2-HMAC-SHA256, for platforms where Argon2 memory cost is a problem
+    pub fn pbkdf2(iterations: u32) -> Self {
+        KdfParams::Pbkdf2Sha256 {
+            salt: random_hex(SALT_LEN),
+            iterations,
+        }
+    }
+
+    /// Same algorithm and cost with a new salt
+    fn with_fresh_salt(&self) -> Self {
+        match self {
+            KdfParams::Argon2id {
+                memory_kib,
+                iterations,
+                ..
+            } => Self::argon2id_with_cost(*memory_kib, *iterations),
+            KdfParams::Pbkdf2Sha256 { iterations, .. } => Self::pbkdf2(*iterations),
+        }
+    }
+
+    /// Fails with `StorageError` for settings costlier than this build allows
+    pub(crate) fn derive_key(&self, password: &str) -> Result<Zeroizing<[u8; 32]>> {
+        let mut key = Zeroizing::new([0u8; 32]);
+
+        match self {
+            KdfParams::Argon2id {
+                salt,
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                if *memory_kib > MAX_ARGON2_MEMORY_KIB
+                    || *iterations > MAX_ARGON2_PASSES
+                    || *parallelism > MAX_ARGON2_LANES
+                {
+                    return Err(CoreError::StorageError);
+                }
+                let salt = hex::decode(salt).map_err(|_| CoreError::StorageError)?;
+                let params = argon2::Params::new(*memory_kib, *iterations, *parallelism, Some(32))
+                    .map_err(|_| CoreError::StorageError)?;
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password_into(password.as_bytes(), &salt, key.as_mut())
+                    .map_err(|_| CoreError::InternalError)?;
+            }
+            KdfParams::Pbkdf2Sha256 { salt, iterations } => {
+                if *iterations == 0 || *iterations > MAX_PBKDF2_ROUNDS {
+                    return Err(CoreError::StorageError);
+                }
+                let salt = hex::decode(salt).map_err(|_| CoreError::StorageError)?;
+                pbkdf2::pbkdf2_hmac::<sha2::Sha256>(
+                    password.as_bytes(),
+                    &salt,
+                    *iterations,
+                    key.as_mut(),
+                );
+            }
+        }
+
+        Ok(key)
+    }
+}
+
+/// On-disk layout of the vault file
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    kdf: KdfParams,
+    cipher: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Authenticated but unencrypted header, so the KDF settings cannot be swapped
+#[derive(Serialize)]
+struct VaultHeader<'a> {
+    version: u32,
+    kdf: &'a KdfParams,
+    cipher: &'a str,
+}
+
+/// Everything the vault encrypts
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct VaultPayload {
+    pub wallets: Vec<WalletRecord>,
+    /// Absent in version 1 files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PolicyState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<AuditState>,
+}
+
+/// A vault file and, while unlocked, the key derived from its password
+pub struct Vault {
+    path: PathBuf,
+    kdf: KdfParams,
+    key: Option<Zeroizing<[u8; 32]>>,
+}
+
+impl Vault {
+    /// Create a new vault file; fails if one already exists at `path`
+    pub fn create(
+        path: &Path,
+        password: &str,
+        kdf: KdfParams,
+        payload: &VaultPayload,
+    ) -> Result<Self> {
+        // Claim the path atomically so two creators cannot both succeed
+        open_private(path, fs::OpenOptions::new().write(true).create_new(true))
+            .map_err(|_| CoreError::StorageError)?;
+
+        let vault = kdf.derive_key(password).and_then(|key| {
+            let vault = Self {
+                path: path.to_path_buf(),
+                kdf,
+                key: Some(key),
+            };
+            vault.save(payload)?;
+            Ok(vault)
+        });
+        if vault.is_err() {
+            let _ = fs::remove_file(path);
+        }
+        vault
+    }
+
+    /// Open a vault file and decrypt its contents with `password`
+    pub fn unlock(path: &Path, password: &str) -> Result<(Self, VaultPayload)> {
+        let (kdf, key, payload) = decrypt_contents(&fs::read(path)?, password)?;
+
+        Ok((
+            Self {
+                path: path.to_path_buf(),
+                kdf,
+                key: Some(key),
+            },
+            payload,
+        ))
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Forget the derived key; the file stays on disk
+    pub fn lock(&mut self) {
+        self.key = None;
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Re-encrypt `payload` under a new password and a fresh salt
+    pub fn change_password(
+        &mut self,
+        old_password: &str,
+        new_password: &str,
+        payload: &VaultPayload,
+    ) -> Result<()> {
+        self.verify_password(old_password)?;
+
+        let kdf = self.kdf.with_fresh_salt();
+        let key = kdf.derive_key(new_password)?;
+        let previous = std::mem::replace(&mut self.kdf, kdf);
+        let previous_key = self.key.replace(key);
+
+        if let Err(e) = self.save(payload) {
+            self.kdf = previous;
+            self.key = previous_key;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Check `password` against the file on disk
+    pub fn verify_password(&self, password: &str) -> Result<()> {
+        let file: VaultFile = serde_json::from_slice(&fs::read(&self.path)?)?;
+        let key = file.kdf.derive_key(password)?;
+        decrypt(&file, &key).map(|_| ())
+    }
+
+    /// Encrypt `payload` and atomically replace the vault file
+    pub fn save(&self, payload: &VaultPayload) -> Result<()> {
+        let key = self.key.as_ref().ok_or(CoreError::VaultLocked)?;
+
+        let plaintext = Zeroizing::new(serde_json::to_vec(payload)?);
+        let header = header_bytes(VAULT_VERSION, &self.kdf)?;
+        let (nonce, ciphertext) = seal(key, &header, &plaintext)?;
+
+        let file = VaultFile {
+            version: VAULT_VERSION,
+            kdf: self.kdf.clone(),
+            cipher: CIPHER.to_string(),
+            nonce,
+            ciphertext,
+        };
+
+        write_atomically(&self.path, &serde_json::to_vec_pretty(&file)?)
+    }
+}
+
+/// Parse a vault file of any supported version and decrypt it, returning
+/// the key as well
+pub(crate) fn decrypt_contents(
+    contents: &[u8],
+    password: &str,
+) -> Result<(KdfParams, Zeroizing<[u8; 32]>, VaultPayload)> {
+    let file: VaultFile = serde_json::from_slice(contents)?;
+    if file.version == 0 || file.version > VAULT_VERSION || file.cipher != CIPHER {
+        return Err(CoreError::StorageError);
+    }
+
+    let key = file.kdf.derive_key(password)?;
+    let payload = decrypt(&file, &key)?;
+    Ok((file.kdf, key, payload))
+}
+
+fn decrypt(file: &VaultFile, key: &[u8; 32]) -> Result<VaultPayload> {
+    let plaintext = open(
+        key,
+        &header_bytes(file.version, &file.kdf)?,
+        &file.nonce,
+        &file.ciphertext,
+    )?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// AES-256-GCM encrypt under a fresh random nonce; returns hex nonce and ciphertext
+pub(crate) fn seal(key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> Result<(String, String)> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| CoreError::InternalError)?;
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| CoreError::InternalError)?;
+
+    Ok((hex::encode(nonce), hex::encode(ciphertext)))
+}
+
+/// Inverse of `seal`
+pub(crate) fn open(
+    key: &[u8; 32],
+    aad: &[u8],
+    nonce: &str,
+    ciphertext: &str,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let nonce = hex::decode(nonce).map_err(|_| CoreError::StorageError)?;
+    let ciphertext = hex::decode(ciphertext).map_err(|_| CoreError::StorageError)?;
+    if nonce.len() != NONCE_LEN {
+        return Err(CoreError::StorageError);
+    }
+
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| CoreError::InternalError)?;
+
+    // A wrong password and a tampered file are indistinguishable under AES-GCM
+    Ok(Zeroizing::new(
+        cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| CoreError::InvalidPassword)?,
+    ))
+}
+
+fn header_bytes(version: u32, kdf: &KdfParams) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(&VaultHeader {
+        version,
+        kdf,
+        cipher: CIPHER,
+    })?)
+}
+
+/// Write to a sibling temp file, fsync, then rename over the target so a
+/// crash never leaves a truncated vault behind
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or(CoreError::StorageError)?
+        .to_string_lossy();
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name));
+
+    let mut file = open_private(
+        &tmp_path,
+        fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true),
+    )?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&tmp_path, path)?;
+
+    // The rename only survives a crash once the directory entry is on disk
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fs::File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Opens with owner-only permissions on unix
+fn open_private(path: &Path, options: &mut fs::OpenOptions) -> std::io::Result<fs::File> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Wallet;
+
+    fn fast_kdf() -> KdfParams {
+        KdfParams::argon2id_with_cost(256, 1)
+    }
+
+    fn payload() -> VaultPayload {
+        let wallet = Wallet::from_entropy(&[9u8; 16], None).unwrap();
+        VaultPayload {
+            wallets: vec![wallet.to_record().unwrap()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_create_and_unlock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.json");
+
+        Vault::create(&path, "correct horse", fast_kdf(), &payload()).unwrap();
+        assert!(Vault::create(&path, "correct horse", fast_kdf(), &Default::default()).is_err());
+
+        let (vault, contents) = Vault::unlock(&path, "correct horse").unwrap();
+        assert!(vault.is_unlocked());
+        assert_eq!(contents.wallets.len(), 1);
+
+        assert!(matches!(
+            Vault::unlock(&path, "wrong"),
+            Err(CoreError::InvalidPassword)
+        ));
+
+        // Secrets never hit the disk in the clear
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("mnemonic"));
+        assert!(contents.contains("argon2id"));
+    }
+
+    #[test]
+    fn test_pbkdf2_and_change_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.json");
+        let payload = payload();
+
+        let mut vault = Vault::create(&path, "old", KdfParams::pbkdf2(1000), &payload).unwrap();
+        assert!(vault.change_password("bad", "new", &payload).is_err());
+        vault.change_password("old", "new", &payload).unwrap();
+
+        assert!(Vault::unlock(&path, "old").is_err());
+        assert_eq!(Vault::unlock(&path, "new").unwrap().1.wallets.len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_unbounded_kdf_params() {
+        // Each would allocate terabytes or spin for hours before the password check
+        for kdf in [
+            KdfParams::argon2id_with_cost(u32::MAX, 1),
+            KdfParams::argon2id_with_cost(8, u32::MAX),
+            KdfParams::Argon2id {
+                salt: "00".repeat(SALT_LEN),
+                memory_kib: 1 << 16,
+                iterations: 1,
+                parallelism: u32::MAX,
+            },
+            KdfParams::pbkdf2(u32::MAX),
+        ] {
+            assert!(matches!(kdf.derive_key("pw"), Err(CoreError::StorageError)));
+        }
+    }
+
+    #[test]
+    fn test_locked_vault_cannot_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.json");
+
+        let mut vault = Vault::create(&path, "pw", fast_kdf(), &Default::default()).unwrap();
+        vault.lock();
+        assert!(matches!(
+            vault.save(&Default::default()),
+            Err(CoreError::VaultLocked)
+        ));
+
+        let (vault, contents) = Vault::unlock(vault.path(), "pw").unwrap();
+        assert!(contents.wallets.is_empty());
+        assert!(vault.save(&payload()).is_ok());
+    }
+
+    #[test]
+    fn test_tampered_header_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.json");
+        Vault::create(&path, "pw", fast_kdf(), &payload()).unwrap();
+
+        let mut file: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        file["kdf"]["iterations"] = serde_json::json!(2);
+        fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+
+        assert!(Vault::unlock(&path, "pw").is_err());
+    }
+
+    #[test]
+    fn test_reads_version_1_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.json");
+        let kdf = fast_kdf();
+        let key = kdf.derive_key("pw").unwrap();
+
+        let wallets = payload().wallets;
+        let plaintext = serde_json::to_vec(&serde_json::json!({ "wallets": wallets })).unwrap();
+        let (nonce, ciphertext) = seal(&key, &header_bytes(1, &kdf).unwrap(), &plaintext).unwrap();
+        let file = VaultFile {
+            version: 1,
+            kdf,
+            cipher: CIPHER.to_string(),
+            nonce,
+            ciphertext,
+        };
+        fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+
+        let (vault, contents) = Vault::unlock(&path, "pw").unwrap();
+        assert_eq!(contents.wallets.len(), 1);
+        assert!(contents.policy.is_none());
+
+        // Saving upgrades the file, which older builds then refuse
+        vault.save(&contents).unwrap();
+        let file: VaultFile = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(file.version, VAULT_VERSION);
+    }
+}
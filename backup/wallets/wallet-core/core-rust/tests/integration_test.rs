@@ -1,8 +1,13 @@
 // Integration tests for Nor Wallet Core
 
 use nor_core::{
-    get_nor_chain_id, get_nor_chain_rpc, Account, EvmManager, NetworkManager, WalletManager,
+    get_nor_chain_id, get_nor_chain_rpc, AccountMetadata, AuditEntry, BackupSettings, Chain,
+    CoreError, CustomToken, EcdsaSignature, EvmManager, EvmTxParams, Keyring, KeystoreKdf,
+    LedgerTransport, LocalSigner, NetworkManager, PolicyDecision, SessionPolicy, Signer,
+    SigningOperation, SigningPayload, Slip39Group, TronManager, UrDecoder, UrEncoder, UrPayload,
+    WalletManager, WalletMetadata,
 };
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[test]
 fn test_nor_chain_config() {
@@ -10,7 +15,7 @@ fn test_nor_chain_config() {
     let chain_id = get_nor_chain_id();
 
     assert_eq!(rpc_url, "https://rpc.norchain.org");
-    assert_eq!(chain_id, 7860);
+    assert_eq!(chain_id, 65001);
 }
 
 #[test]
@@ -19,7 +24,7 @@ fn test_network_manager() {
     let info = manager.get_network_info();
 
     assert_eq!(info.rpc_url, "https://rpc.norchain.org");
-    assert_eq!(info.chain_id, 7860);
+    assert_eq!(info.chain_id, 65001);
     assert_eq!(info.name, "Nor Chain");
     assert_eq!(info.symbol, "NOR");
     assert_eq!(info.decimals, 18);
@@ -82,9 +87,23 @@ fn test_derive_multiple_accounts() {
     assert_ne!(addr1, addr3);
 }
 
+/// Give `manager`'s keyring a vault and a fresh password proof, which
+/// exporting secrets and changing the spending policy require
+fn create_authorized_vault(manager: &WalletManager, dir: &tempfile::TempDir) {
+    manager
+        .create_vault(
+            dir.path().join("wallets.vault").display().to_string(),
+            "pw".to_string(),
+        )
+        .unwrap();
+    manager.authorize_sensitive("pw".to_string()).unwrap();
+}
+
 #[test]
 fn test_export_mnemonic() {
-    let manager = WalletManager::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let manager = WalletManager::with_keyring(Keyring::new());
+    create_authorized_vault(&manager, &dir);
     let mnemonic = "test walk nut penalty hip pave soap entry language right filter choice";
 
     let wallet = manager
@@ -97,8 +116,7 @@ fn test_export_mnemonic() {
 
 #[test]
 fn test_evm_manager() {
-    let manager = EvmManager::new().unwrap();
-    assert!(true); // Manager created successfully
+    assert!(EvmManager::new().is_ok());
 }
 
 #[test]
@@ -118,7 +136,9 @@ fn test_wallet_from_private_key() {
 
 #[test]
 fn test_export_private_key() {
-    let manager = WalletManager::new().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let manager = WalletManager::with_keyring(Keyring::new());
+    create_authorized_vault(&manager, &dir);
     let entropy = vec![2u8; 32];
 
     let wallet = manager.create_wallet(entropy, None).unwrap();
@@ -127,3 +147,762 @@ fn test_export_private_key() {
     assert!(private_key.starts_with("0x"));
     assert_eq!(private_key.len(), 66); // 0x + 64 hex chars
 }
+
+#[test]
+fn test_manager_level_signing_round_trip() {
+    // Managers created with new() share the process-wide keyring
+    let wallet_manager = WalletManager::new().unwrap();
+    let evm_manager = EvmManager::new().unwrap();
+
+    let wallet = wallet_manager.create_wallet(vec![4u8; 16], None).unwrap();
+    let address = wallet.accounts[0].address.clone();
+
+    let signature = evm_manager
+        .sign_message(wallet.id.clone(), 0, "login".to_string())
+        .unwrap();
+    assert!(evm_manager
+        .verify_message(address.clone(), "login".to_string(), signature)
+        .unwrap());
+
+    let params = EvmTxParams {
+        from: address,
+        to: "0x3535353535353535353535353535353535353535".to_string(),
+        value: "1".to_string(),
+        gas_limit: 21000,
+        gas_price: "1000000000".to_string(),
+        chain_id: 65001,
+        ..Default::default()
+    };
+    let tx = evm_manager.sign_transaction(wallet.id, 0, params).unwrap();
+    assert!(tx.signed_tx.starts_with("0xf8"));
+}
+
+#[test]
+fn test_injected_keyring_round_trip() {
+    let keyring = Keyring::new();
+    let wallet_manager = WalletManager::with_keyring(keyring.clone());
+    let evm_manager = EvmManager::with_keyring(keyring);
+
+    let wallet = wallet_manager
+        .import_from_private_key(
+            "0x4646464646464646464646464646464646464646464646464646464646464646".to_string(),
+        )
+        .unwrap();
+
+    let signature = evm_manager
+        .sign_message(wallet.id.clone(), 0, "login".to_string())
+        .unwrap();
+    assert_eq!(
+        evm_manager
+            .recover_signer("login".to_string(), signature)
+            .unwrap(),
+        "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"
+    );
+
+    // Not visible through the global keyring
+    assert!(EvmManager::new()
+        .unwrap()
+        .sign_message(wallet.id, 0, "login".to_string())
+        .is_err());
+}
+
+#[test]
+fn test_keystore_export_import_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let manager = WalletManager::with_keyring(Keyring::new());
+    create_authorized_vault(&manager, &dir);
+    let wallet = manager.create_wallet(vec![3u8; 16], None).unwrap();
+
+    let json = manager
+        .export_keystore_with_kdf(
+            wallet.id.clone(),
+            0,
+            "secret".to_string(),
+            KeystoreKdf::pbkdf2(1000),
+        )
+        .unwrap();
+    assert!(manager
+        .import_from_keystore(json.clone(), "wrong".to_string())
+        .is_err());
+
+    let imported = manager
+        .import_from_keystore(json, "secret".to_string())
+        .unwrap();
+    assert_eq!(imported.accounts[0].address, wallet.accounts[0].address);
+}
+
+#[test]
+fn test_reimport_is_reported() {
+    let manager = WalletManager::with_keyring(Keyring::new());
+    let mnemonic = "test walk nut penalty hip pave soap entry language right filter choice";
+
+    let wallet = manager
+        .import_from_mnemonic(mnemonic.to_string(), None)
+        .unwrap();
+    assert!(matches!(
+        manager.import_from_mnemonic(mnemonic.to_string(), None),
+        Err(CoreError::DuplicateWallet)
+    ));
+
+    // The fingerprint leads back to the wallet that is already there
+    let fingerprint = manager
+        .mnemonic_fingerprint(mnemonic.to_string(), None)
+        .unwrap();
+    assert_eq!(fingerprint, wallet.fingerprint);
+    let existing = manager
+        .find_wallet_by_fingerprint(fingerprint)
+        .unwrap()
+        .unwrap();
+    assert_eq!(existing.id, wallet.id);
+}
+
+#[test]
+fn test_slip39_backup_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let manager = WalletManager::with_keyring(Keyring::new());
+    create_authorized_vault(&manager, &dir);
+    let wallet = manager.create_wallet(vec![9u8; 16], None).unwrap();
+
+    let groups = vec![
+        Slip39Group {
+            member_threshold: 1,
+            member_count: 1,
+        },
+        Slip39Group {
+            member_threshold: 2,
+            member_count: 3,
+        },
+    ];
+    let shares = manager
+        .export_slip39_shares(wallet.id.clone(), 2, groups, Some("vault".to_string()))
+        .unwrap();
+
+    // Restore on another device
+    let other = WalletManager::with_keyring(Keyring::new());
+    let quorum = vec![
+        shares[0][0].clone(),
+        shares[1][0].clone(),
+        shares[1][2].clone(),
+    ];
+    let restored = other
+        .import_from_slip39(quorum, Some("vault".to_string()))
+        .unwrap();
+    assert_eq!(restored.accounts[0].address, wallet.accounts[0].address);
+    assert_eq!(restored.fingerprint, wallet.fingerprint);
+
+    // The first group alone is below the group threshold
+    assert!(matches!(
+        other.import_from_slip39(shares[0].clone(), Some("vault".to_string())),
+        Err(CoreError::InvalidShare)
+    ));
+}
+
+#[test]
+fn test_watch_only_wallet_rejects_signing() {
+    let dir = tempfile::tempdir().unwrap();
+    let keyring = Keyring::new();
+    let manager = WalletManager::with_keyring(keyring.clone());
+    let evm = EvmManager::with_keyring(keyring);
+    create_authorized_vault(&manager, &dir);
+
+    let wallet = manager.create_wallet(vec![4u8; 16], None).unwrap();
+    let xpub = manager.export_xpub(wallet.id.clone(), Chain::Evm).unwrap();
+
+    let watch = manager
+        .import_watch_only_xpub(xpub.xpub, Chain::Evm, None, None)
+        .unwrap();
+    assert_eq!(watch.accounts[0].address, wallet.accounts[0].address);
+    assert!(manager.is_watch_only(watch.id.clone()).unwrap());
+    assert!(manager.derive_account(watch.id.clone(), 3).is_ok());
+
+    let err = evm
+        .sign_message(watch.id.clone(), 0, "hello".to_string())
+        .unwrap_err();
+    assert!(matches!(err, CoreError::WatchOnly));
+    assert!(matches!(
+        manager.export_private_key(watch.id, 0),
+        Err(CoreError::WatchOnly)
+    ));
+}
+
+#[test]
+fn test_vault_session_gates_exports() {
+    let dir = tempfile::tempdir().unwrap();
+    let keyring = Keyring::new();
+    let manager = WalletManager::with_keyring(keyring.clone());
+    let evm = EvmManager::with_keyring(keyring);
+
+    let wallet = manager.create_wallet(vec![10u8; 16], None).unwrap();
+    manager
+        .create_vault(
+            dir.path().join("wallets.vault").display().to_string(),
+            "pw".to_string(),
+        )
+        .unwrap();
+
+    // Signing stays open; revealing secrets needs the password again
+    assert!(evm
+        .sign_message(wallet.id.clone(), 0, "hi".to_string())
+        .is_ok());
+    assert!(matches!(
+        manager.export_private_key(wallet.id.clone(), 0),
+        Err(CoreError::ReauthenticationRequired)
+    ));
+    manager.authorize_sensitive("pw".to_string()).unwrap();
+    assert!(manager.export_mnemonic(wallet.id.clone()).is_ok());
+
+    manager
+        .set_session_policy(SessionPolicy {
+            idle_timeout_ms: 1,
+            sensitive_window_ms: 60_000,
+        })
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    assert!(manager.enforce_idle_timeout().unwrap());
+    assert!(manager.session_status().unwrap().locked);
+    assert!(evm.sign_message(wallet.id, 0, "hi".to_string()).is_err());
+}
+
+#[test]
+fn test_full_backup_moves_setup_between_devices() {
+    let dir = tempfile::tempdir().unwrap();
+    let manager = WalletManager::with_keyring(Keyring::new());
+    create_authorized_vault(&manager, &dir);
+    let seed_wallet = manager.create_wallet(vec![13u8; 16], None).unwrap();
+    manager
+        .derive_accounts(seed_wallet.id.clone(), 1, 2)
+        .unwrap();
+    let key_wallet = manager
+        .import_from_private_key(
+            "0x5656565656565656565656565656565656565656565656565656565656565656".to_string(),
+        )
+        .unwrap();
+
+    let settings = BackupSettings {
+        networks: vec![NetworkManager::new().unwrap().get_network_info()],
+        tokens: vec![CustomToken {
+            chain_id: 65001,
+            address: "0x3535353535353535353535353535353535353535".to_string(),
+            symbol: "TKN".to_string(),
+            decimals: 18,
+            name: Some("Token".to_string()),
+        }],
+    };
+    let bundle = manager
+        .export_backup("backup-pw".to_string(), settings.clone())
+        .unwrap();
+
+    let other = WalletManager::with_keyring(Keyring::new());
+    assert!(matches!(
+        other.import_backup(bundle.clone(), "nope".to_string()),
+        Err(CoreError::InvalidPassword)
+    ));
+    let restore = other
+        .import_backup(bundle.clone(), "backup-pw".to_string())
+        .unwrap();
+    assert_eq!(restore.restored.len(), 2);
+    assert_eq!(restore.settings, settings);
+
+    let restored = other.get_wallet(seed_wallet.id.clone()).unwrap();
+    assert_eq!(restored.accounts.len(), 3);
+    assert_eq!(restored.fingerprint, seed_wallet.fingerprint);
+    assert_eq!(
+        other.get_wallet(key_wallet.id.clone()).unwrap().accounts[0].address,
+        key_wallet.accounts[0].address
+    );
+
+    // Restoring twice adds nothing
+    let again = other
+        .import_backup(bundle, "backup-pw".to_string())
+        .unwrap();
+    assert!(again.restored.is_empty());
+    assert_eq!(again.skipped.len(), 2);
+}
+
+#[test]
+fn test_metadata_persists_in_vault() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("wallets.vault").display().to_string();
+    let manager = WalletManager::with_keyring(Keyring::new());
+    let first = manager.create_wallet(vec![14u8; 16], None).unwrap();
+    let second = manager.create_wallet(vec![15u8; 16], None).unwrap();
+    manager.derive_accounts(first.id.clone(), 1, 1).unwrap();
+    manager
+        .create_vault(path.clone(), "pw".to_string())
+        .unwrap();
+
+    let wallet = manager
+        .set_wallet_metadata(
+            first.id.clone(),
+            WalletMetadata {
+                label: Some("Main".to_string()),
+                color: Some("#5b8def".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(wallet.metadata.label.as_deref(), Some("Main"));
+    let account = manager
+        .set_account_metadata(
+            first.id.clone(),
+            Chain::Evm,
+            1,
+            AccountMetadata {
+                label: Some("Savings".to_string()),
+                hidden: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert!(account.metadata.hidden);
+    assert!(manager
+        .set_account_metadata(first.id.clone(), Chain::Evm, 9, AccountMetadata::default())
+        .is_err());
+
+    manager
+        .reorder_wallets(vec![second.id.clone(), first.id.clone()])
+        .unwrap();
+    assert!(manager
+        .reorder_wallets(vec!["missing".to_string()])
+        .is_err());
+
+    let reopened = WalletManager::with_keyring(Keyring::new());
+    reopened.unlock_vault(path, "pw".to_string()).unwrap();
+    let wallets = reopened.list_wallets().unwrap();
+    let ids: Vec<_> = wallets.iter().map(|w| w.id.clone()).collect();
+    assert_eq!(ids, vec![second.id.clone(), first.id.clone()]);
+
+    let restored = &wallets[1];
+    assert_eq!(restored.created_at, first.created_at);
+    assert_eq!(restored.metadata.label.as_deref(), Some("Main"));
+    assert_eq!(restored.metadata.color.as_deref(), Some("#5b8def"));
+    assert_eq!(
+        restored.accounts[1].metadata.label.as_deref(),
+        Some("Savings")
+    );
+}
+
+/// Stands in for a hardware or remote signer
+struct CountingSigner {
+    inner: LocalSigner,
+    calls: AtomicUsize,
+}
+
+impl Signer for CountingSigner {
+    fn sign_digest(&self, digest: &[u8; 32]) -> nor_core::Result<EcdsaSignature> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.sign_digest(digest)
+    }
+
+    fn public_key(&self) -> nor_core::Result<secp256k1::PublicKey> {
+        self.inner.public_key()
+    }
+
+    fn address(&self) -> nor_core::Result<String> {
+        self.inner.address()
+    }
+}
+
+#[test]
+fn test_custom_signer_plugs_into_managers() {
+    let keyring = Keyring::new();
+    let manager = WalletManager::with_keyring(keyring.clone());
+    let evm = EvmManager::with_keyring(keyring.clone());
+    let wallet = manager.create_wallet(vec![16u8; 16], None).unwrap();
+    let signer = CountingSigner {
+        inner: LocalSigner::new(&keyring, &wallet.id, Chain::Evm, 0),
+        calls: AtomicUsize::new(0),
+    };
+
+    let signature = evm.sign_message_with(&signer, "login".to_string()).unwrap();
+    assert_eq!(
+        signature,
+        evm.sign_message(wallet.id.clone(), 0, "login".to_string())
+            .unwrap()
+    );
+
+    let params = EvmTxParams {
+        from: wallet.accounts[0].address.clone(),
+        to: "0x3535353535353535353535353535353535353535".to_string(),
+        value: "1".to_string(),
+        gas_limit: 21000,
+        gas_price: "1000000000".to_string(),
+        chain_id: 65001,
+        ..Default::default()
+    };
+    let tx = evm.sign_transaction_with(&signer, params.clone()).unwrap();
+    assert_eq!(
+        tx.signed_tx,
+        evm.sign_transaction(wallet.id, 0, params)
+            .unwrap()
+            .signed_tx
+    );
+    assert_eq!(signer.calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_spending_policy_blocks_signing() {
+    let dir = tempfile::tempdir().unwrap();
+    let keyring = Keyring::new();
+    let manager = WalletManager::with_keyring(keyring.clone());
+    let evm = EvmManager::with_keyring(keyring);
+    create_authorized_vault(&manager, &dir);
+    let wallet = manager.create_wallet(vec![17u8; 16], None).unwrap();
+    let friend = "0x3535353535353535353535353535353535353535";
+
+    let policy = serde_json::json!({
+        "version": 1,
+        "rules": [
+            {"id": "friends-only", "type": "recipient_allowlist", "addresses": [friend]},
+            {"id": "daily", "type": "daily_limit", "max_value": "1000"},
+        ]
+    });
+    manager.set_spending_policy(policy.to_string()).unwrap();
+    assert!(manager.spending_policy().unwrap().is_some());
+
+    let params = |to: &str, value: &str| EvmTxParams {
+        from: wallet.accounts[0].address.clone(),
+        to: to.to_string(),
+        value: value.to_string(),
+        gas_limit: 21000,
+        gas_price: "1000000000".to_string(),
+        chain_id: 65001,
+        ..Default::default()
+    };
+    let violation = |result: nor_core::Result<nor_core::EvmTransaction>| match result {
+        Err(CoreError::PolicyViolation(violation)) => violation.rule_id,
+        other => panic!("expected a violation, got {:?}", other),
+    };
+
+    evm.sign_transaction(wallet.id.clone(), 0, params(friend, "600"))
+        .unwrap();
+    let stranger = "0x5757575757575757575757575757575757575757";
+    assert_eq!(
+        violation(evm.sign_transaction(wallet.id.clone(), 0, params(stranger, "1"))),
+        "friends-only"
+    );
+    assert_eq!(
+        violation(evm.sign_transaction(wallet.id.clone(), 0, params(friend, "600"))),
+        "daily"
+    );
+
+    // A restart keeps the policy and what was spent today
+    let vault_path = dir.path().join("wallets.vault").display().to_string();
+    let restarted = Keyring::new();
+    WalletManager::with_keyring(restarted.clone())
+        .unlock_vault(vault_path.clone(), "pw".to_string())
+        .unwrap();
+    assert_eq!(
+        violation(EvmManager::with_keyring(restarted).sign_transaction(
+            wallet.id.clone(),
+            0,
+            params(friend, "600")
+        )),
+        "daily"
+    );
+
+    // Once the grant is revoked, changing the policy needs the password again
+    manager.lock_all().unwrap();
+    manager.unlock_vault(vault_path, "pw".to_string()).unwrap();
+    assert!(matches!(
+        manager.clear_spending_policy(),
+        Err(CoreError::ReauthenticationRequired)
+    ));
+    manager.authorize_sensitive("pw".to_string()).unwrap();
+    manager.clear_spending_policy().unwrap();
+    assert!(evm
+        .sign_transaction(wallet.id, 0, params(stranger, "600"))
+        .is_ok());
+}
+
+#[test]
+fn test_audit_log_records_every_signature() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.jsonl");
+    let keyring = Keyring::new();
+    let manager = WalletManager::with_keyring(keyring.clone());
+    let evm = EvmManager::with_keyring(keyring.clone());
+    let tron = TronManager::with_keyring(keyring);
+    create_authorized_vault(&manager, &dir);
+    manager.open_audit_log(path.display().to_string()).unwrap();
+
+    let wallet = manager.create_wallet(vec![18u8; 16], None).unwrap();
+    manager
+        .derive_chain_account(wallet.id.clone(), Chain::Tron, 0)
+        .unwrap();
+    manager
+        .set_spending_policy(
+            serde_json::json!({"version": 1, "rules": [
+                {"id": "small", "type": "transaction_limit", "max_value": "10"}
+            ]})
+            .to_string(),
+        )
+        .unwrap();
+
+    let params = |value: &str| EvmTxParams {
+        from: wallet.accounts[0].address.clone(),
+        to: "0x3535353535353535353535353535353535353535".to_string(),
+        value: value.to_string(),
+        gas_limit: 21000,
+        gas_price: "1000000000".to_string(),
+        chain_id: 65001,
+        ..Default::default()
+    };
+    evm.sign_transaction(wallet.id.clone(), 0, params("5"))
+        .unwrap();
+    assert!(evm
+        .sign_transaction(wallet.id.clone(), 0, params("50"))
+        .is_err());
+    tron.sign_message(wallet.id.clone(), 0, "hello".to_string())
+        .unwrap();
+
+    let export = manager.export_audit_log().unwrap();
+    let entries: Vec<AuditEntry> = export
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].decision, PolicyDecision::Allowed);
+    assert!(entries[0].digest.is_some());
+    assert_eq!(entries[1].decision, PolicyDecision::Blocked);
+    assert_eq!(entries[1].violation.as_ref().unwrap().rule_id, "small");
+    assert_eq!(entries[2].chain, Chain::Tron);
+    assert_eq!(entries[2].operation, SigningOperation::Message);
+    assert!(manager.verify_audit_log().unwrap().valid);
+    assert!(manager.verify_audit_export(export.clone()).unwrap().valid);
+
+    // Deleting the blocked attempt from the file is noticed
+    let kept: Vec<&str> = export
+        .lines()
+        .enumerate()
+        .filter(|(i, _)| *i != 1)
+        .map(|(_, line)| line)
+        .collect();
+    std::fs::write(&path, kept.join("\n")).unwrap();
+    let verification = manager.verify_audit_log().unwrap();
+    assert!(!verification.valid);
+    assert_eq!(verification.first_invalid, Some(1));
+
+    // A restarted app notices both that and the last entry going missing
+    let reopen = |contents: String| {
+        std::fs::write(&path, contents).unwrap();
+        let restarted = WalletManager::with_keyring(Keyring::new());
+        restarted
+            .unlock_vault(
+                dir.path().join("wallets.vault").display().to_string(),
+                "pw".to_string(),
+            )
+            .unwrap();
+        restarted.open_audit_log(path.display().to_string())
+    };
+    assert!(matches!(
+        reopen(kept.join("\n")),
+        Err(CoreError::AuditLogTampered)
+    ));
+    let lines: Vec<&str> = export.lines().collect();
+    assert!(matches!(
+        reopen(lines[..2].join("\n")),
+        Err(CoreError::AuditLogTampered)
+    ));
+    reopen(export.clone()).unwrap();
+
+    // Without a vault there is nowhere to keep the key
+    assert!(matches!(
+        WalletManager::with_keyring(Keyring::new()).open_audit_log(path.display().to_string()),
+        Err(CoreError::InvalidInput)
+    ));
+}
+
+#[test]
+fn test_watch_only_desktop_and_offline_phone() {
+    let phone = WalletManager::with_keyring(Keyring::new());
+    let seed = phone.create_wallet(vec![19u8; 16], None).unwrap();
+    let address = seed.accounts[0].address.clone();
+
+    let desktop = WalletManager::with_keyring(Keyring::new());
+    let watch = desktop
+        .import_watch_only_addresses(Chain::Evm, vec![address.clone()])
+        .unwrap();
+    let params = EvmTxParams {
+        to: "0x3535353535353535353535353535353535353535".to_string(),
+        value: "1".to_string(),
+        gas_limit: 21000,
+        gas_price: "1000000000".to_string(),
+        chain_id: 65001,
+        ..Default::default()
+    };
+    let request = desktop
+        .create_signing_request(
+            watch.id,
+            0,
+            SigningPayload::EvmTransaction { params },
+            Some("desktop".to_string()),
+        )
+        .unwrap();
+
+    // The phone shows the hints, signs, and hands the response back
+    let shown = phone.parse_signing_request(request.clone()).unwrap();
+    assert_eq!(shown.account, address);
+    assert_eq!(shown.display.title, "Send transaction");
+    let response = phone.sign_signing_request(request.clone()).unwrap();
+
+    let accepted = desktop
+        .accept_signing_response(request.clone(), response.clone())
+        .unwrap();
+    let signed_tx = accepted.signed_tx.unwrap();
+    assert!(signed_tx.starts_with("0xf8"));
+    assert!(accepted.tx_hash.is_some());
+
+    // The desktop cannot sign the request itself
+    assert!(desktop.sign_signing_request(request.clone()).is_err());
+    // A response for another request is refused
+    let other = desktop
+        .create_signing_request(
+            desktop.list_wallets().unwrap()[0].id.clone(),
+            0,
+            SigningPayload::PersonalMessage {
+                chain: Chain::Evm,
+                message: "hi".to_string(),
+            },
+            None,
+        )
+        .unwrap();
+    assert!(matches!(
+        desktop.accept_signing_response(other, response),
+        Err(CoreError::EnvelopeMismatch)
+    ));
+}
+
+/// Frames of an animated QR code until the scanner has the whole UR
+fn show_qr(payload: UrPayload) -> UrPayload {
+    let encoder = UrEncoder::new(payload, 60).unwrap();
+    let scanner = UrDecoder::new();
+    while !scanner.receive(encoder.next_part().unwrap()).unwrap() {}
+    scanner.result().unwrap()
+}
+
+#[test]
+fn test_qr_signer_over_animated_ur() {
+    let phone = WalletManager::with_keyring(Keyring::new());
+    let phone_evm = EvmManager::with_keyring(phone.keyring());
+    let seed = phone.create_wallet(vec![29u8; 16], None).unwrap();
+    let xpub = phone.export_xpub(seed.id, Chain::Evm).unwrap();
+
+    let desktop = WalletManager::with_keyring(Keyring::new());
+    let desktop_evm = EvmManager::with_keyring(desktop.keyring());
+    let watch = desktop
+        .import_watch_only_xpub(xpub.xpub, Chain::Evm, None, xpub.master_fingerprint)
+        .unwrap();
+    let params = EvmTxParams {
+        to: "0x3535353535353535353535353535353535353535".to_string(),
+        value: "1".to_string(),
+        data: Some(format!("0x{}", "00".repeat(200))),
+        gas_limit: 60000,
+        max_fee_per_gas: Some("2000000000".to_string()),
+        max_priority_fee_per_gas: Some("1000000000".to_string()),
+        chain_id: 65001,
+        ..Default::default()
+    };
+    let request = desktop
+        .create_signing_request(
+            watch.id,
+            0,
+            SigningPayload::EvmTransaction { params },
+            Some("desktop".to_string()),
+        )
+        .unwrap();
+
+    // Without the xpub's own path the signer could not find the key
+    assert!(desktop_evm
+        .eth_sign_request_for(request.clone(), None)
+        .is_err());
+    let eth_request = desktop_evm
+        .eth_sign_request_for(request.clone(), Some(xpub.account_path))
+        .unwrap();
+    assert_eq!(eth_request.derivation_path, "m/44'/60'/0'/0/0");
+    assert!(eth_request.source_fingerprint.is_some());
+    assert_eq!(eth_request.source_fingerprint, xpub.master_fingerprint);
+    let shown = desktop_evm.encode_eth_sign_request(eth_request).unwrap();
+    assert!(!UrEncoder::new(shown.clone(), 60).unwrap().is_single_part());
+
+    // The phone scans, signs as a QR signer, and shows its answer
+    let scanned = phone_evm.decode_eth_sign_request(show_qr(shown)).unwrap();
+    assert_eq!(scanned.origin.as_deref(), Some("desktop"));
+    let scanned_copy = scanned.clone();
+    let mut other_seed = scanned.clone();
+    other_seed.source_fingerprint = other_seed.source_fingerprint.map(|f| f ^ 1);
+    assert!(phone_evm.sign_eth_sign_request(other_seed).is_err());
+    let signature = phone_evm.sign_eth_sign_request(scanned).unwrap();
+    let answer = phone_evm.encode_eth_signature(signature).unwrap();
+
+    let signature = desktop_evm.decode_eth_signature(show_qr(answer)).unwrap();
+    let accepted = desktop_evm
+        .accept_eth_signature(request, signature)
+        .unwrap();
+    assert!(accepted.signed_tx.unwrap().starts_with("0x02"));
+    assert_eq!(phone.export_audit_log().unwrap().lines().count(), 1);
+
+    // The desktop holds no key to answer with
+    assert!(desktop_evm.sign_eth_sign_request(scanned_copy).is_err());
+}
+
+/// Replays recorded APDU exchanges with the Ethereum app, failing on any
+/// command that differs from the recording
+struct ScriptedLedger {
+    exchanges: std::sync::Mutex<Vec<(&'static str, &'static str)>>,
+}
+
+impl LedgerTransport for ScriptedLedger {
+    fn exchange(&self, apdu: Vec<u8>) -> Result<Vec<u8>, CoreError> {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        assert!(!exchanges.is_empty(), "unexpected APDU");
+        let (command, response) = exchanges.remove(0);
+        assert_eq!(hex::encode(apdu), command);
+        Ok(hex::decode(response).unwrap())
+    }
+}
+
+#[test]
+fn test_ledger_personal_sign_exchange() {
+    // Account m/44'/60'/0'/0/0 of a device holding key 0x4646..46
+    let get_address = (
+        "e002000015058000002c8000003c800000000000000000000000",
+        "41044bc2a31265153f07e70e0bab08724e6b85e217f8cd628ceb62974247bb493382ce28cab79ad7119ee1ad3ebcdb98a16805211530ecc6cfefa1b88e6dff99232a28396438613632663635366138643136313563313239346664373165396366623365343835356134669000",
+    );
+    let sign = (
+        "e008000029058000002c8000003c800000000000000000000000000000104e6f722057616c6c6574206c6f67696e",
+        "1b896025aaeae7a4c3d969f1713494ebc8b5b37124c13f4b6e907c01aaeb3fd719659d15c3829bfaeb4298c569a31f8e4f77f51dbd9f85747d0f5f785585f2fcc99000",
+    );
+    let evm = EvmManager::with_keyring(Keyring::new());
+    let transport = ScriptedLedger {
+        exchanges: std::sync::Mutex::new(vec![get_address, sign]),
+    };
+
+    let signature = evm
+        .sign_message_ledger(
+            Box::new(transport),
+            "m/44'/60'/0'/0/0".to_string(),
+            "Nor Wallet login".to_string(),
+        )
+        .unwrap();
+    assert!(evm
+        .verify_message(
+            "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f".to_string(),
+            "Nor Wallet login".to_string(),
+            signature,
+        )
+        .unwrap());
+
+    // The user pressed reject on the device
+    let transport = ScriptedLedger {
+        exchanges: std::sync::Mutex::new(vec![get_address, (sign.0, "6985")]),
+    };
+    assert!(matches!(
+        evm.sign_message_ledger(
+            Box::new(transport),
+            "m/44'/60'/0'/0/0".to_string(),
+            "Nor Wallet login".to_string(),
+        ),
+        Err(CoreError::DeviceRejected)
+    ));
+}